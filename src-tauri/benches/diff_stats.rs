@@ -0,0 +1,237 @@
+//! Compares the two ways of taking a commit's line counts and patch-ids:
+//! numstat, which scans use, and rendering the full patch text, which they
+//! used before.
+//! Also times the walk and the per-commit diff stats of both backends.
+//!
+//! Runs on a generated repository, or on an existing one:
+//!
+//!     GIT_STATS_BENCH_REPO=/path/to/repo GIT_STATS_BENCH_COMMITS=500 cargo bench --bench diff_stats
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use git_statistics_lib::git_backend::{open_backend, GitBackendKind};
+use git_statistics_lib::models::Repository;
+use std::path::{Path, PathBuf};
+
+// Size of the generated repository
+const FIXTURE_COMMITS: usize = 2000;
+const FIXTURE_FILES: usize = 200;
+const FIXTURE_LINES: usize = 400;
+
+// Commits diffed per iteration unless GIT_STATS_BENCH_COMMITS says otherwise
+const DEFAULT_SAMPLE: usize = 500;
+
+/// Builds a repository where every commit edits a few of many large files,
+/// once per fixture size, and reuses it on later runs.
+fn fixture() -> PathBuf {
+    let path = std::env::temp_dir().join(format!("git-stats-bench-{}x{}", FIXTURE_COMMITS, FIXTURE_FILES));
+    if path.join(".git").exists() {
+        return path;
+    }
+
+    let building = path.with_extension("tmp");
+    let _ = std::fs::remove_dir_all(&building);
+    let repo = git2::Repository::init(&building).unwrap();
+
+    // Deterministic pseudo-random edits
+    let mut seed: u64 = 0x9e37_79b9_7f4a_7c15;
+    let mut next = move |bound: usize| {
+        seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+        (seed >> 33) as usize % bound
+    };
+
+    let mut files: Vec<Vec<String>> = (0..FIXTURE_FILES)
+        .map(|file| (0..FIXTURE_LINES).map(|line| format!("fn item_{}_{}() {{ value({}) }}", file, line, line)).collect())
+        .collect();
+
+    let mut parent: Option<git2::Oid> = None;
+    for commit in 0..FIXTURE_COMMITS {
+        let touched: Vec<usize> = if commit == 0 {
+            (0..FIXTURE_FILES).collect()
+        } else {
+            (0..1 + next(4)).map(|_| next(FIXTURE_FILES)).collect()
+        };
+        for &file in &touched {
+            if commit > 0 {
+                let lines = &mut files[file];
+                for _ in 0..1 + next(12) {
+                    let at = next(lines.len());
+                    match next(3) {
+                        0 => lines.insert(at, format!("// note {} of commit {}", at, commit)),
+                        1 if lines.len() > 1 => {
+                            lines.remove(at);
+                        }
+                        _ => lines[at] = format!("fn edited_{}_{}() {{ value({}) }}", commit, at, next(1000)),
+                    }
+                }
+            }
+            let relative = format!("src/module_{}/file_{}.rs", file % 10, file);
+            let full = building.join(&relative);
+            std::fs::create_dir_all(full.parent().unwrap()).unwrap();
+            std::fs::write(&full, files[file].join("\n") + "\n").unwrap();
+        }
+
+        let mut index = repo.index().unwrap();
+        index.add_all(["src"], git2::IndexAddOption::DEFAULT, None).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let parents: Vec<git2::Commit> = parent.map(|oid| repo.find_commit(oid).unwrap()).into_iter().collect();
+        let parents: Vec<&git2::Commit> = parents.iter().collect();
+        let time = git2::Time::new(1_700_000_000 + commit as i64 * 60, 0);
+        let signature = git2::Signature::new("bench", "bench@example.com", &time).unwrap();
+        parent = Some(
+            repo.commit(Some("HEAD"), &signature, &signature, &format!("commit {}", commit), &tree, &parents)
+                .unwrap(),
+        );
+    }
+
+    std::fs::rename(&building, &path).unwrap();
+    path
+}
+
+fn repository(path: &Path) -> Repository {
+    Repository {
+        id: 0,
+        path: path.to_string_lossy().to_string(),
+        name: "bench".to_string(),
+        last_scanned: None,
+        display_name: None,
+        color: None,
+        description: None,
+        archived: false,
+    }
+}
+
+fn bench_repository() -> (PathBuf, usize) {
+    let path = std::env::var_os("GIT_STATS_BENCH_REPO").map(PathBuf::from).unwrap_or_else(fixture);
+    let sample = std::env::var("GIT_STATS_BENCH_COMMITS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_SAMPLE);
+    (path, sample)
+}
+
+/// The newest `limit` non-merge commits of HEAD.
+fn newest_commit_ids(repo: &git2::Repository, limit: usize) -> Vec<String> {
+    let mut revwalk = repo.revwalk().unwrap();
+    revwalk.push_head().unwrap();
+    revwalk.set_sorting(git2::Sort::TIME).unwrap();
+
+    revwalk
+        .map(|oid| repo.find_commit(oid.unwrap()).unwrap())
+        .filter(|commit| commit.parent_count() <= 1)
+        .map(|commit| commit.id().to_string())
+        .take(limit)
+        .collect()
+}
+
+/// Line counts of a commit from its rendered patch text, the way scans
+/// took them before numstat: the diff's stats plus the patch text of every
+/// file, which scans never stored. The patch-ids come from libgit2, with
+/// the trees diffed a second time for the inverse one.
+fn patch_diff_stats(repo: &git2::Repository, commit_id: &str) -> (i32, i32, i32, Vec<String>) {
+    let commit = repo.find_commit(git2::Oid::from_str(commit_id).unwrap()).unwrap();
+    let tree = commit.tree().unwrap();
+    let parent_tree = commit.parents().next().map(|parent| parent.tree().unwrap());
+    let diff_trees = |old_tree: Option<&git2::Tree>, new_tree: Option<&git2::Tree>| {
+        let mut options = git2::DiffOptions::new();
+        options.ignore_whitespace(true).ignore_blank_lines(true);
+        repo.diff_tree_to_tree(old_tree, new_tree, Some(&mut options)).unwrap()
+    };
+    let diff = diff_trees(parent_tree.as_ref(), Some(&tree));
+    black_box(diff.patchid(None).unwrap());
+    black_box(diff_trees(Some(&tree), parent_tree.as_ref()).patchid(None).unwrap());
+    let stats = diff.stats().unwrap();
+
+    let mut patches: Vec<(PathBuf, String)> = Vec::new();
+    diff.print(git2::DiffFormat::Patch, |delta, _hunk, line| {
+        let path = delta.new_file().path().or(delta.old_file().path()).unwrap().to_path_buf();
+        if patches.last().map(|(last, _)| last != &path).unwrap_or(true) {
+            patches.push((path, String::new()));
+        }
+        let patch = &mut patches.last_mut().unwrap().1;
+        match line.origin() {
+            origin @ ('+' | '-') => patch.push(origin),
+            _ => patch.push(' '),
+        }
+        patch.push_str(&String::from_utf8_lossy(line.content()));
+        true
+    })
+    .unwrap();
+
+    (
+        stats.insertions() as i32,
+        stats.deletions() as i32,
+        stats.files_changed() as i32,
+        patches.into_iter().map(|(_, patch)| patch).collect(),
+    )
+}
+
+fn diff_stats(c: &mut Criterion) {
+    let (path, sample) = bench_repository();
+    let repo = git2::Repository::open(&path).unwrap();
+    let backend = open_backend(GitBackendKind::Git2, &repository(&path)).unwrap();
+    let commit_ids = newest_commit_ids(&repo, sample);
+
+    // Both paths have to agree before their timings mean anything
+    for commit_id in &commit_ids {
+        let stats = backend.diff_stats(commit_id).unwrap().unwrap();
+        let (patch_additions, patch_deletions, patch_files_changed, _) = patch_diff_stats(&repo, commit_id);
+        assert_eq!(
+            (stats.additions, stats.deletions, stats.files_changed),
+            (patch_additions, patch_deletions, patch_files_changed),
+            "line counts of {} differ",
+            commit_id
+        );
+    }
+
+    let mut group = c.benchmark_group("diff_stats");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(commit_ids.len() as u64));
+    group.bench_function("numstat", |b| {
+        b.iter(|| {
+            for commit_id in &commit_ids {
+                black_box(backend.diff_stats(commit_id).unwrap());
+            }
+        })
+    });
+    group.bench_function("full_patch", |b| {
+        b.iter(|| {
+            for commit_id in &commit_ids {
+                black_box(patch_diff_stats(&repo, commit_id));
+            }
+        })
+    });
+    group.finish();
+}
+
+fn backends(c: &mut Criterion) {
+    let (path, sample) = bench_repository();
+    let repository = repository(&path);
+    let git2 = open_backend(GitBackendKind::Git2, &repository).unwrap();
+    let commit_ids = git2.scan_commit_ids(None).unwrap();
+    let commit_ids = &commit_ids[..commit_ids.len().min(sample)];
+
+    let backends = [GitBackendKind::Git2, GitBackendKind::Gix].map(|kind| (kind, open_backend(kind, &repository).unwrap()));
+
+    let mut group = c.benchmark_group("backends");
+    group.sample_size(10);
+    for (kind, backend) in &backends {
+        group.bench_function(format!("walk/{}", kind.as_str()), |b| {
+            b.iter(|| black_box(backend.scan_commit_ids(None).unwrap()))
+        });
+    }
+    group.throughput(Throughput::Elements(commit_ids.len() as u64));
+    for (kind, backend) in &backends {
+        group.bench_function(format!("diff_stats/{}", kind.as_str()), |b| {
+            b.iter(|| {
+                for commit_id in commit_ids {
+                    black_box(backend.diff_stats(commit_id).unwrap());
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, diff_stats, backends);
+criterion_main!(benches);
@@ -1,13 +1,31 @@
-use tauri::{command, AppHandle, State};
+use tauri::{command, AppHandle, Emitter, Manager, State};
 use crate::database::{self, get_db_pool};
-use crate::git_analyzer::{analyze_repository, GitAnalyzer};
-use crate::models::{Repository, Commit, CommitDetail, Statistics, TimeFilter};
+use crate::diff_search::{self, DiffSearches, Pickaxe};
+use crate::filter_query;
+use crate::git_analyzer::{self, GitAnalyzer};
+use crate::git_backend::{compare_backends, open_backend, CommitDiffStats, GitBackendKind};
+use crate::search;
+use crate::settings;
+use crate::stats_cache::StatisticsCache;
+use crate::models::{
+    Repository, RepositoryGroup, BackendComparison, Commit, CommitDetail, CommitPage, DiffSearchFinished, DiffSearchMatch, DiffSearchProgress, DiffSearchQuery, FilterPreset, FilterQuerySuggestion, IngestReport, ParsedFilterQuery, RevisionComparison, LineHistory, ReconcileResult, RevertReport, SearchPage, SettingChanged, Statistics, StatisticsCacheDiagnostics, StatisticsTotals, TimeFilter,
+    HourlyStats, DailyStats, WeeklyStats, AuthorStats, RepositoryStats, GroupStats, HourlyCommitDistribution, AuthorActivityTrend,
+    CommitFrequencyDistribution, CommitSizeDistribution, EfficiencyTrend, HotFile, CommitMessageWord,
+    VerificationIssue, VerificationReport,
+    FileHistory, FileHistoryEntry,
+};
 use anyhow::Result;
+use std::collections::{HashMap, HashSet};
 use std::sync::Mutex;
+use sqlx::SqlitePool;
 
 #[derive(Default)]
 pub struct AppState {
     pub scanning: Mutex<bool>,
+    pub last_ingest: Mutex<Option<IngestReport>>,
+    pub git_backend: Mutex<GitBackendKind>,
+    pub stats_cache: StatisticsCache,
+    pub diff_searches: DiffSearches,
 }
 
 #[command]
@@ -40,12 +58,13 @@ pub async fn add_repository(app_handle: AppHandle, path: String) -> Result<Repos
 }
 
 #[command]
-pub async fn remove_repository(app_handle: AppHandle, id: i64) -> Result<(), String> {
+pub async fn remove_repository(app_handle: AppHandle, id: i64, state: State<'_, AppState>) -> Result<(), String> {
     let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
     
-    database::remove_repository(&pool, id)
-        .await
-        .map_err(|e| format!("删除仓库失败: {}", e))?;
+    let result = database::remove_repository(&pool, id).await;
+    invalidate_statistics(&app_handle, &state, Some(id)).await;
+    
+    result.map_err(|e| format!("删除仓库失败: {}", e))?;
     
     Ok(())
 }
@@ -61,28 +80,127 @@ pub async fn get_repositories(app_handle: AppHandle) -> Result<Vec<Repository>,
     Ok(repositories)
 }
 
+#[command]
+pub async fn update_repository(
+    app_handle: AppHandle,
+    id: i64,
+    display_name: Option<String>,
+    color: Option<String>,
+    description: Option<String>,
+    archived: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<Repository, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    
+    let repository = database::update_repository(
+        &pool,
+        id,
+        display_name.as_deref(),
+        color.as_deref(),
+        description.as_deref(),
+        archived,
+    )
+    .await
+    .map_err(|e| format!("更新仓库失败: {}", e))?;
+    
+    // Cached repository statistics carry the display name and color
+    if display_name.is_some() || color.is_some() {
+        invalidate_statistics(&app_handle, &state, Some(id)).await;
+    }
+    
+    Ok(repository)
+}
+
+#[command]
+pub async fn get_repository_groups(app_handle: AppHandle) -> Result<Vec<RepositoryGroup>, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    
+    database::get_repository_groups(&pool)
+        .await
+        .map_err(|e| format!("获取仓库分组失败: {}", e))
+}
+
+fn repository_group_error(action: &str, e: anyhow::Error) -> String {
+    if e.to_string().contains("UNIQUE constraint failed") {
+        "已存在同名的仓库分组".to_string()
+    } else if e.to_string().contains("FOREIGN KEY constraint failed") {
+        format!("{}仓库分组失败: 仓库不存在", action)
+    } else {
+        format!("{}仓库分组失败: {}", action, e)
+    }
+}
+
+#[command]
+pub async fn create_repository_group(
+    app_handle: AppHandle,
+    name: String,
+    repository_ids: Option<Vec<i64>>,
+    state: State<'_, AppState>
+) -> Result<RepositoryGroup, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    
+    let result = database::create_repository_group(&pool, &name, repository_ids.as_deref().unwrap_or_default()).await;
+    invalidate_statistics(&app_handle, &state, None).await;
+    
+    result.map_err(|e| repository_group_error("创建", e))
+}
+
+/// Renames the group, replaces its repositories, or both.
+#[command]
+pub async fn update_repository_group(
+    app_handle: AppHandle,
+    id: i64,
+    name: Option<String>,
+    repository_ids: Option<Vec<i64>>,
+    state: State<'_, AppState>
+) -> Result<RepositoryGroup, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    
+    let result = database::update_repository_group(&pool, id, name.as_deref(), repository_ids.as_deref()).await;
+    invalidate_statistics(&app_handle, &state, None).await;
+    
+    result.map_err(|e| repository_group_error("更新", e))
+}
+
+/// Deletes the group; its repositories stay.
+#[command]
+pub async fn delete_repository_group(app_handle: AppHandle, id: i64, state: State<'_, AppState>) -> Result<bool, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    
+    let result = database::delete_repository_group(&pool, id).await;
+    invalidate_statistics(&app_handle, &state, None).await;
+    
+    result.map_err(|e| repository_group_error("删除", e))
+}
+
 #[command]
 pub async fn scan_repository(
     app_handle: AppHandle, 
     repository_id: i64,
+    batch_size: Option<usize>,
+    threads: Option<usize>,
     state: State<'_, AppState>
 ) -> Result<i32, String> {
-    scan_repository_internal(app_handle, repository_id, state, true).await
+    scan_repository_internal(app_handle, repository_id, batch_size, threads, state, true).await
 }
 
 #[command]
 pub async fn force_scan_repository(
     app_handle: AppHandle, 
     repository_id: i64,
+    batch_size: Option<usize>,
+    threads: Option<usize>,
     state: State<'_, AppState>
 ) -> Result<i32, String> {
-    scan_repository_internal(app_handle, repository_id, state, false).await
+    scan_repository_internal(app_handle, repository_id, batch_size, threads, state, false).await
 }
 
 #[command]
 pub async fn scan_last_24_hours(
     app_handle: AppHandle, 
     repository_id: i64,
+    batch_size: Option<usize>,
+    threads: Option<usize>,
     state: State<'_, AppState>
 ) -> Result<i32, String> {
     // Check if already scanning
@@ -112,32 +230,18 @@ pub async fn scan_last_24_hours(
         // Calculate the time 24 hours ago
         let since = Some(chrono::Utc::now() - chrono::Duration::hours(24));
         
-        // Analyze commits
-        let analyzed_commits = analyze_repository(repository.clone(), since)?;
-        let commit_count = analyzed_commits.len() as i32;
+        // Analyze commits and save them to the database
+        let commit_ids = analyze_and_ingest(&pool, &repository, since, batch_size, threads, &state).await?;
         
-        // Extract commits and file changes
-        let commits: Vec<Commit> = analyzed_commits.iter().map(|ac| ac.commit.clone()).collect();
-        
-        // Save to database
-        if !commits.is_empty() {
-            database::save_commits(&pool, &commits).await?;
-            
-            // Save file changes for each commit
-            for analyzed_commit in analyzed_commits {
-                database::save_file_changes(
-                    &pool, 
-                    &analyzed_commit.commit.id, 
-                    analyzed_commit.commit.repository_id, 
-                    &analyzed_commit.file_changes
-                ).await?;
-            }
+        if !commit_ids.is_empty() {
+            // Link reverts to the commits they undo
+            database::detect_reverts(&pool, repository_id, &commit_ids).await?;
         }
         
         // Update last scanned time
         database::update_repository_scan_time(&pool, repository_id).await?;
         
-        Ok(commit_count)
+        Ok(commit_ids.len() as i32)
     }.await;
 
     // Clear scanning flag
@@ -145,13 +249,88 @@ pub async fn scan_last_24_hours(
         let mut scanning = state.scanning.lock().unwrap();
         *scanning = false;
     }
+    
+    invalidate_statistics(&app_handle, &state, Some(repository_id)).await;
 
     result.map_err(|e: anyhow::Error| format!("扫描仓库失败: {}", e))
 }
 
+/// Analyzes the repository's commits on worker threads while streaming
+/// them, in revwalk order, into the database. Returns their IDs.
+async fn analyze_and_ingest(
+    pool: &SqlitePool,
+    repository: &Repository,
+    since: Option<chrono::DateTime<chrono::Utc>>,
+    batch_size: Option<usize>,
+    threads: Option<usize>,
+    state: &AppState,
+) -> Result<Vec<String>> {
+    let backend = *state.git_backend.lock().unwrap();
+    // The walk reads every new commit, so it runs off the async runtime too
+    let walked_repository = repository.clone();
+    let commit_ids = tauri::async_runtime::spawn_blocking(move || {
+        open_backend(backend, &walked_repository)?.scan_commit_ids(since)
+    })
+    .await
+    .map_err(|e| anyhow::anyhow!("Commit walk failed: {}", e))??;
+    if commit_ids.is_empty() {
+        return Ok(commit_ids);
+    }
+    
+    let expected_commits = commit_ids.len();
+    let batch_size = batch_size.unwrap_or(database::DEFAULT_INGEST_BATCH_SIZE).max(1);
+    let threads = threads.unwrap_or_else(git_analyzer::default_thread_count);
+    
+    let (sender, receiver) = tokio::sync::mpsc::channel(batch_size);
+    let repository = repository.clone();
+    let analysis = tauri::async_runtime::spawn_blocking(move || {
+        git_analyzer::analyze_commit_ids(&repository, backend, &commit_ids, threads, |analyzed_commit| {
+            sender
+                .blocking_send(analyzed_commit)
+                .map_err(|_| anyhow::anyhow!("Ingestion stopped"))
+        })
+        .map(|_| commit_ids)
+    });
+    
+    let ingested = database::ingest_commits(pool, expected_commits, batch_size, receiver).await;
+    let analyzed = analysis.await;
+    
+    // A failed ingestion also stops the analysis, so report its error first
+    let report = ingested?;
+    let commit_ids = analyzed.map_err(|e| anyhow::anyhow!("Analysis task failed: {}", e))??;
+    
+    *state.last_ingest.lock().unwrap() = Some(report);
+    Ok(commit_ids)
+}
+
+/// Throughput of the most recent scan's database writes.
+#[command]
+pub async fn get_ingest_report(state: State<'_, AppState>) -> Result<Option<IngestReport>, String> {
+    Ok(state.last_ingest.lock().unwrap().clone())
+}
+
+/// Drops cached statistics and diff search results a change to the
+/// repository, or with `None` to every repository, may have made stale.
+/// Runs after failed changes too, since they may have committed part of
+/// their writes.
+async fn invalidate_statistics(app_handle: &AppHandle, state: &AppState, repository_id: Option<i64>) {
+    state.diff_searches.invalidate(repository_id);
+    
+    let result = async {
+        let pool = get_db_pool(app_handle).await?;
+        state.stats_cache.invalidate(&pool, repository_id).await
+    }.await;
+    
+    if let Err(e) = result {
+        eprintln!("Failed to invalidate statistics cache: {}", e);
+    }
+}
+
 async fn scan_repository_internal(
     app_handle: AppHandle, 
     repository_id: i64,
+    batch_size: Option<usize>,
+    threads: Option<usize>,
     state: State<'_, AppState>,
     use_incremental: bool
 ) -> Result<i32, String> {
@@ -188,34 +367,25 @@ async fn scan_repository_internal(
             None
         };
         
-        // Analyze commits
-        let analyzed_commits = analyze_repository(repository.clone(), since)?;
-        let commit_count = analyzed_commits.len() as i32;
+        // Analyze commits and save them to the database
+        let commit_ids = analyze_and_ingest(&pool, &repository, since, batch_size, threads, &state).await?;
         
-        // Extract commits and file changes
-        let commits: Vec<Commit> = analyzed_commits.iter().map(|ac| ac.commit.clone()).collect();
-        
-        // Save to database
-        if !commits.is_empty() {
-            database::save_commits(&pool, &commits).await?;
-            
-            // Save file changes for each commit
-            for analyzed_commit in analyzed_commits {
-                database::save_file_changes(
-                    &pool, 
-                    &analyzed_commit.commit.id, 
-                    analyzed_commit.commit.repository_id, 
-                    &analyzed_commit.file_changes
-                ).await?;
-            }
+        if !commit_ids.is_empty() {
+            // Link reverts to the commits they undo
+            database::detect_reverts(&pool, repository_id, &commit_ids).await?;
         }
         
+        // Mark commits dropped by force-pushes or branch deletions
+        let backend = *state.git_backend.lock().unwrap();
+        let reachable = reachable_commit_ids(backend, &repository).await?;
+        database::reconcile_commits(&pool, repository_id, &reachable).await?;
+        
         // Update last scanned time (only for incremental scan)
         if use_incremental {
             database::update_repository_scan_time(&pool, repository_id).await?;
         }
         
-        Ok(commit_count)
+        Ok(commit_ids.len() as i32)
     }.await;
 
     // Clear scanning flag
@@ -223,50 +393,302 @@ async fn scan_repository_internal(
         let mut scanning = state.scanning.lock().unwrap();
         *scanning = false;
     }
+    
+    invalidate_statistics(&app_handle, &state, Some(repository_id)).await;
 
     result.map_err(|e: anyhow::Error| format!("扫描仓库失败: {}", e))
 }
 
+/// Commits reachable from the repository's branches. The walk covers
+/// every branch's whole history, so it runs on the blocking pool.
+async fn reachable_commit_ids(backend: GitBackendKind, repository: &Repository) -> Result<HashSet<String>> {
+    let repository = repository.clone();
+    tauri::async_runtime::spawn_blocking(move || open_backend(backend, &repository)?.reachable_commit_ids())
+        .await
+        .map_err(|e| anyhow::anyhow!("Reachability walk failed: {}", e))?
+}
+
 #[command]
-pub async fn get_statistics(
+pub async fn reconcile_repository(
     app_handle: AppHandle,
-    start_date: Option<String>,
-    end_date: Option<String>,
-    author: Option<String>,
-    exclude_authors: Option<Vec<String>>,
-    repository_id: Option<i64>
-) -> Result<Statistics, String> {
+    repository_id: i64,
+    state: State<'_, AppState>
+) -> Result<ReconcileResult, String> {
+    // Check and set the scanning flag under one lock, a scan would
+    // re-insert commits while they are marked orphaned
+    {
+        let mut scanning = state.scanning.lock().unwrap();
+        if *scanning {
+            return Err("正在扫描中，请稍候...".to_string());
+        }
+        *scanning = true;
+    }
+    
+    let result = async {
+        let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+        
+        // Get repository info
+        let repositories = database::get_repositories(&pool)
+            .await
+            .map_err(|e| format!("获取仓库信息失败: {}", e))?;
+            
+        let repository = repositories
+            .into_iter()
+            .find(|r| r.id == repository_id)
+            .ok_or_else(|| "仓库未找到".to_string())?;
+        
+        let backend = *state.git_backend.lock().unwrap();
+        let reachable = reachable_commit_ids(backend, &repository)
+            .await
+            .map_err(|e| format!("无法读取仓库引用: {}", e))?;
+        
+        database::reconcile_commits(&pool, repository_id, &reachable)
+            .await
+            .map_err(|e| format!("校准提交失败: {}", e))
+    }.await;
+    
+    // Clear scanning flag
+    {
+        let mut scanning = state.scanning.lock().unwrap();
+        *scanning = false;
+    }
+    
+    invalidate_statistics(&app_handle, &state, Some(repository_id)).await;
+    
+    result
+}
+
+#[command]
+pub async fn purge_orphaned_commits(
+    app_handle: AppHandle,
+    repository_id: Option<i64>,
+    state: State<'_, AppState>
+) -> Result<u64, String> {
     let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
     
-    let filter = TimeFilter {
-        start_date: start_date.and_then(|s| chrono::DateTime::parse_from_rfc3339(&s).ok())
-            .map(|dt| dt.with_timezone(&chrono::Utc)),
-        end_date: end_date.and_then(|s| chrono::DateTime::parse_from_rfc3339(&s).ok())
-            .map(|dt| dt.with_timezone(&chrono::Utc)),
-        author,
-        exclude_authors,
-        repository_id,
-    };
+    let purged = database::purge_orphaned_commits(&pool, repository_id).await;
+    invalidate_statistics(&app_handle, &state, repository_id).await;
+    
+    let purged = purged.map_err(|e| format!("清除孤立提交失败: {}", e))?;
     
-    let statistics = database::get_statistics(&pool, &filter)
+    Ok(purged)
+}
+
+#[command]
+pub async fn verify_repository(
+    app_handle: AppHandle,
+    repository_id: i64,
+    sample_size: Option<u32>,
+    repair: Option<bool>,
+    state: State<'_, AppState>
+) -> Result<VerificationReport, String> {
+    // Check and set the scanning flag under one lock, repairs write to
+    // the same tables
+    {
+        let mut scanning = state.scanning.lock().unwrap();
+        if *scanning {
+            return Err("正在扫描中，请稍候...".to_string());
+        }
+        *scanning = true;
+    }
+
+    let repair = repair.unwrap_or(false);
+    let backend_kind = *state.git_backend.lock().unwrap();
+    let result = async {
+        let pool = get_db_pool(&app_handle).await?;
+        
+        // Get repository info
+        let repositories = database::get_repositories(&pool).await?;
+        let repository = repositories
+            .into_iter()
+            .find(|r| r.id == repository_id)
+            .ok_or_else(|| anyhow::anyhow!("Repository not found"))?;
+        
+        let total_commits = database::count_commits(&pool, repository_id).await?;
+        let commits = database::get_commits_for_verification(&pool, repository_id, sample_size).await?;
+        
+        // Diff the sampled commits off the async runtime
+        let commit_ids: Vec<String> = commits.iter().map(|commit| commit.id.clone()).collect();
+        let actual_stats = tauri::async_runtime::spawn_blocking(move || -> Result<Vec<Option<CommitDiffStats>>> {
+            let backend = open_backend(backend_kind, &repository)?;
+            commit_ids.iter().map(|commit_id| backend.diff_stats(commit_id)).collect()
+        })
         .await
-        .map_err(|e| format!("获取统计数据失败: {}", e))?;
+        .map_err(|e| anyhow::anyhow!("Verification task failed: {}", e))??;
+        
+        let (issues, repaired_commits) = verify_commits(&pool, repository_id, &commits, actual_stats, repair).await?;
+        
+        Ok(VerificationReport {
+            repository_id,
+            total_commits,
+            checked_commits: commits.len() as i32,
+            sampled: sample_size.is_some(),
+            issues,
+            repaired_commits,
+        })
+    }.await;
+
+    // Clear scanning flag
+    {
+        let mut scanning = state.scanning.lock().unwrap();
+        *scanning = false;
+    }
     
-    Ok(statistics)
+    if repair {
+        invalidate_statistics(&app_handle, &state, Some(repository_id)).await;
+    }
+
+    result.map_err(|e: anyhow::Error| format!("校验仓库失败: {}", e))
 }
 
+/// Compares each stored commit with its stats from git, `None` where git
+/// no longer has it, and with `repair` rewrites or deletes the commits
+/// that differ. A failed repair is recorded on that commit's issues and
+/// the remaining commits are still checked and repaired.
+async fn verify_commits(
+    pool: &SqlitePool,
+    repository_id: i64,
+    commits: &[Commit],
+    actual_stats: Vec<Option<CommitDiffStats>>,
+    repair: bool
+) -> Result<(Vec<VerificationIssue>, i32)> {
+    let mut issues = Vec::new();
+    let mut repaired_commits = 0;
+    
+    for (commit, actual) in commits.iter().zip(actual_stats) {
+        let mut commit_issues = match &actual {
+            Some(stats) => {
+                let stored_files = database::get_stored_file_changes(pool, repository_id, &commit.id).await?;
+                compare_stored_commit(commit, &stored_files, (stats.additions, stats.deletions, stats.files_changed), &stats.file_changes)
+            }
+            None => vec![VerificationIssue {
+                commit_id: commit.id.clone(),
+                kind: "missing_commit".to_string(),
+                file_path: None,
+                stored: Some(commit.message.lines().next().unwrap_or("").to_string()),
+                actual: None,
+                repaired: false,
+                repair_error: None,
+            }],
+        };
+        
+        if repair && !commit_issues.is_empty() {
+            let repaired = match &actual {
+                Some(stats) => {
+                    database::repair_commit(pool, repository_id, &commit.id, stats.additions, stats.deletions, stats.files_changed, &stats.file_changes).await
+                }
+                None => database::delete_commit(pool, repository_id, &commit.id).await,
+            };
+            match repaired {
+                Ok(()) => {
+                    repaired_commits += 1;
+                    for issue in &mut commit_issues {
+                        issue.repaired = true;
+                    }
+                }
+                Err(e) => {
+                    for issue in &mut commit_issues {
+                        issue.repair_error = Some(e.to_string());
+                    }
+                }
+            }
+        }
+        
+        issues.extend(commit_issues);
+    }
+    
+    Ok((issues, repaired_commits))
+}
+
+fn compare_stored_commit(
+    commit: &Commit,
+    stored_files: &[(String, i32, i32)],
+    (additions, deletions, files_changed): (i32, i32, i32),
+    file_changes: &[git_analyzer::FileChange]
+) -> Vec<VerificationIssue> {
+    let mut issues = Vec::new();
+    let issue = |kind: &str, file_path: Option<&str>, stored: Option<String>, actual: Option<String>| VerificationIssue {
+        commit_id: commit.id.clone(),
+        kind: kind.to_string(),
+        file_path: file_path.map(|path| path.to_string()),
+        stored,
+        actual,
+        repaired: false,
+        repair_error: None,
+    };
+    
+    if (commit.additions, commit.deletions, commit.files_changed) != (additions, deletions, files_changed) {
+        issues.push(issue(
+            "commit_stats",
+            None,
+            Some(format!("+{} -{} ({} files)", commit.additions, commit.deletions, commit.files_changed)),
+            Some(format!("+{} -{} ({} files)", additions, deletions, files_changed)),
+        ));
+    }
+    
+    let mut stored_by_path: HashMap<&str, Vec<(i32, i32)>> = HashMap::new();
+    for (path, additions, deletions) in stored_files {
+        stored_by_path.entry(path.as_str()).or_default().push((*additions, *deletions));
+    }
+    
+    for file_change in file_changes {
+        match stored_by_path.remove(file_change.path.as_str()) {
+            None => issues.push(issue(
+                "missing_file",
+                Some(&file_change.path),
+                None,
+                Some(format!("+{} -{}", file_change.additions, file_change.deletions)),
+            )),
+            Some(rows) => {
+                if rows.len() > 1 {
+                    issues.push(issue(
+                        "duplicate_file_rows",
+                        Some(&file_change.path),
+                        Some(format!("{} rows", rows.len())),
+                        Some("1 row".to_string()),
+                    ));
+                }
+                let (stored_additions, stored_deletions) = rows[0];
+                if (stored_additions, stored_deletions) != (file_change.additions, file_change.deletions) {
+                    issues.push(issue(
+                        "file_stats",
+                        Some(&file_change.path),
+                        Some(format!("+{} -{}", stored_additions, stored_deletions)),
+                        Some(format!("+{} -{}", file_change.additions, file_change.deletions)),
+                    ));
+                }
+            }
+        }
+    }
+    
+    let mut extra_paths: Vec<&str> = stored_by_path.into_keys().collect();
+    extra_paths.sort();
+    for path in extra_paths {
+        issues.push(issue("extra_file", Some(path), Some("stored".to_string()), None));
+    }
+    
+    issues
+}
+
+/// All statistics series at once. A `filter` object, which carries every
+/// criterion, takes the place of the individual filter arguments.
 #[command]
-pub async fn get_commit_timeline(
+pub async fn get_statistics(
     app_handle: AppHandle,
     start_date: Option<String>,
     end_date: Option<String>,
     author: Option<String>,
     exclude_authors: Option<Vec<String>>,
-    repository_id: Option<i64>
-) -> Result<Vec<Commit>, String> {
+    repository_id: Option<i64>,
+    deduplicate_patches: Option<bool>,
+    exclude_reverts: Option<bool>,
+    include_orphaned: Option<bool>,
+    filter: Option<TimeFilter>,
+    state: State<'_, AppState>
+) -> Result<Statistics, String> {
     let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
     
-    let filter = TimeFilter {
+    let filter = filter.unwrap_or_else(|| TimeFilter {
         start_date: start_date.and_then(|s| chrono::DateTime::parse_from_rfc3339(&s).ok())
             .map(|dt| dt.with_timezone(&chrono::Utc)),
         end_date: end_date.and_then(|s| chrono::DateTime::parse_from_rfc3339(&s).ok())
@@ -274,39 +696,1111 @@ pub async fn get_commit_timeline(
         author,
         exclude_authors,
         repository_id,
-    };
+        deduplicate_patches: deduplicate_patches.unwrap_or(false),
+        exclude_reverts: exclude_reverts.unwrap_or(false),
+        include_orphaned: include_orphaned.unwrap_or(false),
+        ..TimeFilter::default()
+    });
     
-    let commits = database::get_commit_timeline_with_remote_urls(&pool, &filter)
+    let statistics = state.stats_cache
+        .get_or_compute(&pool, "statistics", &filter, database::get_statistics(&pool, &filter))
         .await
-        .map_err(|e| format!("获取提交时间线失败: {}", e))?;
+        .map_err(|e| format!("获取统计数据失败: {}", e))?;
     
-    Ok(commits)
+    Ok(statistics)
 }
 
+// Single statistics series, so the dashboard can load only the charts it
+// shows and fetch them concurrently. They take the same filter as
+// `get_statistics`, as one `filter` object.
+
 #[command]
-pub async fn get_commit_detail(
-    app_handle: AppHandle,
-    repository_id: i64,
-    commit_id: String
-) -> Result<CommitDetail, String> {
+pub async fn get_hourly_stats(app_handle: AppHandle, filter: TimeFilter, state: State<'_, AppState>) -> Result<Vec<HourlyStats>, String> {
     let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
-    
-    // Get repository info
-    let repositories = database::get_repositories(&pool)
+    state.stats_cache
+        .get_or_compute(&pool, "hourly_stats", &filter, database::get_hourly_stats(&pool, &filter))
         .await
-        .map_err(|e| format!("获取仓库信息失败: {}", e))?;
-        
-    let repository = repositories
-        .into_iter()
-        .find(|r| r.id == repository_id)
-        .ok_or_else(|| "仓库未找到".to_string())?;
-    
-    // Create GitAnalyzer and get commit detail
-    let analyzer = GitAnalyzer::new(repository)
-        .map_err(|e| format!("无法打开仓库: {}", e))?;
-        
-    let commit_detail = analyzer.get_commit_detail(&commit_id)
-        .map_err(|e| format!("获取提交详情失败: {}", e))?;
-    
-    Ok(commit_detail)
-}
\ No newline at end of file
+        .map_err(|e| format!("获取每小时统计失败: {}", e))
+}
+
+#[command]
+pub async fn get_daily_stats(app_handle: AppHandle, filter: TimeFilter, state: State<'_, AppState>) -> Result<Vec<DailyStats>, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    state.stats_cache
+        .get_or_compute(&pool, "daily_stats", &filter, database::get_daily_stats(&pool, &filter))
+        .await
+        .map_err(|e| format!("获取每日统计失败: {}", e))
+}
+
+#[command]
+pub async fn get_weekly_stats(app_handle: AppHandle, filter: TimeFilter, state: State<'_, AppState>) -> Result<Vec<WeeklyStats>, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    state.stats_cache
+        .get_or_compute(&pool, "weekly_stats", &filter, database::get_weekly_stats(&pool, &filter))
+        .await
+        .map_err(|e| format!("获取每周统计失败: {}", e))
+}
+
+#[command]
+pub async fn get_statistics_totals(app_handle: AppHandle, filter: TimeFilter, state: State<'_, AppState>) -> Result<StatisticsTotals, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    state.stats_cache
+        .get_or_compute(&pool, "statistics_totals", &filter, database::get_statistics_totals(&pool, &filter))
+        .await
+        .map_err(|e| format!("获取统计总数失败: {}", e))
+}
+
+#[command]
+pub async fn get_author_stats(app_handle: AppHandle, filter: TimeFilter, state: State<'_, AppState>) -> Result<HashMap<String, AuthorStats>, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    state.stats_cache
+        .get_or_compute(&pool, "author_stats", &filter, database::get_author_stats(&pool, &filter))
+        .await
+        .map_err(|e| format!("获取作者统计失败: {}", e))
+}
+
+#[command]
+pub async fn get_repository_stats(app_handle: AppHandle, filter: TimeFilter, state: State<'_, AppState>) -> Result<HashMap<i64, RepositoryStats>, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    state.stats_cache
+        .get_or_compute(&pool, "repository_stats", &filter, database::get_repository_stats(&pool, &filter))
+        .await
+        .map_err(|e| format!("获取仓库统计失败: {}", e))
+}
+
+#[command]
+pub async fn get_group_stats(app_handle: AppHandle, filter: TimeFilter, state: State<'_, AppState>) -> Result<Vec<GroupStats>, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    state.stats_cache
+        .get_or_compute(&pool, "group_stats", &filter, database::get_group_stats(&pool, &filter))
+        .await
+        .map_err(|e| format!("获取分组统计失败: {}", e))
+}
+
+#[command]
+pub async fn get_hourly_commit_distribution(app_handle: AppHandle, filter: TimeFilter, state: State<'_, AppState>) -> Result<Vec<HourlyCommitDistribution>, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    state.stats_cache
+        .get_or_compute(&pool, "hourly_commit_distribution", &filter, database::get_hourly_commit_distribution(&pool, &filter))
+        .await
+        .map_err(|e| format!("获取提交热力图失败: {}", e))
+}
+
+#[command]
+pub async fn get_author_activity_trends(app_handle: AppHandle, filter: TimeFilter, state: State<'_, AppState>) -> Result<Vec<AuthorActivityTrend>, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    state.stats_cache
+        .get_or_compute(&pool, "author_activity_trends", &filter, database::get_author_activity_trends(&pool, &filter))
+        .await
+        .map_err(|e| format!("获取作者活跃趋势失败: {}", e))
+}
+
+#[command]
+pub async fn get_commit_frequency_distribution(app_handle: AppHandle, filter: TimeFilter, state: State<'_, AppState>) -> Result<Vec<CommitFrequencyDistribution>, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    state.stats_cache
+        .get_or_compute(&pool, "commit_frequency_distribution", &filter, database::get_commit_frequency_distribution(&pool, &filter))
+        .await
+        .map_err(|e| format!("获取提交频率分布失败: {}", e))
+}
+
+#[command]
+pub async fn get_commit_size_distribution(app_handle: AppHandle, filter: TimeFilter, state: State<'_, AppState>) -> Result<Vec<CommitSizeDistribution>, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    state.stats_cache
+        .get_or_compute(&pool, "commit_size_distribution", &filter, database::get_commit_size_distribution(&pool, &filter))
+        .await
+        .map_err(|e| format!("获取提交大小分布失败: {}", e))
+}
+
+#[command]
+pub async fn get_efficiency_trends(app_handle: AppHandle, filter: TimeFilter, state: State<'_, AppState>) -> Result<Vec<EfficiencyTrend>, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    state.stats_cache
+        .get_or_compute(&pool, "efficiency_trends", &filter, database::get_efficiency_trends(&pool, &filter))
+        .await
+        .map_err(|e| format!("获取效率趋势失败: {}", e))
+}
+
+#[command]
+pub async fn get_hot_files(app_handle: AppHandle, filter: TimeFilter, state: State<'_, AppState>) -> Result<Vec<HotFile>, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    state.stats_cache
+        .get_or_compute(&pool, "hot_files", &filter, database::get_hot_files(&pool, &filter))
+        .await
+        .map_err(|e| format!("获取热点文件失败: {}", e))
+}
+
+#[command]
+pub async fn get_commit_message_words(app_handle: AppHandle, filter: TimeFilter, state: State<'_, AppState>) -> Result<Vec<CommitMessageWord>, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    state.stats_cache
+        .get_or_compute(&pool, "commit_message_words", &filter, database::get_commit_message_words(&pool, &filter))
+        .await
+        .map_err(|e| format!("获取提交信息词频失败: {}", e))
+}
+
+/// Hit and miss counts of the statistics cache.
+#[command]
+pub async fn get_statistics_cache_diagnostics(
+    app_handle: AppHandle,
+    state: State<'_, AppState>
+) -> Result<StatisticsCacheDiagnostics, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    
+    state.stats_cache
+        .diagnostics(&pool)
+        .await
+        .map_err(|e| format!("获取缓存诊断信息失败: {}", e))
+}
+
+/// Whether statistics results are also kept on disk, across restarts.
+/// Saved as a setting, so it survives restarts as well.
+#[command]
+pub async fn set_statistics_cache_persistence(
+    app_handle: AppHandle,
+    enabled: bool,
+    state: State<'_, AppState>
+) -> Result<(), String> {
+    store_setting(&app_handle, &state, settings::STATISTICS_CACHE_PERSISTENT, serde_json::Value::Bool(enabled)).await
+}
+
+/// Revert rates over the filter; like `get_statistics`, it also takes a
+/// whole `filter` object instead of the individual arguments.
+#[command]
+pub async fn get_revert_report(
+    app_handle: AppHandle,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    author: Option<String>,
+    exclude_authors: Option<Vec<String>>,
+    repository_id: Option<i64>,
+    deduplicate_patches: Option<bool>,
+    include_orphaned: Option<bool>,
+    filter: Option<TimeFilter>
+) -> Result<RevertReport, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    
+    let filter = filter.unwrap_or_else(|| TimeFilter {
+        start_date: start_date.and_then(|s| chrono::DateTime::parse_from_rfc3339(&s).ok())
+            .map(|dt| dt.with_timezone(&chrono::Utc)),
+        end_date: end_date.and_then(|s| chrono::DateTime::parse_from_rfc3339(&s).ok())
+            .map(|dt| dt.with_timezone(&chrono::Utc)),
+        author,
+        exclude_authors,
+        repository_id,
+        deduplicate_patches: deduplicate_patches.unwrap_or(false),
+        exclude_reverts: false,
+        include_orphaned: include_orphaned.unwrap_or(false),
+        ..TimeFilter::default()
+    });
+    
+    let report = database::get_revert_report(&pool, &filter)
+        .await
+        .map_err(|e| format!("获取回滚报告失败: {}", e))?;
+    
+    Ok(report)
+}
+
+/// The whole timeline; like `get_statistics`, it also takes a whole
+/// `filter` object instead of the individual arguments.
+#[command]
+pub async fn get_commit_timeline(
+    app_handle: AppHandle,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    author: Option<String>,
+    exclude_authors: Option<Vec<String>>,
+    repository_id: Option<i64>,
+    include_orphaned: Option<bool>,
+    filter: Option<TimeFilter>
+) -> Result<Vec<Commit>, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    
+    let filter = filter.unwrap_or_else(|| TimeFilter {
+        start_date: start_date.and_then(|s| chrono::DateTime::parse_from_rfc3339(&s).ok())
+            .map(|dt| dt.with_timezone(&chrono::Utc)),
+        end_date: end_date.and_then(|s| chrono::DateTime::parse_from_rfc3339(&s).ok())
+            .map(|dt| dt.with_timezone(&chrono::Utc)),
+        author,
+        exclude_authors,
+        repository_id,
+        deduplicate_patches: false,
+        exclude_reverts: false,
+        include_orphaned: include_orphaned.unwrap_or(false),
+        ..TimeFilter::default()
+    });
+    
+    let commits = database::get_commit_timeline_with_remote_urls(&pool, &filter)
+        .await
+        .map_err(|e| format!("获取提交时间线失败: {}", e))?;
+    
+    Ok(commits)
+}
+
+/// A page of the timeline; pass the previous page's `next_cursor` to get
+/// the next one.
+#[command]
+pub async fn get_commit_timeline_page(
+    app_handle: AppHandle,
+    filter: TimeFilter,
+    cursor: Option<String>,
+    page_size: Option<u32>
+) -> Result<CommitPage, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    
+    let page = database::get_commit_timeline_page(
+        &pool,
+        &filter,
+        cursor.as_deref(),
+        page_size.unwrap_or(database::DEFAULT_TIMELINE_PAGE_SIZE),
+    )
+    .await
+    .map_err(|e| format!("获取提交时间线失败: {}", e))?;
+    
+    Ok(page)
+}
+
+/// Full-text search over commit messages, authors and changed file paths,
+/// best match first and restricted to the commits `filter` selects. Pass
+/// the previous page's `next_cursor` to get the next page.
+#[command]
+pub async fn search_commits(
+    app_handle: AppHandle,
+    query: String,
+    filter: TimeFilter,
+    cursor: Option<String>,
+    page_size: Option<u32>
+) -> Result<SearchPage, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    
+    let page = search::search_commits(
+        &pool,
+        &query,
+        &filter,
+        cursor.as_deref(),
+        page_size.unwrap_or(search::DEFAULT_SEARCH_PAGE_SIZE),
+    )
+    .await
+    .map_err(|e| format!("搜索提交失败: {}", e))?;
+    
+    Ok(page)
+}
+
+/// Searches the diffs of the commits `filter` selects for a string or
+/// regex, like `git log -S`/`-G` across all tracked repositories, newest
+/// commits first. Matching commits and their hunks are sent as
+/// "diff-search-match" events while the search runs, the commits checked
+/// so far as "diff-search-progress" events, and the summary that is also
+/// returned as a "diff-search-finished" event. The caller picks
+/// `search_id`, so it can tell the events apart and cancel the search
+/// with `cancel_diff_search` before this returns. A repeated query is
+/// replayed from the results of the earlier one.
+#[command]
+pub async fn search_diffs(
+    app_handle: AppHandle,
+    search_id: String,
+    query: DiffSearchQuery,
+    filter: TimeFilter,
+    state: State<'_, AppState>
+) -> Result<DiffSearchFinished, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    
+    let pickaxe = Pickaxe::new(&query, &filter).map_err(|e| format!("搜索差异失败: {}", e))?;
+    let cache_key = diff_search::cache_key(&query, &filter).map_err(|e| format!("搜索差异失败: {}", e))?;
+    let cancelled = state.diff_searches
+        .start(&search_id)
+        .ok_or_else(|| format!("差异搜索 {} 正在进行中", search_id))?;
+    
+    let start_time = std::time::Instant::now();
+    let result = async {
+        if let Some((total, matches)) = state.diff_searches.cached(&cache_key) {
+            let match_count = matches.len() as i64;
+            for diff_match in matches {
+                emit_event(&app_handle, "diff-search-match", DiffSearchMatch { search_id: search_id.clone(), ..diff_match });
+            }
+            return Ok::<_, anyhow::Error>((total, total, match_count, false, true));
+        }
+        
+        let generation = state.diff_searches.generation();
+        let repositories = database::get_repositories(&pool).await?;
+        let total = database::count_filtered_commits(&pool, &filter).await?;
+        
+        // Candidates are read a page at a time while the walk checks the
+        // previous ones, so only a few pages are held at once
+        let (page_sender, mut pages) = tokio::sync::mpsc::channel::<Vec<Commit>>(2);
+        let walk_handle = app_handle.clone();
+        let walk_id = search_id.clone();
+        let walk_cancelled = cancelled.clone();
+        let walk = tauri::async_runtime::spawn_blocking(move || {
+            let mut matches = diff_search::CachedMatches::default();
+            let match_count = std::cell::Cell::new(0);
+            let checked = diff_search::walk_commits(
+                &repositories,
+                std::iter::from_fn(|| pages.blocking_recv()).flatten(),
+                &pickaxe,
+                &walk_cancelled,
+                |commit, hunks| {
+                    let diff_match = DiffSearchMatch { search_id: walk_id.clone(), commit, hunks };
+                    emit_event(&walk_handle, "diff-search-match", &diff_match);
+                    matches.push(diff_match);
+                    match_count.set(match_count.get() + 1);
+                },
+                |checked| {
+                    emit_event(&walk_handle, "diff-search-progress", DiffSearchProgress {
+                        search_id: walk_id.clone(),
+                        checked,
+                        total,
+                        matches: match_count.get(),
+                    });
+                },
+            );
+            (checked, match_count.get(), matches)
+        });
+        
+        let mut cursor: Option<String> = None;
+        let paged = async {
+            loop {
+                let (commits, next_cursor) = database::get_commit_page(
+                    &pool,
+                    &filter,
+                    cursor.as_deref(),
+                    diff_search::CANDIDATE_PAGE_SIZE,
+                )
+                .await?;
+                // A closed channel means the walk was cancelled
+                if commits.is_empty() || page_sender.send(commits).await.is_err() {
+                    return Ok::<_, anyhow::Error>(());
+                }
+                match next_cursor {
+                    Some(next_cursor) => cursor = Some(next_cursor),
+                    None => return Ok(()),
+                }
+            }
+        }.await;
+        // Ends the walk once it has checked the pages sent so far
+        drop(page_sender);
+        
+        let (checked, match_count, matches) = walk
+            .await
+            .map_err(|e| anyhow::anyhow!("Diff search task failed: {}", e))?;
+        paged?;
+        
+        let cancelled = cancelled.load(std::sync::atomic::Ordering::Relaxed);
+        if let Some(matches) = matches.into_matches().filter(|_| !cancelled) {
+            state.diff_searches.store(cache_key, &filter, generation, total, matches);
+        }
+        Ok((checked, total, match_count, cancelled, false))
+    }.await;
+    
+    state.diff_searches.finish(&search_id);
+    
+    let (checked, total, matches, cancelled, cached, error) = match result {
+        Ok((checked, total, matches, cancelled, cached)) => (checked, total, matches, cancelled, cached, None),
+        Err(e) => (0, 0, 0, false, false, Some(format!("搜索差异失败: {}", e))),
+    };
+    let finished = DiffSearchFinished {
+        search_id,
+        checked,
+        total,
+        matches,
+        cancelled,
+        cached,
+        error,
+        elapsed_ms: start_time.elapsed().as_millis() as i64,
+    };
+    println!("🔍 差异搜索 {:?}: {} / {} 个提交，{} 个匹配，耗时 {:?}", query.pattern, finished.checked, finished.total, finished.matches, start_time.elapsed());
+    
+    emit_event(&app_handle, "diff-search-finished", &finished);
+    match &finished.error {
+        Some(error) => Err(error.clone()),
+        None => Ok(finished),
+    }
+}
+
+/// Stops a running diff search; it returns what it found so far. Returns
+/// false when no search with that ID is running.
+#[command]
+pub async fn cancel_diff_search(search_id: String, state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.diff_searches.cancel(&search_id))
+}
+
+// Events only reach windows that are listening, so a failed emit is logged
+fn emit_event<S: serde::Serialize + Clone>(app_handle: &AppHandle, event: &str, payload: S) {
+    if let Err(e) = app_handle.emit(event, payload) {
+        eprintln!("Failed to emit {}: {}", event, e);
+    }
+}
+
+#[command]
+pub async fn get_authors(app_handle: AppHandle, repository_id: Option<i64>) -> Result<Vec<String>, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    
+    let authors = database::get_authors(&pool, repository_id)
+        .await
+        .map_err(|e| format!("获取作者列表失败: {}", e))?;
+    
+    Ok(authors)
+}
+
+/// Parses a filter query such as `author:alice repo:backend size:>100`
+/// into the filter `get_statistics` and the timeline commands take. Syntax
+/// errors come back as `errors` with their positions, not as a failure.
+#[command]
+pub async fn parse_filter_query(app_handle: AppHandle, query: String) -> Result<ParsedFilterQuery, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    
+    let (repositories, groups) = tokio::try_join!(
+        database::get_repositories(&pool),
+        database::get_repository_groups(&pool)
+    )
+    .map_err(|e| format!("解析筛选语句失败: {}", e))?;
+    
+    Ok(match filter_query::parse_filter_query(&query, &repositories, &groups) {
+        Ok(filter) => ParsedFilterQuery { filter: Some(filter), errors: Vec::new() },
+        Err(errors) => ParsedFilterQuery { filter: None, errors },
+    })
+}
+
+/// Completions for the filter query term at `cursor`, a UTF-16 offset.
+#[command]
+pub async fn complete_filter_query(app_handle: AppHandle, query: String, cursor: usize) -> Result<Vec<FilterQuerySuggestion>, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    
+    let (authors, repositories, groups, branches) = tokio::try_join!(
+        database::get_authors(&pool, None),
+        database::get_repositories(&pool),
+        database::get_repository_groups(&pool),
+        database::get_branches(&pool)
+    )
+    .map_err(|e| format!("获取补全建议失败: {}", e))?;
+    
+    Ok(filter_query::complete_filter_query(&query, cursor, &authors, &repositories, &groups, &branches))
+}
+
+#[command]
+pub async fn get_commit_detail(
+    app_handle: AppHandle,
+    repository_id: i64,
+    commit_id: String
+) -> Result<CommitDetail, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    
+    // Get repository info
+    let repositories = database::get_repositories(&pool)
+        .await
+        .map_err(|e| format!("获取仓库信息失败: {}", e))?;
+        
+    let repository = repositories
+        .into_iter()
+        .find(|r| r.id == repository_id)
+        .ok_or_else(|| "仓库未找到".to_string())?;
+    
+    // Create GitAnalyzer and get commit detail
+    let analyzer = GitAnalyzer::new(repository)
+        .map_err(|e| format!("无法打开仓库: {}", e))?;
+        
+    let mut commit_detail = analyzer.get_commit_detail(&commit_id)
+        .map_err(|e| format!("获取提交详情失败: {}", e))?;
+    
+    // List the same patch stored in other repositories or branches
+    if let Some(patch_id) = commit_detail.patch_id.clone() {
+        commit_detail.duplicates = database::get_patch_duplicates(&pool, repository_id, &commit_id, &patch_id)
+            .await
+            .map_err(|e| format!("获取重复提交失败: {}", e))?;
+    }
+    
+    let (reverts, reverted_by) = database::get_commit_revert_links(&pool, repository_id, &commit_id)
+        .await
+        .map_err(|e| format!("获取回滚关系失败: {}", e))?;
+    commit_detail.reverts = reverts;
+    commit_detail.reverted_by = reverted_by;
+    
+    Ok(commit_detail)
+}
+
+/// Backend used by scans, reconciliation and verification.
+#[command]
+pub async fn get_git_backend(state: State<'_, AppState>) -> Result<GitBackendKind, String> {
+    Ok(*state.git_backend.lock().unwrap())
+}
+
+#[command]
+pub async fn set_git_backend(app_handle: AppHandle, backend: GitBackendKind, state: State<'_, AppState>) -> Result<(), String> {
+    let value = serde_json::to_value(backend).map_err(|e| format!("保存设置失败: {}", e))?;
+    store_setting(&app_handle, &state, settings::GIT_BACKEND, value).await
+}
+
+/// Reads the newest scanned commits through both backends and reports
+/// every difference, to check the gix backend before switching to it.
+#[command]
+pub async fn compare_git_backends(
+    app_handle: AppHandle,
+    repository_id: i64,
+    limit: Option<usize>
+) -> Result<BackendComparison, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    
+    let repositories = database::get_repositories(&pool)
+        .await
+        .map_err(|e| format!("获取仓库信息失败: {}", e))?;
+        
+    let repository = repositories
+        .into_iter()
+        .find(|r| r.id == repository_id)
+        .ok_or_else(|| "仓库未找到".to_string())?;
+    
+    tauri::async_runtime::spawn_blocking(move || compare_backends(&repository, limit.unwrap_or(500)))
+        .await
+        .map_err(|e| format!("后端对比失败: {}", e))?
+        .map_err(|e| format!("后端对比失败: {}", e))
+}
+
+#[command]
+pub async fn compare_revisions(
+    app_handle: AppHandle,
+    repository_id: i64,
+    from_revision: String,
+    to_revision: String,
+    three_dot: Option<bool>
+) -> Result<RevisionComparison, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    
+    // Get repository info
+    let repositories = database::get_repositories(&pool)
+        .await
+        .map_err(|e| format!("获取仓库信息失败: {}", e))?;
+        
+    let repository = repositories
+        .into_iter()
+        .find(|r| r.id == repository_id)
+        .ok_or_else(|| "仓库未找到".to_string())?;
+    
+    // Diffing and walking the range is blocking git work
+    let three_dot = three_dot.unwrap_or(false);
+    tauri::async_runtime::spawn_blocking(move || {
+        let analyzer = GitAnalyzer::new(repository)
+            .map_err(|e| format!("无法打开仓库: {}", e))?;
+        
+        analyzer.compare_revisions(&from_revision, &to_revision, three_dot)
+            .map_err(|e| format!("比较版本失败: {}", e))
+    })
+    .await
+    .map_err(|e| format!("比较版本失败: {}", e))?
+}
+
+#[command]
+pub async fn get_file_history(
+    app_handle: AppHandle,
+    repository_id: i64,
+    path: String
+) -> Result<FileHistory, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    
+    // Get repository info
+    let repositories = database::get_repositories(&pool)
+        .await
+        .map_err(|e| format!("获取仓库信息失败: {}", e))?;
+        
+    let repository = repositories
+        .into_iter()
+        .find(|r| r.id == repository_id)
+        .ok_or_else(|| "仓库未找到".to_string())?;
+    
+    load_file_history(&pool, repository, &path)
+        .await
+        .map_err(|e| format!("获取文件历史失败: {}", e))
+}
+
+/// The commits that touched `path`, newest first, following renames back
+/// like `git log --follow`, with the file's authors and monthly changes.
+///
+/// Stored file changes are used where the file was scanned, and git is only
+/// asked where the oldest stored commit of each name got the file from. A
+/// file that was never scanned is looked up by walking git history. Both
+/// git lookups run on the blocking pool.
+async fn load_file_history(pool: &SqlitePool, repository: Repository, path: &str) -> Result<FileHistory> {
+    let repository_id = repository.id;
+    
+    let mut entries: Vec<FileHistoryEntry> = Vec::new();
+    let mut seen_commits = HashSet::new();
+    let mut paths = vec![path.to_string()];
+    let mut current_path = path.to_string();
+    let mut until = None;
+    
+    loop {
+        let batch = database::get_file_history_entries(pool, repository_id, &current_path, until).await?;
+        
+        let batch: Vec<FileHistoryEntry> = batch
+            .into_iter()
+            .filter(|entry| seen_commits.insert(entry.commit_id.clone()))
+            .collect();
+        
+        let oldest = match batch.last() {
+            Some(oldest) => oldest.clone(),
+            None => break,
+        };
+        entries.extend(batch);
+        
+        let rename = {
+            let (repository, commit_id, current_path) = (repository.clone(), oldest.commit_id.clone(), current_path.clone());
+            tauri::async_runtime::spawn_blocking(move || GitAnalyzer::new(repository)?.find_rename_source(&commit_id, &current_path))
+                .await
+                .map_err(|e| anyhow::anyhow!("Rename lookup task failed: {}", e))??
+        };
+        
+        match rename {
+            Some((old_path, additions, deletions)) if !paths.contains(&old_path) => {
+                if let Some(entry) = entries.last_mut() {
+                    entry.old_path = Some(old_path.clone());
+                    entry.additions = additions;
+                    entry.deletions = deletions;
+                }
+                paths.push(old_path.clone());
+                current_path = old_path;
+                until = Some(oldest.timestamp);
+            }
+            _ => break,
+        }
+    }
+    
+    // Fall back to walking git history when the file was never scanned
+    let source = if entries.is_empty() {
+        let (repository, walked_path) = (repository.clone(), path.to_string());
+        entries = tauri::async_runtime::spawn_blocking(move || GitAnalyzer::new(repository)?.get_file_history(&walked_path))
+            .await
+            .map_err(|e| anyhow::anyhow!("File history task failed: {}", e))??;
+        paths = vec![path.to_string()];
+        for entry in &entries {
+            if let Some(old_path) = &entry.old_path {
+                paths.push(old_path.clone());
+            }
+        }
+        "git"
+    } else {
+        "database"
+    };
+    
+    let (authors, change_frequency) = database::summarize_file_history(&entries);
+    
+    Ok(FileHistory {
+        repository_id,
+        path: path.to_string(),
+        source: source.to_string(),
+        paths,
+        commits: entries,
+        authors,
+        change_frequency,
+    })
+}
+
+#[command]
+pub async fn get_line_history(
+    app_handle: AppHandle,
+    repository_id: i64,
+    path: String,
+    revision: Option<String>,
+    start_line: u32,
+    end_line: u32
+) -> Result<LineHistory, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    
+    // Get repository info
+    let repositories = database::get_repositories(&pool)
+        .await
+        .map_err(|e| format!("获取仓库信息失败: {}", e))?;
+        
+    let repository = repositories
+        .into_iter()
+        .find(|r| r.id == repository_id)
+        .ok_or_else(|| "仓库未找到".to_string())?;
+    
+    // Walking history and diffing blobs is blocking git work
+    let revision = revision.unwrap_or_else(|| "HEAD".to_string());
+    tauri::async_runtime::spawn_blocking(move || {
+        let analyzer = GitAnalyzer::new(repository)
+            .map_err(|e| format!("无法打开仓库: {}", e))?;
+        
+        analyzer.get_line_history(&path, &revision, start_line, end_line)
+            .map_err(|e| format!("获取行历史失败: {}", e))
+    })
+    .await
+    .map_err(|e| format!("获取行历史失败: {}", e))?
+}
+
+/// Applies the saved settings the backend itself uses. Runs once the
+/// database is ready at startup.
+pub async fn restore_settings(app_handle: &AppHandle) -> Result<()> {
+    let pool = get_db_pool(app_handle).await?;
+    let state = app_handle.state::<AppState>();
+    
+    *state.git_backend.lock().unwrap() = settings::git_backend(&pool).await?;
+    state.stats_cache.set_persistent(settings::statistics_cache_persistent(&pool).await?);
+    
+    Ok(())
+}
+
+// Applies a stored or reset (`None`) setting to the app state, for the
+// keys that have one, and drops the cached results it may have changed
+async fn apply_setting(pool: &SqlitePool, state: &AppState, key: &str, value: Option<&serde_json::Value>) -> Result<()> {
+    let value = value.cloned().unwrap_or(serde_json::Value::Null);
+    match key {
+        settings::GIT_BACKEND => {
+            *state.git_backend.lock().unwrap() = serde_json::from_value(value).unwrap_or_default();
+        }
+        settings::STATISTICS_CACHE_PERSISTENT => {
+            state.stats_cache.set_persistent(serde_json::from_value(value).unwrap_or(false));
+        }
+        _ => {}
+    }
+    
+    if settings::affects_statistics(key) {
+        state.diff_searches.invalidate(None);
+        state.stats_cache.invalidate(pool, None).await?;
+    }
+    Ok(())
+}
+
+// Saves a setting, applies it and tells every window about it
+async fn store_setting(app_handle: &AppHandle, state: &AppState, key: &str, value: serde_json::Value) -> Result<(), String> {
+    let pool = get_db_pool(app_handle).await.map_err(|e| e.to_string())?;
+    
+    let value = settings::set_setting(&pool, key, &value)
+        .await
+        .map_err(|e| format!("保存设置失败: {}", e))?;
+    
+    apply_setting(&pool, state, key, Some(&value))
+        .await
+        .map_err(|e| format!("应用设置失败: {}", e))?;
+    emit_event(app_handle, "settings-changed", SettingChanged { key: key.to_string(), value: Some(value) });
+    Ok(())
+}
+
+// Tells every window the presets changed, sending the whole list
+async fn emit_filter_presets(app_handle: &AppHandle, pool: &SqlitePool) -> Result<(), String> {
+    let presets = settings::get_filter_presets(pool)
+        .await
+        .map_err(|e| format!("获取筛选预设失败: {}", e))?;
+    
+    emit_event(app_handle, "filter-presets-changed", presets);
+    Ok(())
+}
+
+/// Every saved setting by key. Changes are broadcast as
+/// "settings-changed" events, so windows can keep their copy current.
+#[command]
+pub async fn get_settings(app_handle: AppHandle) -> Result<HashMap<String, serde_json::Value>, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    
+    settings::get_settings(&pool)
+        .await
+        .map_err(|e| format!("获取设置失败: {}", e))
+}
+
+#[command]
+pub async fn set_setting(
+    app_handle: AppHandle,
+    key: String,
+    value: serde_json::Value,
+    state: State<'_, AppState>
+) -> Result<(), String> {
+    store_setting(&app_handle, &state, &key, value).await
+}
+
+/// Resets the setting to its default. Returns false when it wasn't set.
+#[command]
+pub async fn reset_setting(app_handle: AppHandle, key: String, state: State<'_, AppState>) -> Result<bool, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    
+    let deleted = settings::delete_setting(&pool, &key)
+        .await
+        .map_err(|e| format!("重置设置失败: {}", e))?;
+    
+    if deleted {
+        apply_setting(&pool, &state, &key, None)
+            .await
+            .map_err(|e| format!("应用设置失败: {}", e))?;
+        emit_event(&app_handle, "settings-changed", SettingChanged { key, value: None });
+    }
+    Ok(deleted)
+}
+
+/// The filter the app showed last, to restore at startup.
+#[command]
+pub async fn get_last_filter(app_handle: AppHandle) -> Result<Option<TimeFilter>, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    
+    settings::last_filter(&pool)
+        .await
+        .map_err(|e| format!("获取上次的筛选条件失败: {}", e))
+}
+
+#[command]
+pub async fn set_last_filter(app_handle: AppHandle, filter: TimeFilter, state: State<'_, AppState>) -> Result<(), String> {
+    let value = serde_json::to_value(filter).map_err(|e| format!("保存设置失败: {}", e))?;
+    store_setting(&app_handle, &state, settings::LAST_FILTER, value).await
+}
+
+/// Saved filters, by name. Changes are broadcast as
+/// "filter-presets-changed" events carrying the new list.
+#[command]
+pub async fn get_filter_presets(app_handle: AppHandle) -> Result<Vec<FilterPreset>, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    
+    settings::get_filter_presets(&pool)
+        .await
+        .map_err(|e| format!("获取筛选预设失败: {}", e))
+}
+
+fn filter_preset_error(action: &str, e: anyhow::Error) -> String {
+    if e.to_string().contains("UNIQUE constraint failed") {
+        "已存在同名的筛选预设".to_string()
+    } else {
+        format!("{}筛选预设失败: {}", action, e)
+    }
+}
+
+#[command]
+pub async fn create_filter_preset(app_handle: AppHandle, name: String, filter: TimeFilter) -> Result<FilterPreset, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    
+    let preset = settings::create_filter_preset(&pool, &name, &filter)
+        .await
+        .map_err(|e| filter_preset_error("保存", e))?;
+    
+    emit_filter_presets(&app_handle, &pool).await?;
+    Ok(preset)
+}
+
+/// Renames the preset, replaces its filter, or both.
+#[command]
+pub async fn update_filter_preset(
+    app_handle: AppHandle,
+    id: i64,
+    name: Option<String>,
+    filter: Option<TimeFilter>
+) -> Result<FilterPreset, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    
+    let preset = settings::update_filter_preset(&pool, id, name.as_deref(), filter.as_ref())
+        .await
+        .map_err(|e| filter_preset_error("更新", e))?;
+    
+    emit_filter_presets(&app_handle, &pool).await?;
+    Ok(preset)
+}
+
+#[command]
+pub async fn delete_filter_preset(app_handle: AppHandle, id: i64) -> Result<bool, String> {
+    let pool = get_db_pool(&app_handle).await.map_err(|e| e.to_string())?;
+    
+    let deleted = settings::delete_filter_preset(&pool, id)
+        .await
+        .map_err(|e| filter_preset_error("删除", e))?;
+    
+    if deleted {
+        emit_filter_presets(&app_handle, &pool).await?;
+    }
+    Ok(deleted)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git_analyzer::{AnalyzedCommit, FileChange};
+    use crate::git_backend::tests::{Fixture, BASE, TEXT};
+    use crate::test_support::{analyzed, ingest, test_pool};
+
+    fn kinds(issues: &[VerificationIssue]) -> Vec<(String, String, Option<String>)> {
+        let mut kinds: Vec<_> = issues
+            .iter()
+            .map(|issue| (issue.commit_id.clone(), issue.kind.clone(), issue.file_path.clone()))
+            .collect();
+        kinds.sort();
+        kinds
+    }
+
+    #[tokio::test]
+    async fn file_history_follows_renames_in_the_database_and_in_git() {
+        // src/a.rs is edited, renamed to src/b.rs, left alone while README
+        // changes, then moved to lib/c.rs with an edit and edited again
+        let fixture = Fixture::new("file-history");
+        let body: String = (1..=12).map(|line| format!("fn item_{}() {{ value({}) }}\n", line, line)).collect();
+        let edited = body.replace("value(3)", "value(30)");
+        let moved = edited.replace("value(9)", "value(90)");
+        let last = format!("{}fn last() {{}}\n", moved);
+        let versions: [&[(&str, &[u8])]; 6] = [
+            &[("src/a.rs", body.as_bytes()), ("README", b"readme\n")],
+            &[("src/a.rs", edited.as_bytes()), ("README", b"readme\n")],
+            &[("src/b.rs", edited.as_bytes()), ("README", b"readme\n")],
+            &[("src/b.rs", edited.as_bytes()), ("README", b"readme again\n")],
+            &[("lib/c.rs", moved.as_bytes()), ("README", b"readme again\n")],
+            &[("lib/c.rs", last.as_bytes()), ("README", b"readme again\n")],
+        ];
+        let mut commit_ids = Vec::new();
+        for (index, files) in versions.iter().enumerate() {
+            let files: Vec<_> = files.iter().map(|(path, content)| (*path, TEXT, *content)).collect();
+            let parents: Vec<_> = commit_ids.last().copied().into_iter().collect();
+            commit_ids.push(fixture.commit("refs/heads/main", &parents, BASE + index as i64 * 100, &files));
+        }
+        let id = |index: usize| commit_ids[index].to_string();
+
+        let pool = test_pool().await;
+        let repository = database::add_repository(&pool, &fixture.path()).await.unwrap();
+        let summary = |history: &FileHistory| {
+            history
+                .commits
+                .iter()
+                .map(|entry| (entry.commit_id.clone(), entry.path.clone(), entry.old_path.clone(), entry.additions, entry.deletions))
+                .collect::<Vec<_>>()
+        };
+        let expected = vec![
+            (id(5), "lib/c.rs".to_string(), None, 1, 0),
+            (id(4), "lib/c.rs".to_string(), Some("src/b.rs".to_string()), 1, 1),
+            (id(2), "src/b.rs".to_string(), Some("src/a.rs".to_string()), 0, 0),
+            (id(1), "src/a.rs".to_string(), None, 1, 1),
+            (id(0), "src/a.rs".to_string(), None, 12, 0),
+        ];
+        let paths = vec!["lib/c.rs".to_string(), "src/b.rs".to_string(), "src/a.rs".to_string()];
+
+        // Never scanned, so git history is walked
+        let history = load_file_history(&pool, repository.clone(), "lib/c.rs").await.unwrap();
+        assert_eq!(history.source, "git");
+        assert_eq!(history.paths, paths);
+        assert_eq!(summary(&history), expected);
+
+        // Scanned without rename detection, a move is stored as an add and
+        // a delete; git only supplies where each name came from
+        let backend = open_backend(GitBackendKind::Git2, &repository).unwrap();
+        let analyzed: Vec<_> = commit_ids
+            .iter()
+            .map(|commit_id| git_analyzer::analyze_commit(&*backend, &repository, &commit_id.to_string(), &None).unwrap())
+            .collect();
+        ingest(&pool, analyzed).await;
+
+        let history = load_file_history(&pool, repository.clone(), "lib/c.rs").await.unwrap();
+        assert_eq!(history.source, "database");
+        assert_eq!(history.paths, paths);
+        assert_eq!(summary(&history), expected);
+        assert_eq!(history.authors.len(), 1);
+        assert_eq!(history.authors[0].commits, 5);
+    }
+
+    #[tokio::test]
+    async fn verification_reports_repairs_and_deletes_drifted_commits() {
+        let fixture = Fixture::new("verify");
+        let first = fixture.commit("refs/heads/main", &[], BASE, &[("a.txt", TEXT, b"a\n"), ("b.txt", TEXT, b"b\n")]);
+        let second = fixture.commit("refs/heads/main", &[first], BASE + 100, &[("a.txt", TEXT, b"a\nb\n"), ("b.txt", TEXT, b"c\n")]);
+        let third = fixture.commit("refs/heads/main", &[second], BASE + 200, &[("a.txt", TEXT, b"a\nb\nc\n"), ("b.txt", TEXT, b"c\n")]);
+        let (first, second, third) = (first.to_string(), second.to_string(), third.to_string());
+        let missing = "0123456789012345678901234567890123456789".to_string();
+
+        let pool = test_pool().await;
+        let repository = database::add_repository(&pool, &fixture.path()).await.unwrap();
+        let backend = open_backend(GitBackendKind::Git2, &repository).unwrap();
+        let mut stored: Vec<AnalyzedCommit> = [&first, &second, &third]
+            .iter()
+            .map(|commit_id| git_analyzer::analyze_commit(&*backend, &repository, commit_id, &None).unwrap())
+            .collect();
+
+        // Wrong totals on the first commit; on the second a wrong count for
+        // a.txt, no row for b.txt and a row for a file it never touched
+        stored[0].commit.additions += 5;
+        stored[1].file_changes = vec![
+            FileChange { path: "a.txt".to_string(), additions: 7, deletions: 0 },
+            FileChange { path: "gone.txt".to_string(), additions: 1, deletions: 0 },
+        ];
+        stored.push(analyzed(repository.id, &missing, 10, &[("x.txt", 1, 0)]));
+        ingest(&pool, stored).await;
+
+        let verify = |repair: bool| {
+            let (pool, backend) = (&pool, &backend);
+            async move {
+                let commits = database::get_commits_for_verification(pool, repository.id, None).await.unwrap();
+                let actual_stats = commits.iter().map(|commit| backend.diff_stats(&commit.id).unwrap()).collect();
+                verify_commits(pool, repository.id, &commits, actual_stats, repair).await.unwrap()
+            }
+        };
+        let issue = |commit_id: &str, kind: &str, file_path: Option<&str>| (commit_id.to_string(), kind.to_string(), file_path.map(str::to_string));
+        let first_issues = vec![issue(&first, "commit_stats", None)];
+        let mut expected = vec![
+            issue(&first, "commit_stats", None),
+            issue(&missing, "missing_commit", None),
+            issue(&second, "extra_file", Some("gone.txt")),
+            issue(&second, "file_stats", Some("a.txt")),
+            issue(&second, "missing_file", Some("b.txt")),
+        ];
+        expected.sort();
+
+        let (issues, repaired_commits) = verify(false).await;
+        assert_eq!(kinds(&issues), expected);
+        assert_eq!(repaired_commits, 0);
+        assert!(issues.iter().all(|issue| !issue.repaired && issue.repair_error.is_none()));
+
+        // The first repair fails, the others still go through
+        let trigger = format!(
+            "CREATE TEMP TRIGGER keep_first BEFORE DELETE ON file_changes WHEN OLD.commit_id = '{}' BEGIN SELECT RAISE(ABORT, 'locked'); END",
+            first
+        );
+        sqlx::query(&trigger).execute(&pool).await.unwrap();
+        let (issues, repaired_commits) = verify(true).await;
+        assert_eq!(kinds(&issues), expected);
+        assert_eq!(repaired_commits, 2);
+        for issue in &issues {
+            assert_eq!(issue.repaired, issue.commit_id != first, "{:?}", issue);
+            assert_eq!(issue.repair_error.is_some(), issue.commit_id == first, "{:?}", issue);
+        }
+        assert_eq!(database::count_commits(&pool, repository.id).await.unwrap(), 3);
+
+        sqlx::query("DROP TRIGGER keep_first").execute(&pool).await.unwrap();
+        let (issues, _) = verify(false).await;
+        assert_eq!(kinds(&issues), first_issues);
+        let (issues, repaired_commits) = verify(true).await;
+        assert_eq!((kinds(&issues), repaired_commits), (first_issues, 1));
+        assert!(verify(false).await.0.is_empty());
+    }
+
+    #[tokio::test]
+    async fn settings_that_may_feed_statistics_drop_cached_results() {
+        let pool = test_pool().await;
+        let state = AppState::default();
+        // The cached totals, or `value` when they had to be computed
+        let totals = |value: i64| {
+            let (pool, state) = (&pool, &state);
+            async move {
+                state
+                    .stats_cache
+                    .get_or_compute(pool, "totals", &TimeFilter::default(), async { Ok(value) })
+                    .await
+                    .unwrap()
+            }
+        };
+        let apply = |key: &'static str, value: Option<serde_json::Value>| {
+            let (pool, state) = (&pool, &state);
+            async move { apply_setting(pool, state, key, value.as_ref()).await.unwrap() }
+        };
+        assert_eq!(totals(1).await, 1);
+        let searches = state.diff_searches.generation();
+
+        // Settings that only choose how results are read or shown
+        apply(settings::LAST_FILTER, Some(serde_json::to_value(TimeFilter::default()).unwrap())).await;
+        apply(settings::FILTER_QUERY, Some(serde_json::json!("author:alice"))).await;
+        apply(settings::GIT_BACKEND, Some(serde_json::json!("gix"))).await;
+        apply(settings::STATISTICS_CACHE_PERSISTENT, Some(serde_json::json!(true))).await;
+        assert_eq!(*state.git_backend.lock().unwrap(), GitBackendKind::Gix);
+        assert_eq!(totals(2).await, 1);
+        assert_eq!(state.diff_searches.generation(), searches);
+
+        // Anything else may change what the statistics count
+        apply("author_identities", Some(serde_json::json!({ "Alice": ["alice@example.com"] }))).await;
+        assert_eq!(totals(3).await, 3);
+        apply("author_identities", None).await;
+        assert_eq!(totals(4).await, 4);
+        assert_eq!(state.diff_searches.generation(), searches + 2);
+        assert_eq!(state.stats_cache.diagnostics(&pool).await.unwrap().invalidations, 2);
+
+        // Resetting the backend settings restores their defaults
+        apply(settings::GIT_BACKEND, None).await;
+        assert_eq!(*state.git_backend.lock().unwrap(), GitBackendKind::Git2);
+        assert_eq!(totals(5).await, 4);
+    }
+}
@@ -1,7 +1,16 @@
 use sqlx::{SqlitePool, Row};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous};
 use tauri::{AppHandle, Manager};
+use tokio::sync::OnceCell;
 use crate::models::*;
 use anyhow::Result;
+use std::time::Duration;
+
+/// Shared connection pool, created once and awaited by every command.
+#[derive(Default)]
+pub struct DbState {
+    pool: OnceCell<SqlitePool>,
+}
 
 pub async fn init_database(app_handle: &AppHandle) -> Result<SqlitePool> {
     let app_dir = app_handle.path().app_data_dir()
@@ -13,98 +22,47 @@ pub async fn init_database(app_handle: &AppHandle) -> Result<SqlitePool> {
     }
     
     let db_path = app_dir.join("git_stats.db");
-    let db_url = format!("sqlite:{}?mode=rwc", db_path.display());
     
-    println!("Initializing database at: {}", db_url);
-    let pool = SqlitePool::connect(&db_url).await?;
+    // WAL lets stats queries read while a scan is writing; the busy timeout
+    // covers the short windows where writers still contend. REGEXP serves
+    // the message and path filters
+    let options = SqliteConnectOptions::new()
+        .filename(&db_path)
+        .create_if_missing(true)
+        .journal_mode(SqliteJournalMode::Wal)
+        .synchronous(SqliteSynchronous::Normal)
+        .busy_timeout(Duration::from_secs(10))
+        .foreign_keys(true)
+        .pragma("cache_size", "-16000")
+        .pragma("temp_store", "memory")
+        .with_regexp();
     
-    // Create tables
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS repositories (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            path TEXT UNIQUE NOT NULL,
-            name TEXT NOT NULL,
-            last_scanned DATETIME
-        )
-        "#,
-    )
-    .execute(&pool)
-    .await?;
-
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS commits (
-            id TEXT NOT NULL,
-            repository_id INTEGER NOT NULL,
-            repository_name TEXT NOT NULL,
-            author TEXT NOT NULL,
-            email TEXT NOT NULL,
-            message TEXT NOT NULL,
-            timestamp DATETIME NOT NULL,
-            additions INTEGER NOT NULL DEFAULT 0,
-            deletions INTEGER NOT NULL DEFAULT 0,
-            files_changed INTEGER NOT NULL DEFAULT 0,
-            branch TEXT,
-            PRIMARY KEY (id, repository_id),
-            FOREIGN KEY (repository_id) REFERENCES repositories (id) ON DELETE CASCADE
-        )
-        "#
-    )
-    .execute(&pool)
-    .await?;
-
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS file_changes (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            commit_id TEXT NOT NULL,
-            repository_id INTEGER NOT NULL,
-            file_path TEXT NOT NULL,
-            additions INTEGER NOT NULL DEFAULT 0,
-            deletions INTEGER NOT NULL DEFAULT 0,
-            FOREIGN KEY (commit_id, repository_id) REFERENCES commits (id, repository_id) ON DELETE CASCADE
-        )
-        "#
-    )
-    .execute(&pool)
-    .await?;
-
-    // Create indexes for performance
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_commits_timestamp ON commits(timestamp)")
-        .execute(&pool)
-        .await?;
-    
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_commits_author ON commits(author)")
-        .execute(&pool)
-        .await?;
-    
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_commits_repository ON commits(repository_id)")
-        .execute(&pool)
-        .await?;
-
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_file_changes_path ON file_changes(file_path)")
-        .execute(&pool)
+    println!("Initializing database at: {}", db_path.display());
+    let pool = SqlitePoolOptions::new()
+        .max_connections(8)
+        .connect_with(options)
         .await?;
     
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_file_changes_commit ON file_changes(commit_id)")
-        .execute(&pool)
-        .await?;
+    // Create or upgrade the schema
+    crate::migrations::run_migrations(&pool, &db_path).await?;
+    ensure_secondary_indexes(&pool).await?;
+    crate::rollups::ensure_rollups_current(&pool).await?;
+    crate::rollups::recover_deferred_load(&pool).await?;
+    crate::search::ensure_search_index(&pool).await?;
 
     Ok(pool)
 }
 
+/// Returns the shared pool, initializing it on first use. Callers that
+/// arrive while initialization is running wait for it to finish.
 pub async fn get_db_pool(app_handle: &AppHandle) -> Result<SqlitePool> {
-    let app_dir = app_handle.path().app_data_dir()
-        .map_err(|e| anyhow::anyhow!("Failed to get app data dir: {}", e))?;
+    let state = app_handle.state::<DbState>();
     
-    // Ensure the directory exists
-    tokio::fs::create_dir_all(&app_dir).await?;
-    
-    let db_path = app_dir.join("git_stats.db");
-    let db_url = format!("sqlite:{}?mode=rwc", db_path.display());
+    let pool = state.pool
+        .get_or_try_init(|| init_database(app_handle))
+        .await?;
     
-    Ok(SqlitePool::connect(&db_url).await?)
+    Ok(pool.clone())
 }
 
 pub async fn add_repository(pool: &SqlitePool, path: &str) -> Result<Repository> {
@@ -114,33 +72,35 @@ pub async fn add_repository(pool: &SqlitePool, path: &str) -> Result<Repository>
         .to_string_lossy()
         .to_string();
 
-    let result = sqlx::query(
-        "INSERT INTO repositories (path, name) VALUES (?, ?) RETURNING id, path, name, last_scanned"
+    let repository = sqlx::query_as::<_, Repository>(
+        "INSERT INTO repositories (path, name) VALUES (?, ?) RETURNING *"
     )
     .bind(path)
     .bind(&name)
     .fetch_one(pool)
     .await?;
 
-    Ok(Repository {
-        id: result.get("id"),
-        path: result.get("path"),
-        name: result.get("name"),
-        last_scanned: result.get("last_scanned"),
-    })
+    Ok(repository)
 }
 
 pub async fn remove_repository(pool: &SqlitePool, id: i64) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    
     sqlx::query("DELETE FROM repositories WHERE id = ?")
         .bind(id)
-        .execute(pool)
+        .execute(&mut *tx)
         .await?;
+    
+    // The search index has no foreign key to cascade from
+    crate::search::purge_search_rows(&mut tx, Some(id)).await?;
+    
+    tx.commit().await?;
     Ok(())
 }
 
 pub async fn get_repositories(pool: &SqlitePool) -> Result<Vec<Repository>> {
     let repositories = sqlx::query_as::<_, Repository>(
-        "SELECT id, path, name, last_scanned FROM repositories ORDER BY name"
+        "SELECT * FROM repositories ORDER BY archived, COALESCE(display_name, name)"
     )
     .fetch_all(pool)
     .await?;
@@ -148,6 +108,72 @@ pub async fn get_repositories(pool: &SqlitePool) -> Result<Vec<Repository>> {
     Ok(repositories)
 }
 
+// Trims the text; an empty value clears the field
+fn optional_text(value: &str) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+// Accepts #rgb and #rrggbb, stored as lowercase #rrggbb
+fn normalize_color(color: &str) -> Result<Option<String>> {
+    let Some(color) = optional_text(color) else { return Ok(None) };
+    let hex = color.strip_prefix('#').unwrap_or(&color);
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) || !matches!(hex.len(), 3 | 6) {
+        return Err(anyhow::anyhow!("无效的颜色 {}，应为 #RRGGBB", color));
+    }
+    
+    let hex = if hex.len() == 3 {
+        hex.chars().flat_map(|c| [c, c]).collect()
+    } else {
+        hex.to_string()
+    };
+    Ok(Some(format!("#{}", hex.to_lowercase())))
+}
+
+/// Changes what is shown for the repository and whether it is archived.
+/// `None` leaves a field as it is, an empty string clears it.
+pub async fn update_repository(
+    pool: &SqlitePool,
+    id: i64,
+    display_name: Option<&str>,
+    color: Option<&str>,
+    description: Option<&str>,
+    archived: Option<bool>,
+) -> Result<Repository> {
+    let mut tx = pool.begin().await?;
+    
+    let mut repository = sqlx::query_as::<_, Repository>("SELECT * FROM repositories WHERE id = ?")
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("仓库 {} 不存在", id))?;
+    
+    if let Some(display_name) = display_name {
+        repository.display_name = optional_text(display_name);
+    }
+    if let Some(color) = color {
+        repository.color = normalize_color(color)?;
+    }
+    if let Some(description) = description {
+        repository.description = optional_text(description);
+    }
+    if let Some(archived) = archived {
+        repository.archived = archived;
+    }
+    
+    sqlx::query("UPDATE repositories SET display_name = ?, color = ?, description = ?, archived = ? WHERE id = ?")
+        .bind(&repository.display_name)
+        .bind(&repository.color)
+        .bind(&repository.description)
+        .bind(repository.archived)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    
+    tx.commit().await?;
+    Ok(repository)
+}
+
 pub async fn update_repository_scan_time(pool: &SqlitePool, id: i64) -> Result<()> {
     sqlx::query("UPDATE repositories SET last_scanned = ? WHERE id = ?")
         .bind(chrono::Utc::now())
@@ -157,173 +183,1196 @@ pub async fn update_repository_scan_time(pool: &SqlitePool, id: i64) -> Result<(
     Ok(())
 }
 
-pub async fn save_commits(pool: &SqlitePool, commits: &[Commit]) -> Result<()> {
+pub async fn get_repository_groups(pool: &SqlitePool) -> Result<Vec<RepositoryGroup>> {
+    let group_rows = sqlx::query("SELECT id, name FROM repository_groups ORDER BY name")
+        .fetch_all(pool)
+        .await?;
+    let member_rows = sqlx::query("SELECT group_id, repository_id FROM repository_group_members ORDER BY repository_id")
+        .fetch_all(pool)
+        .await?;
+    
+    let mut members: std::collections::HashMap<i64, Vec<i64>> = std::collections::HashMap::new();
+    for row in member_rows {
+        members.entry(row.get("group_id")).or_default().push(row.get("repository_id"));
+    }
+    
+    Ok(group_rows
+        .into_iter()
+        .map(|row| {
+            let id: i64 = row.get("id");
+            RepositoryGroup {
+                id,
+                name: row.get("name"),
+                repository_ids: members.remove(&id).unwrap_or_default(),
+            }
+        })
+        .collect())
+}
+
+pub async fn get_repository_group(pool: &SqlitePool, id: i64) -> Result<RepositoryGroup> {
+    get_repository_groups(pool)
+        .await?
+        .into_iter()
+        .find(|group| group.id == id)
+        .ok_or_else(|| anyhow::anyhow!("仓库分组 {} 不存在", id))
+}
+
+fn group_name(name: &str) -> Result<&str> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(anyhow::anyhow!("分组名称不能为空"));
+    }
+    Ok(name)
+}
+
+// Replaces the group's repositories
+async fn set_group_members(conn: &mut sqlx::SqliteConnection, group_id: i64, repository_ids: &[i64]) -> Result<()> {
+    sqlx::query("DELETE FROM repository_group_members WHERE group_id = ?")
+        .bind(group_id)
+        .execute(&mut *conn)
+        .await?;
+    
+    for repository_id in repository_ids {
+        sqlx::query("INSERT OR IGNORE INTO repository_group_members (group_id, repository_id) VALUES (?, ?)")
+            .bind(group_id)
+            .bind(repository_id)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+pub async fn create_repository_group(pool: &SqlitePool, name: &str, repository_ids: &[i64]) -> Result<RepositoryGroup> {
     let mut tx = pool.begin().await?;
     
-    for commit in commits {
-        sqlx::query(
-            r#"
-            INSERT OR REPLACE INTO commits 
-            (id, repository_id, repository_name, author, email, message, timestamp, additions, deletions, files_changed, branch)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-            "#
-        )
-        .bind(&commit.id)
-        .bind(commit.repository_id)
-        .bind(&commit.repository_name)
-        .bind(&commit.author)
-        .bind(&commit.email)
-        .bind(&commit.message)
-        .bind(commit.timestamp)
-        .bind(commit.additions)
-        .bind(commit.deletions)
-        .bind(commit.files_changed)
-        .bind(&commit.branch)
-        .execute(&mut *tx)
+    let id: i64 = sqlx::query_scalar("INSERT INTO repository_groups (name, created_at) VALUES (?, ?) RETURNING id")
+        .bind(group_name(name)?)
+        .bind(chrono::Utc::now())
+        .fetch_one(&mut *tx)
+        .await?;
+    set_group_members(&mut tx, id, repository_ids).await?;
+    
+    tx.commit().await?;
+    get_repository_group(pool, id).await
+}
+
+/// Renames the group, replaces its repositories, or both.
+pub async fn update_repository_group(
+    pool: &SqlitePool,
+    id: i64,
+    name: Option<&str>,
+    repository_ids: Option<&[i64]>,
+) -> Result<RepositoryGroup> {
+    let mut tx = pool.begin().await?;
+    
+    let exists: Option<i64> = sqlx::query_scalar("SELECT id FROM repository_groups WHERE id = ?")
+        .bind(id)
+        .fetch_optional(&mut *tx)
         .await?;
+    if exists.is_none() {
+        return Err(anyhow::anyhow!("仓库分组 {} 不存在", id));
+    }
+    
+    if let Some(name) = name {
+        sqlx::query("UPDATE repository_groups SET name = ? WHERE id = ?")
+            .bind(group_name(name)?)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    if let Some(repository_ids) = repository_ids {
+        set_group_members(&mut tx, id, repository_ids).await?;
     }
     
     tx.commit().await?;
+    get_repository_group(pool, id).await
+}
+
+/// Returns false when there was no such group.
+pub async fn delete_repository_group(pool: &SqlitePool, id: i64) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM repository_groups WHERE id = ?")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Commits written per transaction unless the caller asks otherwise.
+pub const DEFAULT_INGEST_BATCH_SIZE: usize = 2000;
+
+/// Timeline commits per page unless the caller asks otherwise.
+pub const DEFAULT_TIMELINE_PAGE_SIZE: u32 = 200;
+
+// Upper bound of a timeline page, larger requests are clamped
+const MAX_TIMELINE_PAGE_SIZE: u32 = 2000;
+
+/// Scans at least this large into an empty database drop the secondary
+/// indexes while loading.
+const BULK_LOAD_THRESHOLD: usize = 20_000;
+
+// Rows per multi-row INSERT, kept well below SQLite's bound parameter limit.
+// Full chunks always produce the same SQL text, so sqlx reuses the prepared
+// statement from its cache instead of re-preparing it.
+const COMMIT_ROWS_PER_STATEMENT: usize = 500;
+const FILE_CHANGE_ROWS_PER_STATEMENT: usize = 1000;
+
+// Indexes only used for reads; the primary key and the unique file change
+// index stay in place because ingestion upserts against them
+const SECONDARY_INDEXES: &[(&str, &str)] = &[
+    ("idx_commits_timestamp", "CREATE INDEX IF NOT EXISTS idx_commits_timestamp ON commits(timestamp)"),
+    ("idx_commits_author", "CREATE INDEX IF NOT EXISTS idx_commits_author ON commits(author)"),
+    ("idx_commits_repository", "CREATE INDEX IF NOT EXISTS idx_commits_repository ON commits(repository_id)"),
+    ("idx_commits_patch_id", "CREATE INDEX IF NOT EXISTS idx_commits_patch_id ON commits(patch_id)"),
+    ("idx_file_changes_path", "CREATE INDEX IF NOT EXISTS idx_file_changes_path ON file_changes(file_path)"),
+    ("idx_file_changes_commit", "CREATE INDEX IF NOT EXISTS idx_file_changes_commit ON file_changes(commit_id)"),
+];
+
+/// Recreates secondary indexes a bulk load dropped and never restored,
+/// for instance because the app stopped during the load.
+pub async fn ensure_secondary_indexes(pool: &SqlitePool) -> Result<()> {
+    for (_, statement) in SECONDARY_INDEXES {
+        sqlx::query(statement).execute(pool).await?;
+    }
     Ok(())
 }
 
-pub async fn save_file_changes(pool: &SqlitePool, commit_id: &str, repository_id: i64, file_changes: &[crate::git_analyzer::FileChange]) -> Result<()> {
+/// Stores analyzed commits and their file changes as they arrive on
+/// `commits`, `batch_size` commits per transaction. `expected_commits`
+/// decides whether this is a bulk load, which only happens while the
+/// database holds no commits yet, so no other repository's queries lose
+/// their indexes. Re-ingesting a commit updates its rows in place, so
+/// rescans never duplicate or change the aggregates.
+pub async fn ingest_commits(
+    pool: &SqlitePool,
+    expected_commits: usize,
+    batch_size: usize,
+    commits: tokio::sync::mpsc::Receiver<crate::git_analyzer::AnalyzedCommit>,
+) -> Result<IngestReport> {
+    let start_time = std::time::Instant::now();
+    let batch_size = batch_size.max(1);
+    let defer_indexes = expected_commits >= BULK_LOAD_THRESHOLD
+        && !sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM commits)")
+            .fetch_one(pool)
+            .await?;
+    
+    // Bulk loads rebuild the rollups and the search index once at the end
+    // instead of per batch; the marker covers a load that never gets there
+    let mut earlier_load_pending = false;
+    if defer_indexes {
+        println!("📦 批量导入 {} 个提交，暂时移除二级索引", expected_commits);
+        earlier_load_pending = crate::rollups::begin_deferred_load(pool).await?;
+        for (name, _) in SECONDARY_INDEXES {
+            sqlx::query(&format!("DROP INDEX IF EXISTS {}", name))
+                .execute(pool)
+                .await?;
+        }
+    }
+    
+    let mut repository_ids = std::collections::HashSet::new();
+    let result = ingest_batches(pool, batch_size, commits, !defer_indexes, &mut repository_ids).await;
+    
+    // Indexes are restored even when a batch failed, earlier batches are committed
+    if defer_indexes {
+        let index_start = std::time::Instant::now();
+        ensure_secondary_indexes(pool).await?;
+        println!("📦 重建索引耗时: {:?}", index_start.elapsed());
+        
+        let repository_ids = (!earlier_load_pending).then_some(&repository_ids);
+        crate::rollups::finish_deferred_load(pool, repository_ids).await?;
+    }
+    
+    let (commit_count, batches, file_changes) = result?;
+    let elapsed = start_time.elapsed();
+    let seconds = elapsed.as_secs_f64().max(f64::EPSILON);
+    
+    let report = IngestReport {
+        commits: commit_count,
+        file_changes,
+        batches,
+        batch_size: batch_size as i64,
+        deferred_indexes: defer_indexes,
+        elapsed_ms: elapsed.as_millis() as i64,
+        commits_per_second: commit_count as f64 / seconds,
+        file_changes_per_second: file_changes as f64 / seconds,
+    };
+    
+    println!(
+        "💾 写入 {} 个提交 / {} 个文件变更，{} 批，耗时 {:?} ({:.0} 提交/秒, {:.0} 文件变更/秒)",
+        report.commits,
+        report.file_changes,
+        report.batches,
+        elapsed,
+        report.commits_per_second,
+        report.file_changes_per_second
+    );
+    
+    Ok(report)
+}
+
+async fn ingest_batches(
+    pool: &SqlitePool,
+    batch_size: usize,
+    mut commits: tokio::sync::mpsc::Receiver<crate::git_analyzer::AnalyzedCommit>,
+    refresh_derived: bool,
+    repository_ids: &mut std::collections::HashSet<i64>,
+) -> Result<(i64, i64, i64)> {
+    let mut commit_count = 0;
+    let mut batches = 0;
+    let mut file_changes = 0;
+    let mut batch = Vec::with_capacity(batch_size);
+    
+    loop {
+        let limit = batch_size - batch.len();
+        let received = commits.recv_many(&mut batch, limit).await;
+        
+        // Flush full batches, and whatever is left once the sender is done
+        if batch.len() < batch_size && received > 0 {
+            continue;
+        }
+        if batch.is_empty() {
+            break;
+        }
+        
+        repository_ids.extend(batch.iter().map(|ac| ac.commit.repository_id));
+        file_changes += write_batch(pool, &batch, refresh_derived).await?;
+        commit_count += batch.len() as i64;
+        batches += 1;
+        batch.clear();
+    }
+    
+    Ok((commit_count, batches, file_changes))
+}
+
+async fn write_batch(pool: &SqlitePool, batch: &[crate::git_analyzer::AnalyzedCommit], refresh_derived: bool) -> Result<i64> {
     let mut tx = pool.begin().await?;
     
-    for file_change in file_changes {
+    for chunk in batch.chunks(COMMIT_ROWS_PER_STATEMENT) {
+        insert_commit_rows(&mut tx, chunk).await?;
+    }
+    
+    let rows: Vec<FileChangeRow> = batch
+        .iter()
+        .flat_map(|ac| ac.file_changes.iter().map(move |fc| (ac.commit.repository_id, ac.commit.id.as_str(), fc)))
+        .collect();
+    
+    for chunk in rows.chunks(FILE_CHANGE_ROWS_PER_STATEMENT) {
+        insert_file_change_rows(&mut tx, chunk).await?;
+    }
+    
+    delete_stale_file_changes(&mut tx, batch).await?;
+    
+    if refresh_derived {
+        for (repository_id, hours) in crate::rollups::batch_hours(batch.iter().map(|ac| &ac.commit)) {
+            crate::rollups::refresh_rollups(&mut tx, repository_id, Some(&hours)).await?;
+        }
+        for (repository_id, commit_ids) in crate::search::batch_commit_ids(batch.iter().map(|ac| &ac.commit)) {
+            crate::search::index_commits(&mut tx, repository_id, Some(&commit_ids)).await?;
+        }
+    }
+    
+    tx.commit().await?;
+    Ok(rows.len() as i64)
+}
+
+async fn insert_commit_rows(
+    conn: &mut sqlx::SqliteConnection,
+    rows: &[crate::git_analyzer::AnalyzedCommit],
+) -> Result<()> {
+    let values = vec!["(?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"; rows.len()].join(", ");
+    let query = format!(
+        r#"
+        INSERT INTO commits 
+        (id, repository_id, repository_name, author, email, message, timestamp, additions, deletions, files_changed, branch, remote_url, patch_id, inverse_patch_id, is_merge)
+        VALUES {}
+        ON CONFLICT(id, repository_id) DO UPDATE SET
+            repository_name = excluded.repository_name,
+            author = excluded.author,
+            email = excluded.email,
+            message = excluded.message,
+            timestamp = excluded.timestamp,
+            additions = excluded.additions,
+            deletions = excluded.deletions,
+            files_changed = excluded.files_changed,
+            branch = excluded.branch,
+            remote_url = excluded.remote_url,
+            patch_id = excluded.patch_id,
+            inverse_patch_id = excluded.inverse_patch_id,
+            is_merge = excluded.is_merge,
+            orphaned_at = NULL
+        "#,
+        values
+    );
+    
+    let mut query_builder = sqlx::query(&query);
+    for analyzed_commit in rows {
+        let commit = &analyzed_commit.commit;
+        query_builder = query_builder
+            .bind(&commit.id)
+            .bind(commit.repository_id)
+            .bind(&commit.repository_name)
+            .bind(&commit.author)
+            .bind(&commit.email)
+            .bind(&commit.message)
+            .bind(commit.timestamp)
+            .bind(commit.additions)
+            .bind(commit.deletions)
+            .bind(commit.files_changed)
+            .bind(&commit.branch)
+            .bind(&commit.remote_url)
+            .bind(&commit.patch_id)
+            .bind(&commit.inverse_patch_id)
+            .bind(commit.is_merge);
+    }
+    
+    query_builder.execute(&mut *conn).await?;
+    Ok(())
+}
+
+// A file change with the repository and commit it belongs to
+type FileChangeRow<'a> = (i64, &'a str, &'a crate::git_analyzer::FileChange);
+
+async fn insert_file_change_rows(
+    conn: &mut sqlx::SqliteConnection,
+    rows: &[FileChangeRow<'_>],
+) -> Result<()> {
+    let values = vec!["(?, ?, ?, ?, ?)"; rows.len()].join(", ");
+    let query = format!(
+        r#"
+        INSERT INTO file_changes 
+        (commit_id, repository_id, file_path, additions, deletions)
+        VALUES {}
+        ON CONFLICT(repository_id, commit_id, file_path) DO UPDATE SET
+            additions = excluded.additions,
+            deletions = excluded.deletions
+        "#,
+        values
+    );
+    
+    let mut query_builder = sqlx::query(&query);
+    for (repository_id, commit_id, file_change) in rows {
+        query_builder = query_builder
+            .bind(*commit_id)
+            .bind(*repository_id)
+            .bind(&file_change.path)
+            .bind(file_change.additions)
+            .bind(file_change.deletions);
+    }
+    
+    query_builder.execute(&mut *conn).await?;
+    Ok(())
+}
+
+// Drop rows for files the batch's commits no longer report. Commit ids have
+// a fixed length, so "<id>:<path>" keys are unambiguous.
+async fn delete_stale_file_changes(
+    conn: &mut sqlx::SqliteConnection,
+    batch: &[crate::git_analyzer::AnalyzedCommit],
+) -> Result<()> {
+    let mut by_repository: std::collections::HashMap<i64, (Vec<&str>, Vec<String>)> = std::collections::HashMap::new();
+    
+    for analyzed_commit in batch {
+        let entry = by_repository.entry(analyzed_commit.commit.repository_id).or_default();
+        entry.0.push(&analyzed_commit.commit.id);
+        for file_change in &analyzed_commit.file_changes {
+            entry.1.push(format!("{}:{}", analyzed_commit.commit.id, file_change.path));
+        }
+    }
+    
+    for (repository_id, (commit_ids, keys)) in by_repository {
         sqlx::query(
             r#"
-            INSERT OR REPLACE INTO file_changes 
-            (commit_id, repository_id, file_path, additions, deletions)
-            VALUES (?, ?, ?, ?, ?)
+            DELETE FROM file_changes
+            WHERE repository_id = ?
+              AND commit_id IN (SELECT value FROM json_each(?))
+              AND commit_id || ':' || file_path NOT IN (SELECT value FROM json_each(?))
             "#
         )
+        .bind(repository_id)
+        .bind(serde_json::to_string(&commit_ids)?)
+        .bind(serde_json::to_string(&keys)?)
+        .execute(&mut *conn)
+        .await?;
+    }
+    
+    Ok(())
+}
+
+pub async fn get_commit_timeline(pool: &SqlitePool, filter: &TimeFilter) -> Result<Vec<Commit>> {
+    let (base_query, params) = crate::filters::build_commit_query(filter)?;
+    let query = format!("SELECT * {} ORDER BY timestamp DESC, repository_id DESC, id DESC", base_query);
+    
+    let mut query_builder = sqlx::query_as::<_, Commit>(&query);
+    
+    for param in params {
+        query_builder = query_builder.bind(param);
+    }
+    
+    let commits = query_builder.fetch_all(pool).await?;
+    Ok(commits)
+}
+
+pub async fn get_commit_timeline_with_remote_urls(pool: &SqlitePool, filter: &TimeFilter) -> Result<Vec<Commit>> {
+    let mut commits = get_commit_timeline(pool, filter).await?;
+    resolve_remote_urls(pool, &mut commits).await?;
+    Ok(commits)
+}
+
+/// One page of the timeline, newest first, with the repositories' current
+/// remote URLs. The cursor is the last commit of the previous page, so
+/// commits written while paging neither shift nor repeat entries.
+pub async fn get_commit_timeline_page(
+    pool: &SqlitePool,
+    filter: &TimeFilter,
+    cursor: Option<&str>,
+    page_size: u32,
+) -> Result<CommitPage> {
+    let page_size = page_size.clamp(1, MAX_TIMELINE_PAGE_SIZE);
+    let total_count = count_filtered_commits(pool, filter).await?;
+    let (mut commits, next_cursor) = get_commit_page(pool, filter, cursor, page_size).await?;
+    
+    resolve_remote_urls(pool, &mut commits).await?;
+    
+    Ok(CommitPage {
+        commits,
+        next_cursor,
+        total_count,
+        page_size,
+    })
+}
+
+/// Number of commits the filter selects.
+pub async fn count_filtered_commits(pool: &SqlitePool, filter: &TimeFilter) -> Result<i64> {
+    let (base_query, params) = crate::filters::build_commit_query(filter)?;
+    let count_query = format!("SELECT COUNT(*) {}", base_query);
+    let mut count_builder = sqlx::query_scalar::<_, i64>(&count_query);
+    for param in &params {
+        count_builder = count_builder.bind(param);
+    }
+    Ok(count_builder.fetch_one(pool).await?)
+}
+
+/// Up to `page_size` commits after `cursor` in timeline order, without
+/// remote URLs, and the cursor of the next page if there is one.
+pub async fn get_commit_page(
+    pool: &SqlitePool,
+    filter: &TimeFilter,
+    cursor: Option<&str>,
+    page_size: u32,
+) -> Result<(Vec<Commit>, Option<String>)> {
+    let (base_query, params) = crate::filters::build_commit_query(filter)?;
+    
+    let mut query = format!("SELECT * {}", base_query);
+    let after = cursor.map(parse_timeline_cursor).transpose()?;
+    if after.is_some() {
+        // The plain timestamp bound lets SQLite use the timestamp index
+        query.push_str(" AND timestamp <= ? AND (timestamp, repository_id, id) < (?, ?, ?)");
+    }
+    query.push_str(" ORDER BY timestamp DESC, repository_id DESC, id DESC LIMIT ?");
+    
+    let mut query_builder = sqlx::query_as::<_, Commit>(&query);
+    for param in &params {
+        query_builder = query_builder.bind(param);
+    }
+    if let Some((timestamp, repository_id, id)) = &after {
+        query_builder = query_builder
+            .bind(timestamp)
+            .bind(timestamp)
+            .bind(repository_id)
+            .bind(id);
+    }
+    
+    // One extra row tells whether another page follows
+    let mut commits = query_builder
+        .bind(page_size as i64 + 1)
+        .fetch_all(pool)
+        .await?;
+    
+    let next_cursor = if commits.len() > page_size as usize {
+        commits.truncate(page_size as usize);
+        commits.last().map(|commit| {
+            format!("{}|{}|{}", commit.timestamp.to_rfc3339(), commit.repository_id, commit.id)
+        })
+    } else {
+        None
+    };
+    
+    Ok((commits, next_cursor))
+}
+
+// Splits a timeline cursor into its timestamp, repository ID and commit ID
+fn parse_timeline_cursor(cursor: &str) -> Result<(String, i64, String)> {
+    let invalid = || anyhow::anyhow!("Invalid timeline cursor: {}", cursor);
+    
+    let mut parts = cursor.splitn(3, '|');
+    let timestamp = parts.next().ok_or_else(invalid)?;
+    let repository_id = parts.next().and_then(|id| id.parse().ok()).ok_or_else(invalid)?;
+    let id = parts.next().ok_or_else(invalid)?;
+    
+    // Stored timestamps are compared as text, so the cursor must use their format
+    let timestamp = chrono::DateTime::parse_from_rfc3339(timestamp)
+        .map_err(|_| invalid())?
+        .with_timezone(&chrono::Utc)
+        .to_rfc3339();
+    
+    Ok((timestamp, repository_id, id.to_string()))
+}
+
+/// Sets the commits' remote URLs to their repositories' current ones,
+/// opening every repository only once.
+pub async fn resolve_remote_urls(pool: &SqlitePool, commits: &mut [Commit]) -> Result<()> {
+    let repositories = get_repositories(pool).await?;
+    let repo_map: std::collections::HashMap<i64, String> = repositories
+        .into_iter()
+        .map(|repo| (repo.id, repo.path))
+        .collect();
+    
+    let mut remote_urls: std::collections::HashMap<i64, Option<String>> = std::collections::HashMap::new();
+    for commit in commits.iter_mut() {
+        if let Some(repo_path) = repo_map.get(&commit.repository_id) {
+            commit.remote_url = remote_urls
+                .entry(commit.repository_id)
+                .or_insert_with(|| crate::git_analyzer::get_remote_url_for_path(repo_path))
+                .clone();
+        }
+    }
+    
+    Ok(())
+}
+
+/// Distinct authors of the reachable commits, for the author filters.
+pub async fn get_authors(pool: &SqlitePool, repository_id: Option<i64>) -> Result<Vec<String>> {
+    let mut query = "SELECT DISTINCT author FROM commits WHERE orphaned_at IS NULL".to_string();
+    if repository_id.is_some() {
+        query.push_str(" AND repository_id = ?");
+    }
+    query.push_str(" ORDER BY author");
+    
+    let mut query_builder = sqlx::query_scalar::<_, String>(&query);
+    if let Some(repository_id) = repository_id {
+        query_builder = query_builder.bind(repository_id);
+    }
+    
+    let authors = query_builder.fetch_all(pool).await?;
+    Ok(authors)
+}
+
+/// Distinct branches of the reachable commits, for completing filter queries.
+pub async fn get_branches(pool: &SqlitePool) -> Result<Vec<String>> {
+    let branches = sqlx::query_scalar::<_, String>(
+        "SELECT DISTINCT branch FROM commits WHERE orphaned_at IS NULL AND branch IS NOT NULL AND branch != '' ORDER BY branch"
+    )
+    .fetch_all(pool)
+    .await?;
+    
+    Ok(branches)
+}
+
+pub async fn get_commits_for_verification(pool: &SqlitePool, repository_id: i64, sample_size: Option<u32>) -> Result<Vec<Commit>> {
+    let commits = match sample_size {
+        Some(sample_size) => {
+            sqlx::query_as::<_, Commit>(
+                "SELECT * FROM commits WHERE repository_id = ? ORDER BY RANDOM() LIMIT ?"
+            )
+            .bind(repository_id)
+            .bind(sample_size)
+            .fetch_all(pool)
+            .await?
+        }
+        None => {
+            sqlx::query_as::<_, Commit>(
+                "SELECT * FROM commits WHERE repository_id = ? ORDER BY timestamp DESC"
+            )
+            .bind(repository_id)
+            .fetch_all(pool)
+            .await?
+        }
+    };
+    
+    Ok(commits)
+}
+
+pub async fn count_commits(pool: &SqlitePool, repository_id: i64) -> Result<i32> {
+    let count: i32 = sqlx::query_scalar("SELECT COUNT(*) FROM commits WHERE repository_id = ?")
+        .bind(repository_id)
+        .fetch_one(pool)
+        .await?;
+    Ok(count)
+}
+
+pub async fn get_stored_file_changes(pool: &SqlitePool, repository_id: i64, commit_id: &str) -> Result<Vec<(String, i32, i32)>> {
+    let rows = sqlx::query(
+        "SELECT file_path, additions, deletions FROM file_changes WHERE repository_id = ? AND commit_id = ? ORDER BY id"
+    )
+    .bind(repository_id)
+    .bind(commit_id)
+    .fetch_all(pool)
+    .await?;
+    
+    Ok(rows
+        .into_iter()
+        .map(|row| (row.get("file_path"), row.get("additions"), row.get("deletions")))
+        .collect())
+}
+
+/// Overwrites the stored stats and file changes of a commit with freshly
+/// computed values.
+pub async fn repair_commit(
+    pool: &SqlitePool,
+    repository_id: i64,
+    commit_id: &str,
+    additions: i32,
+    deletions: i32,
+    files_changed: i32,
+    file_changes: &[crate::git_analyzer::FileChange]
+) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    
+    sqlx::query("UPDATE commits SET additions = ?, deletions = ?, files_changed = ? WHERE id = ? AND repository_id = ?")
+        .bind(additions)
+        .bind(deletions)
+        .bind(files_changed)
+        .bind(commit_id)
+        .bind(repository_id)
+        .execute(&mut *tx)
+        .await?;
+    
+    sqlx::query("DELETE FROM file_changes WHERE commit_id = ? AND repository_id = ?")
         .bind(commit_id)
         .bind(repository_id)
-        .bind(&file_change.path)
-        .bind(file_change.additions)
-        .bind(file_change.deletions)
         .execute(&mut *tx)
         .await?;
+    
+    let rows: Vec<FileChangeRow> = file_changes
+        .iter()
+        .map(|file_change| (repository_id, commit_id, file_change))
+        .collect();
+    for chunk in rows.chunks(FILE_CHANGE_ROWS_PER_STATEMENT) {
+        insert_file_change_rows(&mut tx, chunk).await?;
     }
     
+    let hours = crate::rollups::commit_hours(&mut tx, repository_id, &[commit_id.to_string()]).await?;
+    crate::rollups::refresh_rollups(&mut tx, repository_id, Some(&hours)).await?;
+    crate::search::index_commits(&mut tx, repository_id, Some(&[commit_id.to_string()])).await?;
+    
     tx.commit().await?;
     Ok(())
 }
 
-pub async fn get_commit_timeline(pool: &SqlitePool, filter: &TimeFilter) -> Result<Vec<Commit>> {
-    let mut query = "SELECT * FROM commits WHERE 1=1".to_string();
-    let mut params: Vec<String> = Vec::new();
+/// Removes a stored commit that no longer exists in the repository.
+pub async fn delete_commit(pool: &SqlitePool, repository_id: i64, commit_id: &str) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    let hours = crate::rollups::commit_hours(&mut tx, repository_id, &[commit_id.to_string()]).await?;
+    crate::search::remove_commits(&mut tx, repository_id, &[commit_id.to_string()]).await?;
+    
+    sqlx::query("DELETE FROM file_changes WHERE commit_id = ? AND repository_id = ?")
+        .bind(commit_id)
+        .bind(repository_id)
+        .execute(&mut *tx)
+        .await?;
+    
+    sqlx::query("DELETE FROM commit_reverts WHERE repository_id = ? AND (revert_commit_id = ? OR reverted_commit_id = ?)")
+        .bind(repository_id)
+        .bind(commit_id)
+        .bind(commit_id)
+        .execute(&mut *tx)
+        .await?;
+    
+    sqlx::query("DELETE FROM commits WHERE id = ? AND repository_id = ?")
+        .bind(commit_id)
+        .bind(repository_id)
+        .execute(&mut *tx)
+        .await?;
     
-    if let Some(start_date) = &filter.start_date {
-        query.push_str(" AND timestamp >= ?");
-        params.push(start_date.to_rfc3339());
+    crate::rollups::refresh_rollups(&mut tx, repository_id, Some(&hours)).await?;
+    
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Marks stored commits that are no longer reachable from any scanned ref
+/// as orphaned, and clears the mark on commits that became reachable again.
+pub async fn reconcile_commits(pool: &SqlitePool, repository_id: i64, reachable: &std::collections::HashSet<String>) -> Result<ReconcileResult> {
+    let mut tx = pool.begin().await?;
+    
+    let rows = sqlx::query("SELECT id, orphaned_at IS NOT NULL as is_orphaned FROM commits WHERE repository_id = ?")
+        .bind(repository_id)
+        .fetch_all(&mut *tx)
+        .await?;
+    
+    let now = chrono::Utc::now();
+    let mut orphaned = 0;
+    let mut restored = 0;
+    let mut total_orphaned = 0;
+    let mut changed = Vec::new();
+    
+    for row in &rows {
+        let id: String = row.get("id");
+        let is_orphaned: bool = row.get("is_orphaned");
+        let is_reachable = reachable.contains(&id);
+        
+        if !is_reachable {
+            total_orphaned += 1;
+        }
+        
+        if is_reachable == is_orphaned {
+            let orphaned_at = if is_reachable { None } else { Some(now) };
+            sqlx::query("UPDATE commits SET orphaned_at = ? WHERE id = ? AND repository_id = ?")
+                .bind(orphaned_at)
+                .bind(&id)
+                .bind(repository_id)
+                .execute(&mut *tx)
+                .await?;
+            
+            if is_reachable {
+                restored += 1;
+            } else {
+                orphaned += 1;
+            }
+            changed.push(id);
+        }
+    }
+    
+    let hours = crate::rollups::commit_hours(&mut tx, repository_id, &changed).await?;
+    crate::rollups::refresh_rollups(&mut tx, repository_id, Some(&hours)).await?;
+    
+    tx.commit().await?;
+    
+    Ok(ReconcileResult {
+        repository_id,
+        checked: rows.len() as i32,
+        orphaned,
+        restored,
+        total_orphaned,
+    })
+}
+
+/// Permanently deletes orphaned commits together with their file changes
+/// and revert relations. Returns the number of deleted commits.
+pub async fn purge_orphaned_commits(pool: &SqlitePool, repository_id: Option<i64>) -> Result<u64> {
+    let mut tx = pool.begin().await?;
+    
+    let condition = if repository_id.is_some() {
+        "c.orphaned_at IS NOT NULL AND c.repository_id = ?"
+    } else {
+        "c.orphaned_at IS NOT NULL"
+    };
+    
+    let statements = [
+        format!(
+            "DELETE FROM file_changes WHERE EXISTS (SELECT 1 FROM commits c
+               WHERE c.id = file_changes.commit_id AND c.repository_id = file_changes.repository_id AND {})",
+            condition
+        ),
+        format!(
+            "DELETE FROM commit_reverts WHERE EXISTS (SELECT 1 FROM commits c
+               WHERE c.repository_id = commit_reverts.repository_id
+               AND c.id IN (commit_reverts.revert_commit_id, commit_reverts.reverted_commit_id) AND {})",
+            condition
+        ),
+        format!("DELETE FROM commits AS c WHERE {}", condition),
+    ];
+    
+    let mut purged = 0;
+    for statement in &statements {
+        let mut query = sqlx::query(statement);
+        if let Some(repository_id) = repository_id {
+            query = query.bind(repository_id);
+        }
+        purged = query.execute(&mut *tx).await?.rows_affected();
     }
     
-    if let Some(end_date) = &filter.end_date {
-        query.push_str(" AND timestamp <= ?");
-        params.push(end_date.to_rfc3339());
+    crate::rollups::purge_orphaned_rollups(&mut tx, repository_id).await?;
+    crate::search::purge_search_rows(&mut tx, repository_id).await?;
+    
+    tx.commit().await?;
+    // The last statement deletes the commits themselves
+    Ok(purged)
+}
+
+pub async fn get_patch_duplicates(pool: &SqlitePool, repository_id: i64, commit_id: &str, patch_id: &str) -> Result<Vec<Commit>> {
+    let duplicates = sqlx::query_as::<_, Commit>(
+        r#"
+        SELECT * FROM commits
+        WHERE patch_id = ? AND NOT (id = ? AND repository_id = ?) AND orphaned_at IS NULL
+        ORDER BY timestamp
+        "#
+    )
+    .bind(patch_id)
+    .bind(commit_id)
+    .bind(repository_id)
+    .fetch_all(pool)
+    .await?;
+    
+    Ok(duplicates)
+}
+
+/// Commits whose revert messages are read per statement.
+const REVERT_MESSAGE_CHUNK: usize = 500;
+
+/// Detects reverts among newly stored commits, both from `git revert`
+/// messages and from commits whose patch is the exact inverse of an
+/// earlier one, and records them in `commit_reverts`.
+pub async fn detect_reverts(pool: &SqlitePool, repository_id: i64, commit_ids: &[String]) -> Result<u64> {
+    let mut tx = pool.begin().await?;
+    let mut detected = 0;
+    
+    // Only the new commits' messages; older ones were read by earlier scans
+    let mut rows = Vec::new();
+    for chunk in commit_ids.chunks(REVERT_MESSAGE_CHUNK) {
+        let message_query = format!(
+            "SELECT id, message FROM commits
+             WHERE repository_id = ? AND id IN ({}) AND message LIKE '%This reverts commit %'",
+            vec!["?"; chunk.len()].join(", ")
+        );
+        let mut query_builder = sqlx::query(&message_query).bind(repository_id);
+        for commit_id in chunk {
+            query_builder = query_builder.bind(commit_id);
+        }
+        rows.extend(query_builder.fetch_all(&mut *tx).await?);
     }
     
-    if let Some(author) = &filter.author {
-        query.push_str(" AND author = ?");
-        params.push(author.clone());
+    for row in rows {
+        let revert_id: String = row.get("id");
+        let message: String = row.get("message");
+        
+        for reverted_id in crate::git_analyzer::parse_reverted_commit_ids(&message) {
+            // Messages may carry abbreviated IDs. IDs are lowercase hex, so
+            // the prefix range can use the primary key where LIKE can't
+            let matches: Vec<String> = sqlx::query_scalar(
+                "SELECT id FROM commits WHERE repository_id = ? AND id >= ? AND id < ? AND id != ? LIMIT 2"
+            )
+            .bind(repository_id)
+            .bind(&reverted_id)
+            .bind(format!("{}g", reverted_id))
+            .bind(&revert_id)
+            .fetch_all(&mut *tx)
+            .await?;
+            
+            // An ambiguous prefix names no commit in particular
+            let [reverted_id] = matches.as_slice() else { continue };
+            let result = sqlx::query(
+                "INSERT OR IGNORE INTO commit_reverts (repository_id, revert_commit_id, reverted_commit_id, detection)
+                 VALUES (?, ?, ?, 'message')"
+            )
+            .bind(repository_id)
+            .bind(&revert_id)
+            .bind(reverted_id)
+            .execute(&mut *tx)
+            .await?;
+            detected += result.rows_affected();
+        }
     }
     
-    if let Some(exclude_authors) = &filter.exclude_authors {
-        if !exclude_authors.is_empty() {
-            let placeholders: Vec<String> = exclude_authors.iter().map(|_| "?".to_string()).collect();
-            query.push_str(&format!(" AND author NOT IN ({})", placeholders.join(",")));
-            for author in exclude_authors {
-                params.push(author.clone());
+    let result = sqlx::query(
+        r#"
+        INSERT OR IGNORE INTO commit_reverts (repository_id, revert_commit_id, reverted_commit_id, detection)
+        SELECT r.repository_id, r.id, o.id, 'patch'
+        FROM commits r
+        JOIN commits o ON o.repository_id = r.repository_id AND o.patch_id = r.inverse_patch_id
+        WHERE r.repository_id = ? AND r.id != o.id AND r.is_merge = 0 AND o.is_merge = 0
+          AND (r.timestamp > o.timestamp
+               -- Within the same second only a "Revert ..." commit can be the revert
+               OR (r.timestamp = o.timestamp AND r.message LIKE 'Revert%' AND o.message NOT LIKE 'Revert%'))
+        "#
+    )
+    .bind(repository_id)
+    .execute(&mut *tx)
+    .await?;
+    detected += result.rows_affected();
+    
+    tx.commit().await?;
+    Ok(detected)
+}
+
+pub async fn get_commit_revert_links(pool: &SqlitePool, repository_id: i64, commit_id: &str) -> Result<(Vec<String>, Vec<String>)> {
+    let reverts = sqlx::query_scalar::<_, String>(
+        "SELECT reverted_commit_id FROM commit_reverts WHERE repository_id = ? AND revert_commit_id = ?"
+    )
+    .bind(repository_id)
+    .bind(commit_id)
+    .fetch_all(pool)
+    .await?;
+    
+    let reverted_by = sqlx::query_scalar::<_, String>(
+        "SELECT revert_commit_id FROM commit_reverts WHERE repository_id = ? AND reverted_commit_id = ?"
+    )
+    .bind(repository_id)
+    .bind(commit_id)
+    .fetch_all(pool)
+    .await?;
+    
+    Ok((reverts, reverted_by))
+}
+
+pub async fn get_revert_report(pool: &SqlitePool, filter: &TimeFilter) -> Result<RevertReport> {
+    // Rates are always computed over all commits, reverts included
+    let report_filter = TimeFilter {
+        exclude_reverts: false,
+        ..filter.clone()
+    };
+    let (base_query, params) = crate::filters::build_commit_query(&report_filter)?;
+    
+    let flagged_query = format!(
+        "SELECT repository_id, repository_name, author,
+         EXISTS (SELECT 1 FROM commit_reverts cr
+                 WHERE cr.repository_id = commits.repository_id AND cr.revert_commit_id = commits.id) as is_revert,
+         EXISTS (SELECT 1 FROM commit_reverts cr
+                 WHERE cr.repository_id = commits.repository_id AND cr.reverted_commit_id = commits.id) as is_reverted
+         {}",
+        base_query
+    );
+    
+    let mut query_builder = sqlx::query(&flagged_query);
+    for param in &params {
+        query_builder = query_builder.bind(param);
+    }
+    
+    let rows = query_builder.fetch_all(pool).await?;
+    
+    let mut authors: std::collections::HashMap<String, RevertRate> = std::collections::HashMap::new();
+    let mut repositories: std::collections::HashMap<i64, RevertRate> = std::collections::HashMap::new();
+    let mut total_reverts = 0;
+    let mut total_reverted = 0;
+    let total_commits = rows.len() as i32;
+    
+    for row in &rows {
+        let repository_id: i64 = row.get("repository_id");
+        let repository_name: String = row.get("repository_name");
+        let author: String = row.get("author");
+        let is_revert: bool = row.get("is_revert");
+        let is_reverted: bool = row.get("is_reverted");
+        
+        if is_revert {
+            total_reverts += 1;
+        }
+        if is_reverted {
+            total_reverted += 1;
+        }
+        
+        let author_rate = authors.entry(author.clone()).or_insert_with(|| RevertRate {
+            key: author,
+            repository_id: None,
+            commits: 0,
+            reverts: 0,
+            reverted: 0,
+            revert_rate: 0.0,
+        });
+        let repository_rate = repositories.entry(repository_id).or_insert_with(|| RevertRate {
+            key: repository_name,
+            repository_id: Some(repository_id),
+            commits: 0,
+            reverts: 0,
+            reverted: 0,
+            revert_rate: 0.0,
+        });
+        
+        for rate in [author_rate, repository_rate] {
+            rate.commits += 1;
+            if is_revert {
+                rate.reverts += 1;
+            }
+            if is_reverted {
+                rate.reverted += 1;
             }
         }
     }
     
-    if let Some(repository_id) = filter.repository_id {
-        query.push_str(" AND repository_id = ?");
-        params.push(repository_id.to_string());
+    let finish = |rates: Vec<RevertRate>| -> Vec<RevertRate> {
+        let mut rates: Vec<RevertRate> = rates
+            .into_iter()
+            .map(|mut rate| {
+                rate.revert_rate = if rate.commits > 0 {
+                    rate.reverted as f64 / rate.commits as f64
+                } else {
+                    0.0
+                };
+                rate
+            })
+            .collect();
+        rates.sort_by(|a, b| b.revert_rate.total_cmp(&a.revert_rate).then_with(|| a.key.cmp(&b.key)));
+        rates
+    };
+    
+    // List the pairs whose revert falls inside the filter
+    let pairs_query = format!(
+        "SELECT cr.repository_id, commits.repository_name, cr.revert_commit_id, cr.reverted_commit_id,
+         cr.detection, commits.author as revert_author,
+         COALESCE(o.author, '') as reverted_author, commits.timestamp
+         FROM commit_reverts cr
+         JOIN (SELECT id, repository_id, repository_name, author, timestamp {}) commits
+           ON commits.repository_id = cr.repository_id AND commits.id = cr.revert_commit_id
+         LEFT JOIN commits o ON o.repository_id = cr.repository_id AND o.id = cr.reverted_commit_id
+         ORDER BY commits.timestamp DESC",
+        base_query
+    );
+    
+    let mut query_builder = sqlx::query_as::<_, RevertPair>(&pairs_query);
+    for param in &params {
+        query_builder = query_builder.bind(param);
+    }
+    let pairs = query_builder.fetch_all(pool).await?;
+    
+    Ok(RevertReport {
+        total_commits,
+        total_reverts,
+        total_reverted,
+        authors: finish(authors.into_values().collect()),
+        repositories: finish(repositories.into_values().collect()),
+        pairs,
+    })
+}
+
+pub async fn get_file_history_entries(
+    pool: &SqlitePool,
+    repository_id: i64,
+    file_path: &str,
+    until: Option<chrono::DateTime<chrono::Utc>>
+) -> Result<Vec<FileHistoryEntry>> {
+    let mut query = r#"
+        SELECT fc.commit_id, c.author, c.email, c.message, c.timestamp,
+               fc.file_path AS path, fc.additions, fc.deletions
+        FROM file_changes fc
+        JOIN commits c ON fc.commit_id = c.id AND fc.repository_id = c.repository_id
+        WHERE fc.repository_id = ? AND fc.file_path = ? AND c.is_merge = 0 AND c.orphaned_at IS NULL
+    "#.to_string();
+    
+    if until.is_some() {
+        query.push_str(" AND c.timestamp <= ?");
     }
     
-    query.push_str(" ORDER BY timestamp DESC");
+    query.push_str(" ORDER BY c.timestamp DESC");
     
-    let mut query_builder = sqlx::query_as::<_, Commit>(&query);
+    let mut query_builder = sqlx::query_as::<_, FileHistoryEntry>(&query)
+        .bind(repository_id)
+        .bind(file_path);
     
-    for param in params {
-        query_builder = query_builder.bind(param);
+    if let Some(until) = until {
+        query_builder = query_builder.bind(until);
     }
     
-    let commits = query_builder.fetch_all(pool).await?;
-    Ok(commits)
+    let entries = query_builder.fetch_all(pool).await?;
+    Ok(entries)
 }
 
-pub async fn get_commit_timeline_with_remote_urls(pool: &SqlitePool, filter: &TimeFilter) -> Result<Vec<Commit>> {
-    // First get all repositories to map repository_id to path
-    let repositories = get_repositories(pool).await?;
-    let repo_map: std::collections::HashMap<i64, String> = repositories
-        .into_iter()
-        .map(|repo| (repo.id, repo.path))
-        .collect();
+/// The authors of a file's history and its changes per month.
+pub fn summarize_file_history(entries: &[FileHistoryEntry]) -> (Vec<FileAuthorContribution>, Vec<FileChangeFrequency>) {
+    let mut authors: std::collections::HashMap<String, FileAuthorContribution> = std::collections::HashMap::new();
+    let mut periods: std::collections::HashMap<String, FileChangeFrequency> = std::collections::HashMap::new();
     
-    // Get commits
-    let commits = get_commit_timeline(pool, filter).await?;
-    
-    // Add remote URLs to commits
-    let commits_with_urls = commits
-        .into_iter()
-        .map(|mut commit| {
-            if let Some(repo_path) = repo_map.get(&commit.repository_id) {
-                commit.remote_url = crate::git_analyzer::get_remote_url_for_path(repo_path);
-            }
-            commit
-        })
-        .collect();
+    for entry in entries {
+        let author = authors.entry(entry.author.clone()).or_insert_with(|| FileAuthorContribution {
+            author: entry.author.clone(),
+            commits: 0,
+            additions: 0,
+            deletions: 0,
+        });
+        author.commits += 1;
+        author.additions += entry.additions;
+        author.deletions += entry.deletions;
         
-    Ok(commits_with_urls)
-}
-
-pub async fn get_statistics(pool: &SqlitePool, filter: &TimeFilter) -> Result<Statistics> {
-    let mut base_query = "FROM commits WHERE 1=1".to_string();
-    let mut params: Vec<String> = Vec::new();
-    
-    if let Some(start_date) = &filter.start_date {
-        base_query.push_str(" AND timestamp >= ?");
-        params.push(start_date.to_rfc3339());
+        let period = entry.timestamp.format("%Y-%m").to_string();
+        let frequency = periods.entry(period.clone()).or_insert_with(|| FileChangeFrequency {
+            period,
+            commits: 0,
+            additions: 0,
+            deletions: 0,
+        });
+        frequency.commits += 1;
+        frequency.additions += entry.additions;
+        frequency.deletions += entry.deletions;
     }
     
-    if let Some(end_date) = &filter.end_date {
-        base_query.push_str(" AND timestamp <= ?");
-        params.push(end_date.to_rfc3339());
-    }
-    
-    if let Some(author) = &filter.author {
-        base_query.push_str(" AND author = ?");
-        params.push(author.clone());
-    }
+    let mut authors: Vec<FileAuthorContribution> = authors.into_values().collect();
+    authors.sort_by(|a, b| b.commits.cmp(&a.commits).then_with(|| a.author.cmp(&b.author)));
     
-    if let Some(exclude_authors) = &filter.exclude_authors {
-        if !exclude_authors.is_empty() {
-            let placeholders: Vec<String> = exclude_authors.iter().map(|_| "?".to_string()).collect();
-            base_query.push_str(&format!(" AND author NOT IN ({})", placeholders.join(",")));
-            for author in exclude_authors {
-                params.push(author.clone());
-            }
-        }
-    }
+    let mut change_frequency: Vec<FileChangeFrequency> = periods.into_values().collect();
+    change_frequency.sort_by(|a, b| a.period.cmp(&b.period));
     
-    if let Some(repository_id) = filter.repository_id {
-        base_query.push_str(" AND repository_id = ?");
-        params.push(repository_id.to_string());
-    }
+    (authors, change_frequency)
+}
+
+/// Words of a commit message counted by the word cloud, lowercased and
+/// without common English words.
+pub fn message_words(message: &str) -> Vec<String> {
+    message
+        .split_whitespace()
+        .filter(|word| word.len() > 2 && !word.starts_with('#'))
+        .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric()))
+        .filter(|word| word.len() > 2)
+        .map(|word| word.to_lowercase())
+        // Skip common words
+        .filter(|word| !matches!(word.as_str(), "the" | "and" | "for" | "are" | "but" | "not" | "you" | "all" | "can" | "her" | "was" | "one" | "our" | "out" | "day" | "get" | "use" | "man" | "new" | "now" | "way" | "may" | "say" | "each" | "which" | "their" | "time" | "will" | "about" | "if" | "up" | "many" | "then" | "them" | "these" | "so" | "some" | "would" | "make" | "like" | "into" | "him" | "has" | "two" | "more" | "very" | "what" | "know" | "just" | "first" | "could" | "any" | "my" | "than" | "much" | "your" | "how" | "said" | "she" | "his" | "been" | "have" | "there" | "we" | "were" | "they" | "who" | "oil" | "its" | "find" | "long" | "down" | "did" | "come" | "made" | "part"))
+        .collect()
+}
+
+/// Computes every statistics series. The series are independent, so they
+/// run concurrently on separate pooled connections.
+pub async fn get_statistics(pool: &SqlitePool, filter: &TimeFilter) -> Result<Statistics> {
+    let (
+        hourly,
+        daily,
+        weekly,
+        totals,
+        authors,
+        repositories,
+        groups,
+        hourly_commit_distribution,
+        author_activity_trends,
+        commit_frequency_distribution,
+        commit_size_distribution,
+        efficiency_trends,
+        hot_files,
+        commit_message_words,
+    ) = tokio::try_join!(
+        get_hourly_stats(pool, filter),
+        get_daily_stats(pool, filter),
+        get_weekly_stats(pool, filter),
+        get_statistics_totals(pool, filter),
+        get_author_stats(pool, filter),
+        get_repository_stats(pool, filter),
+        get_group_stats(pool, filter),
+        get_hourly_commit_distribution(pool, filter),
+        get_author_activity_trends(pool, filter),
+        get_commit_frequency_distribution(pool, filter),
+        get_commit_size_distribution(pool, filter),
+        get_efficiency_trends(pool, filter),
+        get_hot_files(pool, filter),
+        get_commit_message_words(pool, filter),
+    )?;
+
+    Ok(Statistics {
+        hourly,
+        daily,
+        weekly,
+        total_commits: totals.total_commits,
+        total_additions: totals.total_additions,
+        total_deletions: totals.total_deletions,
+        authors,
+        repositories,
+        groups,
+        hourly_commit_distribution,
+        author_activity_trends,
+        commit_frequency_distribution,
+        commit_size_distribution,
+        efficiency_trends,
+        hot_files,
+        commit_message_words,
+    })
+}
 
-    // Get hourly stats (convert UTC to local time for proper hour grouping)
+/// Additions, deletions and commits per local hour of the day.
+pub async fn get_hourly_stats(pool: &SqlitePool, filter: &TimeFilter) -> Result<Vec<HourlyStats>> {
+    // Whole hours are read from the rollup tables, see `StatisticsSource`
+    let (buckets, params) = crate::rollups::StatisticsSource::for_filter(filter).buckets()?;
+
+    // Rollups store the local hour of every commit
     let hourly_query = format!(
-        "SELECT strftime('%H', timestamp, 'localtime') as hour, 
+        "SELECT local_hour as hour, 
          SUM(additions) as additions, 
          SUM(deletions) as deletions, 
-         COUNT(*) as commits 
-         {} GROUP BY hour ORDER BY hour",
-        base_query
+         SUM(commits) as commits 
+         FROM {} GROUP BY hour ORDER BY hour",
+        buckets
     );
     
     let mut query_builder = sqlx::query(&hourly_query);
@@ -332,24 +1381,30 @@ pub async fn get_statistics(pool: &SqlitePool, filter: &TimeFilter) -> Result<St
     }
     
     let hourly_rows = query_builder.fetch_all(pool).await?;
-    let hourly: Vec<HourlyStats> = hourly_rows
+    let hourly = hourly_rows
         .into_iter()
         .map(|row| HourlyStats {
-            hour: row.get::<String, _>("hour").parse().unwrap_or(0),
+            hour: row.get("hour"),
             additions: row.get("additions"),
             deletions: row.get("deletions"),
             commits: row.get("commits"),
         })
         .collect();
+    
+    Ok(hourly)
+}
+
+/// Additions, deletions and commits per day, newest first.
+pub async fn get_daily_stats(pool: &SqlitePool, filter: &TimeFilter) -> Result<Vec<DailyStats>> {
+    let (buckets, params) = crate::rollups::StatisticsSource::for_filter(filter).buckets()?;
 
-    // Get daily stats
     let daily_query = format!(
-        "SELECT DATE(timestamp) as date, 
+        "SELECT substr(hour_start, 1, 10) as date, 
          SUM(additions) as additions, 
          SUM(deletions) as deletions, 
-         COUNT(*) as commits 
-         {} GROUP BY date ORDER BY date DESC",
-        base_query
+         SUM(commits) as commits 
+         FROM {} GROUP BY date ORDER BY date DESC",
+        buckets
     );
     
     let mut query_builder = sqlx::query(&daily_query);
@@ -358,7 +1413,7 @@ pub async fn get_statistics(pool: &SqlitePool, filter: &TimeFilter) -> Result<St
     }
     
     let daily_rows = query_builder.fetch_all(pool).await?;
-    let daily: Vec<DailyStats> = daily_rows
+    let daily = daily_rows
         .into_iter()
         .map(|row| DailyStats {
             date: row.get("date"),
@@ -367,15 +1422,22 @@ pub async fn get_statistics(pool: &SqlitePool, filter: &TimeFilter) -> Result<St
             commits: row.get("commits"),
         })
         .collect();
+    
+    Ok(daily)
+}
 
-    // Get weekly stats (convert UTC to local time for proper weekday grouping)
+/// Additions, deletions and commits per local day of the week.
+pub async fn get_weekly_stats(pool: &SqlitePool, filter: &TimeFilter) -> Result<Vec<WeeklyStats>> {
+    let (buckets, params) = crate::rollups::StatisticsSource::for_filter(filter).buckets()?;
+
+    // Rollups store the local weekday of every commit
     let weekly_query = format!(
-        "SELECT strftime('%w', timestamp, 'localtime') as weekday, 
+        "SELECT local_weekday as weekday, 
          SUM(additions) as additions, 
          SUM(deletions) as deletions, 
-         COUNT(*) as commits 
-         {} GROUP BY weekday ORDER BY weekday",
-        base_query
+         SUM(commits) as commits 
+         FROM {} GROUP BY weekday ORDER BY weekday",
+        buckets
     );
     
     let mut query_builder = sqlx::query(&weekly_query);
@@ -384,23 +1446,28 @@ pub async fn get_statistics(pool: &SqlitePool, filter: &TimeFilter) -> Result<St
     }
     
     let weekly_rows = query_builder.fetch_all(pool).await?;
-    let weekly: Vec<WeeklyStats> = weekly_rows
+    let weekly = weekly_rows
         .into_iter()
         .map(|row| WeeklyStats {
-            weekday: row.get::<String, _>("weekday").parse().unwrap_or(0),
+            weekday: row.get("weekday"),
             additions: row.get("additions"),
             deletions: row.get("deletions"),
             commits: row.get("commits"),
         })
         .collect();
+    
+    Ok(weekly)
+}
+
+pub async fn get_statistics_totals(pool: &SqlitePool, filter: &TimeFilter) -> Result<StatisticsTotals> {
+    let (buckets, params) = crate::rollups::StatisticsSource::for_filter(filter).buckets()?;
 
-    // Get total stats
     let total_query = format!(
-        "SELECT SUM(additions) as total_additions, 
-         SUM(deletions) as total_deletions, 
-         COUNT(*) as total_commits 
-         {}",
-        base_query
+        "SELECT COALESCE(SUM(additions), 0) as total_additions, 
+         COALESCE(SUM(deletions), 0) as total_deletions, 
+         COALESCE(SUM(commits), 0) as total_commits 
+         FROM {}",
+        buckets
     );
     
     let mut query_builder = sqlx::query(&total_query);
@@ -409,18 +1476,23 @@ pub async fn get_statistics(pool: &SqlitePool, filter: &TimeFilter) -> Result<St
     }
     
     let total_row = query_builder.fetch_one(pool).await?;
-    let total_commits: i32 = total_row.get("total_commits");
-    let total_additions: i32 = total_row.get("total_additions");
-    let total_deletions: i32 = total_row.get("total_deletions");
+    Ok(StatisticsTotals {
+        total_commits: total_row.get("total_commits"),
+        total_additions: total_row.get("total_additions"),
+        total_deletions: total_row.get("total_deletions"),
+    })
+}
+
+pub async fn get_author_stats(pool: &SqlitePool, filter: &TimeFilter) -> Result<std::collections::HashMap<String, AuthorStats>> {
+    let (buckets, params) = crate::rollups::StatisticsSource::for_filter(filter).buckets()?;
 
-    // Get author stats
     let author_query = format!(
         "SELECT author, 
          SUM(additions) as additions, 
          SUM(deletions) as deletions, 
-         COUNT(*) as commits 
-         {} GROUP BY author ORDER BY (additions + deletions) DESC",
-        base_query
+         SUM(commits) as commits 
+         FROM {} GROUP BY author ORDER BY (additions + deletions) DESC",
+        buckets
     );
     
     let mut query_builder = sqlx::query(&author_query);
@@ -439,15 +1511,26 @@ pub async fn get_statistics(pool: &SqlitePool, filter: &TimeFilter) -> Result<St
         };
         authors.insert(author, stats);
     }
+    
+    Ok(authors)
+}
+
+/// Totals per repository, by repository ID so clones with the same folder
+/// name stay apart.
+pub async fn get_repository_stats(pool: &SqlitePool, filter: &TimeFilter) -> Result<std::collections::HashMap<i64, RepositoryStats>> {
+    let (buckets, params) = crate::rollups::StatisticsSource::for_filter(filter).buckets()?;
 
-    // Get repository stats
     let repo_query = format!(
-        "SELECT repository_name, 
-         SUM(additions) as additions, 
-         SUM(deletions) as deletions, 
-         COUNT(*) as commits 
-         {} GROUP BY repository_name ORDER BY (additions + deletions) DESC",
-        base_query
+        "SELECT r.id as repository_id, 
+         COALESCE(r.display_name, r.name) as repository_name, 
+         r.path as path, 
+         r.color as color, 
+         SUM(buckets.additions) as additions, 
+         SUM(buckets.deletions) as deletions, 
+         SUM(buckets.commits) as commits 
+         FROM {} JOIN repositories r ON r.id = buckets.repository_id
+         GROUP BY r.id ORDER BY (additions + deletions) DESC",
+        buckets
     );
     
     let mut query_builder = sqlx::query(&repo_query);
@@ -458,22 +1541,72 @@ pub async fn get_statistics(pool: &SqlitePool, filter: &TimeFilter) -> Result<St
     let repo_rows = query_builder.fetch_all(pool).await?;
     let mut repositories = std::collections::HashMap::new();
     for row in repo_rows {
-        let repo_name: String = row.get("repository_name");
+        let repository_id: i64 = row.get("repository_id");
         let stats = RepositoryStats {
+            name: row.get("repository_name"),
+            path: row.get("path"),
+            color: row.get("color"),
             additions: row.get("additions"),
             deletions: row.get("deletions"),
             commits: row.get("commits"),
         };
-        repositories.insert(repo_name, stats);
+        repositories.insert(repository_id, stats);
+    }
+    
+    Ok(repositories)
+}
+
+/// Totals per repository group. A repository in several groups counts
+/// towards each of them; groups without commits in the filter are left out.
+pub async fn get_group_stats(pool: &SqlitePool, filter: &TimeFilter) -> Result<Vec<GroupStats>> {
+    let (buckets, params) = crate::rollups::StatisticsSource::for_filter(filter).buckets()?;
+
+    let group_query = format!(
+        "SELECT g.id as group_id, g.name as name,
+         SUM(buckets.additions) as additions,
+         SUM(buckets.deletions) as deletions,
+         SUM(buckets.commits) as commits,
+         COUNT(DISTINCT buckets.repository_id) as repositories,
+         COUNT(DISTINCT buckets.author) as authors
+         FROM {}
+         JOIN repository_group_members m ON m.repository_id = buckets.repository_id
+         JOIN repository_groups g ON g.id = m.group_id
+         GROUP BY g.id ORDER BY SUM(buckets.additions) + SUM(buckets.deletions) DESC, g.name",
+        buckets
+    );
+    
+    let mut query_builder = sqlx::query(&group_query);
+    for param in &params {
+        query_builder = query_builder.bind(param);
     }
+    
+    let group_rows = query_builder.fetch_all(pool).await?;
+    let groups = group_rows
+        .into_iter()
+        .map(|row| GroupStats {
+            group_id: row.get("group_id"),
+            name: row.get("name"),
+            additions: row.get("additions"),
+            deletions: row.get("deletions"),
+            commits: row.get("commits"),
+            repositories: row.get("repositories"),
+            authors: row.get("authors"),
+        })
+        .collect();
+    
+    Ok(groups)
+}
+
+/// Commits per local hour and day of the week, for the heatmap.
+pub async fn get_hourly_commit_distribution(pool: &SqlitePool, filter: &TimeFilter) -> Result<Vec<HourlyCommitDistribution>> {
+    let (buckets, params) = crate::rollups::StatisticsSource::for_filter(filter).buckets()?;
 
-    // Get hourly commit distribution for heatmap (hour x day of week)
     let hourly_dist_query = format!(
-        "SELECT strftime('%H', timestamp, 'localtime') as hour,
-         strftime('%w', timestamp, 'localtime') as day_of_week,
-         COUNT(*) as commits
-         {} GROUP BY hour, day_of_week ORDER BY hour, day_of_week",
-        base_query
+        "SELECT local_hour as hour,
+         local_weekday as day_of_week,
+         SUM(commits) as commits
+         FROM {} GROUP BY hour, day_of_week ORDER BY hour, day_of_week",
+        buckets
     );
     
     let mut query_builder = sqlx::query(&hourly_dist_query);
@@ -482,24 +1615,30 @@ pub async fn get_statistics(pool: &SqlitePool, filter: &TimeFilter) -> Result<St
     }
     
     let hourly_dist_rows = query_builder.fetch_all(pool).await?;
-    let hourly_commit_distribution: Vec<HourlyCommitDistribution> = hourly_dist_rows
+    let hourly_commit_distribution = hourly_dist_rows
         .into_iter()
         .map(|row| HourlyCommitDistribution {
-            hour: row.get::<String, _>("hour").parse().unwrap_or(0),
-            day_of_week: row.get::<String, _>("day_of_week").parse().unwrap_or(0),
+            hour: row.get("hour"),
+            day_of_week: row.get("day_of_week"),
             commits: row.get("commits"),
         })
         .collect();
+    
+    Ok(hourly_commit_distribution)
+}
+
+/// Daily activity of every author.
+pub async fn get_author_activity_trends(pool: &SqlitePool, filter: &TimeFilter) -> Result<Vec<AuthorActivityTrend>> {
+    let (buckets, params) = crate::rollups::StatisticsSource::for_filter(filter).buckets()?;
 
-    // Get author activity trends (daily)
     let author_trend_query = format!(
         "SELECT author,
-         DATE(timestamp) as period,
-         COUNT(*) as commits,
+         substr(hour_start, 1, 10) as period,
+         SUM(commits) as commits,
          SUM(additions) as additions,
          SUM(deletions) as deletions
-         {} GROUP BY author, period ORDER BY period, commits DESC",
-        base_query
+         FROM {} GROUP BY author, period ORDER BY period, commits DESC",
+        buckets
     );
     
     let mut query_builder = sqlx::query(&author_trend_query);
@@ -508,7 +1647,7 @@ pub async fn get_statistics(pool: &SqlitePool, filter: &TimeFilter) -> Result<St
     }
     
     let author_trend_rows = query_builder.fetch_all(pool).await?;
-    let author_activity_trends: Vec<AuthorActivityTrend> = author_trend_rows
+    let author_activity_trends = author_trend_rows
         .into_iter()
         .map(|row| AuthorActivityTrend {
             author: row.get("author"),
@@ -518,13 +1657,19 @@ pub async fn get_statistics(pool: &SqlitePool, filter: &TimeFilter) -> Result<St
             deletions: row.get("deletions"),
         })
         .collect();
+    
+    Ok(author_activity_trends)
+}
+
+/// Commits per day, oldest first.
+pub async fn get_commit_frequency_distribution(pool: &SqlitePool, filter: &TimeFilter) -> Result<Vec<CommitFrequencyDistribution>> {
+    let (buckets, params) = crate::rollups::StatisticsSource::for_filter(filter).buckets()?;
 
-    // Get commit frequency distribution (commits per day)
     let freq_dist_query = format!(
-        "SELECT DATE(timestamp) as date,
-         COUNT(*) as commit_count
-         {} GROUP BY date ORDER BY date",
-        base_query
+        "SELECT substr(hour_start, 1, 10) as date,
+         SUM(commits) as commit_count
+         FROM {} GROUP BY date ORDER BY date",
+        buckets
     );
     
     let mut query_builder = sqlx::query(&freq_dist_query);
@@ -533,32 +1678,29 @@ pub async fn get_statistics(pool: &SqlitePool, filter: &TimeFilter) -> Result<St
     }
     
     let freq_dist_rows = query_builder.fetch_all(pool).await?;
-    let commit_frequency_distribution: Vec<CommitFrequencyDistribution> = freq_dist_rows
+    let commit_frequency_distribution = freq_dist_rows
         .into_iter()
         .map(|row| CommitFrequencyDistribution {
             date: row.get("date"),
             commit_count: row.get("commit_count"),
         })
         .collect();
+    
+    Ok(commit_frequency_distribution)
+}
 
-    // Get commit size distribution
+/// Commits per size range of changed lines, skipping empty ranges.
+pub async fn get_commit_size_distribution(pool: &SqlitePool, filter: &TimeFilter) -> Result<Vec<CommitSizeDistribution>> {
+    let (buckets, params) = crate::rollups::StatisticsSource::for_filter(filter).buckets()?;
+
+    // The buckets count every size range
     let size_dist_query = format!(
-        "SELECT 
-         CASE 
-           WHEN (additions + deletions) <= 10 THEN 'small'
-           WHEN (additions + deletions) <= 100 THEN 'medium'
-           WHEN (additions + deletions) <= 500 THEN 'large'
-           ELSE 'huge'
-         END as size_range,
-         COUNT(*) as count
-         {} GROUP BY size_range ORDER BY 
-         CASE size_range 
-           WHEN 'small' THEN 1
-           WHEN 'medium' THEN 2
-           WHEN 'large' THEN 3
-           WHEN 'huge' THEN 4
-         END",
-        base_query
+        "SELECT COALESCE(SUM(small_commits), 0) as small_commits,
+         COALESCE(SUM(medium_commits), 0) as medium_commits,
+         COALESCE(SUM(large_commits), 0) as large_commits,
+         COALESCE(SUM(huge_commits), 0) as huge_commits
+         FROM {}",
+        buckets
     );
     
     let mut query_builder = sqlx::query(&size_dist_query);
@@ -566,35 +1708,37 @@ pub async fn get_statistics(pool: &SqlitePool, filter: &TimeFilter) -> Result<St
         query_builder = query_builder.bind(param);
     }
     
-    let size_dist_rows = query_builder.fetch_all(pool).await?;
-    let commit_size_distribution: Vec<CommitSizeDistribution> = size_dist_rows
-        .into_iter()
-        .map(|row| {
-            let size_range: String = row.get("size_range");
-            let (min_lines, max_lines) = match size_range.as_str() {
-                "small" => (0, 10),
-                "medium" => (11, 100),
-                "large" => (101, 500),
-                "huge" => (501, i32::MAX),
-                _ => (0, 0),
-            };
-            CommitSizeDistribution {
-                size_range,
-                count: row.get("count"),
-                min_lines,
-                max_lines,
-            }
-        })
-        .collect();
+    let size_dist_row = query_builder.fetch_one(pool).await?;
+    let commit_size_distribution = [
+        ("small", "small_commits", 0, 10),
+        ("medium", "medium_commits", 11, 100),
+        ("large", "large_commits", 101, 500),
+        ("huge", "huge_commits", 501, i32::MAX),
+    ]
+    .into_iter()
+    .map(|(size_range, column, min_lines, max_lines)| CommitSizeDistribution {
+        size_range: size_range.to_string(),
+        count: size_dist_row.get(column),
+        min_lines,
+        max_lines,
+    })
+    .filter(|size| size.count > 0)
+    .collect();
+    
+    Ok(commit_size_distribution)
+}
+
+/// Share of additions among all changed lines per day (additions / (additions + deletions)).
+pub async fn get_efficiency_trends(pool: &SqlitePool, filter: &TimeFilter) -> Result<Vec<EfficiencyTrend>> {
+    let (buckets, params) = crate::rollups::StatisticsSource::for_filter(filter).buckets()?;
 
-    // Get efficiency trends (additions / (additions + deletions))
     let efficiency_query = format!(
-        "SELECT DATE(timestamp) as date,
+        "SELECT substr(hour_start, 1, 10) as date,
          SUM(additions) as total_additions,
          SUM(deletions) as total_deletions,
          (SUM(additions) + SUM(deletions)) as total_changes
-         {} GROUP BY date ORDER BY date",
-        base_query
+         FROM {} GROUP BY date ORDER BY date",
+        buckets
     );
     
     let mut query_builder = sqlx::query(&efficiency_query);
@@ -603,7 +1747,7 @@ pub async fn get_statistics(pool: &SqlitePool, filter: &TimeFilter) -> Result<St
     }
     
     let efficiency_rows = query_builder.fetch_all(pool).await?;
-    let efficiency_trends: Vec<EfficiencyTrend> = efficiency_rows
+    let efficiency_trends = efficiency_rows
         .into_iter()
         .map(|row| {
             let additions: i32 = row.get("total_additions");
@@ -621,59 +1765,34 @@ pub async fn get_statistics(pool: &SqlitePool, filter: &TimeFilter) -> Result<St
             }
         })
         .collect();
+    
+    Ok(efficiency_trends)
+}
+
+/// The 20 most frequently changed files.
+pub async fn get_hot_files(pool: &SqlitePool, filter: &TimeFilter) -> Result<Vec<HotFile>> {
+    let (files, params) = crate::rollups::StatisticsSource::for_filter(filter).files()?;
 
-    // Get hot files (most frequently changed files)
-    // We now have actual file-level data stored in the database
     let hot_files_query = format!(
-        "SELECT fc.file_path,
-         COUNT(*) as change_count,
-         SUM(fc.additions) as total_additions,
-         SUM(fc.deletions) as total_deletions,
-         MAX(c.timestamp) as last_modified
-         FROM file_changes fc
-         JOIN commits c ON fc.commit_id = c.id AND fc.repository_id = c.repository_id
-         WHERE 1=1 {}
-         GROUP BY fc.file_path 
-         ORDER BY change_count DESC 
+        "SELECT file_path,
+         SUM(changes) as change_count,
+         SUM(additions) as total_additions,
+         SUM(deletions) as total_deletions,
+         MAX(last_modified) as last_modified
+         FROM {}
+         GROUP BY file_path 
+         ORDER BY change_count DESC, file_path 
          LIMIT 20",
-        {
-            let mut conditions = String::new();
-            if let Some(start_date) = &filter.start_date {
-                conditions.push_str(" AND c.timestamp >= ?");
-            }
-            if let Some(end_date) = &filter.end_date {
-                conditions.push_str(" AND c.timestamp <= ?");
-            }
-            if let Some(author) = &filter.author {
-                conditions.push_str(" AND c.author = ?");
-            }
-            if let Some(exclude_authors) = &filter.exclude_authors {
-                if !exclude_authors.is_empty() {
-                    let placeholders: Vec<String> = exclude_authors.iter().map(|_| "?".to_string()).collect();
-                    conditions.push_str(&format!(" AND c.author NOT IN ({})", placeholders.join(",")));
-                }
-            }
-            if let Some(repository_id) = filter.repository_id {
-                conditions.push_str(" AND c.repository_id = ?");
-            }
-            conditions
-        }
+        files
     );
     
     let mut query_builder = sqlx::query(&hot_files_query);
-    // Bind parameters for the file changes query
     for param in &params {
         query_builder = query_builder.bind(param);
     }
-    // Bind additional parameters for exclude_authors if needed
-    if let Some(exclude_authors) = &filter.exclude_authors {
-        for author in exclude_authors {
-            query_builder = query_builder.bind(author);
-        }
-    }
     
     let hot_files_rows = query_builder.fetch_all(pool).await?;
-    let hot_files: Vec<HotFile> = hot_files_rows
+    let hot_files = hot_files_rows
         .into_iter()
         .map(|row| HotFile {
             file_path: row.get("file_path"),
@@ -683,39 +1802,15 @@ pub async fn get_statistics(pool: &SqlitePool, filter: &TimeFilter) -> Result<St
             last_modified: row.get::<chrono::DateTime<chrono::Utc>, _>("last_modified").to_rfc3339(),
         })
         .collect();
-
-    // Get commit message words (basic word frequency analysis)
-    let message_query = format!(
-        "SELECT message {} ORDER BY timestamp DESC",
-        base_query
-    );
-    
-    let mut query_builder = sqlx::query(&message_query);
-    for param in &params {
-        query_builder = query_builder.bind(param);
-    }
     
-    let message_rows = query_builder.fetch_all(pool).await?;
-    let mut word_counts = std::collections::HashMap::new();
-    
-    // Process commit messages to extract words
-    for row in message_rows {
-        let message: String = row.get("message");
-        let words: Vec<&str> = message
-            .split_whitespace()
-            .filter(|word| word.len() > 2 && !word.starts_with('#'))
-            .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric()))
-            .filter(|word| word.len() > 2)
-            .collect();
-        
-        for word in words {
-            let word = word.to_lowercase();
-            // Skip common words
-            if !matches!(word.as_str(), "the" | "and" | "for" | "are" | "but" | "not" | "you" | "all" | "can" | "her" | "was" | "one" | "our" | "out" | "day" | "get" | "use" | "man" | "new" | "now" | "way" | "may" | "say" | "each" | "which" | "their" | "time" | "will" | "about" | "if" | "up" | "out" | "many" | "then" | "them" | "these" | "so" | "some" | "her" | "would" | "make" | "like" | "into" | "him" | "has" | "two" | "more" | "very" | "what" | "know" | "just" | "first" | "could" | "any" | "my" | "than" | "much" | "your" | "how" | "said" | "each" | "she" | "which" | "their" | "his" | "been" | "have" | "there" | "we" | "what" | "were" | "they" | "who" | "oil" | "its" | "now" | "find" | "long" | "down" | "day" | "did" | "get" | "come" | "made" | "may" | "part") {
-                *word_counts.entry(word).or_insert(0) += 1;
-            }
-        }
-    }
+    Ok(hot_files)
+}
+
+/// The 50 most common commit message words, for the word cloud.
+pub async fn get_commit_message_words(pool: &SqlitePool, filter: &TimeFilter) -> Result<Vec<CommitMessageWord>> {
+    let word_counts = crate::rollups::StatisticsSource::for_filter(filter)
+        .word_counts(pool, 50)
+        .await?;
     
     // Convert to sorted list
     let mut commit_message_words: Vec<CommitMessageWord> = word_counts
@@ -728,24 +1823,410 @@ pub async fn get_statistics(pool: &SqlitePool, filter: &TimeFilter) -> Result<St
         })
         .collect();
     
-    commit_message_words.sort_by(|a, b| b.count.cmp(&a.count));
+    commit_message_words.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.word.cmp(&b.word)));
     commit_message_words.truncate(50); // Limit to top 50 words
+    
+    Ok(commit_message_words)
+}
 
-    Ok(Statistics {
-        hourly,
-        daily,
-        weekly,
-        total_commits,
-        total_additions,
-        total_deletions,
-        authors,
-        repositories,
-        hourly_commit_distribution,
-        author_activity_trends,
-        commit_frequency_distribution,
-        commit_size_distribution,
-        efficiency_trends,
-        hot_files,
-        commit_message_words,
-    })
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git_analyzer::AnalyzedCommit;
+    use crate::test_support::{analyzed, ingest, test_pool};
+
+    async fn file_change_rows(pool: &SqlitePool) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM file_changes").fetch_one(pool).await.unwrap()
+    }
+
+    // (path, changes, additions, deletions) of the hot files
+    async fn hot_files(pool: &SqlitePool) -> Vec<(String, i32, i32, i32)> {
+        get_hot_files(pool, &TimeFilter::default())
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|file| (file.file_path, file.change_count, file.total_additions, file.total_deletions))
+            .collect()
+    }
+
+    fn sorted_files(mut files: Vec<(String, i32, i32)>) -> Vec<(String, i32, i32)> {
+        files.sort();
+        files
+    }
+
+    // The stored (path, additions, deletions) of each commit, in path order
+    async fn stored_file_changes(pool: &SqlitePool, commits: &[AnalyzedCommit]) -> Vec<Vec<(String, i32, i32)>> {
+        let mut stored = Vec::new();
+        for commit in commits {
+            let files = get_stored_file_changes(pool, commit.commit.repository_id, &commit.commit.id).await.unwrap();
+            stored.push(sorted_files(files));
+        }
+        stored
+    }
+
+    async fn deleted_file_lines(pool: &SqlitePool) -> i32 {
+        hot_files(pool).await.iter().map(|(.., deletions)| deletions).sum()
+    }
+
+    async fn totals(pool: &SqlitePool) -> (i32, i32, i32) {
+        filtered_totals(pool, &TimeFilter::default()).await
+    }
+
+    async fn filtered_totals(pool: &SqlitePool, filter: &TimeFilter) -> (i32, i32, i32) {
+        let totals = get_statistics_totals(pool, filter).await.unwrap();
+        (totals.total_commits, totals.total_additions, totals.total_deletions)
+    }
+
+    #[tokio::test]
+    async fn orphaned_commits_leave_file_history_and_patch_duplicates() {
+        use crate::git_backend::tests::{Fixture, BASE, TEXT};
+
+        // The same edit lands on main and on a topic branch that is later
+        // dropped, leaving its commit orphaned
+        let fixture = Fixture::new("orphaned-history");
+        let root = fixture.commit("refs/heads/main", &[], BASE, &[("a.txt", TEXT, b"a\n")]);
+        let topic = fixture.commit("refs/heads/topic", &[root], BASE + 100, &[("a.txt", TEXT, b"a\nb\n")]);
+        let picked = fixture.commit("refs/heads/main", &[root], BASE + 200, &[("a.txt", TEXT, b"a\nb\n")]);
+        let (root, topic, picked) = (root.to_string(), topic.to_string(), picked.to_string());
+
+        let pool = test_pool().await;
+        let repository = add_repository(&pool, &fixture.path()).await.unwrap();
+        let backend = crate::git_backend::open_backend(crate::git_backend::GitBackendKind::Git2, &repository).unwrap();
+        let analyzed: Vec<_> = [&root, &topic, &picked]
+            .iter()
+            .map(|commit_id| crate::git_analyzer::analyze_commit(&*backend, &repository, commit_id, &None).unwrap())
+            .collect();
+        let patch_id = analyzed[2].commit.patch_id.clone().unwrap();
+        assert_eq!(analyzed[1].commit.patch_id.as_deref(), Some(patch_id.as_str()));
+        ingest(&pool, analyzed).await;
+
+        let duplicate_ids = |duplicates: Vec<Commit>| duplicates.into_iter().map(|commit| commit.id).collect::<Vec<_>>();
+        let history_ids = |entries: Vec<FileHistoryEntry>| entries.into_iter().map(|entry| entry.commit_id).collect::<Vec<_>>();
+        let duplicates = get_patch_duplicates(&pool, repository.id, &picked, &patch_id).await.unwrap();
+        assert_eq!(duplicate_ids(duplicates), vec![topic.clone()]);
+        let history = get_file_history_entries(&pool, repository.id, "a.txt", None).await.unwrap();
+        assert_eq!(history_ids(history), vec![picked.clone(), topic.clone(), root.clone()]);
+
+        let reachable = [root.clone(), picked.clone()].into_iter().collect();
+        reconcile_commits(&pool, repository.id, &reachable).await.unwrap();
+        let duplicates = get_patch_duplicates(&pool, repository.id, &picked, &patch_id).await.unwrap();
+        assert!(duplicates.is_empty());
+        let history = get_file_history_entries(&pool, repository.id, "a.txt", None).await.unwrap();
+        assert_eq!(history_ids(history), vec![picked.clone(), root.clone()]);
+
+        // A stored commit git no longer has ends the rename chain
+        let analyzer = crate::git_analyzer::GitAnalyzer::new(repository).unwrap();
+        let missing = "0123456789012345678901234567890123456789";
+        assert_eq!(analyzer.find_rename_source(missing, "a.txt").unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn type_changes_keep_one_row_per_path_through_rescans_and_repairs() {
+        use crate::git_backend::{open_backend, tests::type_and_mode_fixture, GitBackendKind};
+
+        // link becomes a symlink, node a directory and vendor/lib a plain
+        // file, each diffed as a delete and an add of the same path
+        let fixture = type_and_mode_fixture("type-change-rows");
+        for kind in [GitBackendKind::Git2, GitBackendKind::Gix] {
+            let pool = test_pool().await;
+            let repository = add_repository(&pool, &fixture.path()).await.unwrap();
+            let backend = open_backend(kind, &repository).unwrap();
+            let analyzed: Vec<AnalyzedCommit> = backend
+                .scan_commit_ids(None)
+                .unwrap()
+                .iter()
+                .map(|commit_id| crate::git_analyzer::analyze_commit(&*backend, &repository, commit_id, &None).unwrap())
+                .collect();
+            for commit in &analyzed {
+                let mut paths: Vec<&str> = commit.file_changes.iter().map(|file| file.path.as_str()).collect();
+                paths.sort();
+                paths.dedup();
+                assert_eq!(paths.len(), commit.file_changes.len(), "{:?} lists a path twice", kind);
+                let file_totals = commit.file_changes.iter().fold((0, 0), |(a, d), file| (a + file.additions, d + file.deletions));
+                assert_eq!(file_totals, (commit.commit.additions, commit.commit.deletions), "{:?}", kind);
+            }
+            let expected: Vec<Vec<(String, i32, i32)>> = analyzed
+                .iter()
+                .map(|commit| sorted_files(commit.file_changes.iter().map(|file| (file.path.clone(), file.additions, file.deletions)).collect()))
+                .collect();
+
+            // Scanning twice stores every deleted line once, in the file rows too
+            ingest(&pool, analyzed.clone()).await;
+            ingest(&pool, analyzed.clone()).await;
+            assert_eq!(stored_file_changes(&pool, &analyzed).await, expected, "{:?}", kind);
+            let deleted_lines = (deleted_file_lines(&pool).await, totals(&pool).await.2);
+            assert!(deleted_lines.1 > 0);
+            assert_eq!(deleted_lines.0, deleted_lines.1, "{:?}", kind);
+
+            // Repairs write the same rows instead of failing on the unique index
+            for commit in &analyzed {
+                let stats = &commit.commit;
+                repair_commit(&pool, repository.id, &stats.id, stats.additions, stats.deletions, stats.files_changed, &commit.file_changes)
+                    .await
+                    .unwrap();
+            }
+            assert_eq!(stored_file_changes(&pool, &analyzed).await, expected, "{:?}", kind);
+            assert_eq!((deleted_file_lines(&pool).await, totals(&pool).await.2), deleted_lines, "{:?}", kind);
+        }
+    }
+
+    #[tokio::test]
+    async fn commit_pages_neither_repeat_nor_skip_commits_with_equal_times() {
+        let pool = test_pool().await;
+        let first = add_repository(&pool, "/tmp/repo-a").await.unwrap().id;
+        let second = add_repository(&pool, "/tmp/repo-b").await.unwrap().id;
+
+        // (repository, id, minute, path, patch-id); most commits share a
+        // minute with commits of both repositories
+        let commits = [
+            (first, "a1", 1, "src/x.rs", None),
+            (first, "a2", 1, "docs/y.md", None),
+            (second, "b1", 1, "src/x.rs", Some("p")),
+            (first, "a3", 2, "src/z.rs", Some("p")),
+            (second, "b2", 2, "docs/y.md", None),
+            (second, "b3", 2, "src/x.rs", None),
+            (first, "a4", 2, "src/q.rs", None),
+            (second, "b4", 3, "src/x.rs", Some("q")),
+            (first, "a5", 3, "src/x.rs", Some("q")),
+            (first, "a6", 4, "docs/y.md", None),
+        ];
+        ingest(&pool, commits.iter().map(|&(repository_id, id, minute, path, patch_id)| {
+            let mut commit = analyzed(repository_id, id, minute, &[(path, 1, 0)]);
+            commit.commit.patch_id = patch_id.map(str::to_string);
+            commit
+        }).collect()).await;
+
+        // Newest first, ties broken by repository and then commit ID; of
+        // equal patches the earliest, and then the first repository's, is kept
+        let expected = |deduplicate: bool, src_only: bool| {
+            let mut expected: Vec<(i64, i64, String)> = commits
+                .iter()
+                .filter(|(_, id, ..)| !deduplicate || !matches!(*id, "a3" | "b4"))
+                .filter(|(.., path, _)| !src_only || path.starts_with("src/"))
+                .map(|&(repository_id, id, minute, ..)| (minute, repository_id, id.to_string()))
+                .collect();
+            expected.sort();
+            expected.reverse();
+            expected.into_iter().map(|(_, repository_id, id)| (repository_id, id)).collect::<Vec<_>>()
+        };
+
+        for (deduplicate, src_only) in [(false, false), (true, false), (false, true), (true, true)] {
+            let filter = TimeFilter {
+                deduplicate_patches: deduplicate,
+                paths: src_only.then(|| vec!["src/**".to_string()]),
+                ..Default::default()
+            };
+            for page_size in [1, 2, 3, 100] {
+                let mut paged = Vec::new();
+                let mut cursor: Option<String> = None;
+                loop {
+                    let (page, next_cursor) = get_commit_page(&pool, &filter, cursor.as_deref(), page_size).await.unwrap();
+                    assert!(page.len() <= page_size as usize);
+                    paged.extend(page.into_iter().map(|commit| (commit.repository_id, commit.id)));
+                    assert!(paged.len() <= commits.len(), "pages repeat commits: {:?}", paged);
+                    match next_cursor {
+                        Some(next_cursor) => cursor = Some(next_cursor),
+                        None => break,
+                    }
+                }
+                assert_eq!(
+                    paged,
+                    expected(deduplicate, src_only),
+                    "deduplicate {}, src only {}, page size {}",
+                    deduplicate,
+                    src_only,
+                    page_size
+                );
+            }
+        }
+
+        assert!(get_commit_page(&pool, &TimeFilter::default(), Some("not a cursor"), 10).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn reingesting_commits_replaces_their_file_changes() {
+        let pool = test_pool().await;
+        let repository = add_repository(&pool, "/tmp/repo").await.unwrap();
+        let commits = |with_readme: bool| {
+            let mut files = vec![("src/main.rs", 10, 2)];
+            if with_readme {
+                files.push(("README.md", 3, 0));
+            }
+            vec![
+                analyzed(repository.id, "a", 1, &files),
+                analyzed(repository.id, "b", 2, &[("src/main.rs", 4, 4), ("src/lib.rs", 7, 0)]),
+            ]
+        };
+        let file = |path: &str, changes, additions, deletions| (path.to_string(), changes, additions, deletions);
+
+        ingest(&pool, commits(true)).await;
+        assert_eq!(file_change_rows(&pool).await, 4);
+        assert_eq!(
+            hot_files(&pool).await,
+            vec![file("src/main.rs", 2, 14, 6), file("README.md", 1, 3, 0), file("src/lib.rs", 1, 7, 0)]
+        );
+        assert_eq!(totals(&pool).await, (2, 24, 6));
+
+        // The same scan again changes nothing
+        ingest(&pool, commits(true)).await;
+        assert_eq!(file_change_rows(&pool).await, 4);
+        assert_eq!(hot_files(&pool).await.len(), 3);
+        assert_eq!(totals(&pool).await, (2, 24, 6));
+
+        // A rescan where the commit no longer lists a file drops its row
+        ingest(&pool, commits(false)).await;
+        assert_eq!(file_change_rows(&pool).await, 3);
+        assert_eq!(hot_files(&pool).await, vec![file("src/main.rs", 2, 14, 6), file("src/lib.rs", 1, 7, 0)]);
+        assert_eq!(totals(&pool).await, (2, 21, 6));
+    }
+
+    #[tokio::test]
+    async fn groups_share_repositories_without_counting_them_twice() {
+        let pool = test_pool().await;
+        let a = add_repository(&pool, "/tmp/a").await.unwrap().id;
+        let b = add_repository(&pool, "/tmp/b").await.unwrap().id;
+        let c = add_repository(&pool, "/tmp/c").await.unwrap().id;
+
+        let by = |author: &str, mut commit: AnalyzedCommit| {
+            commit.commit.author = author.to_string();
+            commit
+        };
+        ingest(&pool, vec![
+            by("Alice", analyzed(a, "a1", 1, &[("src/a.rs", 10, 0)])),
+            by("Bob", analyzed(b, "b1", 2, &[("src/b.rs", 15, 5), ("README.md", 5, 0)])),
+            by("Alice", analyzed(c, "c1", 3, &[("src/c.rs", 1, 1)])),
+            by("Carol", analyzed(c, "c2", 4, &[("docs/c.md", 2, 0)])),
+        ])
+        .await;
+
+        // Names are trimmed and must be present and unique; listed
+        // repositories are kept once
+        let work = create_repository_group(&pool, " work ", &[b, a, b]).await.unwrap();
+        assert_eq!((work.name.as_str(), work.repository_ids.clone()), ("work", vec![a, b]));
+        let oss = create_repository_group(&pool, "oss", &[b, c]).await.unwrap();
+        assert!(create_repository_group(&pool, "  ", &[a]).await.is_err());
+        assert!(create_repository_group(&pool, "work", &[c]).await.is_err());
+        assert!(update_repository_group(&pool, oss.id, Some("work"), None).await.is_err());
+        assert!(update_repository_group(&pool, oss.id + 100, Some("other"), None).await.is_err());
+        let names: Vec<String> = get_repository_groups(&pool).await.unwrap().into_iter().map(|group| group.name).collect();
+        assert_eq!(names, vec!["oss", "work"]);
+
+        let groups = |ids: &[i64]| TimeFilter { group_ids: Some(ids.to_vec()), ..Default::default() };
+        let both = groups(&[work.id, oss.id]);
+        assert_eq!(filtered_totals(&pool, &both).await, (4, 33, 6));
+        assert_eq!(filtered_totals(&pool, &groups(&[work.id])).await, (2, 30, 5));
+
+        // Groups narrow the selected repositories rather than adding to them
+        let work_within = TimeFilter { repository_ids: Some(vec![b, c]), ..groups(&[work.id]) };
+        assert_eq!(filtered_totals(&pool, &work_within).await, (1, 20, 5));
+        let oss_within = TimeFilter { repository_id: Some(a), ..groups(&[oss.id]) };
+        assert_eq!(filtered_totals(&pool, &oss_within).await, (0, 0, 0));
+
+        // The raw commits answer the same way as the rollups
+        let sources = TimeFilter { paths: Some(vec!["src".to_string()]), ..both.clone() };
+        assert_eq!(filtered_totals(&pool, &sources).await, (3, 26, 6));
+        let sources = TimeFilter { paths: Some(vec!["src".to_string()]), ..work_within };
+        assert_eq!(filtered_totals(&pool, &sources).await, (1, 15, 5));
+
+        // Each group counts the shared repository once, and towards both
+        let group_totals = |stats: Vec<GroupStats>| -> Vec<(String, i32, i32, i32, i32, i32)> {
+            stats
+                .into_iter()
+                .map(|group| (group.name, group.commits, group.additions, group.deletions, group.repositories, group.authors))
+                .collect()
+        };
+        let group = |name: &str, commits, additions, deletions, repositories, authors| {
+            (name.to_string(), commits, additions, deletions, repositories, authors)
+        };
+        assert_eq!(
+            group_totals(get_group_stats(&pool, &TimeFilter::default()).await.unwrap()),
+            vec![group("work", 2, 30, 5, 2, 2), group("oss", 3, 23, 6, 2, 3)]
+        );
+        assert_eq!(
+            group_totals(get_group_stats(&pool, &groups(&[work.id])).await.unwrap()),
+            vec![group("work", 2, 30, 5, 2, 2), group("oss", 1, 20, 5, 1, 1)]
+        );
+
+        // Renaming keeps the members and replacing them keeps the name
+        let job = update_repository_group(&pool, work.id, Some("job"), None).await.unwrap();
+        assert_eq!(job.repository_ids, vec![a, b]);
+        let job = update_repository_group(&pool, work.id, None, Some(&[a])).await.unwrap();
+        assert_eq!((job.name.as_str(), job.repository_ids), ("job", vec![a]));
+        assert_eq!(filtered_totals(&pool, &groups(&[work.id])).await, (1, 10, 0));
+
+        // Removed repositories and groups leave no members behind
+        remove_repository(&pool, b).await.unwrap();
+        assert_eq!(get_repository_group(&pool, oss.id).await.unwrap().repository_ids, vec![c]);
+        assert!(delete_repository_group(&pool, oss.id).await.unwrap());
+        assert!(!delete_repository_group(&pool, oss.id).await.unwrap());
+        assert!(get_repository_group(&pool, oss.id).await.is_err());
+        assert_eq!(filtered_totals(&pool, &groups(&[oss.id])).await, (0, 0, 0));
+        let members: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM repository_group_members")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(members, 1);
+    }
+
+    #[tokio::test]
+    async fn reverts_are_linked_from_new_commits_only() {
+        let pool = test_pool().await;
+        let repository_id = add_repository(&pool, "/tmp/repo").await.unwrap().id;
+        let id = |prefix: &str| format!("{:0<40}", prefix);
+        let commit = |commit_id: &str, minute, additions, message: &str| {
+            let mut commit = analyzed(repository_id, commit_id, minute, &[("a.txt", additions, 0)]);
+            commit.commit.message = message.to_string();
+            commit
+        };
+        let (original, first_twin, second_twin, reapplied) = (id("aaaa111"), id("bbbb1110"), id("bbbb1111"), id("cccc"));
+        let (revert, ambiguous, revert_of_reapplied, reapply) = (id("dddd"), id("eeee"), id("ffff"), id("f0f0"));
+        ingest(&pool, vec![
+            commit(&original, 1, 1, "add a"),
+            commit(&first_twin, 2, 2, "add b"),
+            commit(&second_twin, 3, 4, "add c"),
+            commit(&reapplied, 4, 8, "add d"),
+            // Abbreviated and upper case, as some tools write them
+            commit(&revert, 5, 16, "Revert \"add a\"\n\nThis reverts commit AAAA111."),
+            commit(&ambiguous, 6, 32, "Revert \"add b\"\n\nThis reverts commit bbbb111."),
+            commit(&revert_of_reapplied, 7, 64, &format!("Revert \"add d\"\n\nThis reverts commit {}.", reapplied)),
+            commit(&reapply, 8, 128, &format!("Reapply \"add d\"\n\nThis reverts commit {}.", revert_of_reapplied)),
+        ])
+        .await;
+
+        let links = || async {
+            let links: Vec<(String, String)> = sqlx::query_as(
+                "SELECT revert_commit_id, reverted_commit_id FROM commit_reverts ORDER BY revert_commit_id"
+            )
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+            links
+        };
+        let net = TimeFilter { exclude_reverts: true, ..Default::default() };
+
+        // Only the messages of the commits a scan stored are read
+        assert_eq!(detect_reverts(&pool, repository_id, std::slice::from_ref(&revert)).await.unwrap(), 1);
+        assert_eq!(links().await, vec![(revert.clone(), original.clone())]);
+
+        // A prefix of two commits links neither
+        let stored = [ambiguous.clone(), revert_of_reapplied.clone()];
+        assert_eq!(detect_reverts(&pool, repository_id, &stored).await.unwrap(), 1);
+        assert_eq!(filtered_totals(&pool, &net).await, (4, 2 + 4 + 32 + 128, 0));
+
+        // Once the revert is reverted, the reapply cancels it and the
+        // original change counts again
+        assert_eq!(detect_reverts(&pool, repository_id, std::slice::from_ref(&reapply)).await.unwrap(), 1);
+        assert_eq!(
+            links().await,
+            vec![
+                (revert.clone(), original.clone()),
+                (reapply.clone(), revert_of_reapplied.clone()),
+                (revert_of_reapplied.clone(), reapplied.clone()),
+            ]
+        );
+        assert_eq!(filtered_totals(&pool, &net).await, (4, 2 + 4 + 8 + 32, 0));
+
+        let all = [original, first_twin, second_twin, reapplied, revert, ambiguous, revert_of_reapplied, reapply];
+        assert_eq!(detect_reverts(&pool, repository_id, &all).await.unwrap(), 0);
+    }
+}
@@ -0,0 +1,306 @@
+use crate::git_analyzer::GitAnalyzer;
+use crate::models::{Commit, DiffSearchHunk, DiffSearchMatch, DiffSearchQuery, PickaxeMode, Repository, TimeFilter};
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+// Finished searches kept for replay, the least recently stored are dropped beyond this
+const MAX_CACHED_SEARCHES: usize = 32;
+
+// Largest result kept for replay, counting the messages and hunk text of its
+// matches; bigger results are only streamed
+const MAX_CACHED_SEARCH_BYTES: usize = 4 * 1024 * 1024;
+
+/// Commits checked between two progress events.
+pub const PROGRESS_INTERVAL: i64 = 50;
+
+/// Candidate commits read from the database at a time, in timeline order.
+pub const CANDIDATE_PAGE_SIZE: u32 = 500;
+
+/// Running diff searches, so they can be cancelled, and the results of
+/// finished ones, so repeating a query replays them instead of walking
+/// history again.
+///
+/// Like `StatisticsCache`, results are dropped when a change touches their
+/// repository, and a search only stores its results if no invalidation
+/// happened while it ran.
+#[derive(Default)]
+pub struct DiffSearches {
+    running: Mutex<HashMap<String, Arc<AtomicBool>>>,
+    cache: Mutex<CacheState>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    entries: HashMap<String, CachedSearch>,
+    generation: u64,
+    next_sequence: u64,
+}
+
+struct CachedSearch {
+    repository_id: Option<i64>,
+    sequence: u64,
+    total: i64,
+    matches: Vec<DiffSearchMatch>,
+}
+
+impl DiffSearches {
+    /// Registers a new search under the caller's ID and returns its
+    /// cancellation flag, or `None` when a search with that ID is running.
+    pub fn start(&self, search_id: &str) -> Option<Arc<AtomicBool>> {
+        let mut running = self.running.lock().unwrap();
+        if running.contains_key(search_id) {
+            return None;
+        }
+        let cancelled = Arc::new(AtomicBool::new(false));
+        running.insert(search_id.to_string(), cancelled.clone());
+        Some(cancelled)
+    }
+
+    pub fn finish(&self, search_id: &str) {
+        self.running.lock().unwrap().remove(search_id);
+    }
+
+    /// Asks a running search to stop. Returns false when it already finished.
+    pub fn cancel(&self, search_id: &str) -> bool {
+        match self.running.lock().unwrap().get(search_id) {
+            Some(cancelled) => {
+                cancelled.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The commit count and matches of an earlier search with this key.
+    pub fn cached(&self, key: &str) -> Option<(i64, Vec<DiffSearchMatch>)> {
+        let cache = self.cache.lock().unwrap();
+        cache.entries.get(key).map(|entry| (entry.total, entry.matches.clone()))
+    }
+
+    /// The generation to pass to `store` for a search starting now.
+    pub fn generation(&self) -> u64 {
+        self.cache.lock().unwrap().generation
+    }
+
+    pub fn store(&self, key: String, filter: &TimeFilter, generation: u64, total: i64, matches: Vec<DiffSearchMatch>) {
+        let mut cache = self.cache.lock().unwrap();
+        if cache.generation != generation {
+            return;
+        }
+
+        if cache.entries.len() >= MAX_CACHED_SEARCHES && !cache.entries.contains_key(&key) {
+            let oldest = cache
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.sequence)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                cache.entries.remove(&oldest);
+            }
+        }
+
+        cache.next_sequence += 1;
+        let sequence = cache.next_sequence;
+        cache.entries.insert(key, CachedSearch {
+            repository_id: crate::stats_cache::cache_scope(filter),
+            sequence,
+            total,
+            matches,
+        });
+    }
+
+    /// Drops the results a change to the repository may have made stale,
+    /// or with `None` all of them.
+    pub fn invalidate(&self, repository_id: Option<i64>) {
+        let mut cache = self.cache.lock().unwrap();
+        cache.generation += 1;
+        match repository_id {
+            Some(id) => cache.entries.retain(|_, entry| entry.repository_id.is_some() && entry.repository_id != Some(id)),
+            None => cache.entries.clear(),
+        }
+    }
+}
+
+/// The matches of a running search, collected to be stored once it
+/// finishes. A search matching much of history would hold all of it in
+/// memory, so past `MAX_CACHED_SEARCH_BYTES` the matches collected so far
+/// are dropped and no more are kept; the caller still gets every match
+/// through its events, the search just isn't replayed.
+#[derive(Default)]
+pub struct CachedMatches {
+    matches: Vec<DiffSearchMatch>,
+    bytes: usize,
+    overflowed: bool,
+}
+
+impl CachedMatches {
+    pub fn push(&mut self, diff_match: DiffSearchMatch) {
+        if self.overflowed {
+            return;
+        }
+
+        self.bytes += diff_match.commit.message.len()
+            + diff_match
+                .hunks
+                .iter()
+                .map(|hunk| hunk.file_path.len() + hunk.header.len() + hunk.patch.len())
+                .sum::<usize>();
+        if self.bytes > MAX_CACHED_SEARCH_BYTES {
+            self.overflowed = true;
+            self.matches = Vec::new();
+            return;
+        }
+        self.matches.push(diff_match);
+    }
+
+    /// The collected matches, or `None` when they grew too large to cache.
+    pub fn into_matches(self) -> Option<Vec<DiffSearchMatch>> {
+        (!self.overflowed).then_some(self.matches)
+    }
+}
+
+/// The cache key of a diff search, shared by queries that select the same
+/// commits and look for the same thing.
+pub fn cache_key(query: &DiffSearchQuery, filter: &TimeFilter) -> Result<String> {
+    crate::stats_cache::cache_key(&format!("diff_search:{}", serde_json::to_string(query)?), filter)
+}
+
+/// A diff search query compiled for walking history.
+pub struct Pickaxe {
+    pattern: regex::Regex,
+    mode: PickaxeMode,
+    paths: Option<regex::Regex>, // The filter's path globs, the only files looked at
+}
+
+impl Pickaxe {
+    /// Compiles the query's pattern, escaping literal ones, together with
+    /// the filter's path globs.
+    pub fn new(query: &DiffSearchQuery, filter: &TimeFilter) -> Result<Self> {
+        if query.pattern.is_empty() {
+            return Err(anyhow::anyhow!("搜索内容不能为空"));
+        }
+
+        let pattern = if query.regex {
+            query.pattern.clone()
+        } else {
+            regex::escape(&query.pattern)
+        };
+        let pattern = regex::RegexBuilder::new(&pattern)
+            .case_insensitive(query.ignore_case)
+            .build()
+            .map_err(|e| anyhow::anyhow!("无效的正则表达式 {}: {}", query.pattern, e))?;
+
+        let globs: Vec<String> = filter
+            .paths
+            .iter()
+            .flatten()
+            .filter(|glob| !glob.trim().is_empty())
+            .map(|glob| format!("(?:{})", crate::filters::glob_to_regex(glob)))
+            .collect();
+        let paths = if globs.is_empty() {
+            None
+        } else {
+            Some(regex::Regex::new(&globs.join("|"))?)
+        };
+
+        Ok(Pickaxe { pattern, mode: query.mode, paths })
+    }
+}
+
+/// Checks every commit's diff against the pickaxe, calling `on_match` for
+/// each matching one, with its repository's current remote URL, and
+/// `on_progress` with the number of commits checked every
+/// `PROGRESS_INTERVAL` commits. Stops early once `cancelled` is set.
+/// Repositories that can't be opened and commits missing from them, such
+/// as garbage-collected orphans, are skipped. Returns the number of
+/// commits checked.
+///
+/// Blocks on git, so run it on a blocking thread.
+pub fn walk_commits<M, P>(
+    repositories: &[Repository],
+    commits: impl IntoIterator<Item = Commit>,
+    pickaxe: &Pickaxe,
+    cancelled: &AtomicBool,
+    mut on_match: M,
+    mut on_progress: P,
+) -> i64
+where
+    M: FnMut(Commit, Vec<DiffSearchHunk>),
+    P: FnMut(i64),
+{
+    let mut analyzers: HashMap<i64, Option<GitAnalyzer>> = HashMap::new();
+    let mut remote_urls: HashMap<i64, Option<String>> = HashMap::new();
+    let mut checked = 0;
+
+    for mut commit in commits {
+        if cancelled.load(Ordering::Relaxed) {
+            break;
+        }
+
+        let analyzer = analyzers.entry(commit.repository_id).or_insert_with(|| {
+            let repository = repositories.iter().find(|r| r.id == commit.repository_id)?;
+            GitAnalyzer::new(repository.clone())
+                .map_err(|e| eprintln!("差异搜索跳过仓库 {}: {}", repository.path, e))
+                .ok()
+        });
+
+        if let Some(analyzer) = analyzer {
+            match analyzer.pickaxe_commit(&commit.id, &pickaxe.pattern, pickaxe.mode, pickaxe.paths.as_ref()) {
+                Ok(hunks) if !hunks.is_empty() => {
+                    commit.remote_url = remote_urls
+                        .entry(commit.repository_id)
+                        .or_insert_with(|| analyzer.get_remote_url())
+                        .clone();
+                    on_match(commit, hunks)
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("差异搜索跳过提交 {}: {}", commit.id, e),
+            }
+        }
+
+        checked += 1;
+        if checked % PROGRESS_INTERVAL == 0 {
+            on_progress(checked);
+        }
+    }
+
+    checked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::analyzed;
+
+    fn diff_match(id: &str, patch_bytes: usize) -> DiffSearchMatch {
+        DiffSearchMatch {
+            search_id: "search".to_string(),
+            commit: analyzed(1, id, 0, &[]).commit,
+            hunks: vec![DiffSearchHunk {
+                file_path: "src/main.rs".to_string(),
+                header: "@@ -1 +1 @@".to_string(),
+                patch: "+".repeat(patch_bytes),
+                matched_lines: 1,
+            }],
+        }
+    }
+
+    #[test]
+    fn large_results_are_not_cached() {
+        let mut small = CachedMatches::default();
+        small.push(diff_match("a", 100));
+        small.push(diff_match("b", 100));
+        let ids: Vec<String> = small.into_matches().unwrap().into_iter().map(|m| m.commit.id).collect();
+        assert_eq!(ids, vec!["a", "b"]);
+
+        // Once over the limit nothing is kept, not even later small matches
+        let mut large = CachedMatches::default();
+        large.push(diff_match("a", MAX_CACHED_SEARCH_BYTES / 2));
+        large.push(diff_match("b", MAX_CACHED_SEARCH_BYTES / 2));
+        assert!(large.matches.is_empty());
+        large.push(diff_match("c", 100));
+        assert!(large.into_matches().is_none());
+    }
+}
@@ -0,0 +1,630 @@
+use crate::models::{FilterQueryError, FilterQuerySuggestion, Repository, RepositoryGroup, TimeFilter};
+
+// Keys of the query language with what their values select
+const KEYS: &[(&str, &str)] = &[
+    ("author", "作者，可重复；-author: 排除"),
+    ("repo", "仓库名，可重复；-repo: 排除"),
+    ("group", "仓库分组名，可重复"),
+    ("branch", "分支，可重复"),
+    ("path", "路径 glob，如 src/**，可重复"),
+    ("after", "起始日期 YYYY-MM-DD（含）"),
+    ("before", "截止日期 YYYY-MM-DD（不含）"),
+    ("msg", "提交消息包含的文字或 /正则/；-msg: 排除"),
+    ("size", "修改行数，如 >100、<=20、10..200"),
+    ("merges", "yes 时包含合并提交"),
+    ("orphaned", "yes 时包含已不可达的提交"),
+    ("dedupe", "yes 时相同补丁只计一次"),
+    ("reverts", "no 时排除回滚及被回滚的提交"),
+];
+
+// At most this many completions are offered at once
+const MAX_SUGGESTIONS: usize = 20;
+
+/// One `key:value` term of a query. Offsets are byte offsets into the query.
+struct Term {
+    negated: bool,
+    key: String,
+    value: String,
+    regex: bool, // Written as /.../
+    start: usize,
+    end: usize,
+    value_start: usize,
+}
+
+/// Parses a filter query such as
+/// `author:alice repo:backend path:src/** after:2025-01-01 -msg:/wip/ size:>100`
+/// into the filter it describes. Repository and group names are resolved
+/// against `repositories` and `groups`. Every problem found is reported,
+/// not only the first.
+pub fn parse_filter_query(
+    query: &str,
+    repositories: &[Repository],
+    groups: &[RepositoryGroup],
+) -> Result<TimeFilter, Vec<FilterQueryError>> {
+    let (terms, mut errors) = tokenize(query);
+    let mut filter = TimeFilter::default();
+    let mut excluded_repositories: Vec<i64> = Vec::new();
+    let mut last_excluded_repository = (0, 0);
+    let mut excluded_messages: Vec<String> = Vec::new();
+
+    for term in &terms {
+        let error = |message: String| error_at(query, term.start, term.end, message);
+        let value_error = |message: String| error_at(query, term.value_start, term.end, message);
+
+        if !KEYS.iter().any(|(key, _)| *key == term.key) {
+            errors.push(error_at(
+                query,
+                term.start,
+                term.value_start,
+                format!("未知的字段: {}，可用: {}", term.key, key_names()),
+            ));
+            continue;
+        }
+        if term.value.is_empty() {
+            errors.push(value_error(format!("{}: 缺少值", term.key)));
+            continue;
+        }
+        if term.negated && !matches!(term.key.as_str(), "author" | "repo" | "msg") {
+            errors.push(error(format!("{}: 不支持用 - 排除", term.key)));
+            continue;
+        }
+        if term.regex && term.key != "msg" {
+            errors.push(value_error(format!("{}: 不支持正则表达式", term.key)));
+            continue;
+        }
+
+        match term.key.as_str() {
+            "author" if term.negated => filter.exclude_authors.get_or_insert_with(Vec::new).push(term.value.clone()),
+            "author" => filter.authors.get_or_insert_with(Vec::new).push(term.value.clone()),
+            "repo" => {
+                let ids = matching_repositories(&term.value, repositories);
+                if ids.is_empty() {
+                    errors.push(value_error(format!("未知的仓库: {}", term.value)));
+                } else if term.negated {
+                    excluded_repositories.extend(ids);
+                    last_excluded_repository = (term.start, term.end);
+                } else {
+                    filter.repository_ids.get_or_insert_with(Vec::new).extend(ids);
+                }
+            }
+            "group" => {
+                let ids = matching_groups(&term.value, groups);
+                if ids.is_empty() {
+                    errors.push(value_error(format!("未知的分组: {}", term.value)));
+                } else {
+                    filter.group_ids.get_or_insert_with(Vec::new).extend(ids);
+                }
+            }
+            "branch" => filter.branches.get_or_insert_with(Vec::new).push(term.value.clone()),
+            "path" => filter.paths.get_or_insert_with(Vec::new).push(term.value.clone()),
+            "after" | "before" => match parse_date(&term.value) {
+                // Whole days, as in the date pickers: after includes the
+                // day, before ends right before it
+                Some(date) if term.key == "after" => filter.start_date = Some(date),
+                Some(date) => filter.end_date = Some(date - chrono::Duration::seconds(1)),
+                None => errors.push(value_error(format!("无效的日期: {}，应为 YYYY-MM-DD", term.value))),
+            },
+            "msg" => {
+                let pattern = if term.regex {
+                    if let Err(e) = regex::Regex::new(&term.value) {
+                        errors.push(value_error(format!("无效的正则表达式: {}", e)));
+                        continue;
+                    }
+                    term.value.clone()
+                } else {
+                    format!("(?i){}", regex::escape(&term.value))
+                };
+
+                if term.negated {
+                    excluded_messages.push(pattern);
+                } else if filter.message_pattern.is_some() {
+                    errors.push(error("msg: 只能出现一次，多个条件请写成一个正则".to_string()));
+                } else {
+                    filter.message_pattern = Some(pattern);
+                }
+            }
+            "size" => match parse_size(&term.value) {
+                Some((min_size, max_size)) => {
+                    if min_size.is_some() {
+                        filter.min_size = min_size;
+                    }
+                    if max_size.is_some() {
+                        filter.max_size = max_size;
+                    }
+                }
+                None => errors.push(value_error(format!("无效的大小: {}，应为 >100、<=20 或 10..200", term.value))),
+            },
+            key => match parse_switch(&term.value) {
+                Some(enabled) => match key {
+                    "merges" => filter.include_merges = enabled,
+                    "orphaned" => filter.include_orphaned = enabled,
+                    "dedupe" => filter.deduplicate_patches = enabled,
+                    _ => filter.exclude_reverts = !enabled,
+                },
+                None => errors.push(value_error(format!("{}: 值应为 yes 或 no", key))),
+            },
+        }
+    }
+
+    // A message matching any excluded pattern is dropped. Groups keep the
+    // flags of one pattern from applying to the others
+    match excluded_messages.len() {
+        0 => {}
+        1 => filter.exclude_message_pattern = excluded_messages.pop(),
+        _ => {
+            let groups: Vec<String> = excluded_messages.iter().map(|pattern| format!("(?:{})", pattern)).collect();
+            filter.exclude_message_pattern = Some(groups.join("|"));
+        }
+    }
+
+    // Excluding repositories selects all the others
+    if !excluded_repositories.is_empty() {
+        let included: Vec<i64> = filter
+            .repository_ids
+            .take()
+            .unwrap_or_else(|| repositories.iter().map(|repository| repository.id).collect());
+        let remaining: Vec<i64> = included.into_iter().filter(|id| !excluded_repositories.contains(id)).collect();
+        if remaining.is_empty() {
+            let (start, end) = last_excluded_repository;
+            errors.push(error_at(query, start, end, "排除后没有剩余的仓库".to_string()));
+        }
+        filter.repository_ids = Some(remaining);
+    }
+
+    if errors.is_empty() {
+        Ok(filter)
+    } else {
+        errors.sort_by_key(|error| error.start);
+        Err(errors)
+    }
+}
+
+/// Completions for the term at `cursor` (a UTF-16 offset): key names while
+/// the key is typed, and authors, repositories, groups or branches for
+/// their values.
+pub fn complete_filter_query(
+    query: &str,
+    cursor: usize,
+    authors: &[String],
+    repositories: &[Repository],
+    groups: &[RepositoryGroup],
+    branches: &[String],
+) -> Vec<FilterQuerySuggestion> {
+    let cursor = byte_offset(query, cursor);
+    let start = query[..cursor]
+        .char_indices()
+        .rev()
+        .find(|(_, c)| c.is_whitespace())
+        .map(|(i, c)| i + c.len_utf8())
+        .unwrap_or(0);
+    let (term_start, term) = match query[start..cursor].strip_prefix('-') {
+        Some(term) => (start + 1, term),
+        None => (start, &query[start..cursor]),
+    };
+
+    let suggestion = |text: String, kind: &str, description: Option<&str>, from: usize| FilterQuerySuggestion {
+        text,
+        kind: kind.to_string(),
+        description: description.map(|description| description.to_string()),
+        start: utf16_offset(query, from),
+        end: utf16_offset(query, cursor),
+    };
+
+    let Some((key, prefix)) = term.split_once(':') else {
+        let term = term.to_lowercase();
+        return KEYS
+            .iter()
+            .filter(|(key, _)| key.starts_with(&term))
+            .map(|(key, description)| suggestion(format!("{}:", key), "key", Some(description), term_start))
+            .collect();
+    };
+
+    let value_start = term_start + key.len() + 1;
+    let prefix = prefix.trim_start_matches('"').to_lowercase();
+    let values: Vec<&str> = match key {
+        "author" => authors.iter().map(|author| author.as_str()).collect(),
+        "repo" => repositories.iter().map(|repository| repository.label()).collect(),
+        "group" => groups.iter().map(|group| group.name.as_str()).collect(),
+        "branch" => branches.iter().map(|branch| branch.as_str()).collect(),
+        "merges" | "orphaned" | "dedupe" | "reverts" => vec!["yes", "no"],
+        _ => Vec::new(),
+    };
+
+    let mut seen = std::collections::HashSet::new();
+    values
+        .into_iter()
+        .filter(|value| value.to_lowercase().starts_with(&prefix) && seen.insert(*value))
+        .take(MAX_SUGGESTIONS)
+        .map(|value| suggestion(quote(value), key, None, value_start))
+        .collect()
+}
+
+// Splits the query into terms, reporting terms that can't be read
+fn tokenize(query: &str) -> (Vec<Term>, Vec<FilterQueryError>) {
+    let mut terms = Vec::new();
+    let mut errors = Vec::new();
+    let mut chars = query.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let negated = c == '-';
+        if negated {
+            chars.next();
+        }
+
+        let mut key = String::new();
+        let mut has_key = false;
+        while let Some(&(_, c)) = chars.peek() {
+            if c.is_whitespace() {
+                break;
+            }
+            chars.next();
+            if c == ':' {
+                has_key = true;
+                break;
+            }
+            key.push(c);
+        }
+
+        let value_start = chars.peek().map(|&(i, _)| i).unwrap_or(query.len());
+        if !has_key {
+            errors.push(error_at(
+                query,
+                start,
+                value_start,
+                format!("缺少字段名，应写成 字段:值，可用: {}", key_names()),
+            ));
+            continue;
+        }
+
+        // Quoted and /regex/ values may contain spaces
+        let mut value = String::new();
+        let mut regex = false;
+        let mut closed = true;
+        match chars.peek().map(|&(_, c)| c) {
+            Some(delimiter @ ('"' | '/')) => {
+                chars.next();
+                regex = delimiter == '/';
+                closed = false;
+                while let Some((_, c)) = chars.next() {
+                    if c == '\\' && chars.peek().map(|&(_, next)| next) == Some(delimiter) {
+                        // In a regex the escape stays, so the pattern keeps its meaning
+                        if regex {
+                            value.push('\\');
+                        }
+                        value.push(delimiter);
+                        chars.next();
+                    } else if c == delimiter {
+                        closed = true;
+                        break;
+                    } else {
+                        value.push(c);
+                    }
+                }
+            }
+            _ => {
+                while let Some(&(_, c)) = chars.peek() {
+                    if c.is_whitespace() {
+                        break;
+                    }
+                    value.push(c);
+                    chars.next();
+                }
+            }
+        }
+
+        let end = chars.peek().map(|&(i, _)| i).unwrap_or(query.len());
+        if !closed {
+            errors.push(error_at(query, value_start, end, "缺少结束的引号或斜杠".to_string()));
+            continue;
+        }
+
+        terms.push(Term {
+            negated,
+            key: key.to_lowercase(),
+            value,
+            regex,
+            start,
+            end,
+            value_start,
+        });
+    }
+
+    (terms, errors)
+}
+
+// Repositories shown as or named `name`, compared case-insensitively when no name matches exactly
+fn matching_repositories(name: &str, repositories: &[Repository]) -> Vec<i64> {
+    let exact: Vec<i64> = repositories
+        .iter()
+        .filter(|repository| repository.label() == name || repository.name == name)
+        .map(|repository| repository.id)
+        .collect();
+    if !exact.is_empty() {
+        return exact;
+    }
+
+    repositories
+        .iter()
+        .filter(|repository| repository.label().eq_ignore_ascii_case(name) || repository.name.eq_ignore_ascii_case(name))
+        .map(|repository| repository.id)
+        .collect()
+}
+
+// Groups named `name`, compared case-insensitively when none matches exactly
+fn matching_groups(name: &str, groups: &[RepositoryGroup]) -> Vec<i64> {
+    match groups.iter().find(|group| group.name == name) {
+        Some(group) => vec![group.id],
+        None => groups
+            .iter()
+            .filter(|group| group.name.eq_ignore_ascii_case(name))
+            .map(|group| group.id)
+            .collect(),
+    }
+}
+
+// Start of a UTC day, or an exact RFC 3339 time
+fn parse_date(value: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    if let Ok(date) = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return date.and_hms_opt(0, 0, 0).map(|time| time.and_utc());
+    }
+    chrono::DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|date| date.with_timezone(&chrono::Utc))
+}
+
+// `>n`, `>=n`, `<n`, `<=n`, `n..m` or `n`, as inclusive bounds on changed
+// lines. Bounds that overflow as inclusive ones are invalid
+fn parse_size(value: &str) -> Option<(Option<i64>, Option<i64>)> {
+    let number = |text: &str| text.trim().parse::<i64>().ok();
+
+    if let Some(rest) = value.strip_prefix(">=") {
+        Some((Some(number(rest)?), None))
+    } else if let Some(rest) = value.strip_prefix("<=") {
+        Some((None, Some(number(rest)?)))
+    } else if let Some(rest) = value.strip_prefix('>') {
+        Some((Some(number(rest)?.checked_add(1)?), None))
+    } else if let Some(rest) = value.strip_prefix('<') {
+        Some((None, Some(number(rest)?.checked_sub(1)?)))
+    } else if let Some((min, max)) = value.split_once("..") {
+        let min = if min.is_empty() { None } else { Some(number(min)?) };
+        let max = if max.is_empty() { None } else { Some(number(max)?) };
+        Some((min, max))
+    } else {
+        let size = number(value)?;
+        Some((Some(size), Some(size)))
+    }
+}
+
+fn parse_switch(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_str() {
+        "yes" | "true" | "on" => Some(true),
+        "no" | "false" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+// Values with spaces or quotes need quoting to read back as one term
+fn quote(value: &str) -> String {
+    if value.is_empty() || value.contains(|c: char| c.is_whitespace() || c == '"') {
+        format!("\"{}\"", value.replace('"', "\\\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn key_names() -> String {
+    KEYS.iter().map(|(key, _)| *key).collect::<Vec<_>>().join(", ")
+}
+
+// Errors carry UTF-16 offsets, which is how the UI indexes strings
+fn error_at(query: &str, start: usize, end: usize, message: String) -> FilterQueryError {
+    FilterQueryError {
+        message,
+        start: utf16_offset(query, start),
+        end: utf16_offset(query, end.max(start)),
+    }
+}
+
+fn utf16_offset(query: &str, byte: usize) -> usize {
+    query[..byte].encode_utf16().count()
+}
+
+fn byte_offset(query: &str, utf16: usize) -> usize {
+    let mut units = 0;
+    for (i, c) in query.char_indices() {
+        if units >= utf16 {
+            return i;
+        }
+        units += c.len_utf16();
+    }
+    query.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repository(id: i64, name: &str, display_name: Option<&str>) -> Repository {
+        Repository {
+            id,
+            path: format!("/src/{}", name),
+            name: name.to_string(),
+            last_scanned: None,
+            display_name: display_name.map(|name| name.to_string()),
+            color: None,
+            description: None,
+            archived: false,
+        }
+    }
+
+    fn repositories() -> Vec<Repository> {
+        vec![
+            repository(1, "backend", None),
+            repository(2, "web-app", Some("Frontend")),
+            repository(3, "tools", None),
+        ]
+    }
+
+    fn groups() -> Vec<RepositoryGroup> {
+        vec![RepositoryGroup { id: 7, name: "Work".to_string(), repository_ids: vec![1, 2] }]
+    }
+
+    fn parse(query: &str) -> Result<TimeFilter, Vec<FilterQueryError>> {
+        parse_filter_query(query, &repositories(), &groups())
+    }
+
+    // (start, end) of every error, in UTF-16 units
+    fn error_ranges(query: &str) -> Vec<(usize, usize)> {
+        parse(query).unwrap_err().iter().map(|error| (error.start, error.end)).collect()
+    }
+
+    fn complete(query: &str, cursor: usize) -> Vec<(String, usize, usize)> {
+        let authors = vec!["Alice".to_string(), "alan".to_string(), "Bob".to_string(), "Alice".to_string()];
+        let branches = vec!["main".to_string(), "feature/x".to_string()];
+        complete_filter_query(query, cursor, &authors, &repositories(), &groups(), &branches)
+            .into_iter()
+            .map(|suggestion| (suggestion.text, suggestion.start, suggestion.end))
+            .collect()
+    }
+
+    #[test]
+    fn parses_the_example_query() {
+        let filter = parse("author:alice repo:backend path:src/** after:2025-01-01 -msg:/wip/ size:>100").unwrap();
+        assert_eq!(filter.authors, Some(vec!["alice".to_string()]));
+        assert_eq!(filter.repository_ids, Some(vec![1]));
+        assert_eq!(filter.paths, Some(vec!["src/**".to_string()]));
+        assert_eq!(filter.start_date, parse_date("2025-01-01"));
+        assert_eq!(filter.end_date, None);
+        assert_eq!(filter.message_pattern, None);
+        assert_eq!(filter.exclude_message_pattern.as_deref(), Some("wip"));
+        assert_eq!((filter.min_size, filter.max_size), (Some(101), None));
+    }
+
+    #[test]
+    fn resolves_names_and_exclusions() {
+        let filter = parse("repo:frontend group:work -author:bot before:2025-02-01 merges:yes reverts:no").unwrap();
+        assert_eq!(filter.repository_ids, Some(vec![2]));
+        assert_eq!(filter.group_ids, Some(vec![7]));
+        assert_eq!(filter.exclude_authors, Some(vec!["bot".to_string()]));
+        assert_eq!(filter.end_date, parse_date("2025-01-31T23:59:59Z"));
+        assert!(filter.include_merges && filter.exclude_reverts);
+
+        // Excluding repositories keeps all the others
+        assert_eq!(parse("-repo:tools").unwrap().repository_ids, Some(vec![1, 2]));
+        assert_eq!(error_ranges("repo:backend -repo:backend"), vec![(13, 26)]);
+    }
+
+    #[test]
+    fn quoted_and_regex_values() {
+        let filter = parse(r#"author:"Alice Smith" msg:"say \"hi\" now""#).unwrap();
+        assert_eq!(filter.authors, Some(vec!["Alice Smith".to_string()]));
+        assert_eq!(filter.message_pattern, Some(format!("(?i){}", regex::escape(r#"say "hi" now"#))));
+
+        // An escaped slash stays escaped in the pattern
+        let filter = parse(r"msg:/fix(es)? \/ typo/").unwrap();
+        let pattern = filter.message_pattern.unwrap();
+        assert_eq!(pattern, r"fix(es)? \/ typo");
+        assert!(regex::Regex::new(&pattern).unwrap().is_match("fixes / typo"));
+
+        // Excluded messages are combined into one pattern
+        let filter = parse("-msg:/^wip/ -msg:Merge").unwrap();
+        assert_eq!(filter.exclude_message_pattern.as_deref(), Some("(?:^wip)|(?:(?i)Merge)"));
+    }
+
+    #[test]
+    fn unclosed_quotes_and_slashes() {
+        let query = r#"author:"alice repo:backend"#;
+        let errors = parse(query).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].start, errors[0].end), (7, query.len()));
+        assert!(errors[0].message.contains("引号"));
+
+        assert_eq!(error_ranges(r"msg:/wip\/ size:>1"), vec![(4, 18)]);
+        assert_eq!(error_ranges(r#"author:"a\""#), vec![(7, 11)]);
+    }
+
+    #[test]
+    fn unknown_keys_and_missing_values() {
+        let errors = parse("auther:alice repo:backend").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].start, errors[0].end), (0, 7));
+        assert!(errors[0].message.contains("auther"));
+
+        assert_eq!(error_ranges("alice"), vec![(0, 5)]);
+        assert_eq!(error_ranges("repo:nowhere group:home"), vec![(5, 12), (19, 23)]);
+        assert_eq!(error_ranges("branch: after:2025-13-01"), vec![(7, 7), (14, 24)]);
+    }
+
+    #[test]
+    fn negation_only_where_supported() {
+        let errors = parse("-branch:main -path:docs").unwrap_err();
+        assert_eq!(errors.iter().map(|error| (error.start, error.end)).collect::<Vec<_>>(), vec![(0, 12), (13, 23)]);
+        assert!(errors[0].message.contains("branch"));
+        assert_eq!(error_ranges("path:/src/"), vec![(5, 10)]);
+        assert!(parse("-author:bot -repo:tools -msg:wip").is_ok());
+    }
+
+    #[test]
+    fn sizes() {
+        let size = |query: &str| parse(query).map(|filter| (filter.min_size, filter.max_size)).ok();
+        assert_eq!(size("size:<=20"), Some((None, Some(20))));
+        assert_eq!(size("size:<20"), Some((None, Some(19))));
+        assert_eq!(size("size:>=5"), Some((Some(5), None)));
+        assert_eq!(size("size:10..200"), Some((Some(10), Some(200))));
+        assert_eq!(size("size:..200"), Some((None, Some(200))));
+        assert_eq!(size("size:42"), Some((Some(42), Some(42))));
+
+        // Bounds that can't be made inclusive are reported, not wrapped
+        assert_eq!(error_ranges("size:>9223372036854775807"), vec![(5, 25)]);
+        assert_eq!(error_ranges("size:<-9223372036854775808"), vec![(5, 26)]);
+        assert_eq!(size("size:>9223372036854775806"), Some((Some(i64::MAX), None)));
+        assert_eq!(error_ranges("size:big"), vec![(5, 8)]);
+    }
+
+    #[test]
+    fn error_ranges_count_utf16_units() {
+        // 张 and 三 are one unit each, 🎉 is a surrogate pair
+        assert_eq!(error_ranges("author:张三 🎉foo:bar"), vec![(10, 16)]);
+        assert_eq!(error_ranges("msg:🎉🎉 after:二〇二五"), vec![(15, 19)]);
+        assert_eq!(error_ranges("author:\"张三 🎉"), vec![(7, 13)]);
+
+        let query = "a张🎉b";
+        for (byte, utf16) in [(0, 0), (1, 1), (4, 2), (8, 4), (9, 5)] {
+            assert_eq!(utf16_offset(query, byte), utf16);
+            assert_eq!(byte_offset(query, utf16), byte);
+        }
+    }
+
+    #[test]
+    fn completes_keys() {
+        assert_eq!(complete("re", 2), vec![("repo:".to_string(), 0, 2), ("reverts:".to_string(), 0, 2)]);
+        assert_eq!(complete("-au", 3), vec![("author:".to_string(), 1, 3)]);
+        // After CJK text the offsets stay in UTF-16 units
+        assert_eq!(complete("msg:你好 🎉 br", 12), vec![("branch:".to_string(), 10, 12)]);
+    }
+
+    #[test]
+    fn completes_values_at_the_end_of_a_term() {
+        assert_eq!(
+            complete("repo:backend author:al", 22),
+            vec![("Alice".to_string(), 20, 22), ("alan".to_string(), 20, 22)]
+        );
+        assert_eq!(complete("repo:fr", 7), vec![("Frontend".to_string(), 5, 7)]);
+        assert_eq!(complete("branch:feat", 11), vec![("feature/x".to_string(), 7, 11)]);
+        assert_eq!(complete("dedupe:", 7), vec![("yes".to_string(), 7, 7), ("no".to_string(), 7, 7)]);
+    }
+
+    #[test]
+    fn completes_values_in_the_middle_of_a_term() {
+        // Only the text before the cursor counts as the prefix
+        assert_eq!(complete("author:bob repo:x", 8), vec![("Bob".to_string(), 7, 8)]);
+        assert_eq!(complete("repo:too", 6), vec![("tools".to_string(), 5, 6)]);
+        assert_eq!(complete("group:Wo repo:x", 7), vec![("Work".to_string(), 6, 7)]);
+        assert_eq!(complete("rep", 1), vec![("repo:".to_string(), 0, 1), ("reverts:".to_string(), 0, 1)]);
+    }
+}
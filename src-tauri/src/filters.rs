@@ -0,0 +1,336 @@
+use crate::models::TimeFilter;
+use anyhow::Result;
+
+/// Builds the `FROM ... AS commits WHERE ...` clause of the commits a
+/// filter selects, together with its bind parameters. Every endpoint that
+/// takes a `TimeFilter` reads its commits through this clause, so the
+/// criteria apply the same way everywhere.
+///
+/// With path globs the source is a subquery whose additions, deletions and
+/// files_changed only count the matching files, and commits without one
+/// are dropped. Size bounds compare against those restricted counts.
+pub fn build_commit_query(filter: &TimeFilter) -> Result<(String, Vec<String>)> {
+    let mut conditions = "1=1".to_string();
+    let mut params: Vec<String> = Vec::new();
+
+    if let Some(start_date) = &filter.start_date {
+        conditions.push_str(" AND commits.timestamp >= ?");
+        params.push(start_date.to_rfc3339());
+    }
+
+    if let Some(end_date) = &filter.end_date {
+        conditions.push_str(" AND commits.timestamp <= ?");
+        params.push(end_date.to_rfc3339());
+    }
+
+    push_scope_conditions(&mut conditions, &mut params, filter, "commits.");
+
+    let branches = non_empty(&filter.branches);
+    if !branches.is_empty() {
+        conditions.push_str(&format!(" AND commits.branch IN ({})", placeholders(branches.len())));
+        params.extend(branches.iter().map(|branch| branch.to_string()));
+    }
+
+    // Commits dropped by force-pushes or branch deletions
+    if !filter.include_orphaned {
+        conditions.push_str(" AND commits.orphaned_at IS NULL");
+    }
+
+    if !filter.include_merges {
+        conditions.push_str(" AND commits.is_merge = 0");
+    }
+
+    if let Some(pattern) = message_pattern(&filter.message_pattern)? {
+        conditions.push_str(" AND commits.message REGEXP ?");
+        params.push(pattern);
+    }
+
+    if let Some(pattern) = message_pattern(&filter.exclude_message_pattern)? {
+        conditions.push_str(" AND NOT (commits.message REGEXP ?)");
+        params.push(pattern);
+    }
+
+    let mut query = match path_condition(filter, "fc.file_path") {
+        Some((path_clause, path_params)) => {
+            params.extend(path_params);
+            format!(
+                "FROM (SELECT commits.id, commits.repository_id, commits.repository_name, commits.author,
+                 commits.email, commits.message, commits.timestamp,
+                 SUM(fc.additions) AS additions, SUM(fc.deletions) AS deletions, COUNT(*) AS files_changed,
+                 commits.branch, commits.remote_url, commits.patch_id, commits.inverse_patch_id,
+                 commits.orphaned_at, commits.is_merge
+                 FROM commits
+                 JOIN file_changes fc ON fc.commit_id = commits.id AND fc.repository_id = commits.repository_id
+                 WHERE {} AND {}
+                 GROUP BY commits.repository_id, commits.id) AS commits WHERE 1=1",
+                conditions, path_clause
+            )
+        }
+        None => format!("FROM commits WHERE {}", conditions),
+    };
+
+    if let Some(min_size) = filter.min_size {
+        query.push_str(" AND (commits.additions + commits.deletions) >= CAST(? AS INTEGER)");
+        params.push(min_size.to_string());
+    }
+
+    if let Some(max_size) = filter.max_size {
+        query.push_str(" AND (commits.additions + commits.deletions) <= CAST(? AS INTEGER)");
+        params.push(max_size.to_string());
+    }
+
+    // Net-effect accounting: a revert and the commit it reverts cancel out.
+    // A revert that was reverted in turn cancels with that reapply instead,
+    // so the original change, which is back in the code, still counts.
+    // Longer chains only cancel their last pair.
+    if filter.exclude_reverts {
+        query.push_str(
+            " AND NOT EXISTS (SELECT 1 FROM commit_reverts cr
+               WHERE cr.repository_id = commits.repository_id
+               AND (cr.revert_commit_id = commits.id OR cr.reverted_commit_id = commits.id)
+               AND NOT EXISTS (SELECT 1 FROM commit_reverts reapply
+                 WHERE reapply.repository_id = cr.repository_id AND reapply.reverted_commit_id = cr.revert_commit_id))"
+        );
+    }
+
+    // Keep only the earliest commit of every patch-id, so cherry-picks and
+    // fork copies of the same change are counted once
+    if filter.deduplicate_patches {
+        query = format!(
+            "FROM (SELECT *, ROW_NUMBER() OVER (
+                 PARTITION BY COALESCE(patch_id, repository_id || ':' || id)
+                 ORDER BY timestamp, repository_id
+             ) AS patch_rank
+             {}) AS commits WHERE patch_rank = 1",
+            query
+        );
+    }
+
+    Ok((query, params))
+}
+
+/// Appends the author, repository and group criteria, which the rollup
+/// tables can answer as well. `prefix` qualifies the `author` and
+/// `repository_id` columns.
+pub fn push_scope_conditions(conditions: &mut String, params: &mut Vec<String>, filter: &TimeFilter, prefix: &str) {
+    // `author` and `authors` together form one set of accepted authors
+    let mut authors: Vec<&str> = filter.author.iter().map(|author| author.as_str()).collect();
+    authors.extend(non_empty(&filter.authors));
+    if !authors.is_empty() {
+        conditions.push_str(&format!(" AND {}author IN ({})", prefix, placeholders(authors.len())));
+        params.extend(authors.iter().map(|author| author.to_string()));
+    }
+
+    let exclude_authors = non_empty(&filter.exclude_authors);
+    if !exclude_authors.is_empty() {
+        conditions.push_str(&format!(" AND {}author NOT IN ({})", prefix, placeholders(exclude_authors.len())));
+        params.extend(exclude_authors.iter().map(|author| author.to_string()));
+    }
+
+    let mut repository_ids: Vec<i64> = filter.repository_id.into_iter().collect();
+    repository_ids.extend(filter.repository_ids.iter().flatten());
+    if !repository_ids.is_empty() {
+        conditions.push_str(&format!(" AND {}repository_id IN ({})", prefix, placeholders(repository_ids.len())));
+        params.extend(repository_ids.iter().map(|id| id.to_string()));
+    }
+
+    let group_ids: Vec<i64> = filter.group_ids.iter().flatten().copied().collect();
+    if !group_ids.is_empty() {
+        conditions.push_str(&format!(
+            " AND {}repository_id IN (SELECT repository_id FROM repository_group_members WHERE group_id IN ({}))",
+            prefix,
+            placeholders(group_ids.len())
+        ));
+        params.extend(group_ids.iter().map(|id| id.to_string()));
+    }
+}
+
+/// Whether the filter needs criteria the rollup tables don't keep, so the
+/// statistics have to read the raw commits.
+pub fn needs_raw_commits(filter: &TimeFilter) -> bool {
+    filter.deduplicate_patches
+        || filter.exclude_reverts
+        || filter.include_merges
+        || !non_empty(&filter.branches).is_empty()
+        || !non_empty(&filter.paths).is_empty()
+        || filter.min_size.is_some()
+        || filter.max_size.is_some()
+        || filter.message_pattern.as_deref().is_some_and(|pattern| !pattern.is_empty())
+        || filter.exclude_message_pattern.as_deref().is_some_and(|pattern| !pattern.is_empty())
+}
+
+/// The condition matching `column` against the filter's path globs, or
+/// `None` when the filter has none.
+pub fn path_condition(filter: &TimeFilter, column: &str) -> Option<(String, Vec<String>)> {
+    let globs = non_empty(&filter.paths);
+    if globs.is_empty() {
+        return None;
+    }
+
+    let clause = vec![format!("{} REGEXP ?", column); globs.len()].join(" OR ");
+    let params = globs.iter().map(|glob| glob_to_regex(glob)).collect();
+    Some((format!("({})", clause), params))
+}
+
+/// Translates a path glob into an anchored regex: `**` matches across
+/// directories, `*` and `?` within one. A glob without a slash matches the
+/// file name at any depth, and a glob without wildcards also matches
+/// everything below it as a directory.
+pub fn glob_to_regex(glob: &str) -> String {
+    let glob = glob.trim().trim_start_matches("./").trim_start_matches('/').trim_end_matches('/');
+    let mut regex = "^".to_string();
+    if !glob.contains('/') {
+        regex.push_str("(.*/)?");
+    }
+
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                // `**/` also matches no directory at all
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+
+    if !glob.contains(['*', '?']) {
+        regex.push_str("(/.*)?");
+    }
+    regex.push('$');
+    regex
+}
+
+/// Validates a message regex up front, so a typo is reported as such
+/// instead of as a failed query.
+fn message_pattern(pattern: &Option<String>) -> Result<Option<String>> {
+    match pattern.as_deref() {
+        None | Some("") => Ok(None),
+        Some(pattern) => {
+            regex::Regex::new(pattern).map_err(|e| anyhow::anyhow!("无效的正则表达式 {}: {}", pattern, e))?;
+            Ok(Some(pattern.to_string()))
+        }
+    }
+}
+
+fn non_empty(values: &Option<Vec<String>>) -> Vec<&str> {
+    values
+        .iter()
+        .flatten()
+        .map(|value| value.as_str())
+        .filter(|value| !value.trim().is_empty())
+        .collect()
+}
+
+fn placeholders(count: usize) -> String {
+    vec!["?"; count].join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{analyzed, ingest, test_pool};
+    use sqlx::SqlitePool;
+
+    fn matches(glob: &str, path: &str) -> bool {
+        regex::Regex::new(&glob_to_regex(glob)).unwrap().is_match(path)
+    }
+
+    #[test]
+    fn double_star_crosses_directories() {
+        assert!(matches("src/**/*.rs", "src/main.rs"));
+        assert!(matches("src/**/*.rs", "src/a/b/c.rs"));
+        assert!(!matches("src/**/*.rs", "lib/src/main.rs"));
+        assert!(matches("**/tests/*.rs", "tests/parse.rs"));
+        assert!(matches("**/tests/*.rs", "crates/core/tests/parse.rs"));
+        assert!(matches("docs/**", "docs/guide/install.md"));
+        assert!(!matches("docs/**", "docs"));
+    }
+
+    #[test]
+    fn single_star_and_question_mark_stay_within_a_directory() {
+        assert!(matches("src/*.rs", "src/main.rs"));
+        assert!(!matches("src/*.rs", "src/bin/main.rs"));
+        assert!(matches("src/?.rs", "src/a.rs"));
+        assert!(!matches("src/?.rs", "src/ab.rs"));
+        assert!(!matches("src?main.rs", "src/main.rs"));
+        assert!(!matches("src/*", "src/a/b.rs"));
+    }
+
+    #[test]
+    fn globs_without_a_slash_match_at_any_depth() {
+        assert!(matches("*.rs", "main.rs"));
+        assert!(matches("*.rs", "src/a/b/main.rs"));
+        assert!(!matches("*.rs", "main.rsx"));
+        assert!(matches("Cargo.toml", "crates/core/Cargo.toml"));
+        assert!(!matches("Cargo.toml", "crates/core/MyCargo.toml"));
+    }
+
+    #[test]
+    fn globs_without_wildcards_match_directory_prefixes() {
+        assert!(matches("src/components", "src/components"));
+        assert!(matches("src/components", "src/components/Timeline.tsx"));
+        assert!(matches("./src/components/", "src/components/ui/Button.tsx"));
+        assert!(!matches("src/components", "src/components-old/Timeline.tsx"));
+        assert!(!matches("src/components", "app/src/components/Timeline.tsx"));
+        // Regex characters in paths are literal
+        assert!(matches("c++/a.b", "c++/a.b"));
+        assert!(!matches("c++/a.b", "cc/aab"));
+    }
+
+    // (id, additions, deletions, files_changed) of the commits the filter selects
+    async fn select(pool: &SqlitePool, filter: &TimeFilter) -> Vec<(String, i64, i64, i64)> {
+        let (query, params) = build_commit_query(filter).unwrap();
+        let query = format!("SELECT id, additions, deletions, files_changed {} ORDER BY id", query);
+        let mut builder = sqlx::query_as(&query);
+        for param in params {
+            builder = builder.bind(param);
+        }
+        builder.fetch_all(pool).await.unwrap()
+    }
+
+    fn paths(globs: &[&str]) -> TimeFilter {
+        TimeFilter {
+            paths: Some(globs.iter().map(|glob| glob.to_string()).collect()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn path_globs_restrict_commits_and_line_counts() {
+        let pool = test_pool().await;
+        let repository = crate::database::add_repository(&pool, "/tmp/repo").await.unwrap();
+
+        ingest(&pool, vec![
+            analyzed(repository.id, "a", 1, &[("src/main.rs", 10, 2), ("docs/readme.md", 5, 0)]),
+            analyzed(repository.id, "b", 2, &[("docs/guide.md", 7, 3)]),
+            analyzed(repository.id, "c", 3, &[("src/parse/mod.rs", 1, 1), ("Cargo.toml", 1, 0)]),
+        ])
+        .await;
+
+        let all = |id: &str, additions, deletions, files| (id.to_string(), additions, deletions, files);
+        assert_eq!(
+            select(&pool, &TimeFilter::default()).await,
+            vec![all("a", 15, 2, 2), all("b", 7, 3, 1), all("c", 2, 1, 2)]
+        );
+        assert_eq!(select(&pool, &paths(&["*.rs"])).await, vec![all("a", 10, 2, 1), all("c", 1, 1, 1)]);
+        assert_eq!(select(&pool, &paths(&["src/*.rs"])).await, vec![all("a", 10, 2, 1)]);
+        assert_eq!(
+            select(&pool, &paths(&["docs", "Cargo.toml"])).await,
+            vec![all("a", 5, 0, 1), all("b", 7, 3, 1), all("c", 1, 0, 1)]
+        );
+        assert_eq!(select(&pool, &paths(&["tests/**"])).await, vec![]);
+
+        // Size bounds apply to the restricted counts
+        let filter = TimeFilter { min_size: Some(10), ..paths(&["docs"]) };
+        assert_eq!(select(&pool, &filter).await, vec![all("b", 7, 3, 1)]);
+    }
+}
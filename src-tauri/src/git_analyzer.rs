@@ -1,16 +1,18 @@
 use git2::{Repository as GitRepository, DiffOptions, DiffFormat, DiffLineType, Oid};
+use crate::git_backend::{open_backend, CommitDiffStats, CommitMetadata, GitBackend, GitBackendKind};
 use crate::models::{Commit, Repository};
 use anyhow::{Result, Context};
 use std::path::Path;
 use std::collections::{HashSet, HashMap};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
 
-// New struct to hold file change information
+// Per-file line counts collected while scanning, without the patch text
 #[derive(Debug, Clone)]
 pub struct FileChange {
     pub path: String,
     pub additions: i32,
     pub deletions: i32,
-    pub diff: String,
 }
 
 #[derive(Debug, Clone)]
@@ -70,78 +72,75 @@ impl GitAnalyzer {
         None
     }
 
-    pub fn analyze_commits(&self, since: Option<chrono::DateTime<chrono::Utc>>) -> Result<Vec<AnalyzedCommit>> {
-        let mut revwalk = self.repo.revwalk()?;
-        // Push all local branches instead of just HEAD
-        revwalk.push_glob("refs/heads/*")?;
-        // Also push all remote branches
-        revwalk.push_glob("refs/remotes/*")?;
-        revwalk.set_sorting(git2::Sort::TIME)?;
-
-        let mut commits = Vec::new();
-        let mut processed_commits = HashSet::new();
+    /// Lists the commits a scan analyzes, newest first: every commit
+    /// reachable from a local or remote branch, stopping at `since`.
+    pub fn get_scan_commit_ids(&self, since: Option<chrono::DateTime<chrono::Utc>>) -> Result<Vec<Oid>> {
+        let walk_start = std::time::Instant::now();
+        let commit_ids = match since {
+            Some(since_time) => self.get_commit_ids_since(since_time.timestamp())?,
+            None => {
+                let mut revwalk = self.repo.revwalk()?;
+                // Push all local branches instead of just HEAD
+                revwalk.push_glob("refs/heads/*")?;
+                // Also push all remote branches
+                revwalk.push_glob("refs/remotes/*")?;
+                revwalk.set_sorting(git2::Sort::TIME)?;
 
-        for oid_result in revwalk {
-            let oid = oid_result?;
-            
-            if processed_commits.contains(&oid) {
-                continue;
+                // Merge commits are stored too, flagged so the statistics can
+                // leave them out and avoid double counting
+                revwalk.collect::<Result<Vec<_>, _>>()?
             }
-            processed_commits.insert(oid);
+        };
+        println!("🚶 遍历待分析提交耗时: {:?}, 提交数: {}", walk_start.elapsed(), commit_ids.len());
 
-            let commit = self.repo.find_commit(oid)?;
-            
-            // Skip if commit is before the 'since' time
-            if let Some(since_time) = since {
-                let commit_time = chrono::DateTime::from_timestamp(commit.time().seconds(), 0)
-                    .unwrap_or_default();
-                
-                if commit_time < since_time {
-                    break;
+        Ok(commit_ids)
+    }
+
+    // Commits from `since` on, newest first. Like `git log --since` and
+    // gix's cutoff walk, parents older than `since` are never followed, so
+    // an incremental scan reads only the new part of the history; libgit2's
+    // time-sorted walk would sort all of it first.
+    fn get_commit_ids_since(&self, since: i64) -> Result<Vec<Oid>> {
+        let mut pending = Vec::new();
+        for glob in ["refs/heads/*", "refs/remotes/*"] {
+            for reference in self.repo.references_glob(glob)? {
+                // Refs that don't point at a commit are skipped, as `push_glob` does
+                if let Ok(commit) = reference?.peel_to_commit() {
+                    pending.push(commit);
                 }
             }
+        }
 
-            // Skip merge commits to avoid double counting
-            if commit.parent_count() > 1 {
-                continue;
+        let mut queue = std::collections::BinaryHeap::new();
+        let mut queued = HashSet::new();
+        let mut commit_ids = Vec::new();
+        loop {
+            for commit in pending.drain(..) {
+                let time = commit.time().seconds();
+                if time >= since && queued.insert(commit.id()) {
+                    queue.push((time, commit.id()));
+                }
             }
+            let Some((_, oid)) = queue.pop() else { break };
+            commit_ids.push(oid);
+            pending.extend(self.repo.find_commit(oid)?.parents());
+        }
+        Ok(commit_ids)
+    }
 
-            let author = commit.author();
-            let author_name = author.name().unwrap_or("Unknown").to_string();
-            let author_email = author.email().unwrap_or("").to_string();
-            let message = commit.message().unwrap_or("").to_string();
-            
-            let timestamp = chrono::DateTime::from_timestamp(commit.time().seconds(), 0)
-                .unwrap_or_default();
-
-            // Get current branch name if possible
-            let branch = self.get_commit_branch(&commit)?;
-
-            // Calculate diff stats and get file changes
-            let (additions, deletions, files_changed, file_changes) = self.get_detailed_commit_stats(&commit)?;
-
-            let commit_data = Commit {
-                id: oid.to_string(),
-                repository_id: self.repository_info.id,
-                repository_name: self.repository_info.name.clone(),
-                author: author_name,
-                email: author_email,
-                message,
-                timestamp,
-                additions,
-                deletions,
-                files_changed,
-                branch: Some(branch),
-                remote_url: None, // This will be filled when retrieving from database
-            };
+    /// Collects the IDs of all commits reachable from the refs that
+    /// `analyze_commits` scans (local and remote branches).
+    pub fn get_reachable_commit_ids(&self) -> Result<HashSet<String>> {
+        let mut revwalk = self.repo.revwalk()?;
+        revwalk.push_glob("refs/heads/*")?;
+        revwalk.push_glob("refs/remotes/*")?;
 
-            commits.push(AnalyzedCommit {
-                commit: commit_data,
-                file_changes,
-            });
+        let mut reachable = HashSet::new();
+        for oid_result in revwalk {
+            reachable.insert(oid_result?.to_string());
         }
 
-        Ok(commits)
+        Ok(reachable)
     }
 
     fn get_commit_stats(&self, commit: &git2::Commit) -> Result<(i32, i32, i32)> {
@@ -197,23 +196,17 @@ impl GitAnalyzer {
         let remote_url = self.get_remote_url();
         println!("🌍 获取远程URL耗时: {:?}", remote_start.elapsed());
 
-        // Calculate diff stats and get file changes
+        // Calculate diff stats and render the patch text of every file
         let diff_start = std::time::Instant::now();
-        let (additions, deletions, files_changed, file_changes) = self.get_detailed_commit_stats(&commit)?;
-        println!("📊 计算diff统计耗时: {:?}, 文件数: {}", diff_start.elapsed(), file_changes.len());
-
-        // Convert FileChange to models::FileChange
-        let model_file_changes = file_changes.into_iter().map(|fc| crate::models::FileChange {
-            path: fc.path,
-            additions: fc.additions,
-            deletions: fc.deletions,
-            diff: fc.diff,
-        }).collect();
+        let diff = self.diff_commit(&commit)?;
+        let (patch_id, _) = get_patch_ids(&diff)?;
+        let (additions, deletions, files_changed, model_file_changes) = self.render_diff(&diff)?;
+        println!("📊 计算diff统计耗时: {:?}, 文件数: {}", diff_start.elapsed(), model_file_changes.len());
 
         Ok(crate::models::CommitDetail {
             id: commit_id.to_string(),
             repository_id: self.repository_info.id,
-            repository_name: self.repository_info.name.clone(),
+            repository_name: self.repository_info.label().to_string(),
             author: author_name,
             email: author_email,
             message,
@@ -223,41 +216,108 @@ impl GitAnalyzer {
             files_changed,
             branch: Some(branch),
             remote_url,
+            patch_id,
             file_changes: model_file_changes,
+            duplicates: Vec::new(), // Filled from the database by the command
+            reverts: Vec::new(),
+            reverted_by: Vec::new(),
         })
     }
 
-    fn get_detailed_commit_stats(&self, commit: &git2::Commit) -> Result<(i32, i32, i32, Vec<FileChange>)> {
-        let start_time = std::time::Instant::now();
-        println!("📈 开始计算详细diff统计");
-        
-        let tree_start = std::time::Instant::now();
+    // Runs on every scan worker for every commit, so it doesn't log
+    fn get_detailed_commit_stats(&self, commit: &git2::Commit) -> Result<CommitDiffStats> {
+        let diff = self.diff_commit(commit)?;
+        self.collect_numstat(&diff)
+    }
+
+    /// Diffs a commit against its first parent, or the empty tree for root commits.
+    fn diff_commit(&self, commit: &git2::Commit) -> Result<git2::Diff<'_>> {
         let tree = commit.tree()?;
         let parent_tree = if commit.parent_count() > 0 {
             Some(commit.parent(0)?.tree()?)
         } else {
             None
         };
-        println!("🌳 获取tree对象耗时: {:?}", tree_start.elapsed());
 
-        let diff_create_start = std::time::Instant::now();
+        self.diff_trees(parent_tree.as_ref(), Some(&tree))
+    }
+
+    fn diff_trees(&self, old_tree: Option<&git2::Tree>, new_tree: Option<&git2::Tree>) -> Result<git2::Diff<'_>> {
         let mut diff_opts = DiffOptions::new();
         diff_opts.ignore_whitespace(true);
         diff_opts.ignore_blank_lines(true);
 
         let diff = self.repo.diff_tree_to_tree(
-            parent_tree.as_ref(),
-            Some(&tree),
+            old_tree,
+            new_tree,
             Some(&mut diff_opts),
         )?;
-        println!("🔄 创建diff对象耗时: {:?}", diff_create_start.elapsed());
 
+        Ok(diff)
+    }
+
+    /// Diffs two trees with the analyzer's standard options and collects
+    /// the aggregate stats together with the per-file patch text.
+    fn get_tree_diff_stats(&self, old_tree: Option<&git2::Tree>, new_tree: Option<&git2::Tree>) -> Result<(i32, i32, i32, Vec<crate::models::FileChange>)> {
+        let diff = self.diff_trees(old_tree, new_tree)?;
+        self.render_diff(&diff)
+    }
+
+    /// Per-file line counts of a diff, the equivalent of `git diff --numstat`,
+    /// and its patch-ids. Used by scans, which only store the counts, so no
+    /// patch text is built and each file is diffed once for both.
+    /// A type change shows up as a delete and an add of the same path; both
+    /// are summed into one entry, since rows are keyed by path.
+    fn collect_numstat(&self, diff: &git2::Diff) -> Result<CommitDiffStats> {
+        let mut patch_ids = PatchIds::new();
+        let mut file_changes: Vec<FileChange> = Vec::with_capacity(diff.deltas().len());
+        let mut file_indexes: HashMap<String, usize> = HashMap::new();
+        let mut total_additions = 0;
+        let mut total_deletions = 0;
+
+        for (index, delta) in diff.deltas().enumerate() {
+            let path = delta.new_file().path().or(delta.old_file().path())
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_else(|| "unknown".to_string());
+
+            // Binary files count as zero lines, like numstat's "-"
+            let (additions, deletions) = match git2::Patch::from_diff(diff, index)? {
+                Some(patch) => {
+                    patch_ids.add_patch(&patch)?;
+                    if is_whitespace_only(&patch) {
+                        continue;
+                    }
+                    let (_, additions, deletions) = patch.line_stats()?;
+                    (additions as i32, deletions as i32)
+                }
+                None => (0, 0),
+            };
+
+            total_additions += additions;
+            total_deletions += deletions;
+            add_file_change(&mut file_changes, &mut file_indexes, path, additions, deletions);
+        }
+
+        let (patch_id, inverse_patch_id) = patch_ids.finish()?;
+        Ok(CommitDiffStats {
+            additions: total_additions,
+            deletions: total_deletions,
+            files_changed: diff.deltas().len() as i32,
+            file_changes,
+            patch_id,
+            inverse_patch_id,
+        })
+    }
+
+    /// Renders the patch text of every file in a diff along with its line counts.
+    fn render_diff(&self, diff: &git2::Diff) -> Result<(i32, i32, i32, Vec<crate::models::FileChange>)> {
         let stats_start = std::time::Instant::now();
         let stats = diff.stats()?;
         println!("📊 获取基础统计耗时: {:?}", stats_start.elapsed());
         
         // Collect file changes with diffs
-        let mut file_changes: Vec<FileChange> = Vec::new();
+        let mut file_changes: Vec<crate::models::FileChange> = Vec::new();
+        let mut file_indexes: HashMap<String, usize> = HashMap::new();
         
         let print_start = std::time::Instant::now();
         println!("🖨️  开始生成diff内容");
@@ -268,62 +328,35 @@ impl GitAnalyzer {
                 .unwrap_or_else(|| "unknown".to_string());
             
             // Find or create file change entry
-            let file_change = file_changes.iter_mut().find(|fc| fc.path == file_path);
-            
-            if let Some(file_change) = file_change {
-                // Update existing file change
-                match line.origin_value() {
-                    DiffLineType::Addition => file_change.additions += 1,
-                    DiffLineType::Deletion => file_change.deletions += 1,
-                    _ => {}
-                }
-                
-                // Append line to diff
-                match line.origin_value() {
-                    DiffLineType::Addition => {
-                        file_change.diff.push_str(&format!("+{}", String::from_utf8_lossy(line.content())));
-                    },
-                    DiffLineType::Deletion => {
-                        file_change.diff.push_str(&format!("-{}", String::from_utf8_lossy(line.content())));
-                    },
-                    DiffLineType::Context => {
-                        file_change.diff.push_str(&format!(" {}", String::from_utf8_lossy(line.content())));
-                    },
-                    _ => {
-                        file_change.diff.push_str(&format!(" {}", String::from_utf8_lossy(line.content())));
-                    }
-                }
-            } else {
-                // Create new file change entry
-                let mut diff_content = String::new();
-                match line.origin_value() {
-                    DiffLineType::Addition => {
-                        diff_content.push_str(&format!("+{}", String::from_utf8_lossy(line.content())));
-                    },
-                    DiffLineType::Deletion => {
-                        diff_content.push_str(&format!("-{}", String::from_utf8_lossy(line.content())));
-                    },
-                    DiffLineType::Context => {
-                        diff_content.push_str(&format!(" {}", String::from_utf8_lossy(line.content())));
-                    },
-                    _ => {
-                        diff_content.push_str(&format!(" {}", String::from_utf8_lossy(line.content())));
-                    }
-                }
-                
-                file_changes.push(FileChange {
+            let index = *file_indexes.entry(file_path.clone()).or_insert_with(|| {
+                file_changes.push(crate::models::FileChange {
                     path: file_path,
-                    additions: if line.origin_value() == DiffLineType::Addition { 1 } else { 0 },
-                    deletions: if line.origin_value() == DiffLineType::Deletion { 1 } else { 0 },
-                    diff: diff_content,
+                    additions: 0,
+                    deletions: 0,
+                    diff: String::new(),
                 });
+                file_changes.len() - 1
+            });
+            let file_change = &mut file_changes[index];
+            
+            let content = String::from_utf8_lossy(line.content());
+            match line.origin_value() {
+                DiffLineType::Addition => {
+                    file_change.additions += 1;
+                    file_change.diff.push('+');
+                },
+                DiffLineType::Deletion => {
+                    file_change.deletions += 1;
+                    file_change.diff.push('-');
+                },
+                _ => file_change.diff.push(' '),
             }
+            file_change.diff.push_str(&content);
             
             true
         })?;
         
         println!("🖨️  生成diff内容耗时: {:?}", print_start.elapsed());
-        println!("📈 总详细统计耗时: {:?}", start_time.elapsed());
 
         Ok((
             stats.insertions() as i32,
@@ -333,6 +366,525 @@ impl GitAnalyzer {
         ))
     }
 
+    /// The hunks of a commit's diff that a diff search matches, the way
+    /// `git log -S` and `git log -G` decide it. `paths` limits the files
+    /// looked at. An empty result means the commit doesn't match.
+    pub fn pickaxe_commit(
+        &self,
+        commit_id: &str,
+        pattern: &regex::Regex,
+        mode: crate::models::PickaxeMode,
+        paths: Option<&regex::Regex>,
+    ) -> Result<Vec<crate::models::DiffSearchHunk>> {
+        let commit = self.repo.find_commit(Oid::from_str(commit_id)?)?;
+        let mut diff = self.diff_commit(&commit)?;
+        let mut hunks = Vec::new();
+
+        // Like git log, a moved file doesn't add or remove its content
+        let mut find_opts = git2::DiffFindOptions::new();
+        find_opts.renames(true);
+        diff.find_similar(Some(&mut find_opts))?;
+
+        for (index, delta) in diff.deltas().enumerate() {
+            let file_path = delta.new_file().path().or(delta.old_file().path())
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_else(|| "unknown".to_string());
+            if paths.is_some_and(|paths| !paths.is_match(&file_path)) {
+                continue;
+            }
+
+            // -S only looks at files where the number of matches changed
+            if mode == crate::models::PickaxeMode::Occurrences
+                && self.count_matches(delta.old_file().id(), pattern)? == self.count_matches(delta.new_file().id(), pattern)?
+            {
+                continue;
+            }
+
+            // Binary files have no patch to search
+            let Some(patch) = git2::Patch::from_diff(&diff, index)? else {
+                continue;
+            };
+
+            let mut file_hunks = Vec::new();
+            for hunk_index in 0..patch.num_hunks() {
+                let (hunk, line_count) = patch.hunk(hunk_index)?;
+                let mut text = String::new();
+                let mut matched_lines = 0;
+                for line_index in 0..line_count {
+                    let line = patch.line_in_hunk(hunk_index, line_index)?;
+                    let content = String::from_utf8_lossy(line.content());
+                    match line.origin_value() {
+                        DiffLineType::Addition | DiffLineType::Deletion => {
+                            // Without the line break, so `$` matches at the end of the line
+                            if pattern.is_match(content.trim_end_matches(['\n', '\r'])) {
+                                matched_lines += 1;
+                            }
+                            text.push(line.origin());
+                        }
+                        _ => text.push(' '),
+                    }
+                    text.push_str(&content);
+                }
+
+                file_hunks.push(crate::models::DiffSearchHunk {
+                    file_path: file_path.clone(),
+                    header: String::from_utf8_lossy(hunk.header()).trim_end().to_string(),
+                    patch: text,
+                    matched_lines,
+                });
+            }
+
+            // A count that changed without a matching line, such as a match
+            // spanning lines, still makes the whole file a match for -S
+            if file_hunks.iter().any(|hunk| hunk.matched_lines > 0) {
+                file_hunks.retain(|hunk| hunk.matched_lines > 0);
+            } else if mode == crate::models::PickaxeMode::Lines {
+                continue;
+            }
+            hunks.extend(file_hunks);
+        }
+
+        Ok(hunks)
+    }
+
+    // Matches of the pattern in a blob; missing and binary blobs have none
+    fn count_matches(&self, blob_id: Oid, pattern: &regex::Regex) -> Result<usize> {
+        if blob_id.is_zero() {
+            return Ok(0);
+        }
+        let blob = self.repo.find_blob(blob_id)?;
+        if blob.is_binary() {
+            return Ok(0);
+        }
+        Ok(pattern.find_iter(&String::from_utf8_lossy(blob.content())).count())
+    }
+
+    fn resolve_commit(&self, revision: &str) -> Result<git2::Commit<'_>> {
+        let object = self.repo.revparse_single(revision)
+            .context(format!("Failed to resolve revision {}", revision))?;
+        let commit = object.peel_to_commit()
+            .context(format!("Revision {} does not point to a commit", revision))?;
+        Ok(commit)
+    }
+
+    /// Compares two arbitrary revisions (commit IDs, branches or tags).
+    ///
+    /// With `three_dot` the diff is taken from the merge base of both
+    /// revisions, like `git diff from...to`, so only the changes made on
+    /// `to` are shown. The commit list always covers `from..to`.
+    pub fn compare_revisions(&self, from: &str, to: &str, three_dot: bool) -> Result<crate::models::RevisionComparison> {
+        let start_time = std::time::Instant::now();
+        println!("🔧 开始比较版本: {}..{}", from, to);
+
+        let from_commit = self.resolve_commit(from)?;
+        let to_commit = self.resolve_commit(to)?;
+
+        let merge_base = self.repo.merge_base(from_commit.id(), to_commit.id()).ok();
+
+        let base_tree = if three_dot {
+            let base_oid = merge_base
+                .ok_or_else(|| anyhow::anyhow!("No merge base between {} and {}", from, to))?;
+            self.repo.find_commit(base_oid)?.tree()?
+        } else {
+            from_commit.tree()?
+        };
+        let to_tree = to_commit.tree()?;
+
+        let (additions, deletions, files_changed, file_changes) =
+            self.get_tree_diff_stats(Some(&base_tree), Some(&to_tree))?;
+
+        // Collect the commits reachable from `to` but not from `from`
+        let walk_start = std::time::Instant::now();
+        let mut revwalk = self.repo.revwalk()?;
+        revwalk.push(to_commit.id())?;
+        revwalk.hide(from_commit.id())?;
+        revwalk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)?;
+
+        let mut commits = Vec::new();
+        for oid_result in revwalk {
+            let oid = oid_result?;
+            let commit = self.repo.find_commit(oid)?;
+            let (commit_additions, commit_deletions, commit_files_changed) = self.get_commit_stats(&commit)?;
+
+            let author = commit.author();
+            commits.push(Commit {
+                id: oid.to_string(),
+                repository_id: self.repository_info.id,
+                repository_name: self.repository_info.label().to_string(),
+                author: author.name().unwrap_or("Unknown").to_string(),
+                email: author.email().unwrap_or("").to_string(),
+                message: commit.message().unwrap_or("").to_string(),
+                timestamp: chrono::DateTime::from_timestamp(commit.time().seconds(), 0)
+                    .unwrap_or_default(),
+                additions: commit_additions,
+                deletions: commit_deletions,
+                files_changed: commit_files_changed,
+                branch: None,
+                remote_url: None,
+                patch_id: None,
+                inverse_patch_id: None,
+                orphaned_at: None,
+                is_merge: commit.parent_count() > 1,
+            });
+        }
+        println!("🚶 遍历区间提交耗时: {:?}, 提交数: {}", walk_start.elapsed(), commits.len());
+
+        println!("🔧 版本比较总耗时: {:?}", start_time.elapsed());
+
+        Ok(crate::models::RevisionComparison {
+            repository_id: self.repository_info.id,
+            repository_name: self.repository_info.label().to_string(),
+            from_revision: from.to_string(),
+            to_revision: to.to_string(),
+            from_commit: from_commit.id().to_string(),
+            to_commit: to_commit.id().to_string(),
+            merge_base: merge_base.map(|oid| oid.to_string()),
+            additions,
+            deletions,
+            files_changed,
+            remote_url: self.get_remote_url(),
+            file_changes,
+            commits,
+        })
+    }
+
+    /// Looks up the previous name of `path` if `commit_id` renamed it.
+    ///
+    /// Returns the old path together with the line counts of the rename
+    /// diff, which are much smaller than those of a plain add. A stored
+    /// commit that git no longer has renamed nothing as far as git knows.
+    pub fn find_rename_source(&self, commit_id: &str, path: &str) -> Result<Option<(String, i32, i32)>> {
+        let commit = match Oid::from_str(commit_id).and_then(|oid| self.repo.find_commit(oid)) {
+            Ok(commit) => commit,
+            Err(_) => return Ok(None),
+        };
+        if commit.parent_count() == 0 {
+            return Ok(None);
+        }
+
+        let tree = commit.tree()?;
+        let parent_tree = commit.parent(0)?.tree()?;
+
+        // A rename only shows up on the commit that added the path
+        if tree.get_path(Path::new(path)).is_err() || parent_tree.get_path(Path::new(path)).is_ok() {
+            return Ok(None);
+        }
+
+        let mut diff_opts = DiffOptions::new();
+        diff_opts.ignore_whitespace(true);
+        diff_opts.ignore_blank_lines(true);
+
+        let mut diff = self.repo.diff_tree_to_tree(
+            Some(&parent_tree),
+            Some(&tree),
+            Some(&mut diff_opts),
+        )?;
+
+        let mut find_opts = git2::DiffFindOptions::new();
+        find_opts.renames(true);
+        diff.find_similar(Some(&mut find_opts))?;
+
+        for (idx, delta) in diff.deltas().enumerate() {
+            if delta.status() != git2::Delta::Renamed {
+                continue;
+            }
+            if delta.new_file().path() != Some(Path::new(path)) {
+                continue;
+            }
+            let old_path = match delta.old_file().path() {
+                Some(old_path) => old_path.to_string_lossy().to_string(),
+                None => continue,
+            };
+
+            let (additions, deletions) = match git2::Patch::from_diff(&diff, idx)? {
+                Some(patch) => {
+                    let (_, additions, deletions) = patch.line_stats()?;
+                    (additions as i32, deletions as i32)
+                }
+                None => (0, 0),
+            };
+
+            return Ok(Some((old_path, additions, deletions)));
+        }
+
+        Ok(None)
+    }
+
+    /// Walks the history of HEAD and collects every non-merge commit that
+    /// touched `path`, following renames backwards like `git log --follow`.
+    pub fn get_file_history(&self, path: &str) -> Result<Vec<crate::models::FileHistoryEntry>> {
+        let start_time = std::time::Instant::now();
+        println!("📜 开始遍历文件历史: {}", path);
+
+        let mut revwalk = self.repo.revwalk()?;
+        revwalk.push_head()?;
+        revwalk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)?;
+
+        let mut entries = Vec::new();
+        let mut current_path = path.to_string();
+
+        for oid_result in revwalk {
+            let oid = oid_result?;
+            let commit = self.repo.find_commit(oid)?;
+
+            // Skip merge commits to stay consistent with scanning
+            if commit.parent_count() > 1 {
+                continue;
+            }
+
+            let tree = commit.tree()?;
+            let parent_tree = if commit.parent_count() > 0 {
+                Some(commit.parent(0)?.tree()?)
+            } else {
+                None
+            };
+
+            // Cheap check: compare blob ids before computing any diff
+            let entry_id = tree.get_path(Path::new(&current_path)).ok().map(|entry| entry.id());
+            let parent_entry_id = parent_tree
+                .as_ref()
+                .and_then(|parent_tree| parent_tree.get_path(Path::new(&current_path)).ok())
+                .map(|entry| entry.id());
+
+            if entry_id == parent_entry_id {
+                continue;
+            }
+
+            let old_path = if parent_entry_id.is_none() {
+                self.find_rename_source(&oid.to_string(), &current_path)?
+            } else {
+                None
+            };
+
+            let (additions, deletions) = match &old_path {
+                Some((_, additions, deletions)) => (*additions, *deletions),
+                None => {
+                    let mut diff_opts = DiffOptions::new();
+                    diff_opts.ignore_whitespace(true);
+                    diff_opts.ignore_blank_lines(true);
+                    diff_opts.pathspec(&current_path);
+                    diff_opts.disable_pathspec_match(true);
+
+                    let diff = self.repo.diff_tree_to_tree(
+                        parent_tree.as_ref(),
+                        Some(&tree),
+                        Some(&mut diff_opts),
+                    )?;
+                    let stats = diff.stats()?;
+                    (stats.insertions() as i32, stats.deletions() as i32)
+                }
+            };
+
+            let author = commit.author();
+            entries.push(crate::models::FileHistoryEntry {
+                commit_id: oid.to_string(),
+                author: author.name().unwrap_or("Unknown").to_string(),
+                email: author.email().unwrap_or("").to_string(),
+                message: commit.message().unwrap_or("").to_string(),
+                timestamp: chrono::DateTime::from_timestamp(commit.time().seconds(), 0)
+                    .unwrap_or_default(),
+                path: current_path.clone(),
+                old_path: old_path.as_ref().map(|(old_path, _, _)| old_path.clone()),
+                additions,
+                deletions,
+            });
+
+            if let Some((old_path, _, _)) = old_path {
+                current_path = old_path;
+            }
+        }
+
+        println!("📜 文件历史遍历耗时: {:?}, 提交数: {}", start_time.elapsed(), entries.len());
+        Ok(entries)
+    }
+
+    /// Traces the commits that modified lines `start_line..=end_line` of
+    /// `path` as of `revision`, like `git log -L`.
+    ///
+    /// History is followed through the parent whose copy of the file is
+    /// unchanged where possible, otherwise through the first parent, and
+    /// the tracked range is mapped back through each diff's hunks.
+    pub fn get_line_history(&self, path: &str, revision: &str, start_line: u32, end_line: u32) -> Result<crate::models::LineHistory> {
+        let start_time = std::time::Instant::now();
+        println!("📏 开始追踪行历史: {}:{}-{}", path, start_line, end_line);
+
+        if start_line == 0 || end_line < start_line {
+            return Err(anyhow::anyhow!("Invalid line range {}-{}", start_line, end_line));
+        }
+
+        let mut commit = self.resolve_commit(revision)?;
+        let mut current_path = path.to_string();
+        let mut range = (start_line, end_line);
+
+        let blob = self.find_blob(&commit, &current_path)?
+            .ok_or_else(|| anyhow::anyhow!("File {} does not exist at {}", path, revision))?;
+        let line_count = count_lines(blob.content());
+        if end_line > line_count {
+            return Err(anyhow::anyhow!("Line range {}-{} exceeds file length {}", start_line, end_line, line_count));
+        }
+
+        let mut entries = Vec::new();
+
+        loop {
+            let blob = match self.find_blob(&commit, &current_path)? {
+                Some(blob) => blob,
+                None => break,
+            };
+
+            // Follow a parent whose copy of the file is identical, so that
+            // merges only show up when they resolved the lines themselves
+            let parents: Vec<git2::Commit> = commit.parents().collect();
+            let mut unchanged_parent = None;
+            for parent in &parents {
+                if let Some(parent_blob) = self.find_blob(parent, &current_path)? {
+                    if parent_blob.id() == blob.id() {
+                        unchanged_parent = Some(parent.clone());
+                        break;
+                    }
+                }
+            }
+            if let Some(parent) = unchanged_parent {
+                commit = parent;
+                continue;
+            }
+
+            // Locate the file in the first parent, following renames
+            let parent = parents.first().cloned();
+            let mut parent_path = current_path.clone();
+            let mut parent_blob = match &parent {
+                Some(parent) => self.find_blob(parent, &current_path)?,
+                None => None,
+            };
+            if parent_blob.is_none() {
+                if let Some(parent) = &parent {
+                    if let Some((old_path, _, _)) = self.find_rename_source(&commit.id().to_string(), &current_path)? {
+                        parent_blob = self.find_blob(parent, &old_path)?;
+                        parent_path = old_path;
+                    }
+                }
+            }
+
+            let mut diff_opts = DiffOptions::new();
+            diff_opts.context_lines(0);
+
+            // Diffing against nothing means every line was introduced here
+            let patch = match &parent_blob {
+                Some(parent_blob) => git2::Patch::from_blobs(
+                    parent_blob,
+                    Some(Path::new(&parent_path)),
+                    &blob,
+                    Some(Path::new(&current_path)),
+                    Some(&mut diff_opts),
+                )?,
+                None => git2::Patch::from_buffers(
+                    &[],
+                    Some(Path::new(&current_path)),
+                    blob.content(),
+                    Some(Path::new(&current_path)),
+                    Some(&mut diff_opts),
+                )?,
+            };
+
+            let mut hunks = Vec::new();
+            for hunk_idx in 0..patch.num_hunks() {
+                let (hunk, _) = patch.hunk(hunk_idx)?;
+                hunks.push(LineHunk {
+                    index: hunk_idx,
+                    old_start: hunk.old_start(),
+                    old_lines: hunk.old_lines(),
+                    new_start: hunk.new_start(),
+                    new_lines: hunk.new_lines(),
+                });
+            }
+
+            let touching: Vec<&LineHunk> = hunks.iter().filter(|hunk| hunk.touches(range)).collect();
+            if !touching.is_empty() {
+                let mut diff_text = String::new();
+                let mut additions = 0;
+                let mut deletions = 0;
+
+                for hunk in &touching {
+                    diff_text.push_str(&format!(
+                        "@@ -{},{} +{},{} @@\n",
+                        hunk.old_start, hunk.old_lines, hunk.new_start, hunk.new_lines
+                    ));
+                    for line_idx in 0..patch.num_lines_in_hunk(hunk.index)? {
+                        let line = patch.line_in_hunk(hunk.index, line_idx)?;
+                        match line.origin_value() {
+                            DiffLineType::Addition => {
+                                additions += 1;
+                                diff_text.push_str(&format!("+{}", String::from_utf8_lossy(line.content())));
+                            },
+                            DiffLineType::Deletion => {
+                                deletions += 1;
+                                diff_text.push_str(&format!("-{}", String::from_utf8_lossy(line.content())));
+                            },
+                            _ => {
+                                diff_text.push_str(&format!(" {}", String::from_utf8_lossy(line.content())));
+                            }
+                        }
+                    }
+                }
+
+                let author = commit.author();
+                entries.push(crate::models::LineHistoryEntry {
+                    commit_id: commit.id().to_string(),
+                    author: author.name().unwrap_or("Unknown").to_string(),
+                    email: author.email().unwrap_or("").to_string(),
+                    message: commit.message().unwrap_or("").to_string(),
+                    timestamp: chrono::DateTime::from_timestamp(commit.time().seconds(), 0)
+                        .unwrap_or_default(),
+                    path: current_path.clone(),
+                    start_line: range.0,
+                    end_line: range.1,
+                    additions,
+                    deletions,
+                    diff: diff_text,
+                });
+            }
+
+            let parent = match parent {
+                Some(parent) if parent_blob.is_some() => parent,
+                _ => break,
+            };
+
+            // Map the range onto the parent's version of the file
+            let mapped_start = map_line_to_parent(&hunks, range.0, true);
+            let mapped_end = map_line_to_parent(&hunks, range.1, false);
+            if mapped_start > mapped_end {
+                // Every tracked line was introduced by this commit
+                break;
+            }
+
+            range = (mapped_start, mapped_end);
+            current_path = parent_path;
+            commit = parent;
+        }
+
+        println!("📏 行历史追踪耗时: {:?}, 提交数: {}", start_time.elapsed(), entries.len());
+
+        Ok(crate::models::LineHistory {
+            repository_id: self.repository_info.id,
+            path: path.to_string(),
+            revision: revision.to_string(),
+            start_line,
+            end_line,
+            commits: entries,
+        })
+    }
+
+    fn find_blob(&self, commit: &git2::Commit, path: &str) -> Result<Option<git2::Blob<'_>>> {
+        let tree = commit.tree()?;
+        let entry = match tree.get_path(Path::new(path)) {
+            Ok(entry) => entry,
+            Err(_) => return Ok(None),
+        };
+        if entry.kind() != Some(git2::ObjectType::Blob) {
+            return Ok(None);
+        }
+        Ok(Some(self.repo.find_blob(entry.id())?))
+    }
+
     fn get_commit_branch(&self, commit: &git2::Commit) -> Result<String> {
         let commit_id = commit.id();
         let current_branch = self.get_current_branch_name();
@@ -437,13 +989,619 @@ impl GitAnalyzer {
     }
 }
 
-pub fn analyze_repository(repository: Repository, since: Option<chrono::DateTime<chrono::Utc>>) -> Result<Vec<AnalyzedCommit>> {
+impl GitBackend for GitAnalyzer {
+    fn scan_commit_ids(&self, since: Option<chrono::DateTime<chrono::Utc>>) -> Result<Vec<String>> {
+        Ok(self.get_scan_commit_ids(since)?.iter().map(|oid| oid.to_string()).collect())
+    }
+
+    fn reachable_commit_ids(&self) -> Result<HashSet<String>> {
+        self.get_reachable_commit_ids()
+    }
+
+    fn branch_refs(&self) -> Result<Vec<(String, String)>> {
+        let mut refs = Vec::new();
+        for glob in ["refs/heads/*", "refs/remotes/*"] {
+            for reference in self.repo.references_glob(glob)? {
+                let reference = reference?;
+                let (Some(name), Ok(commit)) = (reference.name(), reference.peel_to_commit()) else { continue };
+                refs.push((name.to_string(), commit.id().to_string()));
+            }
+        }
+        Ok(refs)
+    }
+
+    fn commit_metadata(&self, commit_id: &str) -> Result<Option<CommitMetadata>> {
+        let commit = match Oid::from_str(commit_id).and_then(|oid| self.repo.find_commit(oid)) {
+            Ok(commit) => commit,
+            Err(_) => return Ok(None),
+        };
+
+        let author = commit.author();
+        Ok(Some(CommitMetadata {
+            author: author.name().unwrap_or("Unknown").to_string(),
+            email: author.email().unwrap_or("").to_string(),
+            message: commit.message().unwrap_or("").to_string(),
+            timestamp: chrono::DateTime::from_timestamp(commit.time().seconds(), 0).unwrap_or_default(),
+            parent_count: commit.parent_count(),
+        }))
+    }
+
+    fn commit_branch(&self, commit_id: &str) -> Result<String> {
+        let commit = self.repo.find_commit(Oid::from_str(commit_id)?)?;
+        self.get_commit_branch(&commit)
+    }
+
+    fn diff_stats(&self, commit_id: &str) -> Result<Option<CommitDiffStats>> {
+        let commit = match Oid::from_str(commit_id).and_then(|oid| self.repo.find_commit(oid)) {
+            Ok(commit) => commit,
+            Err(_) => return Ok(None),
+        };
+
+        Ok(Some(self.get_detailed_commit_stats(&commit)?))
+    }
+
+    fn remote_url(&self) -> Option<String> {
+        self.get_remote_url()
+    }
+
+    fn reopen(&self) -> Result<Box<dyn GitBackend>> {
+        Ok(Box::new(GitAnalyzer::new(self.repository_info.clone())?))
+    }
+}
+
+/// Extracts the commit IDs referenced by `git revert` style messages
+/// ("This reverts commit <id>.").
+pub fn parse_reverted_commit_ids(message: &str) -> Vec<String> {
+    const MARKER: &str = "This reverts commit ";
+
+    message
+        .match_indices(MARKER)
+        .filter_map(|(idx, _)| {
+            let id: String = message[idx + MARKER.len()..]
+                .chars()
+                .take_while(|c| c.is_ascii_hexdigit())
+                .collect();
+            if id.len() >= 7 {
+                Some(id.to_lowercase())
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Modified text files whose hunks were all dropped by the whitespace
+/// options. Patch output leaves these out entirely.
+fn is_whitespace_only(patch: &git2::Patch) -> bool {
+    let delta = patch.delta();
+    delta.status() == git2::Delta::Modified
+        && patch.num_hunks() == 0
+        && !delta.flags().is_binary()
+        && delta.old_file().mode() == delta.new_file().mode()
+}
+
+/// Adds a file's line counts to `file_changes`, summing them into the
+/// existing entry when the diff already listed the path. Both backends use
+/// this, so a type change is stored as one row.
+pub(crate) fn add_file_change(
+    file_changes: &mut Vec<FileChange>,
+    file_indexes: &mut HashMap<String, usize>,
+    path: String,
+    additions: i32,
+    deletions: i32,
+) {
+    match file_indexes.get(&path) {
+        Some(&index) => {
+            file_changes[index].additions += additions;
+            file_changes[index].deletions += deletions;
+        }
+        None => {
+            file_indexes.insert(path.clone(), file_changes.len());
+            file_changes.push(FileChange {
+                path,
+                additions,
+                deletions,
+            });
+        }
+    }
+}
+
+/// Computes the stable patch-ids of a diff and of the same diff applied
+/// backwards, as `git patch-id --stable` would for each, so identical
+/// changes and exact reverts can be matched across repositories and
+/// branches. Used for commit details; scans feed `PatchIds` from the
+/// patches they already build for the line counts.
+fn get_patch_ids(diff: &git2::Diff) -> Result<(Option<String>, Option<String>)> {
+    let mut patch_ids = PatchIds::new();
+    for index in 0..diff.deltas().len() {
+        if let Some(patch) = git2::Patch::from_diff(diff, index)? {
+            patch_ids.add_patch(&patch)?;
+        }
+    }
+    patch_ids.finish()
+}
+
+/// One changed file as libgit2's patch-id output describes it. Renames are
+/// never detected, so both sides share the path; a missing side is an add
+/// or a delete.
+pub(crate) struct PatchIdFile<'a> {
+    pub path: &'a [u8],
+    pub old_mode: Option<u32>,
+    pub new_mode: Option<u32>,
+    /// Both sides hold the same blob, so only the mode changed
+    pub same_content: bool,
+    pub binary: bool,
+}
+
+impl PatchIdFile<'_> {
+    fn inverted(&self) -> Self {
+        PatchIdFile {
+            path: self.path,
+            old_mode: self.new_mode,
+            new_mode: self.old_mode,
+            same_content: self.same_content,
+            binary: self.binary,
+        }
+    }
+
+    fn old_side(&self) -> Vec<u8> {
+        match self.old_mode {
+            Some(_) => quote_path(b"a/", self.path),
+            None => b"/dev/null".to_vec(),
+        }
+    }
+
+    fn new_side(&self) -> Vec<u8> {
+        match self.new_mode {
+            Some(_) => quote_path(b"b/", self.path),
+            None => b"/dev/null".to_vec(),
+        }
+    }
+
+    // The file header libgit2 prints in patch-id format, which leaves out
+    // the index line
+    fn header(&self) -> Vec<u8> {
+        let mut header = [b"diff --git ".as_slice(), &quote_path(b"a/", self.path), b" ", &quote_path(b"b/", self.path), b"\n"].concat();
+        let (old_mode, new_mode) = (self.old_mode.unwrap_or(0), self.new_mode.unwrap_or(0));
+        let modes = |header: &mut Vec<u8>| header.extend(format!("old mode {:o}\nnew mode {:o}\n", old_mode, new_mode).bytes());
+
+        if self.same_content {
+            if old_mode != new_mode {
+                modes(&mut header);
+            }
+            return header;
+        }
+        match (self.old_mode, self.new_mode) {
+            (None, Some(mode)) => header.extend(format!("new file mode {:o}\n", mode).bytes()),
+            (Some(mode), None) => header.extend(format!("deleted file mode {:o}\n", mode).bytes()),
+            _ if old_mode != new_mode => modes(&mut header),
+            _ => {}
+        }
+        if !self.binary {
+            header.extend([b"--- ".as_slice(), &self.old_side(), b"\n+++ ", &self.new_side(), b"\n"].concat());
+        }
+        header
+    }
+
+    fn binary_line(&self) -> Vec<u8> {
+        [b"Binary files ".as_slice(), &self.old_side(), b" and ", &self.new_side(), b" differ\n"].concat()
+    }
+}
+
+/// Builds the patch-ids of a diff and of its inverse from each file's
+/// header and hunk lines, the text libgit2 prints in its patch-id format,
+/// so any diff with the same hunks gets the same ids whichever backend
+/// produced it.
+///
+/// The inverse diff swaps the sides of every file and replays each change
+/// with its added lines removed and its removed lines added. That is the
+/// text diffing the trees the other way round prints, except where lines
+/// that compare equal could be aligned more than one way (repeated or
+/// whitespace-only lines in a large rewrite) and the reverse diff picks
+/// another alignment; such reverts are then only found through their
+/// message.
+pub(crate) struct PatchIds {
+    forward: PatchIdHasher,
+    inverse: PatchIdHasher,
+    removed: Vec<Vec<u8>>,
+    added: Vec<Vec<u8>>,
+    // Headers of the current file, hashed once it prints anything
+    pending_headers: Option<(Vec<u8>, Vec<u8>)>,
+    files: usize,
+}
+
+impl PatchIds {
+    pub(crate) fn new() -> Self {
+        PatchIds {
+            forward: PatchIdHasher::new(),
+            inverse: PatchIdHasher::new(),
+            removed: Vec::new(),
+            added: Vec::new(),
+            pending_headers: None,
+            files: 0,
+        }
+    }
+
+    /// Starts the next file. Like libgit2, a file whose mode changed always
+    /// prints its header, others only once they print a line.
+    pub(crate) fn start_file(&mut self, file: &PatchIdFile) -> Result<()> {
+        self.replay_inverted();
+        self.files += 1;
+        self.pending_headers = Some((file.header(), file.inverted().header()));
+        if file.old_mode.unwrap_or(0) != file.new_mode.unwrap_or(0) {
+            self.flush_headers()?;
+        }
+        Ok(())
+    }
+
+    /// A line of the current file's hunks: `+`, `-` or ` ` for context.
+    /// End-of-file newline markers are not part of the id.
+    pub(crate) fn line(&mut self, origin: char, content: &[u8]) -> Result<()> {
+        match origin {
+            '+' => {
+                self.flush_headers()?;
+                self.forward.update(b"+");
+                self.forward.update(content);
+                self.added.push(content.to_vec());
+            }
+            '-' => {
+                self.flush_headers()?;
+                self.forward.update(b"-");
+                self.forward.update(content);
+                self.removed.push(content.to_vec());
+            }
+            ' ' => {
+                self.flush_headers()?;
+                self.replay_inverted();
+                self.forward.update(content);
+                self.inverse.update(content);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Marks the current file as binary, which libgit2 prints as one line.
+    pub(crate) fn binary(&mut self, file: &PatchIdFile) -> Result<()> {
+        self.flush_headers()?;
+        self.forward.update(&file.binary_line());
+        self.inverse.update(&file.inverted().binary_line());
+        Ok(())
+    }
+
+    /// Adds one file of a libgit2 diff.
+    pub(crate) fn add_patch(&mut self, patch: &git2::Patch) -> Result<()> {
+        let delta = patch.delta();
+        let side_mode = |file: &git2::DiffFile| (!file.id().is_zero()).then(|| u32::from(file.mode()));
+        let is_commit = |file: &git2::DiffFile| file.mode() == git2::FileMode::Commit;
+        let file = PatchIdFile {
+            path: delta.new_file().path_bytes().or(delta.old_file().path_bytes()).unwrap_or_default(),
+            old_mode: side_mode(&delta.old_file()),
+            new_mode: side_mode(&delta.new_file()),
+            // Submodules always count as changed
+            same_content: delta.old_file().id() == delta.new_file().id()
+                && !is_commit(&delta.old_file())
+                && !is_commit(&delta.new_file()),
+            binary: delta.flags().is_binary(),
+        };
+
+        self.start_file(&file)?;
+        if file.binary {
+            // Printed even when only the mode changed
+            self.binary(&file)?;
+            return Ok(());
+        }
+        for hunk_index in 0..patch.num_hunks() {
+            for line_index in 0..patch.num_lines_in_hunk(hunk_index)? {
+                let line = patch.line_in_hunk(hunk_index, line_index)?;
+                self.line(line.origin(), line.content())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The ids of the diff and of its inverse; `None` for a diff without files.
+    pub(crate) fn finish(mut self) -> Result<(Option<String>, Option<String>)> {
+        if self.files == 0 {
+            // Empty commits would otherwise all share one patch-id
+            return Ok((None, None));
+        }
+        self.replay_inverted();
+        Ok((self.forward.finish()?, self.inverse.finish()?))
+    }
+
+    fn flush_headers(&mut self) -> Result<()> {
+        if let Some((forward, inverse)) = self.pending_headers.take() {
+            self.forward.start_file()?;
+            self.forward.update(&forward);
+            self.inverse.start_file()?;
+            self.inverse.update(&inverse);
+        }
+        Ok(())
+    }
+
+    // Feeds a finished change to the inverse hasher, which sees its added
+    // lines first, as removals, and then its removed lines as additions
+    fn replay_inverted(&mut self) {
+        for line in self.added.drain(..) {
+            self.inverse.update(b"-");
+            self.inverse.update(&line);
+        }
+        for line in self.removed.drain(..) {
+            self.inverse.update(b"+");
+            self.inverse.update(&line);
+        }
+    }
+}
+
+// libgit2's patch-id: a SHA-1 of every file's output with whitespace
+// removed, summed over the files so their order does not matter
+struct PatchIdHasher {
+    hasher: gix::hash::Hasher,
+    sum: [u8; 20],
+    first_file: bool,
+}
+
+impl PatchIdHasher {
+    fn new() -> Self {
+        PatchIdHasher {
+            hasher: gix::hash::hasher(gix::hash::Kind::Sha1),
+            sum: [0; 20],
+            first_file: true,
+        }
+    }
+
+    fn update(&mut self, text: &[u8]) {
+        // The C implementation treats each line as a string, so it ends at a NUL
+        let text = text.split(|&byte| byte == 0).next().unwrap_or_default();
+        let stripped: Vec<u8> = text
+            .iter()
+            .copied()
+            .filter(|byte| !matches!(byte, b' ' | b'\t' | b'\n' | 0x0b | 0x0c | b'\r'))
+            .collect();
+        self.hasher.update(&stripped);
+    }
+
+    fn start_file(&mut self) -> Result<()> {
+        if !std::mem::take(&mut self.first_file) {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let hasher = std::mem::replace(&mut self.hasher, gix::hash::hasher(gix::hash::Kind::Sha1));
+        let hash = hasher.try_finalize()?;
+        let mut carry = 0u16;
+        for (sum, byte) in self.sum.iter_mut().zip(hash.as_bytes()) {
+            carry += *sum as u16 + *byte as u16;
+            *sum = carry as u8;
+            carry >>= 8;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Option<String>> {
+        self.flush()?;
+        if self.sum.iter().all(|&byte| byte == 0) {
+            return Ok(None);
+        }
+        Ok(Some(self.sum.iter().map(|byte| format!("{:02x}", byte)).collect()))
+    }
+}
+
+// A path behind its "a/" or "b/" prefix, quoted the way git does when it
+// holds quotes, backslashes, control characters or non-ASCII bytes
+fn quote_path(prefix: &[u8], path: &[u8]) -> Vec<u8> {
+    let full = [prefix, path].concat();
+    if !full.iter().any(|&byte| byte == b'"' || byte == b'\\' || !(b' '..=b'~').contains(&byte)) {
+        return full;
+    }
+
+    let mut quoted = vec![b'"'];
+    for &byte in &full {
+        match byte {
+            0x07..=0x0d => quoted.extend([b'\\', b"abtnvfr"[(byte - 0x07) as usize]]),
+            b'"' | b'\\' => quoted.extend([b'\\', byte]),
+            b' '..=b'~' => quoted.push(byte),
+            _ => quoted.extend(format!("\\{:03o}", byte).bytes()),
+        }
+    }
+    quoted.push(b'"');
+    quoted
+}
+
+// Hunk boundaries of a zero-context diff, used to map line ranges
+struct LineHunk {
+    index: usize,
+    old_start: u32,
+    old_lines: u32,
+    new_start: u32,
+    new_lines: u32,
+}
+
+impl LineHunk {
+    fn touches(&self, (start, end): (u32, u32)) -> bool {
+        if self.new_lines == 0 {
+            // Pure deletion between new_start and new_start + 1
+            start <= self.new_start && self.new_start < end
+        } else {
+            self.new_start <= end && start < self.new_start + self.new_lines
+        }
+    }
+}
+
+fn map_line_to_parent(hunks: &[LineHunk], line: u32, is_start: bool) -> u32 {
+    let mut offset: i64 = 0;
+
+    for hunk in hunks {
+        let new_end = if hunk.new_lines == 0 {
+            hunk.new_start
+        } else {
+            hunk.new_start + hunk.new_lines - 1
+        };
+
+        if hunk.new_lines > 0 && line >= hunk.new_start && line <= new_end {
+            // The line itself was changed: widen to the replaced lines
+            return if is_start {
+                if hunk.old_lines == 0 { hunk.old_start + 1 } else { hunk.old_start }
+            } else if hunk.old_lines == 0 {
+                hunk.old_start
+            } else {
+                hunk.old_start + hunk.old_lines - 1
+            };
+        }
+
+        if line > new_end {
+            offset += hunk.old_lines as i64 - hunk.new_lines as i64;
+        } else {
+            break;
+        }
+    }
+
+    (line as i64 + offset).max(0) as u32
+}
+
+fn count_lines(content: &[u8]) -> u32 {
+    if content.is_empty() {
+        return 0;
+    }
+    let newlines = content.iter().filter(|&&b| b == b'\n').count() as u32;
+    if content.ends_with(b"\n") { newlines } else { newlines + 1 }
+}
+
+/// Worker threads used for commit analysis when none are configured.
+pub fn default_thread_count() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+}
+
+/// Builds the stored form of a commit from the backend's metadata, branch
+/// lookup and diff stats.
+pub fn analyze_commit(
+    backend: &dyn GitBackend,
+    repository: &Repository,
+    commit_id: &str,
+    remote_url: &Option<String>,
+) -> Result<AnalyzedCommit> {
+    let metadata = backend
+        .commit_metadata(commit_id)?
+        .ok_or_else(|| anyhow::anyhow!("Commit not found: {}", commit_id))?;
+    let branch = backend.commit_branch(commit_id)?;
+    let stats = backend
+        .diff_stats(commit_id)?
+        .ok_or_else(|| anyhow::anyhow!("Commit not found: {}", commit_id))?;
+
+    let commit = Commit {
+        id: commit_id.to_string(),
+        repository_id: repository.id,
+        repository_name: repository.name.clone(),
+        author: metadata.author,
+        email: metadata.email,
+        message: metadata.message,
+        timestamp: metadata.timestamp,
+        additions: stats.additions,
+        deletions: stats.deletions,
+        files_changed: stats.files_changed,
+        branch: Some(branch),
+        remote_url: remote_url.clone(),
+        patch_id: stats.patch_id,
+        inverse_patch_id: stats.inverse_patch_id,
+        orphaned_at: None,
+        is_merge: metadata.parent_count > 1,
+    };
+
+    Ok(AnalyzedCommit {
+        commit,
+        file_changes: stats.file_changes,
+    })
+}
+
+/// Analyzes `commit_ids` on `threads` worker threads, each with its own
+/// `backend` handle, and hands the results to `on_commit` in the order of
+/// `commit_ids`. Stops at the first error from a worker or from `on_commit`.
+pub fn analyze_commit_ids<F>(
+    repository: &Repository,
+    backend: GitBackendKind,
+    commit_ids: &[String],
+    threads: usize,
+    mut on_commit: F,
+) -> Result<usize>
+where
+    F: FnMut(AnalyzedCommit) -> Result<()>,
+{
     if !GitAnalyzer::is_valid_git_repo(&repository.path) {
         return Err(anyhow::anyhow!("Path is not a valid git repository: {}", repository.path));
     }
 
-    let analyzer = GitAnalyzer::new(repository)?;
-    analyzer.analyze_commits(since)
+    let start_time = std::time::Instant::now();
+    let threads = threads.clamp(1, commit_ids.len().max(1));
+    let first_backend = open_backend(backend, repository)?;
+    let remote_url = first_backend.remote_url();
+    // One handle per worker, sharing the per-scan branch lookup
+    let mut worker_backends = vec![first_backend];
+    while worker_backends.len() < threads {
+        worker_backends.push(worker_backends[0].reopen()?);
+    }
+
+    // Workers claim commits through a shared cursor so the busiest ones
+    // never wait on a slow neighbour; results are reordered below
+    let next_index = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+
+    let analyzed = std::thread::scope(|scope| -> Result<usize> {
+        // Bounded so workers pause while ingestion catches up
+        let (result_tx, result_rx) = mpsc::sync_channel::<(usize, Result<AnalyzedCommit>)>(threads * 16);
+
+        for backend in worker_backends {
+            let result_tx = result_tx.clone();
+            let (next_index, stop, remote_url) = (&next_index, &stop, &remote_url);
+            scope.spawn(move || {
+                while !stop.load(Ordering::SeqCst) {
+                    let index = next_index.fetch_add(1, Ordering::SeqCst);
+                    let Some(commit_id) = commit_ids.get(index) else { break };
+
+                    let result = analyze_commit(backend.as_ref(), repository, commit_id, remote_url);
+                    if result_tx.send((index, result)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(result_tx);
+
+        let mut pending: HashMap<usize, AnalyzedCommit> = HashMap::new();
+        let mut next_to_emit = 0;
+
+        let outcome = (|| {
+            for (index, result) in result_rx.iter() {
+                pending.insert(index, result?);
+
+                while let Some(analyzed_commit) = pending.remove(&next_to_emit) {
+                    on_commit(analyzed_commit)?;
+                    next_to_emit += 1;
+                }
+            }
+            Ok(next_to_emit)
+        })();
+
+        // Let the workers finish early when analysis or ingestion failed
+        if outcome.is_err() {
+            stop.store(true, Ordering::SeqCst);
+        }
+        outcome
+    })?;
+
+    println!(
+        "🧵 {} 个线程({})分析 {} 个提交耗时: {:?}",
+        threads,
+        backend.as_str(),
+        analyzed,
+        start_time.elapsed()
+    );
+
+    Ok(analyzed)
 }
 
 // Static method to get remote URL for a repository path
@@ -469,4 +1627,247 @@ pub fn get_remote_url_for_path(repo_path: &str) -> Option<String> {
     }
     
     None
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git_backend::tests::{Fixture, BASE, TEXT};
+
+    fn analyzer(fixture: &Fixture) -> GitAnalyzer {
+        GitAnalyzer::new(Repository {
+            id: 1,
+            path: fixture.path(),
+            name: "fixture".to_string(),
+            last_scanned: None,
+            display_name: None,
+            color: None,
+            description: None,
+            archived: false,
+        })
+        .unwrap()
+    }
+
+    // The zero-context hunks `get_line_history` works with, from diffing
+    // two versions of a file whose lines are given as words
+    fn hunks(old: &str, new: &str) -> Vec<LineHunk> {
+        let lines = |words: &str| words.split(' ').filter(|word| !word.is_empty()).map(|word| format!("{}\n", word)).collect::<String>();
+        let mut options = DiffOptions::new();
+        options.context_lines(0);
+        let (old, new) = (lines(old), lines(new));
+        let patch = git2::Patch::from_buffers(old.as_bytes(), None, new.as_bytes(), None, Some(&mut options)).unwrap();
+        (0..patch.num_hunks())
+            .map(|index| {
+                let (hunk, _) = patch.hunk(index).unwrap();
+                LineHunk {
+                    index,
+                    old_start: hunk.old_start(),
+                    old_lines: hunk.old_lines(),
+                    new_start: hunk.new_start(),
+                    new_lines: hunk.new_lines(),
+                }
+            })
+            .collect()
+    }
+
+    fn touched(hunks: &[LineHunk], range: (u32, u32)) -> Vec<usize> {
+        hunks.iter().filter(|hunk| hunk.touches(range)).map(|hunk| hunk.index).collect()
+    }
+
+    fn to_parent(hunks: &[LineHunk], (start, end): (u32, u32)) -> (u32, u32) {
+        (map_line_to_parent(hunks, start, true), map_line_to_parent(hunks, end, false))
+    }
+
+    #[test]
+    fn pure_insertions() {
+        // x and y are inserted after d, as new lines 5 and 6
+        let hunks = hunks("a b c d e f g h", "a b c d x y e f g h");
+        assert_eq!((hunks[0].old_lines, hunks[0].new_start, hunks[0].new_lines), (0, 5, 2));
+
+        assert_eq!(touched(&hunks, (5, 6)), vec![0]);
+        assert_eq!(touched(&hunks, (4, 5)), vec![0]);
+        assert_eq!(touched(&hunks, (6, 9)), vec![0]);
+        assert!(touched(&hunks, (1, 4)).is_empty());
+        assert!(touched(&hunks, (7, 10)).is_empty());
+
+        // Lines introduced here have no parent; around them the range
+        // keeps only the lines that were already there
+        let (start, end) = to_parent(&hunks, (5, 6));
+        assert!(start > end);
+        assert_eq!(to_parent(&hunks, (3, 6)), (3, 4));
+        assert_eq!(to_parent(&hunks, (5, 8)), (5, 6));
+    }
+
+    #[test]
+    fn pure_deletions() {
+        // c and d are deleted, so new line 2 is followed by old line 5
+        let hunks = hunks("a b c d e f g h", "a b e f g h");
+        assert_eq!((hunks[0].old_start, hunks[0].old_lines, hunks[0].new_start, hunks[0].new_lines), (3, 2, 2, 0));
+
+        // Only a range spanning the gap touches the deletion
+        assert_eq!(touched(&hunks, (2, 3)), vec![0]);
+        assert_eq!(touched(&hunks, (1, 6)), vec![0]);
+        assert!(touched(&hunks, (1, 2)).is_empty());
+        assert!(touched(&hunks, (3, 6)).is_empty());
+
+        // Spanning the gap takes the deleted lines along into the parent
+        assert_eq!(to_parent(&hunks, (2, 3)), (2, 5));
+        assert_eq!(to_parent(&hunks, (3, 4)), (5, 6));
+    }
+
+    #[test]
+    fn changed_lines_widen_to_the_lines_they_replace() {
+        // c d become x y z
+        let hunks = hunks("a b c d e f g h", "a b x y z e f g h");
+        assert_eq!((hunks[0].old_start, hunks[0].old_lines, hunks[0].new_start, hunks[0].new_lines), (3, 2, 3, 3));
+
+        assert_eq!(touched(&hunks, (4, 4)), vec![0]);
+        assert_eq!(to_parent(&hunks, (4, 4)), (3, 4));
+        assert_eq!(to_parent(&hunks, (5, 5)), (3, 4));
+        assert_eq!(to_parent(&hunks, (2, 6)), (2, 5));
+        assert_eq!(to_parent(&hunks, (3, 9)), (3, 8));
+    }
+
+    #[test]
+    fn ranges_before_after_and_between_hunks() {
+        // One line changes near the top, two are inserted near the end
+        let hunks = hunks("a b c d e f g h i j", "a X c d e f g h y z i j");
+        assert_eq!(hunks.len(), 2);
+
+        assert!(touched(&hunks, (1, 1)).is_empty());
+        assert!(touched(&hunks, (3, 8)).is_empty());
+        assert!(touched(&hunks, (11, 12)).is_empty());
+        assert_eq!(touched(&hunks, (1, 12)), vec![0, 1]);
+
+        assert_eq!(to_parent(&hunks, (1, 1)), (1, 1));
+        assert_eq!(to_parent(&hunks, (3, 8)), (3, 8));
+        assert_eq!(to_parent(&hunks, (11, 12)), (9, 10));
+    }
+
+    #[test]
+    fn compare_revisions_with_two_and_three_dots() {
+        // main appends to a.txt while feature adds and edits b.txt
+        let fixture = Fixture::new("compare");
+        let root = fixture.commit("refs/heads/main", &[], BASE, &[("a.txt", TEXT, b"1\n2\n3\n")]);
+        let appended = fixture.commit("refs/heads/main", &[root], BASE + 100, &[("a.txt", TEXT, b"1\n2\n3\n4\n")]);
+        let added = fixture.commit("refs/heads/feature", &[root], BASE + 200, &[("a.txt", TEXT, b"1\n2\n3\n"), ("b.txt", TEXT, b"b\n")]);
+        let edited = fixture.commit("refs/heads/feature", &[added], BASE + 300, &[("a.txt", TEXT, b"1\n2\n3\n"), ("b.txt", TEXT, b"b\nc\n")]);
+        let analyzer = analyzer(&fixture);
+
+        let files = |comparison: &crate::models::RevisionComparison| {
+            comparison
+                .file_changes
+                .iter()
+                .map(|file| (file.path.clone(), file.additions, file.deletions))
+                .collect::<Vec<_>>()
+        };
+        let commit_ids = |comparison: &crate::models::RevisionComparison| {
+            comparison.commits.iter().map(|commit| commit.id.clone()).collect::<Vec<_>>()
+        };
+
+        // Two dots diff the tips, so main's line shows up as removed
+        let comparison = analyzer.compare_revisions("main", "feature", false).unwrap();
+        assert_eq!((comparison.from_commit.clone(), comparison.to_commit.clone()), (appended.to_string(), edited.to_string()));
+        assert_eq!(comparison.merge_base, Some(root.to_string()));
+        assert_eq!((comparison.additions, comparison.deletions, comparison.files_changed), (2, 1, 2));
+        assert_eq!(files(&comparison), vec![("a.txt".to_string(), 0, 1), ("b.txt".to_string(), 2, 0)]);
+        assert_eq!(commit_ids(&comparison), vec![edited.to_string(), added.to_string()]);
+
+        // Three dots diff from the merge base, leaving only feature's changes;
+        // the commit list is the same
+        let comparison = analyzer.compare_revisions("main", "feature", true).unwrap();
+        assert_eq!((comparison.additions, comparison.deletions, comparison.files_changed), (2, 0, 1));
+        assert_eq!(files(&comparison), vec![("b.txt".to_string(), 2, 0)]);
+        assert_eq!(commit_ids(&comparison), vec![edited.to_string(), added.to_string()]);
+        assert_eq!(comparison.commits[0].additions, 1);
+
+        let comparison = analyzer.compare_revisions("feature", "main", true).unwrap();
+        assert_eq!(files(&comparison), vec![("a.txt".to_string(), 1, 0)]);
+        assert_eq!(commit_ids(&comparison), vec![appended.to_string()]);
+
+        // A revision and one of its ancestors
+        let comparison = analyzer.compare_revisions(&edited.to_string(), &root.to_string(), false).unwrap();
+        assert_eq!(comparison.merge_base, Some(root.to_string()));
+        assert!(comparison.commits.is_empty());
+        assert!(analyzer.compare_revisions("main", "missing", false).is_err());
+    }
+
+    #[test]
+    fn line_history_across_a_rename_and_a_merge() {
+        let text = |lines: &[&str]| lines.iter().map(|line| format!("{}\n", line)).collect::<String>();
+        let mut lines = vec!["1", "2", "3", "4", "5", "6", "7", "8", "9", "10"];
+        let fixture = Fixture::new("line-history");
+        let root = fixture.commit("refs/heads/main", &[], BASE, &[("src/a.txt", TEXT, text(&lines).as_bytes())]);
+        lines[3] = "four";
+        let edited = fixture.commit("refs/heads/main", &[root], BASE + 100, &[("src/a.txt", TEXT, text(&lines).as_bytes())]);
+
+        // Moved with two lines on top, so lines 4-5 become 6-7
+        lines.splice(0..0, ["x", "y"]);
+        let moved = fixture.commit("refs/heads/main", &[edited], BASE + 200, &[("lib/b.txt", TEXT, text(&lines).as_bytes())]);
+        let unrelated = fixture.commit("refs/heads/main", &[moved], BASE + 300, &[
+            ("lib/b.txt", TEXT, text(&lines).as_bytes()),
+            ("c.txt", TEXT, b"c\n"),
+        ]);
+        let mut side_lines = lines.clone();
+        side_lines[6] = "five";
+        let side = fixture.commit("refs/heads/side", &[moved], BASE + 400, &[("lib/b.txt", TEXT, text(&side_lines).as_bytes())]);
+        // The merge takes the side's copy as is, so history follows the side
+        fixture.commit("refs/heads/main", &[unrelated, side], BASE + 500, &[
+            ("lib/b.txt", TEXT, text(&side_lines).as_bytes()),
+            ("c.txt", TEXT, b"c\n"),
+        ]);
+
+        let history = analyzer(&fixture).get_line_history("lib/b.txt", "main", 6, 7).unwrap();
+        let summary: Vec<_> = history
+            .commits
+            .iter()
+            .map(|entry| (entry.commit_id.clone(), entry.path.clone(), entry.start_line, entry.end_line, entry.additions, entry.deletions))
+            .collect();
+        assert_eq!(summary, vec![
+            (side.to_string(), "lib/b.txt".to_string(), 6, 7, 1, 1),
+            (edited.to_string(), "src/a.txt".to_string(), 4, 5, 1, 1),
+            (root.to_string(), "src/a.txt".to_string(), 4, 5, 10, 0),
+        ]);
+        assert!(history.commits[0].diff.contains("-5\n+five\n"));
+        assert!(history.commits[1].diff.starts_with("@@ -4,1 +4,1 @@\n-4\n+four\n"));
+
+        // Lines added by the move itself stop there
+        let history = analyzer(&fixture).get_line_history("lib/b.txt", "main", 1, 2).unwrap();
+        let commit_ids: Vec<_> = history.commits.iter().map(|entry| entry.commit_id.clone()).collect();
+        assert_eq!(commit_ids, vec![moved.to_string()]);
+        assert!(analyzer(&fixture).get_line_history("lib/b.txt", "main", 12, 13).is_err());
+    }
+
+    #[test]
+    fn line_counts() {
+        assert_eq!(count_lines(b""), 0);
+        assert_eq!(count_lines(b"a"), 1);
+        assert_eq!(count_lines(b"a\n"), 1);
+        assert_eq!(count_lines(b"a\nb"), 2);
+        assert_eq!(count_lines(b"a\n\n"), 2);
+        assert_eq!(count_lines(b"\r\n"), 1);
+    }
+
+    #[test]
+    fn reverted_ids_from_revert_messages() {
+        let full = "0123456789abcdef0123456789abcdef01234567";
+        assert_eq!(
+            parse_reverted_commit_ids(&format!("Revert \"Add parser\"\n\nThis reverts commit {}.\n", full)),
+            vec![full.to_string()]
+        );
+
+        // Abbreviated and upper-case ids, with or without the trailing period
+        assert_eq!(parse_reverted_commit_ids("This reverts commit ABC1234."), vec!["abc1234".to_string()]);
+        assert_eq!(parse_reverted_commit_ids("This reverts commit abc1234"), vec!["abc1234".to_string()]);
+
+        // Squashed reverts list every commit
+        assert_eq!(
+            parse_reverted_commit_ids("Revert two commits\n\nThis reverts commit 1111111.\nThis reverts commit 2222222aa, reversing\nchanges made to 3333333."),
+            vec!["1111111".to_string(), "2222222aa".to_string()]
+        );
+
+        // Ids shorter than git's minimum abbreviation are too ambiguous to match
+        assert!(parse_reverted_commit_ids("This reverts commit abc12.").is_empty());
+        assert!(parse_reverted_commit_ids("This reverts commit HEAD~1.").is_empty());
+        assert!(parse_reverted_commit_ids("Fix the revert of abc1234").is_empty());
+    }
+}
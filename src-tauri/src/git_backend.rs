@@ -0,0 +1,654 @@
+use crate::git_analyzer::{FileChange, GitAnalyzer};
+use crate::gix_backend::GixBackend;
+use crate::models::{BackendComparison, BackendMismatch, Repository};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+/// Library used to read repository history during scans.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GitBackendKind {
+    #[default]
+    Git2,
+    Gix,
+}
+
+impl GitBackendKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GitBackendKind::Git2 => "git2",
+            GitBackendKind::Gix => "gix",
+        }
+    }
+}
+
+// Author, message and committer time of a commit
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommitMetadata {
+    pub author: String,
+    pub email: String,
+    pub message: String,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub parent_count: usize,
+}
+
+// Whitespace-insensitive stats of a commit's diff against its first parent
+#[derive(Debug, Clone)]
+pub struct CommitDiffStats {
+    pub additions: i32,
+    pub deletions: i32,
+    pub files_changed: i32,
+    pub file_changes: Vec<FileChange>,
+    pub patch_id: Option<String>,
+    pub inverse_patch_id: Option<String>,
+}
+
+/// The repository reads a scan needs. Every implementation must produce
+/// the same commits, metadata and patch-ids for the same repository, so
+/// stored commits don't depend on which backend scanned them. Line counts
+/// come from each backend's own diff and may differ where its heuristics do.
+pub trait GitBackend: Send {
+    /// Commits reachable from local and remote branches, merges included,
+    /// newest first, leaving out commits older than `since`. Commits with
+    /// equal times may come in any order.
+    fn scan_commit_ids(&self, since: Option<chrono::DateTime<chrono::Utc>>) -> Result<Vec<String>>;
+
+    /// Every commit reachable from local and remote branches.
+    fn reachable_commit_ids(&self) -> Result<HashSet<String>>;
+
+    /// Local and remote branch refs with the commit each one points to.
+    fn branch_refs(&self) -> Result<Vec<(String, String)>>;
+
+    /// Returns `None` when the commit doesn't exist in the repository.
+    fn commit_metadata(&self, commit_id: &str) -> Result<Option<CommitMetadata>>;
+
+    /// Name of a branch containing the commit, or "" when none is found
+    /// within the search window.
+    fn commit_branch(&self, commit_id: &str) -> Result<String>;
+
+    /// Returns `None` when the commit doesn't exist in the repository.
+    fn diff_stats(&self, commit_id: &str) -> Result<Option<CommitDiffStats>>;
+
+    /// URL of `origin`, or of the first remote that has one.
+    fn remote_url(&self) -> Option<String>;
+
+    /// Another handle on the same repository, for a worker thread. Shares
+    /// what this one computes once per scan, such as the branch lookup.
+    fn reopen(&self) -> Result<Box<dyn GitBackend>>;
+}
+
+pub fn open_backend(kind: GitBackendKind, repository: &Repository) -> Result<Box<dyn GitBackend>> {
+    Ok(match kind {
+        GitBackendKind::Git2 => Box::new(GitAnalyzer::new(repository.clone())?),
+        GitBackendKind::Gix => Box::new(GixBackend::open(repository)?),
+    })
+}
+
+/// Reads up to `limit` scanned commits through both backends and lists every
+/// value they disagree on.
+pub fn compare_backends(repository: &Repository, limit: usize) -> Result<BackendComparison> {
+    let git2 = open_backend(GitBackendKind::Git2, repository)?;
+    let gix = open_backend(GitBackendKind::Gix, repository)?;
+    let mut mismatches = Vec::new();
+    let mut check = |commit_id: Option<&str>, field: &str, git2: String, gix: String| {
+        if git2 != gix {
+            mismatches.push(BackendMismatch {
+                commit_id: commit_id.map(str::to_string),
+                field: field.to_string(),
+                git2,
+                gix,
+            });
+        }
+    };
+
+    let (git2_ids, mut git2_time) = timed(|| git2.scan_commit_ids(None))?;
+    let (gix_ids, mut gix_time) = timed(|| gix.scan_commit_ids(None))?;
+    // Commits with equal times may come out in either order
+    let git2_scanned: HashSet<&String> = git2_ids.iter().collect();
+    let gix_scanned: HashSet<&String> = gix_ids.iter().collect();
+    check(None, "scan_commit_ids", format!("{} commits", git2_ids.len()), format!("{} commits", gix_ids.len()));
+    for commit_id in git2_scanned.symmetric_difference(&gix_scanned).take(limit) {
+        let (in_git2, in_gix) = (git2_scanned.contains(commit_id), gix_scanned.contains(commit_id));
+        check(Some(commit_id), "scanned", in_git2.to_string(), in_gix.to_string());
+    }
+
+    let (git2_reachable, elapsed) = timed(|| git2.reachable_commit_ids())?;
+    git2_time += elapsed;
+    let (gix_reachable, elapsed) = timed(|| gix.reachable_commit_ids())?;
+    gix_time += elapsed;
+    check(None, "reachable_commit_ids", format!("{} commits", git2_reachable.len()), format!("{} commits", gix_reachable.len()));
+    for commit_id in git2_reachable.symmetric_difference(&gix_reachable).take(limit) {
+        let (in_git2, in_gix) = (git2_reachable.contains(commit_id), gix_reachable.contains(commit_id));
+        check(Some(commit_id), "reachable", in_git2.to_string(), in_gix.to_string());
+    }
+
+    let mut git2_refs = git2.branch_refs()?;
+    let mut gix_refs = gix.branch_refs()?;
+    git2_refs.sort();
+    gix_refs.sort();
+    check(None, "branch_refs", format!("{:?}", git2_refs), format!("{:?}", gix_refs));
+    check(None, "remote_url", format!("{:?}", git2.remote_url()), format!("{:?}", gix.remote_url()));
+
+    let commit_ids = &git2_ids[..git2_ids.len().min(limit)];
+    for commit_id in commit_ids {
+        let id = Some(commit_id.as_str());
+        let (git2_meta, elapsed) = timed(|| git2.commit_metadata(commit_id))?;
+        git2_time += elapsed;
+        let (gix_meta, elapsed) = timed(|| gix.commit_metadata(commit_id))?;
+        gix_time += elapsed;
+        check(id, "metadata", format!("{:?}", git2_meta), format!("{:?}", gix_meta));
+
+        let (git2_branch, elapsed) = timed(|| git2.commit_branch(commit_id))?;
+        git2_time += elapsed;
+        let (gix_branch, elapsed) = timed(|| gix.commit_branch(commit_id))?;
+        gix_time += elapsed;
+        check(id, "branch", git2_branch, gix_branch);
+
+        let (git2_stats, elapsed) = timed(|| git2.diff_stats(commit_id))?;
+        git2_time += elapsed;
+        let (gix_stats, elapsed) = timed(|| gix.diff_stats(commit_id))?;
+        gix_time += elapsed;
+        let (Some(git2_stats), Some(gix_stats)) = (git2_stats, gix_stats) else { continue };
+        let totals = |stats: &CommitDiffStats| format!("+{} -{} {} files", stats.additions, stats.deletions, stats.files_changed);
+        check(id, "diff_stats", totals(&git2_stats), totals(&gix_stats));
+        check(id, "file_changes", file_counts(&git2_stats).join("\n"), file_counts(&gix_stats).join("\n"));
+        let patch_ids = |stats: &CommitDiffStats| format!("{:?} {:?}", stats.patch_id, stats.inverse_patch_id);
+        check(id, "patch_ids", patch_ids(&git2_stats), patch_ids(&gix_stats));
+    }
+
+    Ok(BackendComparison {
+        repository_id: repository.id,
+        commits_checked: commit_ids.len() as i32,
+        git2_ms: git2_time.as_secs_f64() * 1000.0,
+        gix_ms: gix_time.as_secs_f64() * 1000.0,
+        mismatches,
+    })
+}
+
+// Per-file line counts in path order
+fn file_counts(stats: &CommitDiffStats) -> Vec<String> {
+    let mut files: Vec<String> = stats
+        .file_changes
+        .iter()
+        .map(|f| format!("{} +{} -{}", f.path, f.additions, f.deletions))
+        .collect();
+    files.sort();
+    files
+}
+
+fn timed<T>(f: impl FnOnce() -> Result<T>) -> Result<(T, Duration)> {
+    let start = Instant::now();
+    let value = f()?;
+    Ok((value, start.elapsed()))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use git2::{FileMode, Oid, Signature, Time};
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+
+    // A repository built commit by commit from in-memory trees, removed on drop
+    pub(crate) struct Fixture {
+        dir: PathBuf,
+        repo: git2::Repository,
+    }
+
+    #[derive(Default)]
+    struct Dir<'a> {
+        files: BTreeMap<&'a str, (FileMode, &'a [u8])>,
+        dirs: BTreeMap<&'a str, Dir<'a>>,
+    }
+
+    impl<'a> Dir<'a> {
+        fn insert(&mut self, path: &'a str, mode: FileMode, content: &'a [u8]) {
+            match path.split_once('/') {
+                Some((dir, rest)) => self.dirs.entry(dir).or_default().insert(rest, mode, content),
+                None => {
+                    self.files.insert(path, (mode, content));
+                }
+            }
+        }
+
+        fn write(&self, repo: &git2::Repository) -> Oid {
+            let mut builder = repo.treebuilder(None).unwrap();
+            for (name, (mode, content)) in &self.files {
+                let id = match mode {
+                    // Gitlinks point at a commit of another repository
+                    FileMode::Commit => Oid::from_str(std::str::from_utf8(content).unwrap()).unwrap(),
+                    _ => repo.blob(content).unwrap(),
+                };
+                builder.insert(name, id, i32::from(*mode)).unwrap();
+            }
+            for (name, dir) in &self.dirs {
+                builder.insert(name, dir.write(repo), i32::from(FileMode::Tree)).unwrap();
+            }
+            builder.write().unwrap()
+        }
+    }
+
+    impl Fixture {
+        pub(crate) fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("git-stats-backend-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            let repo = git2::Repository::init(&dir).unwrap();
+            repo.set_head("refs/heads/main").unwrap();
+            Fixture { dir, repo }
+        }
+
+        /// Commits the files as the whole tree, moving `reference` to the commit.
+        pub(crate) fn commit(&self, reference: &str, parents: &[Oid], time: i64, files: &[(&str, FileMode, &[u8])]) -> Oid {
+            let mut root = Dir::default();
+            for (path, mode, content) in files {
+                root.insert(path, *mode, content);
+            }
+            let tree = self.repo.find_tree(root.write(&self.repo)).unwrap();
+            let parents: Vec<git2::Commit> = parents.iter().map(|id| self.repo.find_commit(*id).unwrap()).collect();
+            let parents: Vec<&git2::Commit> = parents.iter().collect();
+            let signature = Signature::new("Alice", "alice@example.com", &Time::new(time, 0)).unwrap();
+            let message = format!("commit at {}\n\nbody", time);
+            let id = self.repo.commit(None, &signature, &signature, &message, &tree, &parents).unwrap();
+            self.repo.reference(reference, id, true, "fixture").unwrap();
+            id
+        }
+
+        /// Moves every loose branch ref into packed-refs, as `git pack-refs --all` would.
+        fn pack_refs(&self) {
+            let mut refs: Vec<(String, Oid)> = self
+                .repo
+                .references()
+                .unwrap()
+                .flatten()
+                .filter_map(|reference| Some((reference.name()?.to_string(), reference.target()?)))
+                .collect();
+            refs.sort();
+            let mut packed = String::from("# pack-refs with: peeled fully-peeled sorted \n");
+            for (name, id) in &refs {
+                packed.push_str(&format!("{} {}\n", id, name));
+                std::fs::remove_file(self.dir.join(".git").join(name)).unwrap();
+            }
+            std::fs::write(self.dir.join(".git/packed-refs"), packed).unwrap();
+        }
+
+        pub(crate) fn path(&self) -> String {
+            self.dir.to_string_lossy().to_string()
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    pub(crate) const TEXT: FileMode = FileMode::Blob;
+
+    // The fixture opened with git2 and with gix
+    fn backends(fixture: &Fixture) -> (Box<dyn GitBackend>, Box<dyn GitBackend>) {
+        let repository = Repository {
+            id: 1,
+            path: fixture.path(),
+            name: "fixture".to_string(),
+            last_scanned: None,
+            display_name: None,
+            color: None,
+            description: None,
+            archived: false,
+        };
+        (
+            open_backend(GitBackendKind::Git2, &repository).unwrap(),
+            open_backend(GitBackendKind::Gix, &repository).unwrap(),
+        )
+    }
+
+    /// Asserts that both backends read the repository the same way.
+    fn assert_parity(fixture: &Fixture) {
+        let (git2, gix) = backends(fixture);
+
+        // The walks agree on which commits to scan; equal commit times may
+        // come out in either order
+        let scanned = |backend: &dyn GitBackend, since| backend.scan_commit_ids(since).unwrap().into_iter().collect::<HashSet<_>>();
+        let commit_ids = git2.scan_commit_ids(None).unwrap();
+        assert!(!commit_ids.is_empty());
+        assert_eq!(commit_ids.len(), gix.scan_commit_ids(None).unwrap().len(), "scan_commit_ids");
+        assert_eq!(scanned(&*git2, None), scanned(&*gix, None), "scan_commit_ids");
+        let since = chrono::DateTime::from_timestamp(1_700_000_150, 0);
+        assert_eq!(scanned(&*git2, since), scanned(&*gix, since), "scan_commit_ids since");
+        assert_eq!(git2.reachable_commit_ids().unwrap(), gix.reachable_commit_ids().unwrap(), "reachable_commit_ids");
+        let sorted_refs = |backend: &dyn GitBackend| {
+            let mut refs = backend.branch_refs().unwrap();
+            refs.sort();
+            refs
+        };
+        assert_eq!(sorted_refs(&*git2), sorted_refs(&*gix), "branch_refs");
+        assert_eq!(git2.remote_url(), gix.remote_url(), "remote_url");
+
+        for commit_id in &commit_ids {
+            assert_eq!(git2.commit_metadata(commit_id).unwrap(), gix.commit_metadata(commit_id).unwrap(), "metadata of {}", commit_id);
+            assert_eq!(git2.commit_branch(commit_id).unwrap(), gix.commit_branch(commit_id).unwrap(), "branch of {}", commit_id);
+
+            let git2_stats = git2.diff_stats(commit_id).unwrap().unwrap();
+            let gix_stats = gix.diff_stats(commit_id).unwrap().unwrap();
+            let totals = |stats: &CommitDiffStats| (stats.additions, stats.deletions, stats.files_changed);
+            assert_eq!(totals(&git2_stats), totals(&gix_stats), "totals of {}", commit_id);
+            assert_eq!(file_counts(&git2_stats), file_counts(&gix_stats), "file changes of {}", commit_id);
+            assert_eq!(git2_stats.patch_id, gix_stats.patch_id, "patch_id of {}", commit_id);
+            assert_eq!(git2_stats.inverse_patch_id, gix_stats.inverse_patch_id, "inverse_patch_id of {}", commit_id);
+            assert_eq!(
+                (git2_stats.patch_id, git2_stats.inverse_patch_id),
+                libgit2_patch_ids(&fixture.repo, commit_id),
+                "patch-ids of {}",
+                commit_id
+            );
+        }
+
+        let missing = "0123456789012345678901234567890123456789";
+        assert!(git2.commit_metadata(missing).unwrap().is_none());
+        assert!(gix.commit_metadata(missing).unwrap().is_none());
+        assert!(gix.diff_stats(missing).unwrap().is_none());
+    }
+
+    // Patch-ids from libgit2 itself, diffing the trees once in each direction
+    fn libgit2_patch_ids(repo: &git2::Repository, commit_id: &str) -> (Option<String>, Option<String>) {
+        let commit = repo.find_commit(Oid::from_str(commit_id).unwrap()).unwrap();
+        let tree = commit.tree().unwrap();
+        let parent_tree = commit.parents().next().map(|parent| parent.tree().unwrap());
+        let patch_id = |old_tree: Option<&git2::Tree>, new_tree: Option<&git2::Tree>| {
+            let mut options = git2::DiffOptions::new();
+            options.ignore_whitespace(true).ignore_blank_lines(true);
+            let diff = repo.diff_tree_to_tree(old_tree, new_tree, Some(&mut options)).unwrap();
+            let id = diff.patchid(None).unwrap();
+            (diff.deltas().len() > 0 && !id.is_zero()).then(|| id.to_string())
+        };
+        (patch_id(parent_tree.as_ref(), Some(&tree)), patch_id(Some(&tree), parent_tree.as_ref()))
+    }
+
+    pub(crate) const BASE: i64 = 1_700_000_000;
+
+    #[test]
+    fn renames_and_moves() {
+        let fixture = Fixture::new("renames");
+        let body = b"fn main() {\n    println!(\"hi\");\n}\n";
+        let first = fixture.commit("refs/heads/main", &[], BASE, &[("src/main.rs", TEXT, body), ("README", TEXT, b"readme\n")]);
+        let second = fixture.commit("refs/heads/main", &[first], BASE + 100, &[("src/app.rs", TEXT, body), ("README", TEXT, b"readme\n")]);
+        let third = fixture.commit("refs/heads/main", &[second], BASE + 200, &[
+            ("lib/app.rs", TEXT, b"fn main() {\n    println!(\"hello\");\n}\n"),
+            ("docs/README", TEXT, b"readme\n"),
+        ]);
+        fixture.commit("refs/heads/main", &[third], BASE + 300, &[("lib/app.rs", TEXT, body), ("docs/README", TEXT, b"readme\n")]);
+        assert_parity(&fixture);
+    }
+
+    #[test]
+    fn merges_and_equal_commit_times() {
+        let fixture = Fixture::new("merges");
+        let root = fixture.commit("refs/heads/main", &[], BASE, &[("a.txt", TEXT, b"1\n2\n3\n")]);
+        // Several commits share a time
+        let left = fixture.commit("refs/heads/feature", &[root], BASE + 100, &[("a.txt", TEXT, b"1\n2\n3\n4\n")]);
+        let right = fixture.commit("refs/heads/main", &[root], BASE + 100, &[("a.txt", TEXT, b"0\n1\n2\n3\n")]);
+        let side = fixture.commit("refs/heads/side", &[root], BASE + 100, &[("b.txt", TEXT, b"b\n")]);
+        let merge = fixture.commit("refs/heads/main", &[right, left], BASE + 200, &[("a.txt", TEXT, b"0\n1\n2\n3\n4\n")]);
+        let octopus = fixture.commit("refs/heads/main", &[merge, side], BASE + 200, &[
+            ("a.txt", TEXT, b"0\n1\n2\n3\n4\n"),
+            ("b.txt", TEXT, b"b\n"),
+        ]);
+        // A commit older than its parent, as clock skew produces
+        fixture.commit("refs/heads/skewed", &[octopus], BASE + 50, &[("a.txt", TEXT, b"0\n1\n2\n3\n4\n5\n")]);
+        fixture.commit("refs/remotes/origin/main", &[merge], BASE + 300, &[("c.txt", TEXT, b"c\n")]);
+        fixture.repo.remote("origin", "https://example.com/fixture.git").unwrap();
+        assert_parity(&fixture);
+
+        // Packed and loose refs side by side
+        fixture.pack_refs();
+        fixture.commit("refs/heads/loose", &[left], BASE + 400, &[("d.txt", TEXT, b"d\n")]);
+        assert_parity(&fixture);
+    }
+
+    #[test]
+    fn incremental_scans_stop_at_since() {
+        let fixture = Fixture::new("since");
+        let a = fixture.commit("refs/heads/main", &[], BASE, &[("a.txt", TEXT, b"a\n")]);
+        let b = fixture.commit("refs/heads/main", &[a], BASE + 100, &[("a.txt", TEXT, b"b\n")]);
+        let c = fixture.commit("refs/heads/main", &[b], BASE + 200, &[("a.txt", TEXT, b"c\n")]);
+        let d = fixture.commit("refs/heads/main", &[c], BASE + 300, &[("a.txt", TEXT, b"d\n")]);
+        // A branch whose tip is older than its parent, as clock skew
+        // produces, and a branch left behind long ago
+        let newer = fixture.commit("refs/heads/skewed", &[b], BASE + 250, &[("b.txt", TEXT, b"b\n")]);
+        let skewed = fixture.commit("refs/heads/skewed", &[newer], BASE + 120, &[("b.txt", TEXT, b"c\n")]);
+        let old = fixture.commit("refs/heads/old", &[a], BASE + 50, &[("c.txt", TEXT, b"c\n")]);
+
+        // Neither walk goes past a commit before `since`, so newer commits
+        // behind an older one are left for a full scan
+        let (git2, gix) = backends(&fixture);
+        for (since, expected) in [
+            (None, vec![a, b, c, d, newer, skewed, old]),
+            (Some(BASE + 50), vec![b, c, d, newer, skewed, old]),
+            (Some(BASE + 100), vec![b, c, d, newer, skewed]),
+            (Some(BASE + 200), vec![c, d]),
+            (Some(BASE + 300), vec![d]),
+            (Some(BASE + 301), vec![]),
+        ] {
+            let since = since.map(|time| chrono::DateTime::from_timestamp(time, 0).unwrap());
+            let expected: HashSet<String> = expected.iter().map(|id| id.to_string()).collect();
+            for backend in [&git2, &gix] {
+                let scanned = backend.scan_commit_ids(since).unwrap();
+                assert_eq!(scanned.len(), expected.len(), "scan since {:?}", since);
+                assert_eq!(scanned.into_iter().collect::<HashSet<_>>(), expected, "scan since {:?}", since);
+            }
+        }
+    }
+
+    #[test]
+    fn many_branches_with_equal_commit_times() {
+        let fixture = Fixture::new("ties");
+        let root = fixture.commit("refs/heads/main", &[], BASE, &[("a.txt", TEXT, b"a\n")]);
+        let mut tips = Vec::new();
+        for branch in 0..12 {
+            let mut tip = root;
+            for depth in 0..3 {
+                let content = format!("{} {}\n", branch, depth);
+                // Only three distinct times across all 36 commits
+                let time = BASE + 100 * (1 + (branch + depth) as i64 % 3);
+                tip = fixture.commit(&format!("refs/heads/b{:02}", branch), &[tip], time, &[("a.txt", TEXT, content.as_bytes())]);
+            }
+            tips.push(tip);
+        }
+        fixture.commit("refs/heads/main", &[tips[0], tips[5], tips[9]], BASE + 300, &[("a.txt", TEXT, b"merged\n")]);
+        assert_parity(&fixture);
+    }
+
+    #[test]
+    fn whitespace_only_and_blank_line_edits() {
+        let fixture = Fixture::new("whitespace");
+        let code = b"fn a() {\n    b();\n}\n\nfn c() {}\n";
+        let first = fixture.commit("refs/heads/main", &[], BASE, &[("a.rs", TEXT, code), ("b.rs", TEXT, b"x\n")]);
+        let second = fixture.commit("refs/heads/main", &[first], BASE + 100, &[
+            ("a.rs", TEXT, b"fn a() {\n\tb( );\n}\n\nfn c() {}  \n"),
+            ("b.rs", TEXT, b"x\n"),
+        ]);
+        let third = fixture.commit("refs/heads/main", &[second], BASE + 200, &[
+            ("a.rs", TEXT, b"fn a() {\n\tb( );\n}\n\n\n\nfn c() {}  \n"),
+            ("b.rs", TEXT, b"y\n"),
+        ]);
+        // Only whitespace changes, across every file
+        let fourth = fixture.commit("refs/heads/main", &[third], BASE + 300, &[
+            ("a.rs", TEXT, b"fn a() {\n  b();\n}\n\n\n\nfn c() {}\n"),
+            ("b.rs", TEXT, b"  y\n"),
+        ]);
+        // An empty commit
+        let tree_files: &[(&str, FileMode, &[u8])] = &[
+            ("a.rs", TEXT, b"fn a() {\n  b();\n}\n\n\n\nfn c() {}\n"),
+            ("b.rs", TEXT, b"  y\n"),
+        ];
+        fixture.commit("refs/heads/main", &[fourth], BASE + 400, tree_files);
+        assert_parity(&fixture);
+    }
+
+    #[test]
+    fn blank_lines_near_and_far_from_changes() {
+        let fixture = Fixture::new("blank");
+        let lines: Vec<String> = (0..40).map(|i| format!("line {}", i)).collect();
+        let text = |lines: &[String]| lines.join("\n") + "\n";
+        let first = fixture.commit("refs/heads/main", &[], BASE, &[("a.txt", TEXT, text(&lines).as_bytes())]);
+
+        // Blank lines next to an edit share its hunk, those far away don't
+        let mut edited = lines.clone();
+        edited[10] = "changed".to_string();
+        edited.insert(12, String::new());
+        edited.insert(30, "  ".to_string());
+        edited.insert(0, String::new());
+        let second = fixture.commit("refs/heads/main", &[first], BASE + 100, &[("a.txt", TEXT, text(&edited).as_bytes())]);
+
+        // Blank lines between two edits within a hunk's reach of each other
+        let mut edited = edited.clone();
+        edited[20] = "changed too".to_string();
+        edited.insert(23, String::new());
+        edited.insert(24, String::new());
+        edited[27] = "and this".to_string();
+        edited.retain(|line| line != "line 35");
+        fixture.commit("refs/heads/main", &[second], BASE + 200, &[("a.txt", TEXT, text(&edited).as_bytes())]);
+        assert_parity(&fixture);
+    }
+
+    #[test]
+    fn crlf_and_missing_final_newline() {
+        let fixture = Fixture::new("crlf");
+        let first = fixture.commit("refs/heads/main", &[], BASE, &[("win.txt", TEXT, b"a\r\nb\r\nc\r\n"), ("end.txt", TEXT, b"a\nb")]);
+        let second = fixture.commit("refs/heads/main", &[first], BASE + 100, &[("win.txt", TEXT, b"a\nb\nc\n"), ("end.txt", TEXT, b"a\nb\n")]);
+        fixture.commit("refs/heads/main", &[second], BASE + 200, &[("win.txt", TEXT, b"a\r\nB\r\nc\r\nd"), ("end.txt", TEXT, b"a\nc")]);
+        assert_parity(&fixture);
+    }
+
+    #[test]
+    fn binary_files() {
+        let fixture = Fixture::new("binary");
+        let image: Vec<u8> = (0..=255u8).cycle().take(4096).collect();
+        let mut changed = image.clone();
+        changed[100] = 0;
+        changed[2000] = 7;
+        let first = fixture.commit("refs/heads/main", &[], BASE, &[("logo.png", TEXT, &image), ("data.txt", TEXT, b"text\n")]);
+        let second = fixture.commit("refs/heads/main", &[first], BASE + 100, &[("logo.png", TEXT, &changed), ("data.txt", TEXT, b"text\0binary\n")]);
+        fixture.commit("refs/heads/main", &[second], BASE + 200, &[("data.txt", TEXT, b"text again\n")]);
+        assert_parity(&fixture);
+    }
+
+    #[test]
+    fn hunks_with_context_at_both_ends() {
+        let fixture = Fixture::new("hunks");
+        let lines: Vec<String> = (0..30).map(|i| format!("line {}", i)).collect();
+        let text = |lines: &[String]| lines.join("\n") + "\n";
+        let image: Vec<u8> = (0..=255u8).cycle().take(1024).collect();
+        let first = fixture.commit("refs/heads/main", &[], BASE, &[("a.txt", TEXT, text(&lines).as_bytes()), ("logo.png", TEXT, &image)]);
+
+        // Edits at the first and last lines, and two close enough to share a hunk
+        let mut edited = lines.clone();
+        edited[0] = "first".to_string();
+        edited[12] = "twelve".to_string();
+        edited.insert(16, "new".to_string());
+        edited.remove(29);
+        edited.push("last".to_string());
+        let second = fixture.commit("refs/heads/main", &[first], BASE + 100, &[
+            ("a.txt", TEXT, text(&edited).as_bytes()),
+            ("logo.png", FileMode::BlobExecutable, &image),
+        ]);
+        fixture.commit("refs/heads/main", &[second], BASE + 200, &[("a.txt", TEXT, b"line 0\n")]);
+        assert_parity(&fixture);
+    }
+
+    /// Files that become symlinks, directories, submodules and back, with
+    /// mode changes, quoted and non-ASCII names along the way.
+    pub(crate) fn type_and_mode_fixture(name: &str) -> Fixture {
+        let fixture = Fixture::new(name);
+        let submodule = b"1111111111111111111111111111111111111111";
+        let other_submodule = b"2222222222222222222222222222222222222222";
+        let first = fixture.commit("refs/heads/main", &[], BASE, &[
+            ("run.sh", TEXT, b"#!/bin/sh\necho hi\n"),
+            ("link", TEXT, b"target\n"),
+            ("node", TEXT, b"file\n"),
+            ("vendor/lib", FileMode::Commit, submodule),
+            ("quoted \"name\".txt", TEXT, b"q\n"),
+        ]);
+        let second = fixture.commit("refs/heads/main", &[first], BASE + 100, &[
+            ("run.sh", FileMode::BlobExecutable, b"#!/bin/sh\necho hi\n"),
+            ("link", FileMode::Link, b"target"),
+            ("node/inner.txt", TEXT, b"now a directory\n"),
+            ("vendor/lib", FileMode::Commit, other_submodule),
+            ("quoted \"name\".txt", TEXT, b"q2\n"),
+            ("ünïcode.txt", TEXT, b"u\n"),
+        ]);
+        fixture.commit("refs/heads/main", &[second], BASE + 200, &[
+            ("run.sh", TEXT, b"#!/bin/sh\necho bye\n"),
+            ("link", TEXT, b"target\n"),
+            ("node", TEXT, b"file again\n"),
+            ("vendor/lib", TEXT, b"plain file\n"),
+        ]);
+        fixture
+    }
+
+    #[test]
+    fn type_and_mode_changes() {
+        assert_parity(&type_and_mode_fixture("types"));
+    }
+
+    #[test]
+    fn deleted_branches() {
+        let fixture = Fixture::new("deleted");
+        let root = fixture.commit("refs/heads/main", &[], BASE, &[("a.txt", TEXT, b"a\n")]);
+        let topic = fixture.commit("refs/heads/topic", &[root], BASE + 100, &[("a.txt", TEXT, b"a\nb\n")]);
+        fixture.commit("refs/heads/topic", &[topic], BASE + 200, &[("a.txt", TEXT, b"a\nb\nc\n")]);
+        fixture.commit("refs/heads/main", &[root], BASE + 300, &[("a.txt", TEXT, b"z\na\n")]);
+        assert_parity(&fixture);
+
+        // Its commits are no longer reachable from any branch
+        fixture.repo.find_reference("refs/heads/topic").unwrap().delete().unwrap();
+        assert_parity(&fixture);
+    }
+
+    #[test]
+    fn branch_lookup_beyond_the_search_window() {
+        let fixture = Fixture::new("window");
+        let mut parent = fixture.commit("refs/heads/main", &[], BASE, &[("n.txt", TEXT, b"0\n")]);
+        let mut side = None;
+        for i in 1..=1100 {
+            let content = format!("{}\n", i);
+            parent = fixture.commit("refs/heads/main", &[parent], BASE + i, &[("n.txt", TEXT, content.as_bytes())]);
+            if i == 40 {
+                side = Some(parent);
+            }
+        }
+        let mut tip = side.unwrap();
+        for i in 0..150 {
+            let content = format!("side {}\n", i);
+            tip = fixture.commit("refs/heads/side", &[tip], BASE + 2000 + i, &[("n.txt", TEXT, content.as_bytes())]);
+        }
+        assert_parity(&fixture);
+    }
+
+    #[test]
+    fn reopened_gix_backends_share_branch_lookups() {
+        let fixture = Fixture::new("reopen");
+        let root = fixture.commit("refs/heads/main", &[], BASE, &[("a.txt", TEXT, b"a\n")]);
+        let topic = fixture.commit("refs/heads/topic", &[root], BASE + 100, &[("a.txt", TEXT, b"b\n")]);
+        let repository = Repository {
+            id: 1,
+            path: fixture.path(),
+            name: "fixture".to_string(),
+            last_scanned: None,
+            display_name: None,
+            color: None,
+            description: None,
+            archived: false,
+        };
+        let backend = open_backend(GitBackendKind::Gix, &repository).unwrap();
+        assert_eq!(backend.commit_branch(&topic.to_string()).unwrap(), "topic");
+
+        // Windows computed before the branch moved are reused by the copy
+        let moved = fixture.commit("refs/heads/topic", &[topic], BASE + 200, &[("a.txt", TEXT, b"c\n")]);
+        let reopened = backend.reopen().unwrap();
+        assert_eq!(reopened.commit_branch(&moved.to_string()).unwrap(), "");
+        assert_eq!(open_backend(GitBackendKind::Gix, &repository).unwrap().commit_branch(&moved.to_string()).unwrap(), "topic");
+    }
+}
@@ -0,0 +1,531 @@
+use crate::git_analyzer::{add_file_change, PatchIdFile, PatchIds};
+use crate::git_backend::{CommitDiffStats, CommitMetadata, GitBackend};
+use crate::models::Repository;
+use anyhow::{Context, Result};
+use gix::bstr::ByteSlice;
+use gix::diff::blob::{intern::InternedInput, sources::byte_lines, Algorithm};
+use gix::diff::tree::recorder::Change;
+use gix::revision::walk::Sorting;
+use gix::traverse::commit::simple::CommitTimeOrder;
+use gix::ObjectId;
+use std::cell::OnceCell;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+const MODE_TYPE_MASK: u32 = 0o170000;
+const MODE_COMMIT: u32 = 0o160000;
+
+// libgit2 treats larger files as binary, and looks for NUL bytes this far
+const MAX_TEXT_SIZE: usize = 512 * 1024 * 1024;
+const BINARY_CHECK_BYTES: usize = 8000;
+
+// Context lines of git2's default diff options, which decide how far apart
+// changes may be and still share a hunk
+const CONTEXT_LINES: u32 = 3;
+
+// Search windows of the branch lookup, as in `GitAnalyzer::get_commit_branch`
+const HEAD_BRANCH_DEPTH: usize = 1000;
+const OTHER_BRANCH_DEPTH: usize = 100;
+
+// Branch names with the commits `get_commit_branch` would search
+type BranchWindows = Vec<(String, HashSet<ObjectId>)>;
+
+/// Reads history through gitoxide: the walk, metadata, refs and tree
+/// diffs, with line counts from gix's blob diff. Its Myers implementation
+/// prunes frequent lines a little differently from libgit2's xdiff, so a
+/// large rewrite can count a line more on each side; `compare_backends`
+/// reports such files.
+///
+/// Patch-ids hash the hunks of the same blob diff, laid out as libgit2
+/// prints them. They are matched against ids stored by earlier scans, which
+/// may have used the other backend, so a rewrite whose lines gix aligns
+/// differently also gets a different id; `compare_backends` reports those
+/// too.
+pub struct GixBackend {
+    repo: gix::Repository,
+    repository: Repository,
+    // Computed once for this backend and the handles `reopen` makes from
+    // it, instead of walking history for every commit and every worker
+    shared_branch_windows: Arc<Mutex<Option<Arc<BranchWindows>>>>,
+    branch_windows: OnceCell<Arc<BranchWindows>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct EntrySide {
+    mode: u32,
+    id: ObjectId,
+}
+
+// One changed path of a tree diff; renames are not detected, like git2's
+struct Delta {
+    path: Vec<u8>,
+    old: Option<EntrySide>,
+    new: Option<EntrySide>,
+}
+
+// A run of changed lines from the blob diff, and whether all of them are blank
+struct LineChange {
+    before: Range<u32>,
+    after: Range<u32>,
+    blank: bool,
+}
+
+impl GixBackend {
+    pub fn open(repository: &Repository) -> Result<Self> {
+        let mut repo = gix::open(&repository.path)
+            .context(format!("Failed to open git repository at {}", repository.path))?;
+        repo.object_cache_size_if_unset(16 * 1024 * 1024);
+
+        Ok(GixBackend {
+            repo,
+            repository: repository.clone(),
+            shared_branch_windows: Arc::default(),
+            branch_windows: OnceCell::new(),
+        })
+    }
+
+    fn find_commit(&self, commit_id: &str) -> Option<gix::Commit<'_>> {
+        let id = ObjectId::from_hex(commit_id.as_bytes()).ok()?;
+        self.repo.find_commit(id).ok()
+    }
+
+    fn branch_tips(&self) -> Result<HashSet<ObjectId>> {
+        self.branch_refs()?
+            .into_iter()
+            .map(|(_, id)| Ok(ObjectId::from_hex(id.as_bytes())?))
+            .collect()
+    }
+
+    /// The first `depth` commits of libgit2's topological walk from `tip`:
+    /// a depth-first order that emits a commit once all its children are
+    /// out, visiting the last parent first.
+    fn topological_window(&self, tip: ObjectId, depth: usize) -> Result<HashSet<ObjectId>> {
+        let mut parents: HashMap<ObjectId, Vec<ObjectId>> = HashMap::new();
+        for info in self.repo.rev_walk([tip]).all()? {
+            let info = info?;
+            parents.insert(info.id, info.parent_ids.to_vec());
+        }
+
+        let mut in_degree: HashMap<ObjectId, usize> = parents.keys().map(|id| (*id, 1)).collect();
+        for commit_parents in parents.values() {
+            for parent in commit_parents {
+                if let Some(degree) = in_degree.get_mut(parent) {
+                    *degree += 1;
+                }
+            }
+        }
+
+        let mut window = HashSet::new();
+        let mut stack = vec![tip];
+        while let Some(next) = stack.pop() {
+            if window.len() >= depth {
+                break;
+            }
+            for parent in parents.get(&next).map(Vec::as_slice).unwrap_or_default() {
+                let Some(degree) = in_degree.get_mut(parent) else { continue };
+                if *degree == 0 {
+                    continue;
+                }
+                *degree -= 1;
+                if *degree == 1 {
+                    stack.push(*parent);
+                }
+            }
+            in_degree.insert(next, 0);
+            window.insert(next);
+        }
+
+        Ok(window)
+    }
+
+    /// The branch windows, loaded by whichever handle asks first while the
+    /// others wait for it.
+    fn branch_windows(&self) -> Result<&BranchWindows> {
+        if let Some(windows) = self.branch_windows.get() {
+            return Ok(windows);
+        }
+
+        let windows = {
+            let mut shared = self.shared_branch_windows.lock().unwrap();
+            match shared.as_ref() {
+                Some(windows) => windows.clone(),
+                None => shared.insert(Arc::new(self.load_branch_windows()?)).clone(),
+            }
+        };
+        Ok(self.branch_windows.get_or_init(|| windows))
+    }
+
+    fn load_branch_windows(&self) -> Result<BranchWindows> {
+        let mut windows = Vec::new();
+
+        let head_name = match self.repo.head_name()? {
+            Some(name) => name.shorten().to_string(),
+            None => "HEAD".to_string(),
+        };
+        if let Ok(head_id) = self.repo.head_id() {
+            windows.push((head_name.clone(), self.topological_window(head_id.detach(), HEAD_BRANCH_DEPTH)?));
+        }
+
+        for reference in self.repo.references()?.local_branches()? {
+            let Ok(mut reference) = reference else { continue };
+            let name = reference.name().shorten().to_string();
+            if name == head_name {
+                continue;
+            }
+            let Ok(commit) = reference.peel_to_commit() else { continue };
+            windows.push((name, self.topological_window(commit.id, OTHER_BRANCH_DEPTH)?));
+        }
+
+        Ok(windows)
+    }
+
+    /// Changed files between two trees, in path order. Type changes become
+    /// a delete and an add, as in git2's diff.
+    fn diff_trees(&self, old: Option<ObjectId>, new: Option<ObjectId>) -> Result<Vec<Delta>> {
+        // An empty buffer reads as the empty tree
+        let tree_data = |id: Option<ObjectId>| -> Result<Vec<u8>> {
+            Ok(match id {
+                Some(id) => self.repo.find_tree(id)?.detach().data,
+                None => Vec::new(),
+            })
+        };
+        let (old_data, new_data) = (tree_data(old)?, tree_data(new)?);
+
+        let mut recorder = gix::diff::tree::Recorder::default();
+        gix::diff::tree(
+            gix::objs::TreeRefIter::from_bytes(&old_data),
+            gix::objs::TreeRefIter::from_bytes(&new_data),
+            gix::diff::tree::State::default(),
+            &self.repo.objects,
+            &mut recorder,
+        )?;
+
+        let side = |mode: gix::object::tree::EntryMode, id| Some(EntrySide { mode: mode.value() as u32, id });
+        let mut deltas = Vec::new();
+        for change in recorder.records {
+            match change {
+                Change::Addition { entry_mode, oid, path, .. } if !entry_mode.is_tree() => {
+                    deltas.push(Delta { path: path.to_vec(), old: None, new: side(entry_mode, oid) });
+                }
+                Change::Deletion { entry_mode, oid, path, .. } if !entry_mode.is_tree() => {
+                    deltas.push(Delta { path: path.to_vec(), old: side(entry_mode, oid), new: None });
+                }
+                Change::Modification { previous_entry_mode, previous_oid, entry_mode, oid, path } if !entry_mode.is_tree() => {
+                    let (old, new) = (side(previous_entry_mode, previous_oid), side(entry_mode, oid));
+                    if (previous_entry_mode.value() as u32 ^ entry_mode.value() as u32) & MODE_TYPE_MASK != 0 {
+                        deltas.push(Delta { path: path.to_vec(), old, new: None });
+                        deltas.push(Delta { path: path.to_vec(), old: None, new });
+                    } else {
+                        deltas.push(Delta { path: path.to_vec(), old, new });
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // Stable, so a type change keeps its delete ahead of its add
+        deltas.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(deltas)
+    }
+
+    /// Contents libgit2 diffs for one side of a delta; submodules are
+    /// compared as their "Subproject commit" line.
+    fn load_content(&self, side: Option<&EntrySide>) -> Result<Vec<u8>> {
+        let Some(side) = side else { return Ok(Vec::new()) };
+        if side.mode == MODE_COMMIT {
+            return Ok(format!("Subproject commit {}\n", side.id).into_bytes());
+        }
+        let mut blob = self.repo.find_blob(side.id)?;
+        Ok(std::mem::take(&mut blob.data))
+    }
+}
+
+impl GitBackend for GixBackend {
+    fn scan_commit_ids(&self, since: Option<chrono::DateTime<chrono::Utc>>) -> Result<Vec<String>> {
+        let walk_start = std::time::Instant::now();
+
+        // Parents older than `since` are never followed, as in git2's walk
+        let sorting = match since {
+            Some(since_time) => Sorting::ByCommitTimeCutoff {
+                order: CommitTimeOrder::NewestFirst,
+                seconds: since_time.timestamp(),
+            },
+            None => Sorting::ByCommitTime(CommitTimeOrder::NewestFirst),
+        };
+        let walk = self.repo.rev_walk(self.branch_tips()?).sorting(sorting).all()?;
+        let mut commit_ids = Vec::new();
+        for info in walk {
+            let info = info?;
+
+            // Merge commits are stored too, flagged so the statistics can
+            // leave them out and avoid double counting
+            commit_ids.push(info.id.to_string());
+        }
+        println!("🚶 遍历待分析提交耗时(gix): {:?}, 提交数: {}", walk_start.elapsed(), commit_ids.len());
+
+        Ok(commit_ids)
+    }
+
+    fn reachable_commit_ids(&self) -> Result<HashSet<String>> {
+        let mut reachable = HashSet::new();
+        for info in self.repo.rev_walk(self.branch_tips()?).all()? {
+            reachable.insert(info?.id.to_string());
+        }
+        Ok(reachable)
+    }
+
+    fn branch_refs(&self) -> Result<Vec<(String, String)>> {
+        let platform = self.repo.references()?;
+        let mut refs = Vec::new();
+        for reference in platform.local_branches()?.chain(platform.remote_branches()?) {
+            let Ok(mut reference) = reference else { continue };
+            let name = reference.name().as_bstr().to_string();
+            let Ok(commit) = reference.peel_to_commit() else { continue };
+            refs.push((name, commit.id.to_string()));
+        }
+        Ok(refs)
+    }
+
+    fn commit_metadata(&self, commit_id: &str) -> Result<Option<CommitMetadata>> {
+        let Some(commit) = self.find_commit(commit_id) else { return Ok(None) };
+
+        let author = commit.author()?.trim();
+        // git2 drops the newlines that may precede the message
+        let message = commit.message_raw()?.trim_start_with(|c| c == '\n');
+
+        Ok(Some(CommitMetadata {
+            author: author.name.to_str().unwrap_or("Unknown").to_string(),
+            email: author.email.to_str().unwrap_or("").to_string(),
+            message: message.to_str().unwrap_or("").to_string(),
+            timestamp: chrono::DateTime::from_timestamp(commit.time()?.seconds, 0).unwrap_or_default(),
+            parent_count: commit.parent_ids().count(),
+        }))
+    }
+
+    fn commit_branch(&self, commit_id: &str) -> Result<String> {
+        let id = ObjectId::from_hex(commit_id.as_bytes())?;
+        let branch = self
+            .branch_windows()?
+            .iter()
+            .find(|(_, window)| window.contains(&id))
+            .map(|(name, _)| name.clone())
+            .unwrap_or_default();
+        Ok(branch)
+    }
+
+    fn diff_stats(&self, commit_id: &str) -> Result<Option<CommitDiffStats>> {
+        let Some(commit) = self.find_commit(commit_id) else { return Ok(None) };
+
+        let tree_id = commit.tree_id()?.detach();
+        let parent_tree_id = match commit.parent_ids().next() {
+            Some(parent_id) => Some(parent_id.object()?.into_commit().tree_id()?.detach()),
+            None => None,
+        };
+        let deltas = self.diff_trees(parent_tree_id, Some(tree_id))?;
+
+        let mut file_changes = Vec::with_capacity(deltas.len());
+        let mut file_indexes = HashMap::new();
+        let mut patch_ids = PatchIds::new();
+        let mut total_additions = 0;
+        let mut total_deletions = 0;
+
+        for delta in &deltas {
+            let old_content = self.load_content(delta.old.as_ref())?;
+            let new_content = self.load_content(delta.new.as_ref())?;
+
+            let binary = is_binary(&old_content) || is_binary(&new_content);
+            let is_commit = |side: Option<EntrySide>| side.is_some_and(|side| side.mode == MODE_COMMIT);
+            let file = PatchIdFile {
+                path: &delta.path,
+                old_mode: delta.old.map(|side| side.mode),
+                new_mode: delta.new.map(|side| side.mode),
+                // Submodules always count as changed
+                same_content: delta.old.map(|side| side.id) == delta.new.map(|side| side.id)
+                    && !is_commit(delta.old)
+                    && !is_commit(delta.new),
+                binary,
+            };
+            patch_ids.start_file(&file)?;
+
+            // Binary files count as zero lines, like numstat's "-"
+            let (additions, deletions, hunks) = if binary {
+                patch_ids.binary(&file)?;
+                (0, 0, 0)
+            } else {
+                diff_lines(&old_content, &new_content, &mut patch_ids)?
+            };
+
+            // Modifications that only touched whitespace or blank lines
+            let whitespace_only = match (delta.old, delta.new) {
+                (Some(old), Some(new)) => hunks == 0 && !binary && old.mode == new.mode,
+                _ => false,
+            };
+            if whitespace_only {
+                continue;
+            }
+
+            total_additions += additions;
+            total_deletions += deletions;
+            // The delete and the add of a type change share one entry
+            let path = String::from_utf8_lossy(&delta.path).to_string();
+            add_file_change(&mut file_changes, &mut file_indexes, path, additions, deletions);
+        }
+
+        let (patch_id, inverse_patch_id) = patch_ids.finish()?;
+
+        Ok(Some(CommitDiffStats {
+            additions: total_additions,
+            deletions: total_deletions,
+            files_changed: deltas.len() as i32,
+            file_changes,
+            patch_id,
+            inverse_patch_id,
+        }))
+    }
+
+    fn remote_url(&self) -> Option<String> {
+        let config = self.repo.config_snapshot();
+        let url_of = |name: &str| {
+            config
+                .string(format!("remote.{}.url", name).as_str())
+                .map(|url| url.to_string())
+        };
+
+        // Try the origin remote first, then any remote in name order
+        url_of("origin").or_else(|| {
+            self.repo
+                .remote_names()
+                .iter()
+                .find_map(|name| url_of(&name.to_str_lossy()))
+        })
+    }
+
+    fn reopen(&self) -> Result<Box<dyn GitBackend>> {
+        Ok(Box::new(GixBackend {
+            repo: self.repo.clone(),
+            repository: self.repository.clone(),
+            shared_branch_windows: self.shared_branch_windows.clone(),
+            branch_windows: OnceCell::new(),
+        }))
+    }
+}
+
+fn is_binary(content: &[u8]) -> bool {
+    content.len() > MAX_TEXT_SIZE || content[..content.len().min(BINARY_CHECK_BYTES)].contains(&0)
+}
+
+/// Added and deleted lines of a text file and its number of hunks, under
+/// git2's `ignore_whitespace` and `ignore_blank_lines` options: lines are
+/// compared with their whitespace removed, and changes made only of blank
+/// lines count only where git would print them inside a hunk. The hunks
+/// are fed to `patch_ids` on the way.
+fn diff_lines(old: &[u8], new: &[u8], patch_ids: &mut PatchIds) -> Result<(i32, i32, usize)> {
+    // C's isspace, which includes the vertical tab
+    let strip = |line: &&[u8]| -> Vec<u8> {
+        line.iter().copied().filter(|b| !b.is_ascii_whitespace() && *b != 0x0b).collect()
+    };
+    let old_lines: Vec<&[u8]> = byte_lines(old).collect();
+    let new_lines: Vec<&[u8]> = byte_lines(new).collect();
+    let mut input = InternedInput::default();
+    input.update_before(old_lines.iter().map(strip));
+    input.update_after(new_lines.iter().map(strip));
+
+    let mut changes = Vec::new();
+    gix::diff::blob::diff(Algorithm::Myers, &input, |before: Range<u32>, after: Range<u32>| {
+        let blank = before
+            .clone()
+            .map(|line| input.before[line as usize])
+            .chain(after.clone().map(|line| input.after[line as usize]))
+            .all(|token| input.interner[token].is_empty());
+        changes.push(LineChange { before, after, blank });
+    });
+
+    let (mut additions, mut deletions, mut hunks) = (0, 0, 0);
+    let mut next = 0;
+    while let Some(hunk) = next_hunk(&changes, next) {
+        for change in &changes[hunk.clone()] {
+            additions += change.after.len() as i32;
+            deletions += change.before.len() as i32;
+        }
+        emit_hunk(&changes[hunk.clone()], &old_lines, &new_lines, patch_ids)?;
+        hunks += 1;
+        next = hunk.end;
+    }
+
+    Ok((additions, deletions, hunks))
+}
+
+/// Feeds one hunk to `patch_ids` the way xdiff's `xdl_emit_diff` prints
+/// it: context is taken from the new side, up to `CONTEXT_LINES` before
+/// and after the changes and all of it between them.
+fn emit_hunk(changes: &[LineChange], old: &[&[u8]], new: &[&[u8]], patch_ids: &mut PatchIds) -> Result<()> {
+    let (first, last) = (&changes[0], &changes[changes.len() - 1]);
+    let trailing = CONTEXT_LINES
+        .min(old.len() as u32 - last.before.end)
+        .min(new.len() as u32 - last.after.end);
+
+    let mut line = first.after.start.saturating_sub(CONTEXT_LINES);
+    for change in changes {
+        for index in line..change.after.start {
+            patch_ids.line(' ', new[index as usize])?;
+        }
+        for index in change.before.clone() {
+            patch_ids.line('-', old[index as usize])?;
+        }
+        for index in change.after.clone() {
+            patch_ids.line('+', new[index as usize])?;
+        }
+        line = change.after.end;
+    }
+    for index in line..line + trailing {
+        patch_ids.line(' ', new[index as usize])?;
+    }
+    Ok(())
+}
+
+/// The changes git prints as the next hunk, from `start` on. Follows
+/// xdiff's `xdl_get_hunk`: blank-only changes are dropped unless they sit
+/// close enough to a kept change to share its hunk.
+fn next_hunk(changes: &[LineChange], mut start: usize) -> Option<Range<usize>> {
+    const MAX_COMMON: u32 = 2 * CONTEXT_LINES;
+    const MAX_IGNORABLE: u32 = CONTEXT_LINES;
+    let gap = |from: &LineChange, to: &LineChange| to.before.start - from.before.end;
+
+    // Leading blank-only changes too far from the change after them
+    let mut index = start;
+    while index < changes.len() && changes[index].blank {
+        if changes.get(index + 1).is_none_or(|next| gap(&changes[index], next) >= MAX_IGNORABLE) {
+            start = index + 1;
+        }
+        index += 1;
+    }
+    if start >= changes.len() {
+        return None;
+    }
+
+    let mut last = start;
+    let mut ignored = 0;
+    for index in start + 1..changes.len() {
+        let (previous, change) = (&changes[index - 1], &changes[index]);
+        let distance = gap(previous, change);
+        if distance > MAX_COMMON {
+            break;
+        }
+        if distance < MAX_IGNORABLE && (!change.blank || last == index - 1) {
+            last = index;
+            ignored = 0;
+        } else if distance < MAX_IGNORABLE {
+            ignored += change.after.len() as u32;
+        } else if last != index - 1 && change.before.start + ignored - changes[last].before.end > MAX_COMMON {
+            break;
+        } else if !change.blank {
+            last = index;
+            ignored = 0;
+        } else {
+            ignored += change.after.len() as u32;
+        }
+    }
+
+    Some(start..last + 1)
+}
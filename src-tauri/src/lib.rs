@@ -0,0 +1,102 @@
+mod commands;
+mod database;
+mod diff_search;
+mod filter_query;
+mod filters;
+pub mod git_analyzer;
+pub mod git_backend;
+mod gix_backend;
+mod migrations;
+pub mod models;
+mod rollups;
+mod search;
+mod settings;
+mod stats_cache;
+#[cfg(test)]
+mod test_support;
+
+use commands::*;
+
+/// Starts the app. The binary only calls this; the git modules are public
+/// so the benches can drive them.
+pub fn run() {
+    tauri::Builder::default()
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_shell::init())
+        .manage(commands::AppState::default())
+        .manage(database::DbState::default())
+        .setup(|app| {
+            // Initialize database; commands invoked meanwhile wait for it
+            let app_handle = app.handle();
+            let handle_clone = app_handle.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = database::get_db_pool(&handle_clone).await {
+                    eprintln!("Failed to initialize database: {}", e);
+                } else if let Err(e) = commands::restore_settings(&handle_clone).await {
+                    eprintln!("Failed to restore settings: {}", e);
+                }
+            });
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            add_repository,
+            remove_repository,
+            get_repositories,
+            update_repository,
+            get_repository_groups,
+            create_repository_group,
+            update_repository_group,
+            delete_repository_group,
+            scan_repository,
+            force_scan_repository,
+            scan_last_24_hours,
+            get_ingest_report,
+            get_git_backend,
+            set_git_backend,
+            compare_git_backends,
+            reconcile_repository,
+            purge_orphaned_commits,
+            verify_repository,
+            get_statistics,
+            get_hourly_stats,
+            get_daily_stats,
+            get_weekly_stats,
+            get_statistics_totals,
+            get_author_stats,
+            get_repository_stats,
+            get_group_stats,
+            get_hourly_commit_distribution,
+            get_author_activity_trends,
+            get_commit_frequency_distribution,
+            get_commit_size_distribution,
+            get_efficiency_trends,
+            get_hot_files,
+            get_commit_message_words,
+            get_statistics_cache_diagnostics,
+            set_statistics_cache_persistence,
+            get_revert_report,
+            get_commit_timeline,
+            get_commit_timeline_page,
+            search_commits,
+            search_diffs,
+            cancel_diff_search,
+            get_authors,
+            parse_filter_query,
+            complete_filter_query,
+            get_commit_detail,
+            compare_revisions,
+            get_file_history,
+            get_line_history,
+            get_settings,
+            set_setting,
+            reset_setting,
+            get_last_filter,
+            set_last_filter,
+            get_filter_presets,
+            create_filter_preset,
+            update_filter_preset,
+            delete_filter_preset
+        ])
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
\ No newline at end of file
@@ -0,0 +1,516 @@
+use sqlx::{SqliteConnection, SqlitePool, Row};
+use anyhow::Result;
+use std::path::Path;
+
+/// Latest schema version known to this build of the app.
+pub const SCHEMA_VERSION: i64 = 13;
+
+// Ordered schema migrations, applied once each and recorded in schema_version
+const MIGRATIONS: &[(i64, &str)] = &[
+    (1, "initial schema"),
+    (2, "commit patch-ids"),
+    (3, "revert relations"),
+    (4, "orphaned commits"),
+    (5, "unique file changes"),
+    (6, "commit remote urls"),
+    (7, "statistics rollups"),
+    (8, "statistics cache"),
+    (9, "merge commits"),
+    (10, "commit search index"),
+    (11, "settings and filter presets"),
+    (12, "repository groups"),
+    (13, "repository metadata"),
+];
+
+/// Brings the database up to `SCHEMA_VERSION`.
+///
+/// Databases created before versioning existed have no schema_version
+/// table; all migrations are written to tolerate the tables they find, so
+/// such databases simply run every migration. A backup is written next to
+/// the database before anything is changed.
+pub async fn run_migrations(pool: &SqlitePool, db_path: &Path) -> Result<()> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            description TEXT NOT NULL,
+            applied_at DATETIME NOT NULL
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    let current = check_schema_version(pool).await?;
+    if current == SCHEMA_VERSION {
+        return Ok(());
+    }
+
+    let has_data: i32 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'repositories'"
+    )
+    .fetch_one(pool)
+    .await?;
+
+    if has_data > 0 {
+        backup_database(pool, db_path, current).await?;
+    }
+
+    for (version, description) in MIGRATIONS.iter().filter(|(version, _)| *version > current) {
+        let mut tx = pool.begin().await?;
+
+        apply_migration(&mut tx, *version).await?;
+
+        sqlx::query("INSERT INTO schema_version (version, description, applied_at) VALUES (?, ?, ?)")
+            .bind(version)
+            .bind(description)
+            .bind(chrono::Utc::now())
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        println!("Applied migration {}: {}", version, description);
+    }
+
+    Ok(())
+}
+
+/// Returns the schema version of the database, refusing databases written
+/// by a newer version of the app.
+pub async fn check_schema_version(pool: &SqlitePool) -> Result<i64> {
+    let has_versions: i32 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'schema_version'"
+    )
+    .fetch_one(pool)
+    .await?;
+
+    if has_versions == 0 {
+        return Ok(0);
+    }
+
+    let current: Option<i64> = sqlx::query_scalar("SELECT MAX(version) FROM schema_version")
+        .fetch_one(pool)
+        .await?;
+    let current = current.unwrap_or(0);
+
+    if current > SCHEMA_VERSION {
+        return Err(anyhow::anyhow!(
+            "数据库由更新版本的应用创建 (schema v{}，当前支持 v{})，请升级应用",
+            current,
+            SCHEMA_VERSION
+        ));
+    }
+
+    Ok(current)
+}
+
+async fn backup_database(pool: &SqlitePool, db_path: &Path, version: i64) -> Result<()> {
+    let backup_path = db_path.with_extension(format!(
+        "v{}-{}.bak",
+        version,
+        chrono::Utc::now().format("%Y%m%d%H%M%S")
+    ));
+
+    // VACUUM INTO writes a consistent copy even while other connections are open
+    sqlx::query("VACUUM INTO ?")
+        .bind(backup_path.to_string_lossy().to_string())
+        .execute(pool)
+        .await?;
+
+    println!("Backed up database to: {}", backup_path.display());
+    Ok(())
+}
+
+async fn apply_migration(conn: &mut SqliteConnection, version: i64) -> Result<()> {
+    match version {
+        1 => {
+            sqlx::query(
+                r#"
+                CREATE TABLE IF NOT EXISTS repositories (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    path TEXT UNIQUE NOT NULL,
+                    name TEXT NOT NULL,
+                    last_scanned DATETIME
+                )
+                "#,
+            )
+            .execute(&mut *conn)
+            .await?;
+
+            sqlx::query(
+                r#"
+                CREATE TABLE IF NOT EXISTS commits (
+                    id TEXT NOT NULL,
+                    repository_id INTEGER NOT NULL,
+                    repository_name TEXT NOT NULL,
+                    author TEXT NOT NULL,
+                    email TEXT NOT NULL,
+                    message TEXT NOT NULL,
+                    timestamp DATETIME NOT NULL,
+                    additions INTEGER NOT NULL DEFAULT 0,
+                    deletions INTEGER NOT NULL DEFAULT 0,
+                    files_changed INTEGER NOT NULL DEFAULT 0,
+                    branch TEXT,
+                    PRIMARY KEY (id, repository_id),
+                    FOREIGN KEY (repository_id) REFERENCES repositories (id) ON DELETE CASCADE
+                )
+                "#
+            )
+            .execute(&mut *conn)
+            .await?;
+
+            sqlx::query(
+                r#"
+                CREATE TABLE IF NOT EXISTS file_changes (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    commit_id TEXT NOT NULL,
+                    repository_id INTEGER NOT NULL,
+                    file_path TEXT NOT NULL,
+                    additions INTEGER NOT NULL DEFAULT 0,
+                    deletions INTEGER NOT NULL DEFAULT 0,
+                    FOREIGN KEY (commit_id, repository_id) REFERENCES commits (id, repository_id) ON DELETE CASCADE
+                )
+                "#
+            )
+            .execute(&mut *conn)
+            .await?;
+
+            // Create indexes for performance
+            for statement in [
+                "CREATE INDEX IF NOT EXISTS idx_commits_timestamp ON commits(timestamp)",
+                "CREATE INDEX IF NOT EXISTS idx_commits_author ON commits(author)",
+                "CREATE INDEX IF NOT EXISTS idx_commits_repository ON commits(repository_id)",
+                "CREATE INDEX IF NOT EXISTS idx_file_changes_path ON file_changes(file_path)",
+                "CREATE INDEX IF NOT EXISTS idx_file_changes_commit ON file_changes(commit_id)",
+            ] {
+                sqlx::query(statement).execute(&mut *conn).await?;
+            }
+        }
+        2 => {
+            ensure_column(conn, "commits", "patch_id", "TEXT").await?;
+
+            sqlx::query("CREATE INDEX IF NOT EXISTS idx_commits_patch_id ON commits(patch_id)")
+                .execute(&mut *conn)
+                .await?;
+        }
+        3 => {
+            ensure_column(conn, "commits", "inverse_patch_id", "TEXT").await?;
+
+            sqlx::query(
+                r#"
+                CREATE TABLE IF NOT EXISTS commit_reverts (
+                    repository_id INTEGER NOT NULL,
+                    revert_commit_id TEXT NOT NULL,
+                    reverted_commit_id TEXT NOT NULL,
+                    detection TEXT NOT NULL,
+                    PRIMARY KEY (repository_id, revert_commit_id, reverted_commit_id),
+                    FOREIGN KEY (repository_id) REFERENCES repositories (id) ON DELETE CASCADE
+                )
+                "#
+            )
+            .execute(&mut *conn)
+            .await?;
+
+            sqlx::query("CREATE INDEX IF NOT EXISTS idx_commit_reverts_reverted ON commit_reverts(repository_id, reverted_commit_id)")
+                .execute(&mut *conn)
+                .await?;
+        }
+        4 => {
+            ensure_column(conn, "commits", "orphaned_at", "DATETIME").await?;
+        }
+        5 => {
+            // Keep the newest row of every (repository, commit, file) so the
+            // unique index that ingestion upserts against can be created
+            let removed = sqlx::query(
+                r#"
+                DELETE FROM file_changes WHERE id NOT IN (
+                    SELECT MAX(id) FROM file_changes GROUP BY repository_id, commit_id, file_path
+                )
+                "#
+            )
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+
+            if removed > 0 {
+                println!("Removed {} duplicate file_changes rows", removed);
+            }
+
+            sqlx::query(
+                "CREATE UNIQUE INDEX IF NOT EXISTS idx_file_changes_unique ON file_changes(repository_id, commit_id, file_path)"
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+        6 => {
+            ensure_column(conn, "commits", "remote_url", "TEXT").await?;
+        }
+        7 => {
+            // Aggregates per UTC hour, kept current at ingest time. They are
+            // filled by `rollups::ensure_rollups_current` once the pool is up
+            for statement in [
+                r#"
+                CREATE TABLE IF NOT EXISTS commit_rollups (
+                    repository_id INTEGER NOT NULL,
+                    author TEXT NOT NULL,
+                    orphaned INTEGER NOT NULL,
+                    hour_start TEXT NOT NULL,
+                    local_hour INTEGER NOT NULL,
+                    local_weekday INTEGER NOT NULL,
+                    commits INTEGER NOT NULL,
+                    additions INTEGER NOT NULL,
+                    deletions INTEGER NOT NULL,
+                    small_commits INTEGER NOT NULL,
+                    medium_commits INTEGER NOT NULL,
+                    large_commits INTEGER NOT NULL,
+                    huge_commits INTEGER NOT NULL,
+                    PRIMARY KEY (repository_id, hour_start, author, orphaned, local_hour),
+                    FOREIGN KEY (repository_id) REFERENCES repositories (id) ON DELETE CASCADE
+                )
+                "#,
+                r#"
+                CREATE TABLE IF NOT EXISTS file_rollups (
+                    repository_id INTEGER NOT NULL,
+                    author TEXT NOT NULL,
+                    orphaned INTEGER NOT NULL,
+                    hour_start TEXT NOT NULL,
+                    file_path TEXT NOT NULL,
+                    changes INTEGER NOT NULL,
+                    additions INTEGER NOT NULL,
+                    deletions INTEGER NOT NULL,
+                    last_modified DATETIME NOT NULL,
+                    PRIMARY KEY (repository_id, hour_start, author, orphaned, file_path),
+                    FOREIGN KEY (repository_id) REFERENCES repositories (id) ON DELETE CASCADE
+                )
+                "#,
+                r#"
+                CREATE TABLE IF NOT EXISTS word_rollups (
+                    repository_id INTEGER NOT NULL,
+                    author TEXT NOT NULL,
+                    orphaned INTEGER NOT NULL,
+                    hour_start TEXT NOT NULL,
+                    word TEXT NOT NULL,
+                    count INTEGER NOT NULL,
+                    PRIMARY KEY (repository_id, hour_start, author, orphaned, word),
+                    FOREIGN KEY (repository_id) REFERENCES repositories (id) ON DELETE CASCADE
+                )
+                "#,
+                r#"
+                CREATE TABLE IF NOT EXISTS rollup_state (
+                    key TEXT PRIMARY KEY,
+                    value TEXT NOT NULL
+                )
+                "#,
+                "CREATE INDEX IF NOT EXISTS idx_commit_rollups_hour ON commit_rollups(hour_start)",
+                "CREATE INDEX IF NOT EXISTS idx_file_rollups_hour ON file_rollups(hour_start)",
+                "CREATE INDEX IF NOT EXISTS idx_word_rollups_hour ON word_rollups(hour_start)",
+            ] {
+                sqlx::query(statement).execute(&mut *conn).await?;
+            }
+        }
+        8 => {
+            // Persisted statistics results, see `stats_cache::StatisticsCache`.
+            // A NULL repository_id marks results over all repositories
+            sqlx::query(
+                r#"
+                CREATE TABLE IF NOT EXISTS statistics_cache (
+                    cache_key TEXT PRIMARY KEY,
+                    repository_id INTEGER,
+                    payload TEXT NOT NULL,
+                    created_at DATETIME NOT NULL
+                )
+                "#
+            )
+            .execute(&mut *conn)
+            .await?;
+
+            sqlx::query("CREATE INDEX IF NOT EXISTS idx_statistics_cache_repository ON statistics_cache(repository_id)")
+                .execute(&mut *conn)
+                .await?;
+        }
+        9 => {
+            // Scans store merge commits as well from now on; the ones skipped
+            // by earlier scans only show up after a full rescan
+            ensure_column(conn, "commits", "is_merge", "INTEGER NOT NULL DEFAULT 0").await?;
+        }
+        10 => {
+            // Full-text index of commit messages, authors and changed paths,
+            // see `search`. commits has no integer key, so commit_search_rows
+            // hands out the stable rowids the FTS rows are stored under. The
+            // trigram tokenizer matches substrings, which also covers Chinese
+            // text that has no spaces between words
+            for statement in [
+                r#"
+                CREATE TABLE IF NOT EXISTS commit_search_rows (
+                    id INTEGER PRIMARY KEY,
+                    repository_id INTEGER NOT NULL,
+                    commit_id TEXT NOT NULL,
+                    UNIQUE (repository_id, commit_id)
+                )
+                "#,
+                r#"
+                CREATE VIRTUAL TABLE IF NOT EXISTS commit_search USING fts5(
+                    message,
+                    author,
+                    files,
+                    tokenize = 'trigram'
+                )
+                "#,
+            ] {
+                sqlx::query(statement).execute(&mut *conn).await?;
+            }
+        }
+        11 => {
+            // Preferences as JSON values, see `settings`, and the named
+            // filters users save
+            for statement in [
+                r#"
+                CREATE TABLE IF NOT EXISTS settings (
+                    key TEXT PRIMARY KEY,
+                    value TEXT NOT NULL,
+                    updated_at DATETIME NOT NULL
+                )
+                "#,
+                r#"
+                CREATE TABLE IF NOT EXISTS filter_presets (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    name TEXT NOT NULL UNIQUE,
+                    filter TEXT NOT NULL,
+                    created_at DATETIME NOT NULL,
+                    updated_at DATETIME NOT NULL
+                )
+                "#,
+            ] {
+                sqlx::query(statement).execute(&mut *conn).await?;
+            }
+        }
+        12 => {
+            // Named sets of repositories; a repository can be in several
+            for statement in [
+                r#"
+                CREATE TABLE IF NOT EXISTS repository_groups (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    name TEXT NOT NULL UNIQUE,
+                    created_at DATETIME NOT NULL
+                )
+                "#,
+                r#"
+                CREATE TABLE IF NOT EXISTS repository_group_members (
+                    group_id INTEGER NOT NULL,
+                    repository_id INTEGER NOT NULL,
+                    PRIMARY KEY (group_id, repository_id),
+                    FOREIGN KEY (group_id) REFERENCES repository_groups (id) ON DELETE CASCADE,
+                    FOREIGN KEY (repository_id) REFERENCES repositories (id) ON DELETE CASCADE
+                )
+                "#,
+                "CREATE INDEX IF NOT EXISTS idx_repository_group_members_repository ON repository_group_members(repository_id)",
+                // Persisted statistics have no per-group aggregates yet
+                "DELETE FROM statistics_cache",
+            ] {
+                sqlx::query(statement).execute(&mut *conn).await?;
+            }
+        }
+        13 => {
+            ensure_column(conn, "repositories", "display_name", "TEXT").await?;
+            ensure_column(conn, "repositories", "color", "TEXT").await?;
+            ensure_column(conn, "repositories", "description", "TEXT").await?;
+            ensure_column(conn, "repositories", "archived", "INTEGER NOT NULL DEFAULT 0").await?;
+
+            // Persisted statistics still key repositories by name
+            sqlx::query("DELETE FROM statistics_cache").execute(&mut *conn).await?;
+        }
+        _ => return Err(anyhow::anyhow!("Unknown schema migration {}", version)),
+    }
+
+    Ok(())
+}
+
+async fn ensure_column(conn: &mut SqliteConnection, table: &str, column: &str, definition: &str) -> Result<()> {
+    let columns = sqlx::query(&format!("PRAGMA table_info({})", table))
+        .fetch_all(&mut *conn)
+        .await?;
+
+    let exists = columns
+        .iter()
+        .any(|row| row.get::<String, _>("name") == column);
+
+    if !exists {
+        sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[tokio::test]
+    async fn dedup_migration_keeps_the_newest_file_change() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        let mut conn = pool.acquire().await.unwrap();
+
+        for version in 1..=4 {
+            apply_migration(&mut conn, version).await.unwrap();
+        }
+
+        sqlx::query("INSERT INTO repositories (id, path, name) VALUES (1, '/tmp/repo', 'repo')")
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        for commit_id in ["a", "b"] {
+            sqlx::query(
+                "INSERT INTO commits (id, repository_id, repository_name, author, email, message, timestamp) \
+                 VALUES (?, 1, 'repo', 'Alice', 'alice@example.com', 'commit', '2023-11-14T22:13:20Z')"
+            )
+            .bind(commit_id)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        }
+
+        // Older versions appended a row per scan, so rescans left duplicates
+        for (commit_id, path, additions) in [
+            ("a", "src/main.rs", 1),
+            ("a", "src/main.rs", 2),
+            ("a", "README.md", 5),
+            ("b", "src/main.rs", 3),
+            ("a", "src/main.rs", 4),
+            ("b", "src/main.rs", 6),
+        ] {
+            sqlx::query("INSERT INTO file_changes (commit_id, repository_id, file_path, additions) VALUES (?, 1, ?, ?)")
+                .bind(commit_id)
+                .bind(path)
+                .bind(additions)
+                .execute(&mut *conn)
+                .await
+                .unwrap();
+        }
+
+        apply_migration(&mut conn, 5).await.unwrap();
+
+        let rows: Vec<(String, String, i64)> = sqlx::query_as(
+            "SELECT commit_id, file_path, additions FROM file_changes ORDER BY commit_id, file_path"
+        )
+        .fetch_all(&mut *conn)
+        .await
+        .unwrap();
+        assert_eq!(rows, vec![
+            ("a".to_string(), "README.md".to_string(), 5),
+            ("a".to_string(), "src/main.rs".to_string(), 4),
+            ("b".to_string(), "src/main.rs".to_string(), 6),
+        ]);
+
+        let duplicate = sqlx::query("INSERT INTO file_changes (commit_id, repository_id, file_path) VALUES ('a', 1, 'README.md')")
+            .execute(&mut *conn)
+            .await;
+        assert!(duplicate.is_err());
+    }
+}
@@ -5,8 +5,28 @@ use sqlx::FromRow;
 pub struct Repository {
     pub id: i64,
     pub path: String,
-    pub name: String,
+    pub name: String, // Folder name
     pub last_scanned: Option<chrono::DateTime<chrono::Utc>>,
+    pub display_name: Option<String>, // Shown instead of the folder name when set
+    pub color: Option<String>, // #rrggbb
+    pub description: Option<String>,
+    pub archived: bool, // Left out when refreshing all repositories
+}
+
+impl Repository {
+    /// The name shown for the repository.
+    pub fn label(&self) -> &str {
+        self.display_name.as_deref().unwrap_or(&self.name)
+    }
+}
+
+/// A named set of repositories, such as work or personal projects. A
+/// repository can be in any number of groups.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepositoryGroup {
+    pub id: i64,
+    pub name: String,
+    pub repository_ids: Vec<i64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -25,6 +45,48 @@ pub struct Commit {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[sqlx(default)]
     pub remote_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[sqlx(default)]
+    pub patch_id: Option<String>,
+    #[serde(default, skip_serializing)]
+    #[sqlx(default)]
+    pub inverse_patch_id: Option<String>, // Patch-id of the reverse diff, used for revert detection
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[sqlx(default)]
+    pub orphaned_at: Option<chrono::DateTime<chrono::Utc>>, // Set once the commit is unreachable from all refs
+    #[serde(default)]
+    #[sqlx(default)]
+    pub is_merge: bool, // Diffed against the first parent; excluded from statistics unless requested
+}
+
+// One page of the commit timeline, newest first
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CommitPage {
+    pub commits: Vec<Commit>,
+    pub next_cursor: Option<String>, // None on the last page
+    pub total_count: i64, // Commits matching the filter over all pages
+    pub page_size: u32,
+}
+
+// One commit found by the commit search, best match first. Snippet matches
+// are wrapped in \u{2} and \u{3}
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchHit {
+    pub commit: Commit,
+    pub score: f64, // bm25 relevance, higher is better; 0 when only short terms were searched
+    pub message_snippet: String, // The message, or the part around its first match
+    pub author_snippet: String, // "name <email>"
+    pub matched_files: Vec<String>, // First few changed paths that match a term
+    pub matched_file_count: i64,
+}
+
+// One page of commit search results
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchPage {
+    pub hits: Vec<SearchHit>,
+    pub next_cursor: Option<String>, // None on the last page
+    pub total_count: i64, // Matching commits over all pages
+    pub page_size: u32,
 }
 
 // New struct for file changes
@@ -51,7 +113,191 @@ pub struct CommitDetail {
     pub files_changed: i32,
     pub branch: Option<String>,
     pub remote_url: Option<String>,
+    pub patch_id: Option<String>,
     pub file_changes: Vec<FileChange>,
+    pub duplicates: Vec<Commit>, // Commits with the same patch-id in tracked repositories
+    pub reverts: Vec<String>, // Commits this commit reverts
+    pub reverted_by: Vec<String>, // Commits that revert this commit
+}
+
+// How a diff search decides that a commit matches, after `git log -S`
+// and `git log -G`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PickaxeMode {
+    #[default]
+    Occurrences, // The number of matches in a file changed, i.e. added or removed (-S)
+    Lines, // An added or removed line matches (-G)
+}
+
+// What a diff search looks for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffSearchQuery {
+    pub pattern: String,
+    #[serde(default)]
+    pub regex: bool, // Otherwise the pattern is a literal string
+    #[serde(default)]
+    pub ignore_case: bool,
+    #[serde(default)]
+    pub mode: PickaxeMode,
+}
+
+// A diff hunk containing a match
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffSearchHunk {
+    pub file_path: String,
+    pub header: String, // "@@ -a,b +c,d @@ ..."
+    pub patch: String, // Lines prefixed with '+', '-' or ' '
+    pub matched_lines: i32, // Added or removed lines matching the pattern
+}
+
+// Payload of the "diff-search-match" event, one per matching commit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffSearchMatch {
+    pub search_id: String,
+    pub commit: Commit,
+    pub hunks: Vec<DiffSearchHunk>,
+}
+
+// Payload of the "diff-search-progress" event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffSearchProgress {
+    pub search_id: String,
+    pub checked: i64,
+    pub total: i64,
+    pub matches: i64,
+}
+
+// Payload of the "diff-search-finished" event, sent once per search
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffSearchFinished {
+    pub search_id: String,
+    pub checked: i64,
+    pub total: i64,
+    pub matches: i64,
+    pub cancelled: bool,
+    pub cached: bool, // Replayed from an earlier search with the same query and filter
+    pub error: Option<String>,
+    pub elapsed_ms: i64,
+}
+
+// A detected revert relation between two commits of one repository
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct RevertPair {
+    pub repository_id: i64,
+    pub repository_name: String,
+    pub revert_commit_id: String,
+    pub reverted_commit_id: String,
+    pub detection: String, // "message" or "patch"
+    pub revert_author: String,
+    pub reverted_author: String,
+    pub timestamp: chrono::DateTime<chrono::Utc>, // Time of the revert
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RevertRate {
+    pub key: String, // Author name or repository name
+    pub repository_id: Option<i64>,
+    pub commits: i32,
+    pub reverts: i32, // Revert commits made
+    pub reverted: i32, // Own commits that were reverted
+    pub revert_rate: f64, // reverted / commits
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RevertReport {
+    pub total_commits: i32,
+    pub total_reverts: i32,
+    pub total_reverted: i32,
+    pub authors: Vec<RevertRate>,
+    pub repositories: Vec<RevertRate>,
+    pub pairs: Vec<RevertPair>,
+}
+
+// Result of comparing two revisions (commits, branches or tags)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RevisionComparison {
+    pub repository_id: i64,
+    pub repository_name: String,
+    pub from_revision: String,
+    pub to_revision: String,
+    pub from_commit: String,
+    pub to_commit: String,
+    pub merge_base: Option<String>,
+    pub additions: i32,
+    pub deletions: i32,
+    pub files_changed: i32,
+    pub remote_url: Option<String>,
+    pub file_changes: Vec<FileChange>,
+    pub commits: Vec<Commit>,
+}
+
+// A single commit in the history of a file
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct FileHistoryEntry {
+    pub commit_id: String,
+    pub author: String,
+    pub email: String,
+    pub message: String,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub path: String,
+    #[sqlx(default)]
+    pub old_path: Option<String>, // Set when the file was renamed from old_path in this commit
+    pub additions: i32,
+    pub deletions: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileAuthorContribution {
+    pub author: String,
+    pub commits: i32,
+    pub additions: i32,
+    pub deletions: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileChangeFrequency {
+    pub period: String, // Format: "YYYY-MM" for monthly
+    pub commits: i32,
+    pub additions: i32,
+    pub deletions: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileHistory {
+    pub repository_id: i64,
+    pub path: String,
+    pub source: String, // "database" or "git"
+    pub paths: Vec<String>, // All names the file had, newest first
+    pub commits: Vec<FileHistoryEntry>,
+    pub authors: Vec<FileAuthorContribution>,
+    pub change_frequency: Vec<FileChangeFrequency>,
+}
+
+// A commit that modified a tracked line range, like `git log -L`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LineHistoryEntry {
+    pub commit_id: String,
+    pub author: String,
+    pub email: String,
+    pub message: String,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub path: String,
+    pub start_line: u32, // Range of the tracked lines in this commit
+    pub end_line: u32,
+    pub additions: i32,
+    pub deletions: i32,
+    pub diff: String, // Only the hunks overlapping the range
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LineHistory {
+    pub repository_id: i64,
+    pub path: String,
+    pub revision: String,
+    pub start_line: u32,
+    pub end_line: u32,
+    pub commits: Vec<LineHistoryEntry>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -87,9 +333,24 @@ pub struct AuthorStats {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RepositoryStats {
+    pub name: String, // Display name, or the folder name
+    pub path: String, // Tells apart repositories shown with the same name
+    pub color: Option<String>,
+    pub additions: i32,
+    pub deletions: i32,
+    pub commits: i32,
+}
+
+// Totals over the repositories of one group
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupStats {
+    pub group_id: i64,
+    pub name: String,
     pub additions: i32,
     pub deletions: i32,
     pub commits: i32,
+    pub repositories: i32, // Repositories of the group with commits in the filter
+    pub authors: i32,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -145,6 +406,25 @@ pub struct CommitMessageWord {
     pub weight: f64,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StatisticsTotals {
+    pub total_commits: i32,
+    pub total_additions: i32,
+    pub total_deletions: i32,
+}
+
+// Statistics cache state and hit counts since the app started
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StatisticsCacheDiagnostics {
+    pub persistent: bool,
+    pub memory_entries: usize,
+    pub disk_entries: i64,
+    pub hits: u64,
+    pub disk_hits: u64,
+    pub misses: u64,
+    pub invalidations: u64,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Statistics {
     pub hourly: Vec<HourlyStats>,
@@ -154,7 +434,8 @@ pub struct Statistics {
     pub total_additions: i32,
     pub total_deletions: i32,
     pub authors: std::collections::HashMap<String, AuthorStats>,
-    pub repositories: std::collections::HashMap<String, RepositoryStats>,
+    pub repositories: std::collections::HashMap<i64, RepositoryStats>, // By repository ID
+    pub groups: Vec<GroupStats>,
     // New fields for additional charts
     pub hourly_commit_distribution: Vec<HourlyCommitDistribution>,
     pub author_activity_trends: Vec<AuthorActivityTrend>,
@@ -166,11 +447,142 @@ pub struct Statistics {
     pub commit_message_words: Vec<CommitMessageWord>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TimeFilter {
     pub start_date: Option<chrono::DateTime<chrono::Utc>>,
     pub end_date: Option<chrono::DateTime<chrono::Utc>>,
     pub author: Option<String>,
+    #[serde(default)]
+    pub authors: Option<Vec<String>>, // Any of these, in addition to `author`
     pub exclude_authors: Option<Vec<String>>,
     pub repository_id: Option<i64>,
+    #[serde(default)]
+    pub repository_ids: Option<Vec<i64>>, // Any of these, in addition to `repository_id`
+    #[serde(default)]
+    pub group_ids: Option<Vec<i64>>, // Repositories in any of these groups, within the ones above
+    #[serde(default)]
+    pub branches: Option<Vec<String>>,
+    #[serde(default)]
+    pub paths: Option<Vec<String>>, // Globs; commits and their line counts are limited to matching files
+    #[serde(default)]
+    pub min_size: Option<i64>, // Bounds on additions + deletions
+    #[serde(default)]
+    pub max_size: Option<i64>,
+    #[serde(default)]
+    pub message_pattern: Option<String>, // Regex the message must match
+    #[serde(default)]
+    pub exclude_message_pattern: Option<String>, // Regex the message must not match
+    #[serde(default)]
+    pub include_merges: bool,
+    #[serde(default)]
+    pub deduplicate_patches: bool, // Count identical patches only once
+    #[serde(default)]
+    pub exclude_reverts: bool, // Drop reverts together with the commits they revert
+    #[serde(default)]
+    pub include_orphaned: bool, // Also count commits no longer reachable from any ref
+}
+
+// A problem in a filter query, located by UTF-16 offsets into the query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilterQueryError {
+    pub message: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+// The filter a query describes, or why it couldn't be read
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParsedFilterQuery {
+    pub filter: Option<TimeFilter>,
+    pub errors: Vec<FilterQueryError>,
+}
+
+// A completion replacing the query's UTF-16 range start..end with `text`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilterQuerySuggestion {
+    pub text: String,
+    pub kind: String, // "key", "author", "repo", "branch" or a switch key such as "merges"
+    pub description: Option<String>,
+    pub start: usize,
+    pub end: usize,
+}
+
+// A mismatch between the database and a fresh analysis of the repository
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerificationIssue {
+    pub commit_id: String,
+    pub kind: String, // "missing_commit", "commit_stats", "missing_file", "extra_file", "file_stats", "duplicate_file_rows"
+    pub file_path: Option<String>,
+    pub stored: Option<String>,
+    pub actual: Option<String>,
+    pub repaired: bool,
+    pub repair_error: Option<String>, // Why the repair of this commit failed
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerificationReport {
+    pub repository_id: i64,
+    pub total_commits: i32,
+    pub checked_commits: i32,
+    pub sampled: bool,
+    pub issues: Vec<VerificationIssue>,
+    pub repaired_commits: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReconcileResult {
+    pub repository_id: i64,
+    pub checked: i32, // Stored commits compared against the refs
+    pub orphaned: i32, // Newly marked as orphaned
+    pub restored: i32, // Reachable again after being orphaned
+    pub total_orphaned: i32,
+}
+
+/// Throughput of one ingestion run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IngestReport {
+    pub commits: i64,
+    pub file_changes: i64,
+    pub batches: i64,
+    pub batch_size: i64,
+    pub deferred_indexes: bool,
+    pub elapsed_ms: i64,
+    pub commits_per_second: f64,
+    pub file_changes_per_second: f64,
+}
+
+/// A value the git2 and gix backends disagree on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackendMismatch {
+    pub commit_id: Option<String>, // None for repository-level values
+    pub field: String,
+    pub git2: String,
+    pub gix: String,
+}
+
+/// Result of reading the same commits through both git backends.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackendComparison {
+    pub repository_id: i64,
+    pub commits_checked: i32,
+    pub git2_ms: f64,
+    pub gix_ms: f64,
+    pub mismatches: Vec<BackendMismatch>,
+}
+
+/// A filter saved under a name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilterPreset {
+    pub id: i64,
+    pub name: String,
+    pub filter: TimeFilter,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+// Payload of the "settings-changed" event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingChanged {
+    pub key: String,
+    pub value: Option<serde_json::Value>, // None when the setting was reset
 }
\ No newline at end of file
@@ -0,0 +1,638 @@
+use sqlx::{SqliteConnection, SqlitePool, Row};
+use crate::models::TimeFilter;
+use anyhow::Result;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// UTC hour a commit is rolled up into, in the format of `commits.timestamp`
+/// so both sort and compare the same way.
+pub const HOUR_FORMAT: &str = "%Y-%m-%dT%H:00:00+00:00";
+
+const ROLLUP_TABLES: &[&str] = &["commit_rollups", "file_rollups", "word_rollups"];
+
+// Word rollup rows per multi-row INSERT
+const WORD_ROWS_PER_STATEMENT: usize = 500;
+
+/// Recomputes the rollup rows of a repository from its stored commits,
+/// either for the given UTC hours or, with `None`, for all of them. Merge
+/// commits are left out, as filters only count them on request.
+pub async fn refresh_rollups(conn: &mut SqliteConnection, repository_id: i64, hours: Option<&[String]>) -> Result<()> {
+    let (scope, params) = match hours {
+        None => ("c.is_merge = 0 AND c.repository_id = ?".to_string(), vec![repository_id.to_string()]),
+        Some([]) => return Ok(()),
+        Some(hours) => {
+            let first = hours.iter().min().cloned().unwrap_or_default();
+            let last = hours.iter().max().cloned().unwrap_or_default();
+            // The timestamp range lets SQLite use the timestamp index
+            let scope = format!(
+                "c.is_merge = 0 AND c.repository_id = ? AND c.timestamp >= ? AND c.timestamp < strftime('{}', ?, '+1 hour')
+                 AND strftime('{}', c.timestamp) IN (SELECT value FROM json_each(?))",
+                HOUR_FORMAT, HOUR_FORMAT
+            );
+            (scope, vec![repository_id.to_string(), first, last, serde_json::to_string(hours)?])
+        }
+    };
+
+    for table in ROLLUP_TABLES {
+        let mut query = format!("DELETE FROM {} WHERE repository_id = ?", table);
+        if hours.is_some() {
+            query.push_str(" AND hour_start IN (SELECT value FROM json_each(?))");
+        }
+        let mut query_builder = sqlx::query(&query).bind(repository_id);
+        if let Some(hours) = hours {
+            query_builder = query_builder.bind(serde_json::to_string(hours)?);
+        }
+        query_builder.execute(&mut *conn).await?;
+    }
+
+    let commit_query = format!(
+        r#"
+        INSERT INTO commit_rollups
+        (repository_id, author, orphaned, hour_start, local_hour, local_weekday,
+         commits, additions, deletions, small_commits, medium_commits, large_commits, huge_commits)
+        SELECT repository_id, author, orphaned, hour_start, local_hour, local_weekday,
+         COUNT(*), SUM(additions), SUM(deletions),
+         SUM(small_commits), SUM(medium_commits), SUM(large_commits), SUM(huge_commits)
+        FROM (SELECT c.repository_id, c.author, c.orphaned_at IS NOT NULL AS orphaned,
+                strftime('{}', c.timestamp) AS hour_start, {},
+                c.additions, c.deletions, {}
+              FROM commits c WHERE {})
+        GROUP BY repository_id, author, orphaned, hour_start, local_hour, local_weekday
+        "#,
+        HOUR_FORMAT,
+        local_time_columns("c."),
+        size_columns("c."),
+        scope
+    );
+    bind_all(sqlx::query(&commit_query), &params).execute(&mut *conn).await?;
+
+    let file_query = format!(
+        r#"
+        INSERT INTO file_rollups
+        (repository_id, author, orphaned, hour_start, file_path, changes, additions, deletions, last_modified)
+        SELECT c.repository_id, c.author, c.orphaned_at IS NOT NULL, strftime('{}', c.timestamp), fc.file_path,
+         COUNT(*), SUM(fc.additions), SUM(fc.deletions), MAX(c.timestamp)
+        FROM file_changes fc
+        JOIN commits c ON fc.commit_id = c.id AND fc.repository_id = c.repository_id
+        WHERE {}
+        GROUP BY 1, 2, 3, 4, 5
+        "#,
+        HOUR_FORMAT,
+        scope
+    );
+    bind_all(sqlx::query(&file_query), &params).execute(&mut *conn).await?;
+
+    // Words come from the same tokenizer the raw statistics use
+    let message_query = format!(
+        "SELECT c.author, c.orphaned_at IS NOT NULL AS orphaned, strftime('{}', c.timestamp) AS hour_start, c.message
+         FROM commits c WHERE {}",
+        HOUR_FORMAT,
+        scope
+    );
+    let rows = bind_all(sqlx::query(&message_query), &params).fetch_all(&mut *conn).await?;
+
+    let mut word_counts: HashMap<(String, bool, String, String), i32> = HashMap::new();
+    for row in rows {
+        let author: String = row.get("author");
+        let orphaned: bool = row.get("orphaned");
+        let hour_start: String = row.get("hour_start");
+        for word in crate::database::message_words(row.get("message")) {
+            *word_counts.entry((author.clone(), orphaned, hour_start.clone(), word)).or_insert(0) += 1;
+        }
+    }
+
+    let word_rows: Vec<_> = word_counts.into_iter().collect();
+    for chunk in word_rows.chunks(WORD_ROWS_PER_STATEMENT) {
+        let values = vec!["(?, ?, ?, ?, ?, ?)"; chunk.len()].join(", ");
+        let query = format!(
+            "INSERT INTO word_rollups (repository_id, author, orphaned, hour_start, word, count) VALUES {}",
+            values
+        );
+        let mut query_builder = sqlx::query(&query);
+        for ((author, orphaned, hour_start, word), count) in chunk {
+            query_builder = query_builder
+                .bind(repository_id)
+                .bind(author)
+                .bind(orphaned)
+                .bind(hour_start)
+                .bind(word)
+                .bind(count);
+        }
+        query_builder.execute(&mut *conn).await?;
+    }
+
+    Ok(())
+}
+
+/// UTC hours of the given stored commits, to refresh after changing them.
+pub async fn commit_hours(conn: &mut SqliteConnection, repository_id: i64, commit_ids: &[String]) -> Result<Vec<String>> {
+    if commit_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let hours = sqlx::query_scalar::<_, String>(&format!(
+        "SELECT DISTINCT strftime('{}', timestamp) FROM commits
+         WHERE repository_id = ? AND id IN (SELECT value FROM json_each(?))",
+        HOUR_FORMAT
+    ))
+    .bind(repository_id)
+    .bind(serde_json::to_string(commit_ids)?)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(hours)
+}
+
+/// Groups the UTC hours of freshly written commits by repository.
+pub fn batch_hours<'a>(commits: impl Iterator<Item = &'a crate::models::Commit>) -> HashMap<i64, Vec<String>> {
+    let mut hours: HashMap<i64, BTreeSet<String>> = HashMap::new();
+    for commit in commits {
+        hours
+            .entry(commit.repository_id)
+            .or_default()
+            .insert(commit.timestamp.format(HOUR_FORMAT).to_string());
+    }
+    hours.into_iter().map(|(id, hours)| (id, hours.into_iter().collect())).collect()
+}
+
+/// Removes the rollups of orphaned commits, after they were purged.
+pub async fn purge_orphaned_rollups(conn: &mut SqliteConnection, repository_id: Option<i64>) -> Result<()> {
+    for table in ROLLUP_TABLES {
+        let mut query = format!("DELETE FROM {} WHERE orphaned = 1", table);
+        if repository_id.is_some() {
+            query.push_str(" AND repository_id = ?");
+        }
+        let mut query_builder = sqlx::query(&query);
+        if let Some(repository_id) = repository_id {
+            query_builder = query_builder.bind(repository_id);
+        }
+        query_builder.execute(&mut *conn).await?;
+    }
+    Ok(())
+}
+
+/// Rebuilds every rollup when they were computed under another local time
+/// zone, since local hours and weekdays are stored in them. Also builds
+/// them the first time, right after the migration that adds the tables.
+pub async fn ensure_rollups_current(pool: &SqlitePool) -> Result<()> {
+    let timezone: String = sqlx::query_scalar(
+        "SELECT strftime('%H:%M', '2000-01-01T12:00:00', 'localtime') || ' ' || strftime('%H:%M', '2000-07-01T12:00:00', 'localtime')"
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let built_for: Option<String> = sqlx::query_scalar("SELECT value FROM rollup_state WHERE key = 'timezone'")
+        .fetch_optional(pool)
+        .await?;
+
+    if built_for.as_deref() == Some(timezone.as_str()) {
+        return Ok(());
+    }
+
+    let start_time = std::time::Instant::now();
+    let mut tx = pool.begin().await?;
+
+    let repository_ids: Vec<i64> = sqlx::query_scalar("SELECT id FROM repositories")
+        .fetch_all(&mut *tx)
+        .await?;
+    for repository_id in repository_ids {
+        refresh_rollups(&mut tx, repository_id, None).await?;
+    }
+
+    // Persisted statistics were grouped by the old local time as well
+    sqlx::query("DELETE FROM statistics_cache").execute(&mut *tx).await?;
+
+    sqlx::query("INSERT INTO rollup_state (key, value) VALUES ('timezone', ?) ON CONFLICT(key) DO UPDATE SET value = excluded.value")
+        .bind(&timezone)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    println!("📊 重建统计汇总表耗时: {:?}", start_time.elapsed());
+    Ok(())
+}
+
+// Set in rollup_state while a bulk load leaves the rollups and the search
+// index behind, and cleared once both are rebuilt
+const DEFERRED_LOAD_KEY: &str = "deferred_load";
+
+/// Records that a bulk load is about to skip the per-batch rollup and search
+/// index updates, so a load that never finishes them is repaired at the next
+/// startup. Returns whether an earlier load had already left them pending.
+pub async fn begin_deferred_load(pool: &SqlitePool) -> Result<bool> {
+    let pending: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM rollup_state WHERE key = ?)")
+        .bind(DEFERRED_LOAD_KEY)
+        .fetch_one(pool)
+        .await?;
+    sqlx::query("INSERT INTO rollup_state (key, value) VALUES (?, '1') ON CONFLICT(key) DO NOTHING")
+        .bind(DEFERRED_LOAD_KEY)
+        .execute(pool)
+        .await?;
+    Ok(pending)
+}
+
+/// Rebuilds the rollups and the search index of `repository_ids`, or of
+/// every repository when `None`, then clears the bulk load marker.
+pub async fn finish_deferred_load(pool: &SqlitePool, repository_ids: Option<&HashSet<i64>>) -> Result<()> {
+    let repository_ids: Vec<i64> = match repository_ids {
+        Some(ids) => ids.iter().copied().collect(),
+        None => sqlx::query_scalar("SELECT id FROM repositories").fetch_all(pool).await?,
+    };
+
+    let rollup_start = std::time::Instant::now();
+    let mut tx = pool.begin().await?;
+    for repository_id in &repository_ids {
+        refresh_rollups(&mut tx, *repository_id, None).await?;
+    }
+    tx.commit().await?;
+    println!("📦 重建统计汇总耗时: {:?}", rollup_start.elapsed());
+
+    let search_start = std::time::Instant::now();
+    let mut tx = pool.begin().await?;
+    for repository_id in &repository_ids {
+        crate::search::index_commits(&mut tx, *repository_id, None).await?;
+    }
+    sqlx::query("DELETE FROM rollup_state WHERE key = ?")
+        .bind(DEFERRED_LOAD_KEY)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    println!("📦 重建搜索索引耗时: {:?}", search_start.elapsed());
+    Ok(())
+}
+
+/// Finishes the rebuild of a bulk load that was interrupted or failed.
+/// Persisted statistics may have been computed from the incomplete rollups.
+pub async fn recover_deferred_load(pool: &SqlitePool) -> Result<()> {
+    let pending: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM rollup_state WHERE key = ?)")
+        .bind(DEFERRED_LOAD_KEY)
+        .fetch_one(pool)
+        .await?;
+    if !pending {
+        return Ok(());
+    }
+
+    println!("📦 上次批量导入未完成，重建统计汇总和搜索索引");
+    sqlx::query("DELETE FROM statistics_cache").execute(pool).await?;
+    finish_deferred_load(pool, None).await
+}
+
+/// Where the statistics read a filter's commits from: whole UTC hours come
+/// from the rollup tables, the partial hours at the edges of the date range
+/// from the raw commits. Filters the rollups can't answer, such as patch
+/// deduplication or path globs, read everything raw.
+pub struct StatisticsSource {
+    rollup_filter: Option<(String, Vec<String>)>,
+    raw_filters: Vec<TimeFilter>,
+}
+
+impl StatisticsSource {
+    pub fn for_filter(filter: &TimeFilter) -> Self {
+        let raw = StatisticsSource {
+            rollup_filter: None,
+            raw_filters: vec![filter.clone()],
+        };
+        if crate::filters::needs_raw_commits(filter) {
+            return raw;
+        }
+
+        // First and one past the last whole hour inside the range
+        let first_hour = filter.start_date.map(|start| {
+            let hour = truncate_to_hour(start);
+            if hour < start { hour + chrono::Duration::hours(1) } else { hour }
+        });
+        let end_hour = filter.end_date.map(|end| truncate_to_hour(end + chrono::Duration::seconds(1)));
+        if let (Some(first), Some(end)) = (first_hour, end_hour) {
+            if first >= end {
+                return raw;
+            }
+        }
+
+        let mut clause = "orphaned = 0".to_string();
+        let mut params = Vec::new();
+        if filter.include_orphaned {
+            clause = "1=1".to_string();
+        }
+        if let Some(first) = first_hour {
+            clause.push_str(" AND hour_start >= ?");
+            params.push(first.to_rfc3339());
+        }
+        if let Some(end) = end_hour {
+            clause.push_str(" AND hour_start < ?");
+            params.push(end.to_rfc3339());
+        }
+        crate::filters::push_scope_conditions(&mut clause, &mut params, filter, "");
+
+        // Commit timestamps have whole seconds, so the edges end one second
+        // before the first whole hour
+        let mut raw_filters = Vec::new();
+        if let (Some(start), Some(first)) = (filter.start_date, first_hour) {
+            if start < first {
+                raw_filters.push(TimeFilter {
+                    end_date: Some(first - chrono::Duration::seconds(1)),
+                    ..filter.clone()
+                });
+            }
+        }
+        if let (Some(end), Some(end_hour)) = (filter.end_date, end_hour) {
+            if end_hour <= end {
+                raw_filters.push(TimeFilter {
+                    start_date: Some(end_hour),
+                    ..filter.clone()
+                });
+            }
+        }
+
+        StatisticsSource {
+            rollup_filter: Some((clause, params)),
+            raw_filters,
+        }
+    }
+
+    /// A subquery with one row per rollup bucket or raw commit, with the
+    /// columns `repository_id, author, hour_start, local_hour,
+    /// local_weekday, commits, additions, deletions` and the size bucket
+    /// counts `small_commits` to `huge_commits`.
+    pub fn buckets(&self) -> Result<(String, Vec<String>)> {
+        let mut parts = Vec::new();
+        let mut params = Vec::new();
+
+        if let Some((clause, rollup_params)) = &self.rollup_filter {
+            parts.push(format!(
+                "SELECT repository_id, author, hour_start, local_hour, local_weekday, commits, additions, deletions,
+                 small_commits, medium_commits, large_commits, huge_commits
+                 FROM commit_rollups WHERE {}",
+                clause
+            ));
+            params.extend(rollup_params.iter().cloned());
+        }
+
+        for filter in &self.raw_filters {
+            let (base_query, raw_params) = crate::filters::build_commit_query(filter)?;
+            parts.push(format!(
+                "SELECT repository_id, author, strftime('{}', timestamp) AS hour_start, {},
+                 1 AS commits, additions, deletions, {}
+                 {}",
+                HOUR_FORMAT,
+                local_time_columns(""),
+                size_columns(""),
+                base_query
+            ));
+            params.extend(raw_params);
+        }
+
+        Ok((format!("({}) AS buckets", union_all(parts)), params))
+    }
+
+    /// A subquery with one row per file rollup or raw file change, with the
+    /// columns `file_path, changes, additions, deletions, last_modified`.
+    /// With path globs only the matching files are listed.
+    pub fn files(&self) -> Result<(String, Vec<String>)> {
+        let mut parts = Vec::new();
+        let mut params = Vec::new();
+
+        if let Some((clause, rollup_params)) = &self.rollup_filter {
+            parts.push(format!(
+                "SELECT file_path, changes, additions, deletions, last_modified FROM file_rollups WHERE {}",
+                clause
+            ));
+            params.extend(rollup_params.iter().cloned());
+        }
+
+        for filter in &self.raw_filters {
+            let (base_query, raw_params) = crate::filters::build_commit_query(filter)?;
+            let mut part = format!(
+                "SELECT fc.file_path, 1 AS changes, fc.additions, fc.deletions, c.timestamp AS last_modified
+                 FROM file_changes fc
+                 JOIN (SELECT id, repository_id, timestamp {}) c
+                   ON fc.commit_id = c.id AND fc.repository_id = c.repository_id",
+                base_query
+            );
+            params.extend(raw_params);
+            if let Some((path_clause, path_params)) = crate::filters::path_condition(filter, "fc.file_path") {
+                part.push_str(&format!(" WHERE {}", path_clause));
+                params.extend(path_params);
+            }
+            parts.push(part);
+        }
+
+        Ok((format!("({}) AS files", union_all(parts)), params))
+    }
+
+    /// Counts message words over the filter. Words outside the rollups'
+    /// top `limit` can only make the final top `limit` if raw commits add
+    /// to them, so only those are looked up besides the top.
+    pub async fn word_counts(&self, pool: &SqlitePool, limit: usize) -> Result<HashMap<String, i32>> {
+        let mut counts: HashMap<String, i32> = HashMap::new();
+
+        for filter in &self.raw_filters {
+            let (base_query, params) = crate::filters::build_commit_query(filter)?;
+            let query = format!("SELECT message {}", base_query);
+            let rows = bind_all(sqlx::query(&query), &params).fetch_all(pool).await?;
+            for row in rows {
+                for word in crate::database::message_words(row.get("message")) {
+                    *counts.entry(word).or_insert(0) += 1;
+                }
+            }
+        }
+
+        let Some((clause, params)) = &self.rollup_filter else { return Ok(counts) };
+
+        let top_query = format!(
+            "SELECT word, SUM(count) AS count FROM word_rollups WHERE {} GROUP BY word ORDER BY count DESC LIMIT ?",
+            clause
+        );
+        let raw_words: Vec<&String> = counts.keys().collect();
+        let raw_word_query = format!(
+            "SELECT word, SUM(count) AS count FROM word_rollups WHERE {} AND word IN (SELECT value FROM json_each(?)) GROUP BY word",
+            clause
+        );
+
+        let top_rows = bind_all(sqlx::query(&top_query), params).bind(limit as i64).fetch_all(pool).await?;
+        let raw_word_rows = bind_all(sqlx::query(&raw_word_query), params)
+            .bind(serde_json::to_string(&raw_words)?)
+            .fetch_all(pool)
+            .await?;
+
+        let mut rollup_counts: HashMap<String, i32> = HashMap::new();
+        for row in top_rows.into_iter().chain(raw_word_rows) {
+            rollup_counts.insert(row.get("word"), row.get("count"));
+        }
+        for (word, count) in rollup_counts {
+            *counts.entry(word).or_insert(0) += count;
+        }
+
+        Ok(counts)
+    }
+}
+
+fn truncate_to_hour(time: chrono::DateTime<chrono::Utc>) -> chrono::DateTime<chrono::Utc> {
+    use chrono::{DurationRound, Duration};
+    time.duration_trunc(Duration::hours(1)).unwrap_or(time)
+}
+
+// Local hour and weekday of a commit, the way the statistics group them
+fn local_time_columns(prefix: &str) -> String {
+    format!(
+        "CAST(strftime('%H', {0}timestamp, 'localtime') AS INTEGER) AS local_hour,
+         CAST(strftime('%w', {0}timestamp, 'localtime') AS INTEGER) AS local_weekday",
+        prefix
+    )
+}
+
+// Commit size buckets of the size distribution chart, one flag per bucket
+fn size_columns(prefix: &str) -> String {
+    format!(
+        "({0}additions + {0}deletions) <= 10 AS small_commits,
+         ({0}additions + {0}deletions) BETWEEN 11 AND 100 AS medium_commits,
+         ({0}additions + {0}deletions) BETWEEN 101 AND 500 AS large_commits,
+         ({0}additions + {0}deletions) > 500 AS huge_commits",
+        prefix
+    )
+}
+
+fn union_all(parts: Vec<String>) -> String {
+    parts.join("\n UNION ALL\n ")
+}
+
+fn bind_all<'q>(
+    mut query: sqlx::query::Query<'q, sqlx::Sqlite, sqlx::sqlite::SqliteArguments<'q>>,
+    params: &'q [String],
+) -> sqlx::query::Query<'q, sqlx::Sqlite, sqlx::sqlite::SqliteArguments<'q>> {
+    for param in params {
+        query = query.bind(param);
+    }
+    query
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{analyzed, ingest, test_pool};
+    use chrono::{DateTime, Utc};
+
+    // Start of a UTC hour, 2023-11-14 23:00
+    const HOUR: i64 = 1_700_002_800;
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(seconds, 0).unwrap()
+    }
+
+    // Commits at the edges and in the middle of several hours
+    async fn seeded_pool() -> SqlitePool {
+        let pool = test_pool().await;
+        let repository = crate::database::add_repository(&pool, "/tmp/repo").await.unwrap();
+
+        let commits = [
+            (HOUR - 1800, "Alice", "fix parser crash", &[("src/parse.rs", 4, 1)][..]),
+            (HOUR, "Bob", "add parser tests", &[("tests/parse.rs", 30, 0), ("src/parse.rs", 2, 2)][..]),
+            (HOUR + 1200, "Alice", "parser cleanup", &[("src/parse.rs", 5, 9)][..]),
+            (HOUR + 3599, "Bob", "fix docs typo", &[("README.md", 1, 1)][..]),
+            (HOUR + 3600, "Alice", "refactor lexer", &[("src/lex.rs", 40, 12), ("src/parse.rs", 3, 3)][..]),
+            (HOUR + 5400, "Bob", "lexer tests", &[("tests/lex.rs", 25, 0)][..]),
+            (HOUR + 7200, "Alice", "fix lexer crash", &[("src/lex.rs", 2, 1)][..]),
+            (HOUR + 7300, "Bob", "release notes", &[("README.md", 8, 0)][..]),
+            (HOUR + 3 * 3600 + 3000, "Alice", "fix release script", &[("release.sh", 6, 2)][..]),
+        ];
+        let commits = commits
+            .into_iter()
+            .enumerate()
+            .map(|(index, (timestamp, author, message, files))| {
+                let mut commit = analyzed(repository.id, &format!("c{}", index), 0, files);
+                commit.commit.timestamp = at(timestamp);
+                commit.commit.author = author.to_string();
+                commit.commit.message = message.to_string();
+                commit
+            })
+            .collect();
+        ingest(&pool, commits).await;
+        pool
+    }
+
+    // Every rollup-backed series, as JSON so the two paths can be compared
+    async fn statistics(pool: &SqlitePool, filter: &TimeFilter) -> Vec<serde_json::Value> {
+        vec![
+            serde_json::to_value(crate::database::get_hourly_stats(pool, filter).await.unwrap()).unwrap(),
+            serde_json::to_value(crate::database::get_daily_stats(pool, filter).await.unwrap()).unwrap(),
+            serde_json::to_value(crate::database::get_statistics_totals(pool, filter).await.unwrap()).unwrap(),
+            serde_json::to_value(crate::database::get_hot_files(pool, filter).await.unwrap()).unwrap(),
+            serde_json::to_value(crate::database::get_commit_message_words(pool, filter).await.unwrap()).unwrap(),
+        ]
+    }
+
+    // Asserts that the rollups answer the filter like the raw commits do.
+    // Including merges forces the raw path; the fixture has no merges.
+    async fn assert_rollups_match_raw(pool: &SqlitePool, start: Option<i64>, end: Option<i64>) {
+        let filter = TimeFilter {
+            start_date: start.map(at),
+            end_date: end.map(at),
+            ..Default::default()
+        };
+        assert!(!crate::filters::needs_raw_commits(&filter));
+        let raw = TimeFilter { include_merges: true, ..filter.clone() };
+        assert!(crate::filters::needs_raw_commits(&raw));
+
+        let from_rollups = statistics(pool, &filter).await;
+        assert_eq!(from_rollups, statistics(pool, &raw).await, "range {:?} to {:?}", start, end);
+    }
+
+    #[tokio::test]
+    async fn rollups_match_raw_commits_over_any_range() {
+        let pool = seeded_pool().await;
+
+        assert_rollups_match_raw(&pool, None, None).await;
+        // Starting and ending mid-hour
+        assert_rollups_match_raw(&pool, Some(HOUR - 600), Some(HOUR + 3600 + 1800)).await;
+        assert_rollups_match_raw(&pool, Some(HOUR + 600), None).await;
+        assert_rollups_match_raw(&pool, None, Some(HOUR + 7250)).await;
+        // Exactly on hour boundaries, at both ends
+        assert_rollups_match_raw(&pool, Some(HOUR), Some(HOUR + 7200)).await;
+        assert_rollups_match_raw(&pool, Some(HOUR), Some(HOUR + 7199)).await;
+        assert_rollups_match_raw(&pool, Some(HOUR + 3600), Some(HOUR + 3 * 3600)).await;
+        // Start and end within the same hour
+        assert_rollups_match_raw(&pool, Some(HOUR + 60), Some(HOUR + 3000)).await;
+        assert_rollups_match_raw(&pool, Some(HOUR), Some(HOUR + 3599)).await;
+        assert_rollups_match_raw(&pool, Some(HOUR + 1200), Some(HOUR + 1200)).await;
+    }
+
+    #[tokio::test]
+    async fn word_counts_add_raw_edges_to_the_rollup_top() {
+        let pool = seeded_pool().await;
+        let filter = TimeFilter {
+            start_date: Some(at(HOUR + 600)),
+            end_date: Some(at(HOUR + 7250)),
+            ..Default::default()
+        };
+
+        // The partial hours at both ends are raw, the hour between them is
+        // rolled up and only contributes its top word and the raw words
+        let counts = StatisticsSource::for_filter(&filter).word_counts(&pool, 1).await.unwrap();
+        assert_eq!(counts.get("fix"), Some(&2));
+        assert_eq!(counts.get("lexer"), Some(&3));
+        assert_eq!(counts.get("crash"), Some(&1));
+        assert_eq!(counts.get("refactor"), None);
+        assert_eq!(counts.get("tests"), None);
+    }
+
+    #[tokio::test]
+    async fn startup_finishes_an_interrupted_bulk_load() {
+        let pool = seeded_pool().await;
+        let expected = statistics(&pool, &TimeFilter::default()).await;
+
+        // A bulk load that stopped before rebuilding the rollups
+        assert!(!begin_deferred_load(&pool).await.unwrap());
+        for table in ["commit_rollups", "file_rollups", "word_rollups"] {
+            sqlx::query(&format!("DELETE FROM {}", table)).execute(&pool).await.unwrap();
+        }
+        assert!(begin_deferred_load(&pool).await.unwrap());
+        assert_ne!(statistics(&pool, &TimeFilter::default()).await, expected);
+
+        recover_deferred_load(&pool).await.unwrap();
+        assert_eq!(statistics(&pool, &TimeFilter::default()).await, expected);
+        let pending: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM rollup_state WHERE key = ?)")
+            .bind(DEFERRED_LOAD_KEY)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert!(!pending);
+    }
+}
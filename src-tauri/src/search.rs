@@ -0,0 +1,542 @@
+use sqlx::{SqliteConnection, SqlitePool, FromRow};
+use crate::models::{Commit, SearchHit, SearchPage, TimeFilter};
+use anyhow::Result;
+use std::collections::HashMap;
+
+/// Search results per page unless the caller asks otherwise.
+pub const DEFAULT_SEARCH_PAGE_SIZE: u32 = 50;
+
+// Upper bound of a search page, larger requests are clamped
+const MAX_SEARCH_PAGE_SIZE: u32 = 500;
+
+/// Wraps every match in the snippets of a `SearchHit`. Control characters
+/// can't occur in the indexed text, so the frontend can split on them
+/// without escaping anything.
+pub const HIGHLIGHT_START: char = '\u{2}';
+pub const HIGHLIGHT_END: char = '\u{3}';
+
+// Shortest term the trigram index can look up, shorter ones are matched with LIKE
+const MIN_INDEXED_TERM_CHARS: usize = 3;
+
+// bm25 weights of the message, author and files columns
+const RANK_WEIGHTS: &str = "10.0, 5.0, 1.0";
+
+// Characters of the message snippet, and of context kept before the first match
+const SNIPPET_CHARS: usize = 160;
+const SNIPPET_CONTEXT_CHARS: usize = 40;
+
+// Matching files listed per hit
+const MAX_MATCHED_FILES: usize = 5;
+
+/// Rewrites the search rows of a repository's commits from the stored
+/// commits and file changes, either for the given commits or, with `None`,
+/// for all of them. Runs next to `rollups::refresh_rollups` wherever
+/// commits are written.
+pub async fn index_commits(conn: &mut SqliteConnection, repository_id: i64, commit_ids: Option<&[String]>) -> Result<()> {
+    let (row_scope, commit_scope, params) = match commit_ids {
+        None => ("r.repository_id = ?", "c.repository_id = ?", vec![repository_id.to_string()]),
+        Some([]) => return Ok(()),
+        Some(commit_ids) => (
+            "r.repository_id = ? AND r.commit_id IN (SELECT value FROM json_each(?))",
+            "c.repository_id = ? AND c.id IN (SELECT value FROM json_each(?))",
+            vec![repository_id.to_string(), serde_json::to_string(commit_ids)?],
+        ),
+    };
+
+    let statements = [
+        format!("DELETE FROM commit_search WHERE rowid IN (SELECT r.id FROM commit_search_rows r WHERE {})", row_scope),
+        format!(
+            "INSERT OR IGNORE INTO commit_search_rows (repository_id, commit_id)
+             SELECT c.repository_id, c.id FROM commits c WHERE {}",
+            commit_scope
+        ),
+        format!(
+            r#"
+            INSERT INTO commit_search (rowid, message, author, files)
+            SELECT r.id, c.message, c.author || ' <' || c.email || '>',
+             COALESCE((SELECT group_concat(fc.file_path, char(10)) FROM file_changes fc
+                       WHERE fc.commit_id = c.id AND fc.repository_id = c.repository_id), '')
+            FROM commits c
+            JOIN commit_search_rows r ON r.repository_id = c.repository_id AND r.commit_id = c.id
+            WHERE {}
+            "#,
+            commit_scope
+        ),
+    ];
+
+    for statement in &statements {
+        let mut query_builder = sqlx::query(statement);
+        for param in &params {
+            query_builder = query_builder.bind(param);
+        }
+        query_builder.execute(&mut *conn).await?;
+    }
+    Ok(())
+}
+
+/// Groups freshly written commits by repository, to index them per batch.
+pub fn batch_commit_ids<'a>(commits: impl Iterator<Item = &'a Commit>) -> HashMap<i64, Vec<String>> {
+    let mut commit_ids: HashMap<i64, Vec<String>> = HashMap::new();
+    for commit in commits {
+        commit_ids.entry(commit.repository_id).or_default().push(commit.id.clone());
+    }
+    commit_ids
+}
+
+/// Removes the search rows of the given commits, before they are deleted.
+pub async fn remove_commits(conn: &mut SqliteConnection, repository_id: i64, commit_ids: &[String]) -> Result<()> {
+    let commit_ids = serde_json::to_string(commit_ids)?;
+    let scope = "repository_id = ? AND commit_id IN (SELECT value FROM json_each(?))";
+
+    for statement in [
+        format!("DELETE FROM commit_search WHERE rowid IN (SELECT id FROM commit_search_rows WHERE {})", scope),
+        format!("DELETE FROM commit_search_rows WHERE {}", scope),
+    ] {
+        sqlx::query(&statement)
+            .bind(repository_id)
+            .bind(&commit_ids)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Removes the search rows whose commits are gone, after commits were
+/// purged or a repository was removed.
+pub async fn purge_search_rows(conn: &mut SqliteConnection, repository_id: Option<i64>) -> Result<()> {
+    let mut stale = "NOT EXISTS (SELECT 1 FROM commits c
+                     WHERE c.repository_id = commit_search_rows.repository_id AND c.id = commit_search_rows.commit_id)"
+        .to_string();
+    if repository_id.is_some() {
+        stale.push_str(" AND commit_search_rows.repository_id = ?");
+    }
+
+    for statement in [
+        format!("DELETE FROM commit_search WHERE rowid IN (SELECT id FROM commit_search_rows WHERE {})", stale),
+        format!("DELETE FROM commit_search_rows WHERE {}", stale),
+    ] {
+        let mut query_builder = sqlx::query(&statement);
+        if let Some(repository_id) = repository_id {
+            query_builder = query_builder.bind(repository_id);
+        }
+        query_builder.execute(&mut *conn).await?;
+    }
+    Ok(())
+}
+
+/// Builds the search index of the commits stored before it existed, right
+/// after the migration that adds it.
+pub async fn ensure_search_index(pool: &SqlitePool) -> Result<()> {
+    let missing: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM commits) AND NOT EXISTS (SELECT 1 FROM commit_search_rows)"
+    )
+    .fetch_one(pool)
+    .await?;
+
+    if !missing {
+        return Ok(());
+    }
+
+    let start_time = std::time::Instant::now();
+    let mut tx = pool.begin().await?;
+
+    let repository_ids: Vec<i64> = sqlx::query_scalar("SELECT id FROM repositories")
+        .fetch_all(&mut *tx)
+        .await?;
+    for repository_id in repository_ids {
+        index_commits(&mut tx, repository_id, None).await?;
+    }
+
+    tx.commit().await?;
+    println!("🔍 建立提交搜索索引耗时: {:?}", start_time.elapsed());
+    Ok(())
+}
+
+#[derive(FromRow)]
+struct SearchRow {
+    #[sqlx(flatten)]
+    commit: Commit,
+    score: f64,
+    indexed_author: String,
+    indexed_files: String,
+}
+
+/// Finds the commits whose message, author name or email, or changed file
+/// paths contain every term of `query`. Terms are separated by spaces,
+/// "double quotes" keep a phrase together, and matching ignores case.
+/// Results are ranked by bm25 with the message weighing most, restricted
+/// to the commits `filter` selects, and paged with `next_cursor`.
+///
+/// Snippets are cut here rather than with FTS5's `snippet()`, which only
+/// sees the terms the index looked up and so would miss short ones.
+pub async fn search_commits(
+    pool: &SqlitePool,
+    query: &str,
+    filter: &TimeFilter,
+    cursor: Option<&str>,
+    page_size: u32,
+) -> Result<SearchPage> {
+    let start_time = std::time::Instant::now();
+    let page_size = page_size.clamp(1, MAX_SEARCH_PAGE_SIZE);
+    let offset: i64 = match cursor {
+        Some(cursor) => cursor.parse().map_err(|_| anyhow::anyhow!("Invalid search cursor: {}", cursor))?,
+        None => 0,
+    };
+
+    let terms = parse_terms(query);
+    if terms.is_empty() {
+        return Err(anyhow::anyhow!("搜索内容不能为空"));
+    }
+
+    let (base_query, mut params) = crate::filters::build_commit_query(filter)?;
+    let mut conditions = Vec::new();
+
+    // Terms long enough for the trigram index go into one MATCH, the others
+    // fall back to scanning the indexed text
+    let indexed: Vec<String> = terms
+        .iter()
+        .filter(|term| term.chars().count() >= MIN_INDEXED_TERM_CHARS)
+        .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+        .collect();
+    let ranked = !indexed.is_empty();
+    if ranked {
+        conditions.push("commit_search MATCH ?".to_string());
+        params.push(indexed.join(" AND "));
+    }
+
+    for term in terms.iter().filter(|term| term.chars().count() < MIN_INDEXED_TERM_CHARS) {
+        conditions.push(
+            "(commit_search.message LIKE ? ESCAPE '\\' OR commit_search.author LIKE ? ESCAPE '\\'
+              OR commit_search.files LIKE ? ESCAPE '\\')"
+                .to_string(),
+        );
+        let pattern = format!("%{}%", escape_like(term));
+        params.extend([pattern.clone(), pattern.clone(), pattern]);
+    }
+
+    let from = format!(
+        "FROM commit_search
+         JOIN commit_search_rows r ON r.id = commit_search.rowid
+         JOIN (SELECT * {}) AS commits ON commits.repository_id = r.repository_id AND commits.id = r.commit_id
+         WHERE {}",
+        base_query,
+        conditions.join(" AND ")
+    );
+
+    let count_query = format!("SELECT COUNT(*) {}", from);
+    let mut count_builder = sqlx::query_scalar::<_, i64>(&count_query);
+    for param in &params {
+        count_builder = count_builder.bind(param);
+    }
+    let total_count = count_builder.fetch_one(pool).await?;
+
+    // bm25 is lower for better matches, so its negation is the score;
+    // without a MATCH all scores are 0 and the newest commits come first
+    let score = if ranked {
+        format!("-bm25(commit_search, {})", RANK_WEIGHTS)
+    } else {
+        "0.0".to_string()
+    };
+    let select = format!(
+        "SELECT commits.*, {} AS score, commit_search.author AS indexed_author, commit_search.files AS indexed_files
+         {} ORDER BY score DESC, commits.timestamp DESC, commits.repository_id, commits.id LIMIT ? OFFSET ?",
+        score, from
+    );
+
+    // One extra row tells whether another page follows
+    let mut query_builder = sqlx::query_as::<_, SearchRow>(&select);
+    for param in &params {
+        query_builder = query_builder.bind(param);
+    }
+    let mut rows = query_builder
+        .bind(page_size as i64 + 1)
+        .bind(offset)
+        .fetch_all(pool)
+        .await?;
+
+    let next_cursor = if rows.len() > page_size as usize {
+        rows.truncate(page_size as usize);
+        Some((offset + page_size as i64).to_string())
+    } else {
+        None
+    };
+
+    let matcher = term_matcher(&terms)?;
+    let mut commits: Vec<Commit> = Vec::with_capacity(rows.len());
+    let mut hits: Vec<SearchHit> = Vec::with_capacity(rows.len());
+    for row in rows {
+        let files: Vec<&str> = row.indexed_files.lines().filter(|path| matcher.is_match(path)).collect();
+        hits.push(SearchHit {
+            commit: row.commit.clone(),
+            score: row.score,
+            message_snippet: snippet(&row.commit.message, &matcher),
+            author_snippet: highlight(&row.indexed_author, &matcher),
+            matched_files: files.iter().take(MAX_MATCHED_FILES).map(|path| highlight(path, &matcher)).collect(),
+            matched_file_count: files.len() as i64,
+        });
+        commits.push(row.commit);
+    }
+
+    crate::database::resolve_remote_urls(pool, &mut commits).await?;
+    for (hit, commit) in hits.iter_mut().zip(commits) {
+        hit.commit = commit;
+    }
+
+    println!("🔍 搜索 \"{}\": {} 个结果，耗时 {:?}", query, total_count, start_time.elapsed());
+
+    Ok(SearchPage {
+        hits,
+        next_cursor,
+        total_count,
+        page_size,
+    })
+}
+
+// Splits a search query into terms, keeping "quoted phrases" together
+fn parse_terms(query: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    for (index, part) in query.split('"').enumerate() {
+        if index % 2 == 1 {
+            terms.push(part.trim().to_string());
+        } else {
+            terms.extend(part.split_whitespace().map(|term| term.to_string()));
+        }
+    }
+    terms.retain(|term| !term.is_empty());
+    terms
+}
+
+fn escape_like(term: &str) -> String {
+    term.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+// Case-insensitive regex matching any of the terms, longest first so a
+// term containing another one is highlighted whole
+fn term_matcher(terms: &[String]) -> Result<regex::Regex> {
+    let mut terms: Vec<&String> = terms.iter().collect();
+    terms.sort_by_key(|term| std::cmp::Reverse(term.len()));
+    let alternatives: Vec<String> = terms.iter().map(|term| regex::escape(term)).collect();
+    Ok(regex::Regex::new(&format!("(?i){}", alternatives.join("|")))?)
+}
+
+fn highlight(text: &str, matcher: &regex::Regex) -> String {
+    let mut result = String::with_capacity(text.len() + 8);
+    let mut last = 0;
+    for found in matcher.find_iter(text) {
+        result.push_str(&text[last..found.start()]);
+        result.push(HIGHLIGHT_START);
+        result.push_str(found.as_str());
+        result.push(HIGHLIGHT_END);
+        last = found.end();
+    }
+    result.push_str(&text[last..]);
+    result
+}
+
+// The part of a long message around its first match, highlighted
+fn snippet(message: &str, matcher: &regex::Regex) -> String {
+    if message.chars().count() <= SNIPPET_CHARS {
+        return highlight(message, matcher);
+    }
+
+    let first_match = matcher.find(message).map(|found| found.start()).unwrap_or(0);
+    let match_char = message[..first_match].chars().count();
+    let start_char = match_char.saturating_sub(SNIPPET_CONTEXT_CHARS);
+
+    let mut chars = message.char_indices().skip(start_char);
+    let start = chars.next().map(|(index, _)| index).unwrap_or(0);
+    let end = chars.nth(SNIPPET_CHARS - 1).map(|(index, _)| index).unwrap_or(message.len());
+
+    let mut result = String::new();
+    if start > 0 {
+        result.push('…');
+    }
+    result.push_str(&highlight(&message[start..end], matcher));
+    if end < message.len() {
+        result.push('…');
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{analyzed, ingest, test_pool};
+
+    // (id, author, message, files) of a stored commit
+    type SeedCommit<'a> = (&'a str, &'a str, &'a str, &'a [(&'a str, i32, i32)]);
+
+    // Stores the commits in a new repository, a minute apart
+    async fn seed(pool: &SqlitePool, path: &str, commits: &[SeedCommit<'_>]) -> i64 {
+        let repository = crate::database::add_repository(pool, path).await.unwrap();
+        let commits = commits
+            .iter()
+            .enumerate()
+            .map(|(minute, (id, author, message, files))| {
+                let mut commit = analyzed(repository.id, id, minute as i64, files);
+                commit.commit.author = author.to_string();
+                commit.commit.message = message.to_string();
+                commit
+            })
+            .collect();
+        ingest(pool, commits).await;
+        repository.id
+    }
+
+    async fn search(pool: &SqlitePool, query: &str) -> Vec<String> {
+        let page = search_commits(pool, query, &TimeFilter::default(), None, DEFAULT_SEARCH_PAGE_SIZE).await.unwrap();
+        page.hits.into_iter().map(|hit| hit.commit.id).collect()
+    }
+
+    // Rows of the FTS table and of its bookkeeping table
+    async fn search_rows(pool: &SqlitePool) -> (i64, i64) {
+        let count = |table: &str| format!("SELECT COUNT(*) FROM {}", table);
+        let indexed = sqlx::query_scalar(&count("commit_search")).fetch_one(pool).await.unwrap();
+        let rows = sqlx::query_scalar(&count("commit_search_rows")).fetch_one(pool).await.unwrap();
+        (indexed, rows)
+    }
+
+    fn matcher(terms: &[&str]) -> regex::Regex {
+        term_matcher(&terms.iter().map(|term| term.to_string()).collect::<Vec<_>>()).unwrap()
+    }
+
+    fn snippet_of(message: &str, term: &str) -> String {
+        snippet(message, &matcher(&[term]))
+    }
+
+    #[test]
+    fn terms_keep_quoted_phrases_together() {
+        assert_eq!(parse_terms(r#"fix  "parser crash"  lexer"#), vec!["fix", "parser crash", "lexer"]);
+        // Blank phrases are dropped and an unclosed quote runs to the end
+        assert_eq!(parse_terms(r#""  " a "unclosed phrase "#), vec!["a", "unclosed phrase"]);
+        assert_eq!(parse_terms(r#"say"hi there"now"#), vec!["say", "hi there", "now"]);
+        assert!(parse_terms("  \t ").is_empty());
+
+        assert_eq!(escape_like(r"100%_a\b"), r"100\%\_a\\b");
+        assert_eq!(escape_like("plain"), "plain");
+    }
+
+    #[test]
+    fn snippets_cut_long_messages_on_char_boundaries() {
+        let strip = |snippet: &str| snippet.chars().filter(|c| !matches!(*c, '…' | HIGHLIGHT_START | HIGHLIGHT_END)).collect::<String>();
+
+        // Multi-byte text around a match in the middle
+        let message = format!("{}解析器{}", "修复".repeat(100), "崩溃".repeat(100));
+        let snippet = snippet(&message, &matcher(&["解析器"]));
+        assert!(snippet.starts_with('…') && snippet.ends_with('…'));
+        assert!(snippet.contains("\u{2}解析器\u{3}"));
+        assert_eq!(strip(&snippet).chars().count(), SNIPPET_CHARS);
+        let before_match = snippet.trim_start_matches('…').split(HIGHLIGHT_START).next().unwrap();
+        assert_eq!(before_match.chars().count(), SNIPPET_CONTEXT_CHARS);
+
+        // A match near the start keeps the start, one near the end the end
+        let message = format!("解析器{}", "崩溃".repeat(100));
+        let snippet = snippet_of(&message, "解析器");
+        assert!(snippet.starts_with('\u{2}') && snippet.ends_with('…'));
+        let message = format!("{}解析器", "崩溃".repeat(100));
+        let snippet = snippet_of(&message, "解析器");
+        assert!(snippet.starts_with('…') && snippet.ends_with('\u{3}'));
+        assert_eq!(strip(&snippet).chars().count(), SNIPPET_CONTEXT_CHARS + 3);
+
+        // Short messages are kept whole, matched without regard to case
+        assert_eq!(snippet_of("Fix the FIX", "fix"), "\u{2}Fix\u{3} the \u{2}FIX\u{3}");
+        let unmatched = "修".repeat(SNIPPET_CHARS + 1);
+        assert_eq!(strip(&snippet_of(&unmatched, "x")).chars().count(), SNIPPET_CHARS);
+    }
+
+    #[test]
+    fn longer_terms_are_highlighted_whole() {
+        assert_eq!(highlight("parser parse", &matcher(&["parse", "parser"])), "\u{2}parser\u{3} \u{2}parse\u{3}");
+        assert_eq!(highlight("a.b axb", &matcher(&["a.b"])), "\u{2}a.b\u{3} axb");
+    }
+
+    #[tokio::test]
+    async fn message_matches_rank_above_author_and_file_matches() {
+        let pool = test_pool().await;
+        seed(&pool, "/tmp/repo", &[
+            ("message", "Alice", "parser: fix crash", &[("src/lib.rs", 1, 0)]),
+            ("files", "Alice", "cleanup", &[("src/parser.rs", 1, 0), ("docs/parser.md", 1, 0), ("README", 1, 0)]),
+            ("author", "Parser Bot", "bump versions", &[("Cargo.toml", 1, 0)]),
+            ("none", "Alice", "unrelated", &[("src/main.rs", 1, 0)]),
+        ])
+        .await;
+
+        assert_eq!(search(&pool, "parser").await, vec!["message", "author", "files"]);
+        assert_eq!(search(&pool, "PARSER crash").await, vec!["message"]);
+        assert!(search(&pool, "parser nothing").await.is_empty());
+
+        let page = search_commits(&pool, "parser", &TimeFilter::default(), None, 1).await.unwrap();
+        assert_eq!(page.total_count, 3);
+        assert_eq!(page.next_cursor.as_deref(), Some("1"));
+        assert!(page.hits[0].score > 0.0);
+        assert_eq!(page.hits[0].message_snippet, "\u{2}parser\u{3}: fix crash");
+        let page = search_commits(&pool, "parser", &TimeFilter::default(), Some("2"), 1).await.unwrap();
+        assert_eq!(page.hits[0].commit.id, "files");
+        let mut matched_files = page.hits[0].matched_files.clone();
+        matched_files.sort();
+        assert_eq!(matched_files, vec!["docs/\u{2}parser\u{3}.md", "src/\u{2}parser\u{3}.rs"]);
+        assert_eq!(page.hits[0].matched_file_count, 2);
+        assert_eq!(page.next_cursor, None);
+
+        assert!(search_commits(&pool, " \"\" ", &TimeFilter::default(), None, 10).await.is_err());
+        assert!(search_commits(&pool, "parser", &TimeFilter::default(), Some("x"), 10).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn short_terms_fall_back_to_like() {
+        let pool = test_pool().await;
+        seed(&pool, "/tmp/repo", &[
+            ("schema", "Alice", "tweak", &[("db/schema.sql", 1, 0)]),
+            ("percent", "Alice", "use 5% less memory", &[("src/lib.rs", 1, 0)]),
+            ("later", "Alice", "db cleanup", &[("src/db.rs", 1, 0)]),
+        ])
+        .await;
+
+        // Without a MATCH the newest commits come first, unscored
+        assert_eq!(search(&pool, "db").await, vec!["later", "schema"]);
+        let page = search_commits(&pool, "db", &TimeFilter::default(), None, 10).await.unwrap();
+        assert!(page.hits.iter().all(|hit| hit.score == 0.0));
+        assert_eq!(page.hits[1].matched_files, vec!["\u{2}db\u{3}/schema.sql"]);
+
+        // Short and indexed terms combine, and LIKE wildcards are literal
+        assert_eq!(search(&pool, "db schema").await, vec!["schema"]);
+        assert!(search(&pool, "d_").await.is_empty());
+        assert_eq!(search(&pool, "5%").await, vec!["percent"]);
+        assert_eq!(search(&pool, "%").await, vec!["percent"]);
+        assert!(search(&pool, "_").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn search_rows_follow_deleted_purged_and_removed_commits() {
+        let pool = test_pool().await;
+        let first = seed(&pool, "/tmp/first", &[
+            ("a1", "Alice", "alpha change", &[("a.rs", 1, 0)]),
+            ("a2", "Alice", "beta change", &[("b.rs", 1, 0)]),
+        ])
+        .await;
+        let second = seed(&pool, "/tmp/second", &[("b1", "Bob", "gamma change", &[("c.rs", 1, 0)])]).await;
+        assert_eq!(search_rows(&pool).await, (3, 3));
+
+        // Reindexing replaces rows instead of adding to them
+        let mut conn = pool.acquire().await.unwrap();
+        index_commits(&mut conn, first, Some(&["a1".to_string()])).await.unwrap();
+        index_commits(&mut conn, first, None).await.unwrap();
+        index_commits(&mut conn, first, Some(&[])).await.unwrap();
+        drop(conn);
+        assert_eq!(search_rows(&pool).await, (3, 3));
+        assert_eq!(search(&pool, "change").await.len(), 3);
+
+        crate::database::delete_commit(&pool, first, "a1").await.unwrap();
+        assert_eq!(search_rows(&pool).await, (2, 2));
+        assert!(search(&pool, "alpha").await.is_empty());
+
+        crate::database::reconcile_commits(&pool, first, &Default::default()).await.unwrap();
+        crate::database::purge_orphaned_commits(&pool, Some(first)).await.unwrap();
+        assert_eq!(search_rows(&pool).await, (1, 1));
+        assert!(search(&pool, "beta").await.is_empty());
+
+        crate::database::remove_repository(&pool, second).await.unwrap();
+        assert_eq!(search_rows(&pool).await, (0, 0));
+        assert!(search(&pool, "change").await.is_empty());
+    }
+}
@@ -0,0 +1,307 @@
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::{sqlite::SqliteRow, Row, SqlitePool};
+use crate::git_backend::GitBackendKind;
+use crate::models::{FilterPreset, TimeFilter};
+use anyhow::Result;
+use std::collections::HashMap;
+
+/// Backend used by scans, a `GitBackendKind`.
+pub const GIT_BACKEND: &str = "git_backend";
+/// Whether statistics results are also kept on disk, a bool.
+pub const STATISTICS_CACHE_PERSISTENT: &str = "statistics_cache_persistent";
+/// The filter the app showed last, restored at startup, a `TimeFilter`.
+pub const LAST_FILTER: &str = "last_filter";
+/// The text of the filter query bar, restored at startup, a string.
+pub const FILTER_QUERY: &str = "filter_query";
+
+/// Checks that a value stored under one of the keys above has the type the
+/// key expects. Other keys are free-form preferences of the frontend.
+pub fn check_value(key: &str, value: &serde_json::Value) -> Result<()> {
+    match key {
+        GIT_BACKEND => {
+            serde_json::from_value::<GitBackendKind>(value.clone())?;
+        }
+        STATISTICS_CACHE_PERSISTENT => {
+            serde_json::from_value::<bool>(value.clone())?;
+        }
+        LAST_FILTER => {
+            serde_json::from_value::<TimeFilter>(value.clone())?;
+        }
+        FILTER_QUERY => {
+            serde_json::from_value::<String>(value.clone())?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Whether changing the setting can change statistics results. The keys
+/// above only choose how results are read, cached or shown; any other
+/// setting, such as an author identity or an excluded path, may feed the
+/// statistics.
+pub fn affects_statistics(key: &str) -> bool {
+    !matches!(key, GIT_BACKEND | STATISTICS_CACHE_PERSISTENT | LAST_FILTER | FILTER_QUERY)
+}
+
+/// Every stored setting by key.
+pub async fn get_settings(pool: &SqlitePool) -> Result<HashMap<String, serde_json::Value>> {
+    let rows = sqlx::query("SELECT key, value FROM settings")
+        .fetch_all(pool)
+        .await?;
+
+    let mut settings = HashMap::new();
+    for row in rows {
+        let value: String = row.get("value");
+        settings.insert(row.get("key"), serde_json::from_str(&value)?);
+    }
+    Ok(settings)
+}
+
+/// The setting under `key`, or `None` when it was never set. A stored value
+/// of another type, as written by a different build, also reads as unset.
+pub async fn get_setting<T: DeserializeOwned>(pool: &SqlitePool, key: &str) -> Result<Option<T>> {
+    let value: Option<String> = sqlx::query_scalar("SELECT value FROM settings WHERE key = ?")
+        .bind(key)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(value.and_then(|value| match serde_json::from_str(&value) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            eprintln!("忽略无法读取的设置 {}: {}", key, e);
+            None
+        }
+    }))
+}
+
+/// Stores the setting and returns it as the JSON value it was saved as.
+pub async fn set_setting<T: Serialize>(pool: &SqlitePool, key: &str, value: &T) -> Result<serde_json::Value> {
+    let value = serde_json::to_value(value)?;
+    check_value(key, &value)?;
+
+    sqlx::query(
+        "INSERT INTO settings (key, value, updated_at) VALUES (?, ?, ?)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at"
+    )
+    .bind(key)
+    .bind(value.to_string())
+    .bind(chrono::Utc::now())
+    .execute(pool)
+    .await?;
+
+    Ok(value)
+}
+
+/// Resets the setting to its default. Returns false when it wasn't set.
+pub async fn delete_setting(pool: &SqlitePool, key: &str) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM settings WHERE key = ?")
+        .bind(key)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn git_backend(pool: &SqlitePool) -> Result<GitBackendKind> {
+    Ok(get_setting(pool, GIT_BACKEND).await?.unwrap_or_default())
+}
+
+pub async fn statistics_cache_persistent(pool: &SqlitePool) -> Result<bool> {
+    Ok(get_setting(pool, STATISTICS_CACHE_PERSISTENT).await?.unwrap_or(false))
+}
+
+pub async fn last_filter(pool: &SqlitePool) -> Result<Option<TimeFilter>> {
+    get_setting(pool, LAST_FILTER).await
+}
+
+fn filter_preset_from_row(row: &SqliteRow) -> Result<FilterPreset> {
+    let filter: String = row.get("filter");
+    Ok(FilterPreset {
+        id: row.get("id"),
+        name: row.get("name"),
+        filter: serde_json::from_str(&filter)?,
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    })
+}
+
+fn preset_name(name: &str) -> Result<&str> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(anyhow::anyhow!("预设名称不能为空"));
+    }
+    Ok(name)
+}
+
+/// Saved filters, by name.
+pub async fn get_filter_presets(pool: &SqlitePool) -> Result<Vec<FilterPreset>> {
+    let rows = sqlx::query("SELECT * FROM filter_presets ORDER BY name")
+        .fetch_all(pool)
+        .await?;
+
+    rows.iter().map(filter_preset_from_row).collect()
+}
+
+pub async fn create_filter_preset(pool: &SqlitePool, name: &str, filter: &TimeFilter) -> Result<FilterPreset> {
+    let now = chrono::Utc::now();
+    let row = sqlx::query(
+        "INSERT INTO filter_presets (name, filter, created_at, updated_at) VALUES (?, ?, ?, ?) RETURNING *"
+    )
+    .bind(preset_name(name)?)
+    .bind(serde_json::to_string(filter)?)
+    .bind(now)
+    .bind(now)
+    .fetch_one(pool)
+    .await?;
+
+    filter_preset_from_row(&row)
+}
+
+/// Renames the preset, replaces its filter, or both.
+pub async fn update_filter_preset(
+    pool: &SqlitePool,
+    id: i64,
+    name: Option<&str>,
+    filter: Option<&TimeFilter>,
+) -> Result<FilterPreset> {
+    let name = name.map(preset_name).transpose()?;
+    let filter = filter.map(serde_json::to_string).transpose()?;
+
+    let row = sqlx::query(
+        "UPDATE filter_presets SET name = COALESCE(?, name), filter = COALESCE(?, filter), updated_at = ?
+         WHERE id = ? RETURNING *"
+    )
+    .bind(name)
+    .bind(filter)
+    .bind(chrono::Utc::now())
+    .bind(id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| anyhow::anyhow!("筛选预设 {} 不存在", id))?;
+
+    filter_preset_from_row(&row)
+}
+
+/// Returns false when there was no such preset.
+pub async fn delete_filter_preset(pool: &SqlitePool, id: i64) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM filter_presets WHERE id = ?")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_pool;
+    use serde_json::json;
+
+    fn filter_json(filter: &TimeFilter) -> serde_json::Value {
+        serde_json::to_value(filter).unwrap()
+    }
+
+    #[test]
+    fn known_keys_reject_values_of_another_type() {
+        assert!(check_value(GIT_BACKEND, &json!("gix")).is_ok());
+        assert!(check_value(GIT_BACKEND, &json!("libgit2")).is_err());
+        assert!(check_value(GIT_BACKEND, &json!(true)).is_err());
+        assert!(check_value(STATISTICS_CACHE_PERSISTENT, &json!(false)).is_ok());
+        assert!(check_value(STATISTICS_CACHE_PERSISTENT, &json!("true")).is_err());
+        assert!(check_value(STATISTICS_CACHE_PERSISTENT, &json!(null)).is_err());
+        assert!(check_value(LAST_FILTER, &filter_json(&TimeFilter::default())).is_ok());
+        assert!(check_value(LAST_FILTER, &json!({ "repository_id": "one" })).is_err());
+        assert!(check_value(LAST_FILTER, &json!("last week")).is_err());
+        assert!(check_value(FILTER_QUERY, &json!("author:alice")).is_ok());
+        assert!(check_value(FILTER_QUERY, &json!(["author:alice"])).is_err());
+
+        // Frontend preferences take anything
+        assert!(check_value("theme", &json!({ "dark": true })).is_ok());
+    }
+
+    #[test]
+    fn only_unknown_settings_may_feed_the_statistics() {
+        for key in [GIT_BACKEND, STATISTICS_CACHE_PERSISTENT, LAST_FILTER, FILTER_QUERY] {
+            assert!(!affects_statistics(key), "{}", key);
+        }
+        assert!(affects_statistics("author_identities"));
+        assert!(affects_statistics("excluded_paths"));
+    }
+
+    #[tokio::test]
+    async fn settings_of_the_wrong_type_read_as_unset() {
+        let pool = test_pool().await;
+        assert_eq!(git_backend(&pool).await.unwrap(), GitBackendKind::Git2);
+        assert!(set_setting(&pool, GIT_BACKEND, &"svn").await.is_err());
+        assert!(get_settings(&pool).await.unwrap().is_empty());
+
+        assert_eq!(set_setting(&pool, GIT_BACKEND, &GitBackendKind::Gix).await.unwrap(), json!("gix"));
+        set_setting(&pool, STATISTICS_CACHE_PERSISTENT, &true).await.unwrap();
+        assert_eq!(git_backend(&pool).await.unwrap(), GitBackendKind::Gix);
+        assert!(statistics_cache_persistent(&pool).await.unwrap());
+
+        // Values written by another build are bypassed instead of failing
+        // startup, and still listed as they were stored
+        sqlx::query("UPDATE settings SET value = ? WHERE key = ?")
+            .bind(json!("svn").to_string())
+            .bind(GIT_BACKEND)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE settings SET value = '1' WHERE key = ?")
+            .bind(STATISTICS_CACHE_PERSISTENT)
+            .execute(&pool)
+            .await
+            .unwrap();
+        set_setting(&pool, LAST_FILTER, &"not a filter").await.unwrap_err();
+        sqlx::query("INSERT INTO settings (key, value, updated_at) VALUES (?, '\"last week\"', ?)")
+            .bind(LAST_FILTER)
+            .bind(chrono::Utc::now())
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(get_setting::<GitBackendKind>(&pool, GIT_BACKEND).await.unwrap(), None);
+        assert_eq!(git_backend(&pool).await.unwrap(), GitBackendKind::Git2);
+        assert!(!statistics_cache_persistent(&pool).await.unwrap());
+        assert!(last_filter(&pool).await.unwrap().is_none());
+        assert_eq!(get_settings(&pool).await.unwrap()[GIT_BACKEND], json!("svn"));
+
+        assert!(delete_setting(&pool, GIT_BACKEND).await.unwrap());
+        assert!(!delete_setting(&pool, GIT_BACKEND).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn presets_need_a_unique_name() {
+        let pool = test_pool().await;
+        let mine = TimeFilter { authors: Some(vec!["Alice".to_string()]), ..Default::default() };
+        let recent = TimeFilter { min_size: Some(10), ..Default::default() };
+
+        let preset = create_filter_preset(&pool, " mine ", &mine).await.unwrap();
+        assert_eq!(preset.name, "mine");
+        assert_eq!(filter_json(&preset.filter), filter_json(&mine));
+        assert!(create_filter_preset(&pool, "", &mine).await.is_err());
+        assert!(create_filter_preset(&pool, " \t", &mine).await.is_err());
+        assert!(create_filter_preset(&pool, "mine", &recent).await.is_err());
+        assert!(create_filter_preset(&pool, " mine", &recent).await.is_err());
+        let other = create_filter_preset(&pool, "large", &recent).await.unwrap();
+
+        assert!(update_filter_preset(&pool, other.id, Some("mine"), None).await.is_err());
+        assert!(update_filter_preset(&pool, other.id, Some("  "), Some(&mine)).await.is_err());
+        assert!(update_filter_preset(&pool, other.id + 100, Some("gone"), None).await.is_err());
+        // A rejected update changes nothing
+        let names = |presets: Vec<FilterPreset>| presets.into_iter().map(|preset| preset.name).collect::<Vec<_>>();
+        assert_eq!(names(get_filter_presets(&pool).await.unwrap()), vec!["large", "mine"]);
+        assert_eq!(filter_json(&get_filter_presets(&pool).await.unwrap()[0].filter), filter_json(&recent));
+
+        let renamed = update_filter_preset(&pool, preset.id, Some("alice"), None).await.unwrap();
+        assert_eq!((renamed.name.as_str(), filter_json(&renamed.filter)), ("alice", filter_json(&mine)));
+        let replaced = update_filter_preset(&pool, preset.id, None, Some(&recent)).await.unwrap();
+        assert_eq!((replaced.name.as_str(), filter_json(&replaced.filter)), ("alice", filter_json(&recent)));
+        assert_eq!(replaced.created_at, preset.created_at);
+        // The freed name can be taken again
+        create_filter_preset(&pool, "mine", &mine).await.unwrap();
+
+        assert!(delete_filter_preset(&pool, preset.id).await.unwrap());
+        assert!(!delete_filter_preset(&pool, preset.id).await.unwrap());
+        assert_eq!(names(get_filter_presets(&pool).await.unwrap()), vec!["large", "mine"]);
+    }
+}
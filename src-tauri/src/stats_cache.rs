@@ -0,0 +1,457 @@
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::SqlitePool;
+use crate::models::{StatisticsCacheDiagnostics, TimeFilter};
+use anyhow::Result;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+
+// Entries kept in memory, the least recently stored are dropped beyond this
+const MAX_MEMORY_ENTRIES: usize = 256;
+
+/// Statistics results keyed by series and normalized filter, kept in memory
+/// and, when persistence is enabled, in the `statistics_cache` table.
+///
+/// Entries are dropped when a change touches their repository. Every
+/// invalidation also bumps a generation counter, and a result is only
+/// stored if the generation it was computed under is still current, so a
+/// query racing a scan never caches what the scan just replaced.
+#[derive(Default)]
+pub struct StatisticsCache {
+    state: Mutex<CacheState>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    entries: HashMap<String, CacheEntry>,
+    persistent: bool,
+    next_sequence: u64,
+    // Bumped for all entries, for entries of one repository and for
+    // entries over all repositories respectively
+    generation: u64,
+    repository_generations: HashMap<i64, u64>,
+    all_repositories_generation: u64,
+    hits: u64,
+    disk_hits: u64,
+    misses: u64,
+    invalidations: u64,
+}
+
+struct CacheEntry {
+    repository_id: Option<i64>,
+    sequence: u64,
+    payload: serde_json::Value,
+}
+
+impl CacheState {
+    fn generation_of(&self, repository_id: Option<i64>) -> u64 {
+        let scope_generation = match repository_id {
+            Some(id) => self.repository_generations.get(&id).copied().unwrap_or(0),
+            None => self.all_repositories_generation,
+        };
+        self.generation + scope_generation
+    }
+
+    fn insert(&mut self, key: String, repository_id: Option<i64>, payload: serde_json::Value) {
+        if self.entries.len() >= MAX_MEMORY_ENTRIES && !self.entries.contains_key(&key) {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.sequence)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+
+        self.next_sequence += 1;
+        self.entries.insert(key, CacheEntry {
+            repository_id,
+            sequence: self.next_sequence,
+            payload,
+        });
+    }
+}
+
+impl StatisticsCache {
+    /// Returns the cached result of `series` for the filter, or awaits
+    /// `compute` and caches what it returns.
+    pub async fn get_or_compute<T, F>(&self, pool: &SqlitePool, series: &str, filter: &TimeFilter, compute: F) -> Result<T>
+    where
+        T: Serialize + DeserializeOwned,
+        F: Future<Output = Result<T>>,
+    {
+        let key = cache_key(series, filter)?;
+        let repository_id = cache_scope(filter);
+
+        let (generation, persistent) = {
+            let mut state = self.state.lock().unwrap();
+            if let Some(entry) = state.entries.get(&key) {
+                let payload = entry.payload.clone();
+                state.hits += 1;
+                return Ok(serde_json::from_value(payload)?);
+            }
+            (state.generation_of(repository_id), state.persistent)
+        };
+
+        if persistent {
+            let stored: Option<String> = sqlx::query_scalar("SELECT payload FROM statistics_cache WHERE cache_key = ?")
+                .bind(&key)
+                .fetch_optional(pool)
+                .await?;
+
+            if let Some(stored) = stored {
+                let payload: serde_json::Value = serde_json::from_str(&stored)?;
+                let result = serde_json::from_value(payload.clone())?;
+
+                let mut state = self.state.lock().unwrap();
+                state.disk_hits += 1;
+                if state.generation_of(repository_id) == generation {
+                    state.insert(key, repository_id, payload);
+                }
+                return Ok(result);
+            }
+        }
+
+        self.state.lock().unwrap().misses += 1;
+        let result = compute.await?;
+        let payload = serde_json::to_value(&result)?;
+
+        if persistent {
+            sqlx::query(
+                "INSERT INTO statistics_cache (cache_key, repository_id, payload, created_at) VALUES (?, ?, ?, ?)
+                 ON CONFLICT(cache_key) DO UPDATE SET payload = excluded.payload, created_at = excluded.created_at"
+            )
+            .bind(&key)
+            .bind(repository_id)
+            .bind(payload.to_string())
+            .bind(chrono::Utc::now())
+            .execute(pool)
+            .await?;
+
+            // An invalidation that ran before the write could not delete the row
+            let current = self.state.lock().unwrap().generation_of(repository_id);
+            if current != generation {
+                sqlx::query("DELETE FROM statistics_cache WHERE cache_key = ?")
+                    .bind(&key)
+                    .execute(pool)
+                    .await?;
+            }
+        }
+
+        let mut state = self.state.lock().unwrap();
+        if state.generation_of(repository_id) == generation {
+            state.insert(key, repository_id, payload);
+        }
+
+        Ok(result)
+    }
+
+    /// Drops the entries a change to the repository may have made stale:
+    /// its own and those over all repositories. `None` drops everything,
+    /// for changes that affect every repository.
+    pub async fn invalidate(&self, pool: &SqlitePool, repository_id: Option<i64>) -> Result<()> {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.invalidations += 1;
+            state.all_repositories_generation += 1;
+            match repository_id {
+                Some(id) => {
+                    *state.repository_generations.entry(id).or_insert(0) += 1;
+                    state.entries.retain(|_, entry| entry.repository_id.is_some() && entry.repository_id != Some(id));
+                }
+                None => {
+                    state.generation += 1;
+                    state.entries.clear();
+                }
+            }
+        }
+
+        // Rows are removed even with persistence off, so enabling it again
+        // never serves results from before a change
+        let mut query = "DELETE FROM statistics_cache".to_string();
+        if repository_id.is_some() {
+            query.push_str(" WHERE repository_id IS NULL OR repository_id = ?");
+        }
+        let mut query_builder = sqlx::query(&query);
+        if let Some(id) = repository_id {
+            query_builder = query_builder.bind(id);
+        }
+        query_builder.execute(pool).await?;
+
+        Ok(())
+    }
+
+    pub fn set_persistent(&self, persistent: bool) {
+        self.state.lock().unwrap().persistent = persistent;
+    }
+
+    pub async fn diagnostics(&self, pool: &SqlitePool) -> Result<StatisticsCacheDiagnostics> {
+        let disk_entries: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM statistics_cache")
+            .fetch_one(pool)
+            .await?;
+
+        let state = self.state.lock().unwrap();
+        Ok(StatisticsCacheDiagnostics {
+            persistent: state.persistent,
+            memory_entries: state.entries.len(),
+            disk_entries,
+            hits: state.hits,
+            disk_hits: state.disk_hits,
+            misses: state.misses,
+            invalidations: state.invalidations,
+        })
+    }
+}
+
+/// The repository whose changes can make the filter's results stale, or
+/// `None` when it spans several or all of them.
+pub fn cache_scope(filter: &TimeFilter) -> Option<i64> {
+    let mut repository_ids: Vec<i64> = filter.repository_id.into_iter().collect();
+    repository_ids.extend(filter.repository_ids.iter().flatten());
+    repository_ids.sort_unstable();
+    repository_ids.dedup();
+
+    match repository_ids.as_slice() {
+        [repository_id] => Some(*repository_id),
+        _ => None,
+    }
+}
+
+/// The cache key of a series: filters that select the same commits, such
+/// as the same excluded authors in another order, share a key. The app version is
+/// part of it so persisted results of older builds are never read.
+pub fn cache_key(series: &str, filter: &TimeFilter) -> Result<String> {
+    // Authors, repositories and groups are sets, only their order is free
+    let mut authors: Vec<&String> = filter.author.iter().chain(filter.authors.iter().flatten()).collect();
+    authors.sort_unstable();
+    authors.dedup();
+    let mut exclude_authors: Vec<&String> = filter.exclude_authors.iter().flatten().collect();
+    exclude_authors.sort_unstable();
+    exclude_authors.dedup();
+    let mut repository_ids: Vec<i64> = filter.repository_id.into_iter().chain(filter.repository_ids.iter().flatten().copied()).collect();
+    repository_ids.sort_unstable();
+    repository_ids.dedup();
+    let mut group_ids: Vec<i64> = filter.group_ids.iter().flatten().copied().collect();
+    group_ids.sort_unstable();
+    group_ids.dedup();
+    let mut branches: Vec<&String> = filter.branches.iter().flatten().collect();
+    branches.sort_unstable();
+    branches.dedup();
+    let mut paths: Vec<&String> = filter.paths.iter().flatten().collect();
+    paths.sort_unstable();
+    paths.dedup();
+
+    let key = serde_json::json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "series": series,
+        "start_date": filter.start_date.map(|date| date.to_rfc3339()),
+        "end_date": filter.end_date.map(|date| date.to_rfc3339()),
+        "authors": authors,
+        "exclude_authors": exclude_authors,
+        "repository_ids": repository_ids,
+        "group_ids": group_ids,
+        "branches": branches,
+        "paths": paths,
+        "min_size": filter.min_size,
+        "max_size": filter.max_size,
+        "message_pattern": filter.message_pattern,
+        "exclude_message_pattern": filter.exclude_message_pattern,
+        "include_merges": filter.include_merges,
+        "deduplicate_patches": filter.deduplicate_patches,
+        "exclude_reverts": filter.exclude_reverts,
+        "include_orphaned": filter.include_orphaned,
+    });
+
+    Ok(serde_json::to_string(&key)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_pool;
+
+    fn repository(id: i64) -> TimeFilter {
+        TimeFilter { repository_id: Some(id), ..Default::default() }
+    }
+
+    // The cached value of the series, or `value` when it had to be computed
+    async fn get(cache: &StatisticsCache, pool: &SqlitePool, series: &str, filter: &TimeFilter, value: i64) -> i64 {
+        cache.get_or_compute(pool, series, filter, async { Ok(value) }).await.unwrap()
+    }
+
+    #[test]
+    fn keys_ignore_the_order_of_set_filters() {
+        let key = |filter: TimeFilter| cache_key("totals", &filter).unwrap();
+        let strings = |values: &[&str]| Some(values.iter().map(|value| value.to_string()).collect::<Vec<_>>());
+
+        let filter = TimeFilter {
+            author: Some("alice".to_string()),
+            authors: strings(&["carol", "bob"]),
+            exclude_authors: strings(&["dave", "erin"]),
+            repository_ids: Some(vec![3, 1]),
+            group_ids: Some(vec![2, 1, 2]),
+            branches: strings(&["main", "dev"]),
+            paths: strings(&["src/**", "*.md"]),
+            ..Default::default()
+        };
+        let reordered = TimeFilter {
+            author: None,
+            authors: strings(&["bob", "alice", "carol", "bob"]),
+            exclude_authors: strings(&["erin", "dave"]),
+            repository_id: Some(1),
+            repository_ids: Some(vec![3]),
+            group_ids: Some(vec![1, 2]),
+            branches: strings(&["dev", "main"]),
+            paths: strings(&["*.md", "src/**"]),
+            ..Default::default()
+        };
+        assert_eq!(key(filter.clone()), key(reordered));
+
+        // An empty list selects what no list does
+        assert_eq!(key(TimeFilter { authors: Some(Vec::new()), ..Default::default() }), key(TimeFilter::default()));
+
+        // Everything else tells results apart
+        assert_ne!(cache_key("daily", &filter).unwrap(), key(filter.clone()));
+        assert_ne!(
+            key(TimeFilter { exclude_authors: strings(&["alice"]), ..Default::default() }),
+            key(TimeFilter { author: Some("alice".to_string()), ..Default::default() })
+        );
+        assert_ne!(key(TimeFilter { min_size: Some(10), ..filter.clone() }), key(filter.clone()));
+        assert_ne!(key(TimeFilter { include_merges: true, ..filter.clone() }), key(filter.clone()));
+        assert_ne!(key(TimeFilter { group_ids: None, ..filter.clone() }), key(filter));
+    }
+
+    #[test]
+    fn scopes_name_a_single_repository() {
+        assert_eq!(cache_scope(&repository(4)), Some(4));
+        assert_eq!(cache_scope(&TimeFilter { repository_ids: Some(vec![4, 4]), ..repository(4) }), Some(4));
+        assert_eq!(cache_scope(&TimeFilter { repository_ids: Some(vec![5]), ..repository(4) }), None);
+        assert_eq!(cache_scope(&TimeFilter::default()), None);
+    }
+
+    #[tokio::test]
+    async fn invalidation_drops_the_repository_and_all_repository_entries() {
+        let pool = test_pool().await;
+        let cache = StatisticsCache::default();
+        let all = TimeFilter::default();
+
+        assert_eq!(get(&cache, &pool, "totals", &repository(1), 1).await, 1);
+        assert_eq!(get(&cache, &pool, "totals", &repository(2), 2).await, 2);
+        assert_eq!(get(&cache, &pool, "totals", &all, 3).await, 3);
+        assert_eq!(get(&cache, &pool, "totals", &repository(1), 10).await, 1);
+
+        // Repository 1 changed: its results and the ones over all
+        // repositories are recomputed, repository 2 keeps its own
+        cache.invalidate(&pool, Some(1)).await.unwrap();
+        assert_eq!(get(&cache, &pool, "totals", &repository(1), 11).await, 11);
+        assert_eq!(get(&cache, &pool, "totals", &repository(2), 12).await, 2);
+        assert_eq!(get(&cache, &pool, "totals", &all, 13).await, 13);
+
+        cache.invalidate(&pool, None).await.unwrap();
+        assert_eq!(get(&cache, &pool, "totals", &repository(1), 21).await, 21);
+        assert_eq!(get(&cache, &pool, "totals", &repository(2), 22).await, 22);
+
+        let diagnostics = cache.diagnostics(&pool).await.unwrap();
+        assert_eq!((diagnostics.hits, diagnostics.misses, diagnostics.invalidations), (2, 7, 2));
+        assert_eq!((diagnostics.memory_entries, diagnostics.disk_entries), (2, 0));
+    }
+
+    #[tokio::test]
+    async fn the_least_recently_stored_entries_are_evicted() {
+        let pool = test_pool().await;
+        let cache = StatisticsCache::default();
+        let all = TimeFilter::default();
+        for index in 0..=MAX_MEMORY_ENTRIES {
+            get(&cache, &pool, &format!("series {}", index), &all, index as i64).await;
+        }
+        assert_eq!(cache.diagnostics(&pool).await.unwrap().memory_entries, MAX_MEMORY_ENTRIES);
+
+        // Reading an entry doesn't keep it; storing one again does
+        let last = MAX_MEMORY_ENTRIES as i64;
+        assert_eq!(get(&cache, &pool, "series 1", &all, -1).await, 1);
+        assert_eq!(get(&cache, &pool, &format!("series {}", last), &all, -1).await, last);
+        assert_eq!(get(&cache, &pool, "series 0", &all, -1).await, -1);
+        assert_eq!(get(&cache, &pool, "series 1", &all, -2).await, -2);
+        assert_eq!(get(&cache, &pool, "series 2", &all, -3).await, -3);
+        assert_eq!(get(&cache, &pool, "series 0", &all, -4).await, -1);
+        assert_eq!(cache.diagnostics(&pool).await.unwrap().memory_entries, MAX_MEMORY_ENTRIES);
+    }
+
+    #[tokio::test]
+    async fn persisted_results_outlive_the_cache_until_invalidated() {
+        let pool = test_pool().await;
+        let cache = StatisticsCache::default();
+        cache.set_persistent(true);
+        let all = TimeFilter::default();
+        get(&cache, &pool, "totals", &repository(1), 1).await;
+        get(&cache, &pool, "totals", &repository(2), 2).await;
+        get(&cache, &pool, "totals", &all, 3).await;
+        assert_eq!(cache.diagnostics(&pool).await.unwrap().disk_entries, 3);
+
+        // A new cache, as after a restart, reads the stored rows
+        let restarted = StatisticsCache::default();
+        restarted.set_persistent(true);
+        assert_eq!(get(&restarted, &pool, "totals", &repository(1), 10).await, 1);
+        assert_eq!(get(&restarted, &pool, "totals", &repository(1), 11).await, 1);
+        let diagnostics = restarted.diagnostics(&pool).await.unwrap();
+        assert_eq!((diagnostics.disk_hits, diagnostics.hits, diagnostics.misses), (1, 1, 0));
+
+        // Rows go with their repository and the all-repository results,
+        // even while persistence is off
+        restarted.set_persistent(false);
+        restarted.invalidate(&pool, Some(1)).await.unwrap();
+        assert_eq!(restarted.diagnostics(&pool).await.unwrap().disk_entries, 1);
+        restarted.set_persistent(true);
+        assert_eq!(get(&restarted, &pool, "totals", &repository(1), 12).await, 12);
+        assert_eq!(get(&restarted, &pool, "totals", &repository(2), 13).await, 2);
+        assert_eq!(get(&restarted, &pool, "totals", &all, 14).await, 14);
+
+        // Without persistence nothing is written
+        let transient = StatisticsCache::default();
+        transient.invalidate(&pool, None).await.unwrap();
+        get(&transient, &pool, "totals", &all, 15).await;
+        assert_eq!(transient.diagnostics(&pool).await.unwrap().disk_entries, 0);
+    }
+
+    #[tokio::test]
+    async fn results_computed_across_an_invalidation_are_not_kept() {
+        let pool = test_pool().await;
+        let cache = StatisticsCache::default();
+        cache.set_persistent(true);
+
+        // A scan of repository 1 finishes while its totals are computed
+        let stale = cache
+            .get_or_compute(&pool, "totals", &repository(1), async {
+                cache.invalidate(&pool, Some(1)).await?;
+                Ok(1)
+            })
+            .await
+            .unwrap();
+        assert_eq!(stale, 1);
+        assert_eq!(cache.diagnostics(&pool).await.unwrap().disk_entries, 0);
+        assert_eq!(get(&cache, &pool, "totals", &repository(1), 2).await, 2);
+        assert_eq!(get(&cache, &pool, "totals", &repository(1), 3).await, 2);
+
+        // Another repository's scan leaves the computation alone
+        cache
+            .get_or_compute(&pool, "daily", &repository(1), async {
+                cache.invalidate(&pool, Some(2)).await?;
+                Ok(4)
+            })
+            .await
+            .unwrap();
+        assert_eq!(get(&cache, &pool, "daily", &repository(1), 5).await, 4);
+
+        // Results over all repositories are stale after any scan
+        cache
+            .get_or_compute(&pool, "totals", &TimeFilter::default(), async {
+                cache.invalidate(&pool, Some(2)).await?;
+                Ok(6)
+            })
+            .await
+            .unwrap();
+        assert_eq!(get(&cache, &pool, "totals", &TimeFilter::default(), 7).await, 7);
+        assert_eq!(cache.diagnostics(&pool).await.unwrap().disk_entries, 3);
+    }
+}
@@ -0,0 +1,67 @@
+use crate::git_analyzer::{AnalyzedCommit, FileChange};
+use crate::models::Commit;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use sqlx::SqlitePool;
+use std::str::FromStr;
+
+// An empty database with the current schema. Every connection to
+// `:memory:` opens its own database, so the pool keeps exactly one.
+pub(crate) async fn test_pool() -> SqlitePool {
+    let options = SqliteConnectOptions::from_str("sqlite::memory:")
+        .unwrap()
+        .foreign_keys(true)
+        .with_regexp();
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .connect_with(options)
+        .await
+        .unwrap();
+    crate::migrations::run_migrations(&pool, std::path::Path::new(":memory:")).await.unwrap();
+    pool
+}
+
+// A commit by Alice `minute` minutes into the test period
+pub(crate) fn analyzed(repository_id: i64, id: &str, minute: i64, files: &[(&str, i32, i32)]) -> AnalyzedCommit {
+    let file_changes: Vec<FileChange> = files
+        .iter()
+        .map(|&(path, additions, deletions)| FileChange {
+            path: path.to_string(),
+            additions,
+            deletions,
+        })
+        .collect();
+    AnalyzedCommit {
+        commit: Commit {
+            id: id.to_string(),
+            repository_id,
+            repository_name: "repo".to_string(),
+            author: "Alice".to_string(),
+            email: "alice@example.com".to_string(),
+            message: format!("commit {}", id),
+            timestamp: chrono::DateTime::from_timestamp(1_700_000_000 + minute * 60, 0).unwrap(),
+            additions: file_changes.iter().map(|change| change.additions).sum(),
+            deletions: file_changes.iter().map(|change| change.deletions).sum(),
+            files_changed: file_changes.len() as i32,
+            branch: Some("main".to_string()),
+            remote_url: None,
+            patch_id: None,
+            inverse_patch_id: None,
+            orphaned_at: None,
+            is_merge: false,
+        },
+        file_changes,
+    }
+}
+
+// Stores the commits the way a scan does
+pub(crate) async fn ingest(pool: &SqlitePool, commits: Vec<AnalyzedCommit>) {
+    let (sender, receiver) = tokio::sync::mpsc::channel(commits.len().max(1));
+    let count = commits.len();
+    for commit in commits {
+        sender.send(commit).await.unwrap();
+    }
+    drop(sender);
+    crate::database::ingest_commits(pool, count, 100, receiver).await.unwrap();
+}
@@ -1,51 +1,103 @@
-import { useState, useEffect } from 'react';
+import { useState, useEffect, useRef } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
 import { open } from '@tauri-apps/plugin-dialog';
 import { useLocation } from 'react-router-dom';
 import RepositoryManager from './components/RepositoryManager';
 import StatisticsCharts from './components/StatisticsCharts';
 import Timeline from './components/Timeline';
-import { Repository, CommitData, Statistics, TimeFilter } from './types';
+import AdvancedFilters from './components/AdvancedFilters';
+import FilterQueryBar from './components/FilterQueryBar';
+import DiffSearch from './components/DiffSearch';
+import FilterPresets from './components/FilterPresets';
+import { Repository, RepositoryChanges, RepositoryGroup, repositoryLabel, CommitData, CommitPage, SearchHit, SearchPage, SettingChanged, Statistics, TimeFilter } from './types';
 import DatePicker from 'react-datepicker';
 import dayjs from 'dayjs';
 import 'react-datepicker/dist/react-datepicker.css';
 
+// 时间线每页加载的提交数
+const TIMELINE_PAGE_SIZE = 200;
+// 搜索结果每页加载的提交数
+const SEARCH_PAGE_SIZE = 50;
+// 筛选条件停止变化多久后保存为上次的筛选条件
+const SAVE_FILTER_DELAY = 500;
+// 本窗口的搜索词
+const SEARCH_TERM_KEY = 'git-stats-search-term';
+// 旧版本保存筛选条件的 localStorage 键
+const LEGACY_FILTER_KEY = 'git-stats-filter';
+
+// 后端使用的筛选条件，日期转换为 ISO 格式
+const toBackendFilter = (filter: TimeFilter): TimeFilter => ({
+  start_date: filter.start_date ? new Date(filter.start_date + 'T00:00:00.000Z').toISOString() : undefined,
+  end_date: filter.end_date ? new Date(filter.end_date + 'T23:59:59.999Z').toISOString() : undefined,
+  author: filter.author,
+  authors: filter.authors,
+  exclude_authors: filter.exclude_authors,
+  repository_id: filter.repository_id,
+  repository_ids: filter.repository_ids,
+  group_ids: filter.group_ids,
+  branches: filter.branches,
+  paths: filter.paths,
+  min_size: filter.min_size,
+  max_size: filter.max_size,
+  message_pattern: filter.message_pattern,
+  exclude_message_pattern: filter.exclude_message_pattern,
+  include_merges: filter.include_merges
+});
+
+// 后端格式的筛选条件转回日期选择器使用的格式
+const fromBackendFilter = (saved: TimeFilter): TimeFilter => ({
+  ...saved,
+  start_date: saved.start_date ? saved.start_date.slice(0, 10) : undefined,
+  end_date: saved.end_date ? saved.end_date.slice(0, 10) : undefined
+});
+
+// 比较两个筛选条件是否相同，忽略搜索词和后端返回的 null 字段
+const filterKey = (filter: TimeFilter) =>
+  JSON.stringify(toBackendFilter(filter), (_key, value) => value === null ? undefined : value);
+
 function App() {
   const location = useLocation();
   const [repositories, setRepositories] = useState<Repository[]>([]);
+  const [repositoryGroups, setRepositoryGroups] = useState<RepositoryGroup[]>([]);
   const [statistics, setStatistics] = useState<Statistics | null>(null);
   const [timeline, setTimeline] = useState<CommitData[]>([]);
+  const [timelineCursor, setTimelineCursor] = useState<string | null>(null);
+  const [timelineTotal, setTimelineTotal] = useState(0);
+  const [timelineLoadingMore, setTimelineLoadingMore] = useState(false);
+  // 有搜索词时时间线显示搜索结果，为 null 时显示全部提交
+  const [searchHits, setSearchHits] = useState<SearchHit[] | null>(null);
+  // 筛选条件变化后，丢弃旧筛选条件下还在加载的分页
+  const timelineRequest = useRef(0);
   const [allAuthors, setAllAuthors] = useState<string[]>([]); // 添加这行来存储所有作者
   const [loading, setLoading] = useState(false);
   const [loadingProgress, setLoadingProgress] = useState<{current: number, total: number, message: string} | null>(null);
-  const [activeTab, setActiveTab] = useState<'charts' | 'timeline'>(() => {
+  const [activeTab, setActiveTab] = useState<'charts' | 'timeline' | 'diff'>(() => {
     const savedTab = localStorage.getItem('activeTab');
-    return savedTab === 'timeline' ? 'timeline' : 'charts';
+    return savedTab === 'timeline' || savedTab === 'diff' ? savedTab : 'charts';
   });
   const [filter, setFilter] = useState<TimeFilter>(() => {
-    // 从 localStorage 恢复筛选条件
-    const savedFilter = localStorage.getItem('git-stats-filter');
-    if (savedFilter) {
-      try {
-        return JSON.parse(savedFilter);
-      } catch (e) {
-        console.error('解析筛选条件失败:', e);
-        return {};
-      }
-    }
-    
-    // 默认设置为最近30天
+    // 默认设置为最近30天，保存的筛选条件由后端恢复；搜索词只在本窗口内保留
     const thirtyDaysAgo = new Date();
     thirtyDaysAgo.setDate(thirtyDaysAgo.getDate() - 30);
     return {
       start_date: thirtyDaysAgo.toISOString().split('T')[0],
-      exclude_authors: []
+      exclude_authors: [],
+      searchTerm: sessionStorage.getItem(SEARCH_TERM_KEY) || undefined
     };
   });
   const [sidebarCollapsed, setSidebarCollapsed] = useState<boolean>(() => {
     const saved = localStorage.getItem('sidebarCollapsed');
     return saved === 'true';
   });
+  // 上次的筛选条件从后端恢复后才开始保存
+  const [lastFilterRestored, setLastFilterRestored] = useState(false);
+  // 最近保存或从其他窗口收到的筛选条件，相同的条件不再保存
+  const lastSavedFilter = useRef<string | null>(null);
+  const [advancedFiltersOpen, setAdvancedFiltersOpen] = useState<boolean>(() => {
+    const saved = localStorage.getItem('advancedFiltersOpen');
+    return saved === 'true';
+  });
 
   // 当从提交详情页面返回时，自动切换到时间线标签页并恢复筛选条件
   useEffect(() => {
@@ -55,18 +107,6 @@ function App() {
       setActiveTab('timeline');
       localStorage.setItem('activeTab', 'timeline');
       
-      // 恢复筛选条件
-      const savedFilter = localStorage.getItem('git-stats-filter');
-      if (savedFilter) {
-        try {
-          const parsedFilter = JSON.parse(savedFilter);
-          setFilter(parsedFilter);
-          console.log('恢复筛选条件:', parsedFilter);
-        } catch (e) {
-          console.error('解析筛选条件失败:', e);
-        }
-      }
-      
       // 清除标记，避免重复触发
       sessionStorage.removeItem('returning-from-commit-detail');
       
@@ -79,19 +119,15 @@ function App() {
 
   useEffect(() => {
     loadRepositories();
+    loadRepositoryGroups();
   }, []);
 
   // 初始化所有作者列表
   useEffect(() => {
     const loadAllAuthors = async () => {
       try {
-        const allCommits = await invoke<CommitData[]>('get_commit_timeline', {
-          startDate: undefined,
-          endDate: undefined,
-          author: undefined,
-          repositoryId: undefined
-        });
-        setAllAuthors(Array.from(new Set(allCommits.map(c => c.author))).sort());
+        const authors = await invoke<string[]>('get_authors', { repositoryId: undefined });
+        setAllAuthors(authors);
       } catch (error) {
         console.error('Failed to load all authors:', error);
       }
@@ -100,10 +136,62 @@ function App() {
     loadAllAuthors();
   }, []);
 
-  // 保存筛选条件到 localStorage
+  // 搜索词保存到 sessionStorage，从提交详情页返回时恢复
+  useEffect(() => {
+    if (filter.searchTerm) {
+      sessionStorage.setItem(SEARCH_TERM_KEY, filter.searchTerm);
+    } else {
+      sessionStorage.removeItem(SEARCH_TERM_KEY);
+    }
+  }, [filter.searchTerm]);
+
+  // 启动时恢复上次的筛选条件，之后其他窗口修改时同步
+  useEffect(() => {
+    // 旧版本保存在 localStorage 的筛选条件，后端没有时迁移一次
+    const legacyFilter = localStorage.getItem(LEGACY_FILTER_KEY);
+
+    invoke<TimeFilter | null>('get_last_filter')
+      .then(saved => {
+        if (saved) {
+          lastSavedFilter.current = filterKey(fromBackendFilter(saved));
+          applyBackendFilter(saved);
+        } else if (legacyFilter) {
+          try {
+            const migrated = JSON.parse(legacyFilter) as TimeFilter;
+            setFilter(prev => ({ ...migrated, searchTerm: prev.searchTerm }));
+          } catch (e) {
+            console.error('解析筛选条件失败:', e);
+          }
+        }
+        localStorage.removeItem(LEGACY_FILTER_KEY);
+      })
+      .catch(error => console.error('恢复上次的筛选条件失败:', error))
+      .finally(() => setLastFilterRestored(true));
+
+    const unlisten = listen<SettingChanged>('settings-changed', (event) => {
+      const { key, value } = event.payload;
+      if (key === 'last_filter' && value) {
+        lastSavedFilter.current = filterKey(fromBackendFilter(value as TimeFilter));
+        applyBackendFilter(value as TimeFilter);
+      }
+    });
+    return () => {
+      unlisten.then(fn => fn());
+    };
+  }, []);
+
+  // 筛选条件变化后保存到后端，恢复完成前不保存，以免覆盖上次的筛选条件
   useEffect(() => {
-    localStorage.setItem('git-stats-filter', JSON.stringify(filter));
-  }, [filter]);
+    if (!lastFilterRestored || filterKey(filter) === lastSavedFilter.current) {
+      return;
+    }
+    const timer = setTimeout(() => {
+      lastSavedFilter.current = filterKey(filter);
+      invoke('set_last_filter', { filter: backendFilter() })
+        .catch(error => console.error('保存筛选条件失败:', error));
+    }, SAVE_FILTER_DELAY);
+    return () => clearTimeout(timer);
+  }, [filter, lastFilterRestored]);
 
   useEffect(() => {
     if (repositories.length > 0) {
@@ -111,47 +199,103 @@ function App() {
     }
   }, [filter, repositories.length]);
 
+  const backendFilter = () => toBackendFilter(filter);
+
+  // 后端格式的条件（筛选语句、预设或保存的筛选条件）替换当前条件
+  const applyBackendFilter = (saved: TimeFilter) => {
+    setFilter(prev => {
+      const next = { ...fromBackendFilter(saved), searchTerm: prev.searchTerm };
+      return filterKey(next) === filterKey(prev) ? prev : next;
+    });
+  };
+
+  const timelinePageSize = () => filter.searchTerm?.trim() ? SEARCH_PAGE_SIZE : TIMELINE_PAGE_SIZE;
+
+  // 时间线的下一页；有搜索词时从全文搜索获取
+  const fetchTimelinePage = async (cursor: string | null, pageSize: number) => {
+    const searchTerm = filter.searchTerm?.trim();
+    if (searchTerm) {
+      const page = await invoke<SearchPage>('search_commits', {
+        query: searchTerm,
+        filter: backendFilter(),
+        cursor,
+        pageSize
+      });
+      return { hits: page.hits, commits: page.hits.map(hit => hit.commit), next_cursor: page.next_cursor, total_count: page.total_count };
+    }
+    const page = await invoke<CommitPage>('get_commit_timeline_page', {
+      filter: backendFilter(),
+      cursor,
+      pageSize
+    });
+    return { hits: null, commits: page.commits, next_cursor: page.next_cursor, total_count: page.total_count };
+  };
+
   const loadData = async () => {
     try {
-      // Convert date strings to ISO format for backend
-      const startDate = filter.start_date ? new Date(filter.start_date + 'T00:00:00.000Z').toISOString() : undefined;
-      const endDate = filter.end_date ? new Date(filter.end_date + 'T23:59:59.999Z').toISOString() : undefined;
+      const query = backendFilter();
       
-      const [stats, timelineData] = await Promise.all([
+      // 从提交详情返回时，一次加载之前已加载的提交，以便恢复滚动位置
+      const returning = sessionStorage.getItem('will-return-from-commit-detail') === 'true';
+      const loadedCount = Number(sessionStorage.getItem('timeline-loaded-count') || 0);
+      const pageSize = timelinePageSize();
+      const firstPageSize = returning ? Math.max(pageSize, loadedCount) : pageSize;
+      
+      const request = ++timelineRequest.current;
+      const [stats, timelinePage] = await Promise.all([
         invoke<Statistics>('get_statistics', {
-          startDate,
-          endDate,
-          author: filter.author,
-          excludeAuthors: filter.exclude_authors,
-          repositoryId: filter.repository_id
+          filter: query
         }),
-        invoke<CommitData[]>('get_commit_timeline', {
-          startDate,
-          endDate,
-          author: filter.author,
-          excludeAuthors: filter.exclude_authors,
-          repositoryId: filter.repository_id
-        })
+        fetchTimelinePage(null, firstPageSize)
       ]);
       
+      if (request !== timelineRequest.current) {
+        return;
+      }
       setStatistics(stats);
-      setTimeline(timelineData);
+      setTimeline(timelinePage.commits);
+      setSearchHits(timelinePage.hits);
+      setTimelineCursor(timelinePage.next_cursor);
+      setTimelineTotal(timelinePage.total_count);
       
       // 更新所有作者列表（不考虑当前筛选条件）
-      const allCommits = await invoke<CommitData[]>('get_commit_timeline', {
-        startDate: undefined,
-        endDate: undefined,
-        author: undefined,
-        excludeAuthors: undefined,
+      const authors = await invoke<string[]>('get_authors', {
         repositoryId: filter.repository_id // 只考虑仓库筛选
       });
       
-      setAllAuthors(Array.from(new Set(allCommits.map(c => c.author))).sort());
+      setAllAuthors(authors);
     } catch (error) {
       console.error('Failed to load data:', error);
     }
   };
 
+  // 加载时间线的下一页
+  const loadMoreTimeline = async () => {
+    if (!timelineCursor || timelineLoadingMore) {
+      return;
+    }
+    
+    const request = timelineRequest.current;
+    setTimelineLoadingMore(true);
+    try {
+      const page = await fetchTimelinePage(timelineCursor, timelinePageSize());
+      if (request !== timelineRequest.current) {
+        return;
+      }
+      setTimeline(prev => [...prev, ...page.commits]);
+      if (page.hits) {
+        const hits = page.hits;
+        setSearchHits(prev => [...(prev || []), ...hits]);
+      }
+      setTimelineCursor(page.next_cursor);
+      setTimelineTotal(page.total_count);
+    } catch (error) {
+      console.error('Failed to load more commits:', error);
+    } finally {
+      setTimelineLoadingMore(false);
+    }
+  };
+
   const loadRepositories = async () => {
     try {
       const repos = await invoke<Repository[]>('get_repositories');
@@ -161,6 +305,47 @@ function App() {
     }
   };
 
+  const loadRepositoryGroups = async () => {
+    try {
+      const groups = await invoke<RepositoryGroup[]>('get_repository_groups');
+      setRepositoryGroups(groups);
+    } catch (error) {
+      console.error('Failed to load repository groups:', error);
+    }
+  };
+
+  // 分组变化会改变分组统计，重新加载数据
+  const handleGroupChange = async (change: Promise<unknown>) => {
+    try {
+      await change;
+    } catch (error) {
+      alert(error);
+    }
+    await loadRepositoryGroups();
+    if (repositories.length > 0) {
+      loadData();
+    }
+  };
+
+  // 名称和颜色会出现在统计中，重新加载数据
+  const handleUpdateRepository = async (id: number, changes: RepositoryChanges) => {
+    try {
+      await invoke('update_repository', {
+        id,
+        displayName: changes.display_name,
+        color: changes.color,
+        description: changes.description,
+        archived: changes.archived
+      });
+    } catch (error) {
+      alert(error);
+    }
+    await loadRepositories();
+    if (repositories.length > 0) {
+      loadData();
+    }
+  };
+
   const handleAddRepository = async () => {
     try {
       const selected = await open({
@@ -184,6 +369,7 @@ function App() {
     try {
       await invoke('remove_repository', { id });
       await loadRepositories();
+      await loadRepositoryGroups();
     } catch (error) {
       console.error('Failed to remove repository:', error);
     }
@@ -206,7 +392,7 @@ function App() {
         setLoadingProgress({ 
           current: 0, 
           total: 1, 
-          message: `开始全量刷新仓库: ${selectedRepo.name}` 
+          message: `开始全量刷新仓库: ${repositoryLabel(selectedRepo)}` 
         });
         
         // For long-running repository scans, show fake progress
@@ -218,7 +404,7 @@ function App() {
             setLoadingProgress({ 
               current: fakeProgressCurrent / fakeProgressTotal, 
               total: 1, 
-              message: `正在刷新仓库: ${selectedRepo.name} - ${fakeProgressCurrent}%` 
+              message: `正在刷新仓库: ${repositoryLabel(selectedRepo)} - ${fakeProgressCurrent}%` 
             });
           }
         }, 50);
@@ -232,20 +418,21 @@ function App() {
         setLoadingProgress({ 
           current: 1, 
           total: 1, 
-          message: `刷新完成 ${selectedRepo.name}，正在加载数据...` 
+          message: `刷新完成 ${repositoryLabel(selectedRepo)}，正在加载数据...` 
         });
       } else {
-        // All repositories refresh
-        setLoadingProgress({ current: 0, total: repositories.length, message: '开始全量刷新所有仓库...' });
+        // All repositories refresh, archived ones are left out
+        const activeRepositories = repositories.filter(repo => !repo.archived);
+        setLoadingProgress({ current: 0, total: activeRepositories.length, message: '开始全量刷新所有仓库...' });
         
         // Force scan all repositories (full scan)
-        for (let i = 0; i < repositories.length; i++) {
-          const repo = repositories[i];
+        for (let i = 0; i < activeRepositories.length; i++) {
+          const repo = activeRepositories[i];
           // Update progress
           setLoadingProgress({ 
             current: i, 
-            total: repositories.length, 
-            message: `正在刷新仓库: ${repo.name} (${i+1}/${repositories.length})` 
+            total: activeRepositories.length, 
+            message: `正在刷新仓库: ${repositoryLabel(repo)} (${i+1}/${activeRepositories.length})` 
           });
           
           // For long-running repository scans, show fake progress
@@ -256,8 +443,8 @@ function App() {
               fakeProgressCurrent += 1;
               setLoadingProgress({ 
                 current: i + (fakeProgressCurrent / fakeProgressTotal), 
-                total: repositories.length, 
-                message: `正在刷新仓库: ${repo.name} - ${fakeProgressCurrent}%` 
+                total: activeRepositories.length, 
+                message: `正在刷新仓库: ${repositoryLabel(repo)} - ${fakeProgressCurrent}%` 
               });
             }
           }, 50);
@@ -270,8 +457,8 @@ function App() {
         }
         
         setLoadingProgress({ 
-          current: repositories.length, 
-          total: repositories.length, 
+          current: activeRepositories.length, 
+          total: activeRepositories.length, 
           message: '刷新完成，正在加载数据...' 
         });
       }
@@ -301,7 +488,7 @@ function App() {
         setLoadingProgress({ 
           current: 0, 
           total: 1, 
-          message: `开始刷新仓库: ${selectedRepo.name} 过去一天数据` 
+          message: `开始刷新仓库: ${repositoryLabel(selectedRepo)} 过去一天数据` 
         });
         
         // For long-running repository scans, show fake progress
@@ -313,7 +500,7 @@ function App() {
             setLoadingProgress({ 
               current: fakeProgressCurrent / fakeProgressTotal, 
               total: 1, 
-              message: `正在刷新仓库: ${selectedRepo.name} - ${fakeProgressCurrent}%` 
+              message: `正在刷新仓库: ${repositoryLabel(selectedRepo)} - ${fakeProgressCurrent}%` 
             });
           }
         }, 50);
@@ -327,20 +514,21 @@ function App() {
         setLoadingProgress({ 
           current: 1, 
           total: 1, 
-          message: `刷新完成 ${selectedRepo.name}，正在加载数据...` 
+          message: `刷新完成 ${repositoryLabel(selectedRepo)}，正在加载数据...` 
         });
       } else {
-        // All repositories refresh
-        setLoadingProgress({ current: 0, total: repositories.length, message: '开始刷新所有仓库过去一天数据...' });
+        // All repositories refresh, archived ones are left out
+        const activeRepositories = repositories.filter(repo => !repo.archived);
+        setLoadingProgress({ current: 0, total: activeRepositories.length, message: '开始刷新所有仓库过去一天数据...' });
         
         // Scan last 24 hours for all repositories
-        for (let i = 0; i < repositories.length; i++) {
-          const repo = repositories[i];
+        for (let i = 0; i < activeRepositories.length; i++) {
+          const repo = activeRepositories[i];
           // Update progress
           setLoadingProgress({ 
             current: i, 
-            total: repositories.length, 
-            message: `正在刷新仓库: ${repo.name} (${i+1}/${repositories.length})` 
+            total: activeRepositories.length, 
+            message: `正在刷新仓库: ${repositoryLabel(repo)} (${i+1}/${activeRepositories.length})` 
           });
           
           // For long-running repository scans, show fake progress
@@ -351,8 +539,8 @@ function App() {
               fakeProgressCurrent += 1;
               setLoadingProgress({ 
                 current: i + (fakeProgressCurrent / fakeProgressTotal), 
-                total: repositories.length, 
-                message: `正在刷新仓库: ${repo.name} - ${fakeProgressCurrent}%` 
+                total: activeRepositories.length, 
+                message: `正在刷新仓库: ${repositoryLabel(repo)} - ${fakeProgressCurrent}%` 
               });
             }
           }, 50);
@@ -365,8 +553,8 @@ function App() {
         }
         
         setLoadingProgress({ 
-          current: repositories.length, 
-          total: repositories.length, 
+          current: activeRepositories.length, 
+          total: activeRepositories.length, 
           message: '刷新完成，正在加载数据...' 
         });
       }
@@ -484,10 +672,13 @@ function App() {
             >
               <option value="">全部仓库</option>
               {repositories.map(repo => (
-                <option key={repo.id} value={repo.id}>{repo.name}</option>
+                <option key={repo.id} value={repo.id}>
+                  {repositoryLabel(repo)}{repo.archived ? ' (已归档)' : ''}
+                </option>
               ))}
             </select>
           </div>
+          <FilterPresets filter={backendFilter()} onApply={applyBackendFilter} />
           <div className="quick-filters">
             <button onClick={() => setFilter(prev => ({ 
               ...prev,
@@ -520,8 +711,27 @@ function App() {
             <button onClick={() => setFilter({})}>
               清除筛选
             </button>
+            <button onClick={() => {
+              const newState = !advancedFiltersOpen;
+              setAdvancedFiltersOpen(newState);
+              localStorage.setItem('advancedFiltersOpen', newState.toString());
+            }}>
+              高级筛选 {advancedFiltersOpen ? '▲' : '▼'}
+            </button>
           </div>
         </div>
+        {advancedFiltersOpen && (
+          <>
+            <FilterQueryBar onApply={applyBackendFilter} />
+            <AdvancedFilters
+              filter={filter}
+              authors={allAuthors}
+              repositories={repositories}
+              groups={repositoryGroups}
+              onFilterChange={(changes) => setFilter(prev => ({ ...prev, ...changes }))}
+            />
+          </>
+        )}
       </header>
 
       <div className="app-content">
@@ -529,8 +739,24 @@ function App() {
           {!sidebarCollapsed && (
             <RepositoryManager
               repositories={repositories}
+              groups={repositoryGroups}
               onAdd={handleAddRepository}
               onRemove={handleRemoveRepository}
+              onUpdate={handleUpdateRepository}
+              onCreateGroup={(name) => handleGroupChange(invoke('create_repository_group', { name }))}
+              onUpdateGroup={(id, changes) => handleGroupChange(invoke('update_repository_group', {
+                id,
+                name: changes.name,
+                repositoryIds: changes.repository_ids
+              }))}
+              onDeleteGroup={(id) => {
+                // 已删除的分组不再参与筛选
+                setFilter(prev => {
+                  const group_ids = prev.group_ids?.filter(groupId => groupId !== id);
+                  return { ...prev, group_ids: group_ids && group_ids.length > 0 ? group_ids : undefined };
+                });
+                handleGroupChange(invoke('delete_repository_group', { id }));
+              }}
             />
           )}
           <button 
@@ -566,6 +792,15 @@ function App() {
             >
               提交时间线
             </button>
+            <button 
+              className={activeTab === 'diff' ? 'active' : ''}
+              onClick={() => {
+                setActiveTab('diff');
+                localStorage.setItem('activeTab', 'diff');
+              }}
+            >
+              代码变更搜索
+            </button>
           </div>
 
           <div className="tab-content">
@@ -575,6 +810,12 @@ function App() {
             {activeTab === 'timeline' && (
               <Timeline 
                 commits={timeline} 
+                searchHits={searchHits}
+                repositories={repositories}
+                totalCount={timelineTotal}
+                hasMore={timelineCursor !== null}
+                loadingMore={timelineLoadingMore}
+                onLoadMore={loadMoreTimeline}
                 filter={{
                   searchTerm: filter.searchTerm
                 }}
@@ -586,6 +827,9 @@ function App() {
                 }}
               />
             )}
+            {activeTab === 'diff' && (
+              <DiffSearch filter={backendFilter()} repositories={repositories} />
+            )}
           </div>
         </main>
       </div>
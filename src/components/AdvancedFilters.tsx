@@ -0,0 +1,156 @@
+import React, { useState, useEffect } from 'react';
+import { Repository, RepositoryGroup, TimeFilter, repositoryLabel } from '../types';
+
+interface AdvancedFiltersProps {
+  filter: TimeFilter;
+  authors: string[];
+  repositories: Repository[];
+  groups: RepositoryGroup[];
+  onFilterChange: (changes: Partial<TimeFilter>) => void;
+}
+
+// 逗号分隔的列表，空列表视为不筛选
+const parseList = (text: string) => {
+  const items = text.split(',').map(item => item.trim()).filter(item => item !== '');
+  return items.length > 0 ? items : undefined;
+};
+
+const parseSize = (text: string) => {
+  const size = parseInt(text, 10);
+  return Number.isNaN(size) ? undefined : size;
+};
+
+const draftFromFilter = (filter: TimeFilter) => ({
+  branches: (filter.branches || []).join(', '),
+  paths: (filter.paths || []).join(', '),
+  min_size: filter.min_size?.toString() || '',
+  max_size: filter.max_size?.toString() || '',
+  message_pattern: filter.message_pattern || '',
+  exclude_message_pattern: filter.exclude_message_pattern || ''
+});
+
+const AdvancedFilters: React.FC<AdvancedFiltersProps> = ({ filter, authors, repositories, groups, onFilterChange }) => {
+  // 文本输入在失去焦点或回车时才生效，避免每次按键都重新查询
+  const [draft, setDraft] = useState(() => draftFromFilter(filter));
+
+  useEffect(() => {
+    setDraft(draftFromFilter(filter));
+  }, [filter.branches, filter.paths, filter.min_size, filter.max_size, filter.message_pattern, filter.exclude_message_pattern]);
+
+  const applyDraft = () => {
+    onFilterChange({
+      branches: parseList(draft.branches),
+      paths: parseList(draft.paths),
+      min_size: parseSize(draft.min_size),
+      max_size: parseSize(draft.max_size),
+      message_pattern: draft.message_pattern || undefined,
+      exclude_message_pattern: draft.exclude_message_pattern || undefined
+    });
+  };
+
+  const textInput = (field: keyof ReturnType<typeof draftFromFilter>, placeholder: string, type = 'text') => (
+    <input
+      type={type}
+      value={draft[field]}
+      placeholder={placeholder}
+      onChange={(e) => setDraft(prev => ({ ...prev, [field]: e.target.value }))}
+      onBlur={applyDraft}
+      onKeyDown={(e) => {
+        if (e.key === 'Enter') {
+          applyDraft();
+        }
+      }}
+    />
+  );
+
+  const selectedValues = (e: React.ChangeEvent<HTMLSelectElement>) =>
+    Array.from(e.target.selectedOptions).map(option => option.value);
+
+  return (
+    <div className="advanced-filters">
+      <div className="filter-group">
+        <label>多个作者:</label>
+        <select
+          multiple
+          value={filter.authors || []}
+          onChange={(e) => {
+            const selected = selectedValues(e);
+            onFilterChange({ authors: selected.length > 0 ? selected : undefined });
+          }}
+        >
+          {authors.map(author => (
+            <option key={author} value={author}>{author}</option>
+          ))}
+        </select>
+      </div>
+      <div className="filter-group">
+        <label>多个仓库:</label>
+        <select
+          multiple
+          value={(filter.repository_ids || []).map(String)}
+          onChange={(e) => {
+            const selected = selectedValues(e).map(Number);
+            onFilterChange({ repository_ids: selected.length > 0 ? selected : undefined });
+          }}
+        >
+          {repositories.map(repo => (
+            <option key={repo.id} value={repo.id}>{repositoryLabel(repo)}</option>
+          ))}
+        </select>
+      </div>
+      {groups.length > 0 && (
+        <div className="filter-group">
+          <label>仓库分组:</label>
+          <select
+            multiple
+            value={(filter.group_ids || []).map(String)}
+            onChange={(e) => {
+              const selected = selectedValues(e).map(Number);
+              onFilterChange({ group_ids: selected.length > 0 ? selected : undefined });
+            }}
+          >
+            {groups.map(group => (
+              <option key={group.id} value={group.id}>{group.name}</option>
+            ))}
+          </select>
+        </div>
+      )}
+      <div className="filter-group">
+        <label>分支:</label>
+        {textInput('branches', 'main, develop')}
+      </div>
+      <div className="filter-group">
+        <label>路径:</label>
+        {textInput('paths', 'src/**, *.rs')}
+      </div>
+      <div className="filter-group">
+        <label>最少修改行数:</label>
+        {textInput('min_size', '不限', 'number')}
+      </div>
+      <div className="filter-group">
+        <label>最多修改行数:</label>
+        {textInput('max_size', '不限', 'number')}
+      </div>
+      <div className="filter-group">
+        <label>消息匹配 (正则):</label>
+        {textInput('message_pattern', '^feat')}
+      </div>
+      <div className="filter-group">
+        <label>消息排除 (正则):</label>
+        {textInput('exclude_message_pattern', 'wip')}
+      </div>
+      <div className="filter-group filter-checkbox">
+        <label>
+          <input
+            type="checkbox"
+            checked={filter.include_merges || false}
+            onChange={(e) => onFilterChange({ include_merges: e.target.checked || undefined })}
+          />
+          包含合并提交
+        </label>
+      </div>
+    </div>
+  );
+};
+
+export default AdvancedFilters;
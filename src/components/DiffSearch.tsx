@@ -0,0 +1,174 @@
+import React, { useState, useEffect, useRef, useMemo } from 'react';
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+import { useNavigate } from 'react-router-dom';
+import { DiffSearchFinished, DiffSearchMatch, DiffSearchProgress, PickaxeMode, Repository, TimeFilter } from '../types';
+import RepositoryBadge from './RepositoryBadge';
+
+interface DiffSearchProps {
+  // 与统计和时间线相同的筛选条件
+  filter: TimeFilter;
+  // 用于显示提交所属仓库的名称和颜色
+  repositories: Repository[];
+}
+
+const DiffSearch: React.FC<DiffSearchProps> = ({ filter, repositories }) => {
+  const navigate = useNavigate();
+  const repositoriesById = useMemo(() => new Map(repositories.map(repo => [repo.id, repo])), [repositories]);
+  const [pattern, setPattern] = useState('');
+  const [regex, setRegex] = useState(false);
+  const [ignoreCase, setIgnoreCase] = useState(false);
+  const [mode, setMode] = useState<PickaxeMode>('occurrences');
+  const [matches, setMatches] = useState<DiffSearchMatch[]>([]);
+  const [progress, setProgress] = useState<DiffSearchProgress | null>(null);
+  const [finished, setFinished] = useState<DiffSearchFinished | null>(null);
+  const [error, setError] = useState<string | null>(null);
+  // 当前搜索的 ID，只接收这次搜索的事件
+  const searchId = useRef<string | null>(null);
+
+  useEffect(() => {
+    const unlisteners = [
+      listen<DiffSearchMatch>('diff-search-match', (event) => {
+        if (event.payload.search_id === searchId.current) {
+          setMatches(prev => [...prev, event.payload]);
+        }
+      }),
+      listen<DiffSearchProgress>('diff-search-progress', (event) => {
+        if (event.payload.search_id === searchId.current) {
+          setProgress(event.payload);
+        }
+      })
+    ];
+    return () => {
+      unlisteners.forEach(unlisten => unlisten.then(fn => fn()));
+    };
+  }, []);
+
+  const running = searchId.current !== null && finished === null && error === null;
+
+  const startSearch = async () => {
+    if (!pattern) {
+      return;
+    }
+    if (searchId.current && running) {
+      await invoke('cancel_diff_search', { searchId: searchId.current });
+    }
+
+    const id = `diff-search-${Date.now()}-${Math.random().toString(36).slice(2, 8)}`;
+    searchId.current = id;
+    setMatches([]);
+    setProgress(null);
+    setFinished(null);
+    setError(null);
+
+    try {
+      const result = await invoke<DiffSearchFinished>('search_diffs', {
+        searchId: id,
+        query: { pattern, regex, ignore_case: ignoreCase, mode },
+        filter
+      });
+      if (searchId.current === id) {
+        setFinished(result);
+      }
+    } catch (e) {
+      if (searchId.current === id) {
+        setError(String(e));
+      }
+    }
+  };
+
+  const cancelSearch = async () => {
+    if (searchId.current) {
+      await invoke('cancel_diff_search', { searchId: searchId.current });
+    }
+  };
+
+  const renderPatch = (patch: string) =>
+    patch.split('\n').filter(line => line !== '').map((line, index) => (
+      <div
+        key={index}
+        className={line.startsWith('+') ? 'diff-search-line added' : line.startsWith('-') ? 'diff-search-line removed' : 'diff-search-line'}
+      >
+        {line}
+      </div>
+    ));
+
+  return (
+    <div className="diff-search">
+      <div className="diff-search-form">
+        <input
+          type="text"
+          value={pattern}
+          placeholder={regex ? '正则表达式，例如 fn\\s+main' : '要查找的字符串'}
+          onChange={(e) => setPattern(e.target.value)}
+          onKeyDown={(e) => {
+            if (e.key === 'Enter') {
+              startSearch();
+            }
+          }}
+          spellCheck={false}
+        />
+        <select value={mode} onChange={(e) => setMode(e.target.value as PickaxeMode)}>
+          <option value="occurrences">出现次数变化 (-S)</option>
+          <option value="lines">修改的行匹配 (-G)</option>
+        </select>
+        <label>
+          <input type="checkbox" checked={regex} onChange={(e) => setRegex(e.target.checked)} />
+          正则
+        </label>
+        <label>
+          <input type="checkbox" checked={ignoreCase} onChange={(e) => setIgnoreCase(e.target.checked)} />
+          忽略大小写
+        </label>
+        {running ? (
+          <button onClick={cancelSearch}>取消</button>
+        ) : (
+          <button onClick={startSearch} disabled={!pattern}>搜索</button>
+        )}
+      </div>
+
+      <div className="diff-search-status">
+        {running && (
+          <span>
+            搜索中... {progress ? `${progress.checked} / ${progress.total} 个提交` : ''}，已找到 {matches.length} 个
+          </span>
+        )}
+        {finished && (
+          <span>
+            {finished.cancelled ? '已取消，' : ''}检查了 {finished.checked} / {finished.total} 个提交，
+            找到 {finished.matches} 个匹配{finished.cached ? '（缓存结果）' : ''}，耗时 {finished.elapsed_ms} ms
+          </span>
+        )}
+        {error && <span className="diff-search-error">{error}</span>}
+      </div>
+
+      <div className="diff-search-results">
+        {matches.map(match => (
+          <div key={`${match.commit.repository_id}-${match.commit.id}`} className="diff-search-match">
+            <div
+              className="diff-search-commit"
+              onClick={() => navigate(`/commit/${match.commit.repository_id}/${match.commit.id}`)}
+            >
+              <span className="commit-hash">{match.commit.id.slice(0, 8)}</span>
+              <RepositoryBadge repository={repositoriesById.get(match.commit.repository_id)} fallbackName={match.commit.repository_name} />
+              <strong>{match.commit.author}</strong>
+              <span className="diff-search-message">{match.commit.message.split('\n')[0]}</span>
+              <span className="commit-time">{new Date(match.commit.timestamp).toLocaleString('zh-CN')}</span>
+            </div>
+            {match.hunks.map((hunk, index) => (
+              <div key={index} className="diff-search-hunk">
+                <div className="diff-search-hunk-header">
+                  <span className="file-path">{hunk.file_path}</span>
+                  <span className="hunk-header">{hunk.header}</span>
+                </div>
+                <pre>{renderPatch(hunk.patch)}</pre>
+              </div>
+            ))}
+          </div>
+        ))}
+      </div>
+    </div>
+  );
+};
+
+export default DiffSearch;
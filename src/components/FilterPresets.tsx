@@ -0,0 +1,117 @@
+import React, { useState, useEffect } from 'react';
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+import { FilterPreset, TimeFilter } from '../types';
+
+interface FilterPresetsProps {
+  // 当前的筛选条件，后端格式，保存预设时使用
+  filter: TimeFilter;
+  onApply: (filter: TimeFilter) => void;
+}
+
+const FilterPresets: React.FC<FilterPresetsProps> = ({ filter, onApply }) => {
+  const [presets, setPresets] = useState<FilterPreset[]>([]);
+  const [selectedId, setSelectedId] = useState<number | null>(null);
+
+  useEffect(() => {
+    invoke<FilterPreset[]>('get_filter_presets')
+      .then(setPresets)
+      .catch(error => console.error('获取筛选预设失败:', error));
+
+    // 其他窗口修改预设后同步列表
+    const unlisten = listen<FilterPreset[]>('filter-presets-changed', (event) => {
+      setPresets(event.payload);
+    });
+    return () => {
+      unlisten.then(fn => fn());
+    };
+  }, []);
+
+  const selected = presets.find(preset => preset.id === selectedId) || null;
+
+  const handleSelect = (id: number | null) => {
+    setSelectedId(id);
+    const preset = presets.find(preset => preset.id === id);
+    if (preset) {
+      onApply(preset.filter);
+    }
+  };
+
+  const handleSave = async () => {
+    const name = window.prompt('预设名称:');
+    if (!name?.trim()) {
+      return;
+    }
+    try {
+      const preset = await invoke<FilterPreset>('create_filter_preset', { name, filter });
+      setSelectedId(preset.id);
+    } catch (error) {
+      alert(error);
+    }
+  };
+
+  const handleOverwrite = async () => {
+    if (!selected || !window.confirm(`用当前筛选条件覆盖预设「${selected.name}」？`)) {
+      return;
+    }
+    try {
+      await invoke('update_filter_preset', { id: selected.id, filter });
+    } catch (error) {
+      alert(error);
+    }
+  };
+
+  const handleRename = async () => {
+    if (!selected) {
+      return;
+    }
+    const name = window.prompt('新的预设名称:', selected.name);
+    if (!name?.trim() || name === selected.name) {
+      return;
+    }
+    try {
+      await invoke('update_filter_preset', { id: selected.id, name });
+    } catch (error) {
+      alert(error);
+    }
+  };
+
+  const handleDelete = async () => {
+    if (!selected || !window.confirm(`删除预设「${selected.name}」？`)) {
+      return;
+    }
+    try {
+      await invoke('delete_filter_preset', { id: selected.id });
+      setSelectedId(null);
+    } catch (error) {
+      alert(error);
+    }
+  };
+
+  return (
+    <div className="filter-group filter-presets">
+      <label>预设:</label>
+      <div className="filter-presets-controls">
+        <select
+          value={selected ? selected.id : ''}
+          onChange={(e) => handleSelect(e.target.value ? Number(e.target.value) : null)}
+        >
+          <option value="">选择预设</option>
+          {presets.map(preset => (
+            <option key={preset.id} value={preset.id}>{preset.name}</option>
+          ))}
+        </select>
+        <button onClick={handleSave} title="将当前筛选条件保存为预设">保存</button>
+        {selected && (
+          <>
+            <button onClick={handleOverwrite} title="用当前筛选条件覆盖此预设">覆盖</button>
+            <button onClick={handleRename}>重命名</button>
+            <button onClick={handleDelete}>删除</button>
+          </>
+        )}
+      </div>
+    </div>
+  );
+};
+
+export default FilterPresets;
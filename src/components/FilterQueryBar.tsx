@@ -0,0 +1,183 @@
+import React, { useState, useRef, useEffect } from 'react';
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+import { FilterQueryError, FilterQuerySuggestion, ParsedFilterQuery, SettingChanged, TimeFilter } from '../types';
+
+const FILTER_QUERY_SETTING = 'filter_query';
+const LEGACY_FILTER_QUERY_KEY = 'filter-query';
+
+interface FilterQueryBarProps {
+  onApply: (filter: TimeFilter) => void;
+}
+
+const FilterQueryBar: React.FC<FilterQueryBarProps> = ({ onApply }) => {
+  const inputRef = useRef<HTMLInputElement>(null);
+  const request = useRef(0);
+  const [query, setQuery] = useState('');
+  const [errors, setErrors] = useState<FilterQueryError[]>([]);
+  const [suggestions, setSuggestions] = useState<FilterQuerySuggestion[]>([]);
+  const [activeSuggestion, setActiveSuggestion] = useState(0);
+
+  // 启动时恢复上次应用的语句，之后其他窗口应用时同步
+  useEffect(() => {
+    // 旧版本保存在 localStorage 的语句，后端没有时迁移一次
+    const legacyQuery = localStorage.getItem(LEGACY_FILTER_QUERY_KEY);
+
+    invoke<Record<string, unknown>>('get_settings')
+      .then(settings => {
+        const saved = settings[FILTER_QUERY_SETTING];
+        if (typeof saved === 'string') {
+          setQuery(prev => prev || saved);
+        } else if (legacyQuery) {
+          setQuery(prev => prev || legacyQuery);
+          invoke('set_setting', { key: FILTER_QUERY_SETTING, value: legacyQuery })
+            .catch(error => console.error('保存筛选语句失败:', error));
+        }
+        localStorage.removeItem(LEGACY_FILTER_QUERY_KEY);
+      })
+      .catch(error => console.error('恢复筛选语句失败:', error));
+
+    const unlisten = listen<SettingChanged>('settings-changed', (event) => {
+      const { key, value } = event.payload;
+      if (key === FILTER_QUERY_SETTING) {
+        setQuery(typeof value === 'string' ? value : '');
+        setErrors([]);
+      }
+    });
+    return () => {
+      unlisten.then(fn => fn());
+    };
+  }, []);
+
+  // 每次输入都重新解析并获取补全，只保留最新一次请求的结果
+  const update = async (text: string, cursor: number) => {
+    const current = ++request.current;
+    try {
+      const [parsed, completions] = await Promise.all([
+        invoke<ParsedFilterQuery>('parse_filter_query', { query: text }),
+        invoke<FilterQuerySuggestion[]>('complete_filter_query', { query: text, cursor })
+      ]);
+      if (current !== request.current) {
+        return;
+      }
+      setErrors(parsed.errors);
+      setSuggestions(completions);
+      setActiveSuggestion(0);
+    } catch (error) {
+      console.error('解析筛选语句失败:', error);
+    }
+  };
+
+  const handleChange = (e: React.ChangeEvent<HTMLInputElement>) => {
+    setQuery(e.target.value);
+    update(e.target.value, e.target.selectionStart ?? e.target.value.length);
+  };
+
+  const acceptSuggestion = (suggestion: FilterQuerySuggestion) => {
+    // 值补全后加空格，字段名补全后继续输入值
+    const suffix = suggestion.kind === 'key' ? '' : ' ';
+    const text = query.slice(0, suggestion.start) + suggestion.text + suffix + query.slice(suggestion.end);
+    const cursor = suggestion.start + suggestion.text.length + suffix.length;
+    setQuery(text);
+    update(text, cursor);
+    setTimeout(() => {
+      inputRef.current?.focus();
+      inputRef.current?.setSelectionRange(cursor, cursor);
+    }, 0);
+  };
+
+  const apply = async () => {
+    try {
+      const parsed = await invoke<ParsedFilterQuery>('parse_filter_query', { query });
+      setErrors(parsed.errors);
+      if (parsed.filter) {
+        invoke('set_setting', { key: FILTER_QUERY_SETTING, value: query })
+          .catch(error => console.error('保存筛选语句失败:', error));
+        setSuggestions([]);
+        onApply(parsed.filter);
+      }
+    } catch (error) {
+      console.error('解析筛选语句失败:', error);
+    }
+  };
+
+  const handleKeyDown = (e: React.KeyboardEvent<HTMLInputElement>) => {
+    if (suggestions.length > 0) {
+      if (e.key === 'ArrowDown' || e.key === 'ArrowUp') {
+        e.preventDefault();
+        const step = e.key === 'ArrowDown' ? 1 : suggestions.length - 1;
+        setActiveSuggestion(prev => (prev + step) % suggestions.length);
+        return;
+      }
+      if (e.key === 'Tab') {
+        e.preventDefault();
+        acceptSuggestion(suggestions[activeSuggestion]);
+        return;
+      }
+      if (e.key === 'Escape') {
+        setSuggestions([]);
+        return;
+      }
+    }
+    if (e.key === 'Enter') {
+      apply();
+    }
+  };
+
+  // 在语句下方标出第一个错误的位置
+  const firstError = errors[0];
+
+  return (
+    <div className="filter-query-bar">
+      <div className="filter-query-input">
+        <input
+          ref={inputRef}
+          type="text"
+          value={query}
+          placeholder="author:alice repo:backend path:src/** after:2025-01-01 -msg:/wip/ size:>100"
+          onChange={handleChange}
+          onKeyDown={handleKeyDown}
+          onBlur={() => setTimeout(() => setSuggestions([]), 150)}
+          className={errors.length > 0 ? 'has-error' : ''}
+          spellCheck={false}
+        />
+        <button onClick={apply} disabled={errors.length > 0}>应用</button>
+        {suggestions.length > 0 && (
+          <ul className="filter-query-suggestions">
+            {suggestions.map((suggestion, index) => (
+              <li
+                key={`${suggestion.kind}-${suggestion.text}`}
+                className={index === activeSuggestion ? 'active' : ''}
+                onMouseDown={(e) => {
+                  e.preventDefault();
+                  acceptSuggestion(suggestion);
+                }}
+              >
+                <span className="suggestion-text">{suggestion.text}</span>
+                {suggestion.description && (
+                  <span className="suggestion-description">{suggestion.description}</span>
+                )}
+              </li>
+            ))}
+          </ul>
+        )}
+      </div>
+      {firstError && (
+        <div className="filter-query-errors">
+          <div className="filter-query-highlight">
+            {query.slice(0, firstError.start)}
+            <mark>{query.slice(firstError.start, firstError.end) || ' '}</mark>
+            {query.slice(firstError.end)}
+          </div>
+          {errors.map((error, index) => (
+            <div key={index} className="filter-query-error">
+              第 {error.start + 1} 个字符: {error.message}
+            </div>
+          ))}
+        </div>
+      )}
+    </div>
+  );
+};
+
+export default FilterQueryBar;
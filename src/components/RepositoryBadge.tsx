@@ -0,0 +1,20 @@
+import React from 'react';
+import { Repository, repositoryLabel } from '../types';
+
+interface RepositoryBadgeProps {
+  repository?: Repository;
+  // 仓库已不在列表中时显示的名称，即提交保存的文件夹名
+  fallbackName: string;
+}
+
+// 提交所属仓库的标记，使用仓库的显示名称和颜色
+const RepositoryBadge: React.FC<RepositoryBadgeProps> = ({ repository, fallbackName }) => (
+  <span
+    className="repository-badge"
+    style={repository?.color ? { background: repository.color } : undefined}
+  >
+    {repository ? repositoryLabel(repository) : fallbackName}
+  </span>
+);
+
+export default RepositoryBadge;
@@ -1,17 +1,75 @@
-import React from 'react';
-import { Repository } from '../types';
+import React, { useState } from 'react';
+import { Repository, RepositoryChanges, RepositoryGroup, repositoryLabel } from '../types';
 
 interface RepositoryManagerProps {
   repositories: Repository[];
+  groups: RepositoryGroup[];
   onAdd: () => void;
   onRemove: (id: number) => void;
+  onUpdate: (id: number, changes: RepositoryChanges) => void;
+  onCreateGroup: (name: string) => void;
+  onUpdateGroup: (id: number, changes: { name?: string; repository_ids?: number[] }) => void;
+  onDeleteGroup: (id: number) => void;
 }
 
 const RepositoryManager: React.FC<RepositoryManagerProps> = ({
   repositories,
+  groups,
   onAdd,
   onRemove,
+  onUpdate,
+  onCreateGroup,
+  onUpdateGroup,
+  onDeleteGroup,
 }) => {
+  // 正在编辑成员的分组
+  const [editingGroupId, setEditingGroupId] = useState<number | null>(null);
+  // 正在编辑的仓库及其未保存的信息
+  const [editingRepositoryId, setEditingRepositoryId] = useState<number | null>(null);
+  const [draft, setDraft] = useState<RepositoryChanges>({});
+
+  const startEditing = (repo: Repository) => {
+    setEditingRepositoryId(repo.id);
+    setDraft({
+      display_name: repo.display_name || '',
+      color: repo.color || '',
+      description: repo.description || '',
+    });
+  };
+
+  const saveRepository = (repo: Repository) => {
+    // 空字符串会清除对应的信息
+    onUpdate(repo.id, draft);
+    setEditingRepositoryId(null);
+  };
+
+  const handleCreateGroup = () => {
+    const name = window.prompt('分组名称，例如 工作项目:');
+    if (name?.trim()) {
+      onCreateGroup(name);
+    }
+  };
+
+  const handleRenameGroup = (group: RepositoryGroup) => {
+    const name = window.prompt('新的分组名称:', group.name);
+    if (name?.trim() && name !== group.name) {
+      onUpdateGroup(group.id, { name });
+    }
+  };
+
+  const handleDeleteGroup = (group: RepositoryGroup) => {
+    if (window.confirm(`删除分组「${group.name}」？分组中的仓库不会被删除。`)) {
+      onDeleteGroup(group.id);
+    }
+  };
+
+  const toggleMember = (group: RepositoryGroup, repositoryId: number) => {
+    const repository_ids = group.repository_ids.includes(repositoryId)
+      ? group.repository_ids.filter(id => id !== repositoryId)
+      : [...group.repository_ids, repositoryId];
+    onUpdateGroup(group.id, { repository_ids });
+  };
+
   return (
     <div className="repository-manager">
       <h3>仓库管理</h3>
@@ -28,26 +86,113 @@ const RepositoryManager: React.FC<RepositoryManagerProps> = ({
           </div>
         ) : (
           repositories.map((repo) => (
-            <div key={repo.id} className="repository-item">
+            <div key={repo.id} className={`repository-item ${repo.archived ? 'archived' : ''}`}>
               <div>
-                <div className="repository-name">{repo.name}</div>
+                <div className="repository-name">
+                  {repo.color && <span className="repository-color" style={{ background: repo.color }} />}
+                  {repositoryLabel(repo)}
+                  {repo.archived && <span className="archived-badge">已归档</span>}
+                </div>
+                {repo.description && <div className="repository-description">{repo.description}</div>}
                 <div className="repository-path">{repo.path}</div>
                 {repo.last_scanned && (
                   <div className="last-scanned">
                     最后扫描: {new Date(repo.last_scanned).toLocaleString('zh-CN')}
                   </div>
                 )}
+                <div className="repository-groups">
+                  {groups.filter(group => group.repository_ids.includes(repo.id)).map(group => (
+                    <span key={group.id} className="group-tag">{group.name}</span>
+                  ))}
+                </div>
+                {editingRepositoryId === repo.id && (
+                  <div className="repository-edit">
+                    <input
+                      type="text"
+                      value={draft.display_name}
+                      placeholder={`显示名称，默认 ${repo.name}`}
+                      onChange={(e) => setDraft(prev => ({ ...prev, display_name: e.target.value }))}
+                    />
+                    <input
+                      type="text"
+                      value={draft.description}
+                      placeholder="描述"
+                      onChange={(e) => setDraft(prev => ({ ...prev, description: e.target.value }))}
+                    />
+                    <div className="repository-edit-color">
+                      <input
+                        type="color"
+                        value={draft.color || '#5470c6'}
+                        onChange={(e) => setDraft(prev => ({ ...prev, color: e.target.value }))}
+                      />
+                      {draft.color && (
+                        <button onClick={() => setDraft(prev => ({ ...prev, color: '' }))}>清除颜色</button>
+                      )}
+                    </div>
+                    <div className="repository-edit-actions">
+                      <button onClick={() => saveRepository(repo)}>保存</button>
+                      <button onClick={() => setEditingRepositoryId(null)}>取消</button>
+                    </div>
+                  </div>
+                )}
+              </div>
+              <div className="repository-actions">
+                <button onClick={() => editingRepositoryId === repo.id ? setEditingRepositoryId(null) : startEditing(repo)}>
+                  编辑
+                </button>
+                <button
+                  onClick={() => onUpdate(repo.id, { archived: !repo.archived })}
+                  title={repo.archived ? undefined : '归档的仓库在刷新全部仓库时跳过'}
+                >
+                  {repo.archived ? '取消归档' : '归档'}
+                </button>
+                <button
+                  className="remove-btn"
+                  onClick={() => onRemove(repo.id)}
+                >
+                  删除
+                </button>
               </div>
-              <button
-                className="remove-btn"
-                onClick={() => onRemove(repo.id)}
-              >
-                删除
-              </button>
             </div>
           ))
         )}
       </div>
+
+      <h3>仓库分组</h3>
+
+      <button className="add-group-btn" onClick={handleCreateGroup}>
+        + 新建分组
+      </button>
+
+      <div className="group-list">
+        {groups.map(group => (
+          <div key={group.id} className="group-item">
+            <div className="group-header">
+              <span className="group-name">{group.name}</span>
+              <span className="group-count">{group.repository_ids.length} 个仓库</span>
+              <button onClick={() => setEditingGroupId(editingGroupId === group.id ? null : group.id)}>
+                {editingGroupId === group.id ? '完成' : '成员'}
+              </button>
+              <button onClick={() => handleRenameGroup(group)}>重命名</button>
+              <button className="remove-btn" onClick={() => handleDeleteGroup(group)}>删除</button>
+            </div>
+            {editingGroupId === group.id && (
+              <div className="group-members">
+                {repositories.map(repo => (
+                  <label key={repo.id}>
+                    <input
+                      type="checkbox"
+                      checked={group.repository_ids.includes(repo.id)}
+                      onChange={() => toggleMember(group, repo.id)}
+                    />
+                    {repositoryLabel(repo)}
+                  </label>
+                ))}
+              </div>
+            )}
+          </div>
+        ))}
+      </div>
     </div>
   );
 };
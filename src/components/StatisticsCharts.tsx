@@ -1,13 +1,31 @@
 import React from 'react';
 import ReactECharts from 'echarts-for-react';
 import 'echarts-wordcloud'; // Import wordcloud extension
-import { Statistics, TimeFilter } from '../types';
+import { RepositoryStats, Statistics, TimeFilter } from '../types';
 
 interface StatisticsChartsProps {
   statistics: Statistics | null;
   filter?: TimeFilter;
 }
 
+// 饼图按名称区分扇区：同名仓库加上路径末尾的两级目录，仍相同时再加上 ID
+const uniqueRepositoryLabels = (repositories: [string, RepositoryStats][]) => {
+  const countLabels = (labels: string[]) =>
+    labels.reduce((counts, label) => counts.set(label, (counts.get(label) || 0) + 1), new Map<string, number>());
+
+  const nameCounts = countLabels(repositories.map(([, repo]) => repo.name));
+  const labels = repositories.map(([, repo]) => {
+    if (nameCounts.get(repo.name)! < 2) {
+      return repo.name;
+    }
+    const pathTail = repo.path.split(/[\\/]/).filter(Boolean).slice(-2).join('/');
+    return `${repo.name} (${pathTail})`;
+  });
+
+  const labelCounts = countLabels(labels);
+  return labels.map((label, index) => labelCounts.get(label)! < 2 ? label : `${label} #${repositories[index][0]}`);
+};
+
 const StatisticsCharts: React.FC<StatisticsChartsProps> = ({ statistics, filter }) => {
   const getWeeklyChartOption = () => {
     // 定义星期名称
@@ -34,7 +52,7 @@ const StatisticsCharts: React.FC<StatisticsChartsProps> = ({ statistics, filter
         trigger: 'axis',
         formatter: (params: any) => {
           const data = weeklyData[params[0].dataIndex];
-          return `${data.name}<br/>` +
+          return `${labels[params.dataIndex]}<br/>` +
                  `新增: ${data.additions} 行<br/>` +
                  `删除: ${data.deletions} 行<br/>` +
                  `提交: ${data.commits} 次`;
@@ -269,8 +287,10 @@ const StatisticsCharts: React.FC<StatisticsChartsProps> = ({ statistics, filter
 
   const getRepositoryChartOption = () => {
     // Sort repositories by total contributions (additions + deletions) in descending order
-    const sortedRepos = Object.entries(statistics.repositories)
-      .sort((a, b) => (b[1].additions + b[1].deletions) - (a[1].additions + a[1].deletions));
+    const sortedEntries = Object.entries(statistics.repositories)
+      .sort(([, a], [, b]) => (b.additions + b.deletions) - (a.additions + a.deletions));
+    const sortedRepos = sortedEntries.map(([, repo]) => repo);
+    const labels = uniqueRepositoryLabels(sortedEntries);
     
     return {
       title: {
@@ -280,8 +300,8 @@ const StatisticsCharts: React.FC<StatisticsChartsProps> = ({ statistics, filter
       tooltip: {
         trigger: 'item',
         formatter: (params: any) => {
-          const [name, data] = sortedRepos[params.dataIndex];
-          return `${name}<br/>` +
+          const data = sortedRepos[params.dataIndex];
+          return `${labels[params.dataIndex]}<br/>` +
                  `新增: ${data.additions} 行<br/>` +
                  `删除: ${data.deletions} 行<br/>` +
                  `提交: ${data.commits} 次`;
@@ -290,9 +310,11 @@ const StatisticsCharts: React.FC<StatisticsChartsProps> = ({ statistics, filter
       series: [{
         type: 'pie',
         radius: ['40%', '70%'],
-        data: sortedRepos.map(([name, data]) => ({
-          name: name.split('/').pop() || name, // 只显示仓库名
-          value: data.additions + data.deletions
+        data: sortedRepos.map((data, index) => ({
+          name: labels[index],
+          value: data.additions + data.deletions,
+          // 仓库设置了颜色时使用该颜色
+          itemStyle: data.color ? { color: data.color } : undefined
         })),
         emphasis: {
           itemStyle: {
@@ -305,6 +327,62 @@ const StatisticsCharts: React.FC<StatisticsChartsProps> = ({ statistics, filter
     };
   };
 
+  // 各仓库分组的贡献，一个仓库属于多个分组时计入每个分组
+  const getGroupChartOption = () => {
+    const groups = statistics.groups;
+
+    return {
+      title: {
+        text: '分组贡献对比',
+        left: 'center'
+      },
+      tooltip: {
+        trigger: 'axis',
+        formatter: (params: any) => {
+          const group = groups[params[0].dataIndex];
+          return `${group.name}<br/>` +
+                 `新增: ${group.additions} 行<br/>` +
+                 `删除: ${group.deletions} 行<br/>` +
+                 `提交: ${group.commits} 次<br/>` +
+                 `活跃仓库: ${group.repositories} 个<br/>` +
+                 `作者: ${group.authors} 人`;
+        }
+      },
+      legend: {
+        data: ['新增', '删除'],
+        top: '10%'
+      },
+      xAxis: {
+        type: 'category',
+        data: groups.map(group => group.name)
+      },
+      yAxis: {
+        type: 'value',
+        name: '代码行数'
+      },
+      series: [
+        {
+          name: '新增',
+          data: groups.map(group => group.additions),
+          type: 'bar',
+          stack: 'total',
+          itemStyle: {
+            color: '#28a745'
+          }
+        },
+        {
+          name: '删除',
+          data: groups.map(group => group.deletions),
+          type: 'bar',
+          stack: 'total',
+          itemStyle: {
+            color: '#dc3545'
+          }
+        }
+      ]
+    };
+  };
+
   // Calendar view for the selected date range or last 30 days
   const getCalendarChartOption = () => {
     let startDate, endDate;
@@ -922,6 +1000,17 @@ const StatisticsCharts: React.FC<StatisticsChartsProps> = ({ statistics, filter
         />
       </div>
       
+      {/* Repository Groups */}
+      {statistics.groups.length > 0 && (
+        <div className="chart-card">
+          <ReactECharts
+            option={getGroupChartOption()}
+            style={{ height: '300px' }}
+            notMerge={true}
+          />
+        </div>
+      )}
+      
       {/* Commit Size Distribution */}
       <div className="chart-card">
         <ReactECharts
@@ -1,24 +1,48 @@
-import React, { useState, useEffect, useRef } from 'react';
-import { CommitData } from '../types';
+import React, { useState, useEffect, useRef, useMemo } from 'react';
+import { CommitData, Repository, SearchHit } from '../types';
+import RepositoryBadge from './RepositoryBadge';
 import { convertGitUrlToGitLabCommitUrl } from '../utils/gitUrlConverter';
 import { useNavigate } from 'react-router-dom';
 import { open } from '@tauri-apps/plugin-shell';
 
 interface TimelineProps {
   commits: CommitData[];
+  searchHits: SearchHit[] | null;
+  // 用于显示提交所属仓库的名称和颜色
+  repositories: Repository[];
+  totalCount: number;
+  hasMore: boolean;
+  loadingMore: boolean;
+  onLoadMore: () => void;
   filter: {
     searchTerm?: string;
   };
   onFilterChange: (newFilter: { searchTerm?: string }) => void;
 }
 
-const Timeline: React.FC<TimelineProps> = ({ commits, filter, onFilterChange }) => {
+const Timeline: React.FC<TimelineProps> = ({ commits, searchHits, repositories, totalCount, hasMore, loadingMore, onLoadMore, filter, onFilterChange }) => {
   const navigate = useNavigate();
   const timelineRef = useRef<HTMLDivElement>(null);
+  const repositoriesById = useMemo(() => new Map(repositories.map(repo => [repo.id, repo])), [repositories]);
   
   // 使用传入的筛选条件
   const { searchTerm = '' } = filter;
   
+  // 输入框内容停止变化一段时间后才搜索，避免每次按键都查询
+  const [searchInput, setSearchInput] = useState(searchTerm);
+  
+  useEffect(() => {
+    setSearchInput(searchTerm);
+  }, [searchTerm]);
+  
+  useEffect(() => {
+    if (searchInput === searchTerm) {
+      return;
+    }
+    const timer = setTimeout(() => onFilterChange({ searchTerm: searchInput }), 300);
+    return () => clearTimeout(timer);
+  }, [searchInput]);
+  
   // 统一折叠状态管理
   const [collapsed, setCollapsed] = useState(() => {
     const saved = localStorage.getItem('timeline-collapsed');
@@ -32,11 +56,6 @@ const Timeline: React.FC<TimelineProps> = ({ commits, filter, onFilterChange })
     localStorage.setItem('timeline-collapsed', newState.toString());
   };
   
-  // 更新筛选条件的函数
-  const handleSearchChange = (term: string) => {
-    onFilterChange({ searchTerm: term });
-  };
-  
   // 使用 localStorage 保存和恢复滚动位置
   useEffect(() => {
     const handleScroll = () => {
@@ -55,6 +74,23 @@ const Timeline: React.FC<TimelineProps> = ({ commits, filter, onFilterChange })
     }
   }, []);
   
+  // 滚动到接近底部时加载下一页
+  useEffect(() => {
+    const container = timelineRef.current;
+    if (!container || !hasMore || loadingMore) {
+      return;
+    }
+    
+    const handleScroll = () => {
+      if (container.scrollTop + container.clientHeight >= container.scrollHeight - 200) {
+        onLoadMore();
+      }
+    };
+    
+    container.addEventListener('scroll', handleScroll);
+    return () => container.removeEventListener('scroll', handleScroll);
+  }, [hasMore, loadingMore, onLoadMore]);
+  
   // 恢复滚动位置
   useEffect(() => {
     // 检查是否是从提交详情页面返回的
@@ -92,7 +128,7 @@ const Timeline: React.FC<TimelineProps> = ({ commits, filter, onFilterChange })
     );
   }
   
-  if (commits.length === 0) {
+  if (commits.length === 0 && !searchTerm) {
     return (
       <div className="empty-state">
         <h3>暂无提交数据</h3>
@@ -101,13 +137,14 @@ const Timeline: React.FC<TimelineProps> = ({ commits, filter, onFilterChange })
     );
   }
 
-  const filteredCommits = commits.filter(commit => {
-    const matchesSearch = !searchTerm || 
-      commit.message.toLowerCase().includes(searchTerm.toLowerCase()) ||
-      commit.author.toLowerCase().includes(searchTerm.toLowerCase());
-    
-    return matchesSearch;
-  });
+  // 搜索结果按提交索引，用于显示高亮片段
+  const hitsByCommit = new Map((searchHits || []).map(hit => [`${hit.commit.repository_id}-${hit.commit.id}`, hit]));
+  
+  // 片段中 \u0002 和 \u0003 之间的部分是匹配内容
+  const renderHighlighted = (text: string) =>
+    text.split(/[\u0002\u0003]/).map((part, index) =>
+      index % 2 === 1 ? <mark key={index}>{part}</mark> : <React.Fragment key={index}>{part}</React.Fragment>
+    );
 
   const formatDate = (timestamp: string) => {
     return new Date(timestamp).toLocaleString('zh-CN', {
@@ -126,6 +163,8 @@ const Timeline: React.FC<TimelineProps> = ({ commits, filter, onFilterChange })
       localStorage.setItem('timeline-scroll-position', scrollTop.toString());
       // 设置标记表示将要进入commit详情页面
       sessionStorage.setItem('will-return-from-commit-detail', 'true');
+      // 记录已加载的提交数，返回时一次加载回来
+      sessionStorage.setItem('timeline-loaded-count', commits.length.toString());
       console.log('点击commit时保存滚动位置:', scrollTop);
     }
     // Navigate to commit detail page
@@ -144,9 +183,9 @@ const Timeline: React.FC<TimelineProps> = ({ commits, filter, onFilterChange })
             <div className="filter-group">
               <input
                 type="text"
-                placeholder="搜索提交消息或作者..."
-                value={searchTerm}
-                onChange={(e) => handleSearchChange(e.target.value)}
+                placeholder="搜索提交消息、作者或文件..."
+                value={searchInput}
+                onChange={(e) => setSearchInput(e.target.value)}
                 className="search-input"
               />
             </div>
@@ -155,18 +194,18 @@ const Timeline: React.FC<TimelineProps> = ({ commits, filter, onFilterChange })
           <div className="timeline-stats">
             <div className="stat-item">
               <span className="stat-label">显示提交:</span>
-              <span className="stat-value">{filteredCommits.length}</span>
+              <span className="stat-value">{commits.length} / {totalCount}</span>
             </div>
             <div className="stat-item">
               <span className="stat-label">总新增:</span>
               <span className="stat-value text-green">
-                +{filteredCommits.reduce((sum, c) => sum + c.additions, 0)}
+                +{commits.reduce((sum, c) => sum + c.additions, 0)}
               </span>
             </div>
             <div className="stat-item">
               <span className="stat-label">总删除:</span>
               <span className="stat-value text-red">
-                -{filteredCommits.reduce((sum, c) => sum + c.deletions, 0)}
+                -{commits.reduce((sum, c) => sum + c.deletions, 0)}
               </span>
             </div>
           </div>
@@ -174,55 +213,86 @@ const Timeline: React.FC<TimelineProps> = ({ commits, filter, onFilterChange })
       )}
 
       <div className="timeline-list" ref={timelineRef}>
-        {filteredCommits.map((commit) => (
-          <div 
-            key={`${commit.repository_id}-${commit.id}`} 
-            className="timeline-item"
-            onClick={() => handleCommitClick(commit)}
-          >
-            <div className="commit-meta">
-              <div className="commit-author">
-                <strong>{commit.author}</strong>
-                <span className="repository-badge">{commit.repository_name}</span>
-                {commit.branch && commit.branch.trim() !== "" && (
-                  <span className="branch-badge">{commit.branch}</span>
-                )}
-                {commit.remote_url && (
-                  <button 
-                    className="gitlab-link"
-                    onClick={async (e) => {
-                      try {
-                        e.stopPropagation();
-                        const gitLabUrl = convertGitUrlToGitLabCommitUrl(commit.remote_url!, commit.id);
-                        if (gitLabUrl) {
-                          await open(gitLabUrl);
+        {searchTerm && commits.length === 0 && (
+          <div className="empty-state">
+            <h3>未找到匹配的提交</h3>
+            <p>请尝试其他搜索词或调整筛选条件</p>
+          </div>
+        )}
+        {commits.map((commit) => {
+          const hit = hitsByCommit.get(`${commit.repository_id}-${commit.id}`);
+          return (
+            <div 
+              key={`${commit.repository_id}-${commit.id}`} 
+              className="timeline-item"
+              onClick={() => handleCommitClick(commit)}
+            >
+              <div className="commit-meta">
+                <div className="commit-author">
+                  <strong>{commit.author}</strong>
+                  <RepositoryBadge repository={repositoriesById.get(commit.repository_id)} fallbackName={commit.repository_name} />
+                  {commit.branch && commit.branch.trim() !== "" && (
+                    <span className="branch-badge">{commit.branch}</span>
+                  )}
+                  {commit.remote_url && (
+                    <button 
+                      className="gitlab-link"
+                      onClick={async (e) => {
+                        try {
+                          e.stopPropagation();
+                          const gitLabUrl = convertGitUrlToGitLabCommitUrl(commit.remote_url!, commit.id);
+                          if (gitLabUrl) {
+                            await open(gitLabUrl);
+                          }
+                        } catch (error) {
+                          console.error('Error opening GitLab URL:', error);
                         }
-                      } catch (error) {
-                        console.error('Error opening GitLab URL:', error);
-                      }
-                    }}
-                  >
-                    在线查看
-                  </button>
-                )}
+                      }}
+                    >
+                      在线查看
+                    </button>
+                  )}
+                </div>
+                <div className="commit-time">{formatDate(commit.timestamp)}</div>
               </div>
-              <div className="commit-time">{formatDate(commit.timestamp)}</div>
-            </div>
             
-            <div className="commit-message">{commit.message}</div>
+              <div className="commit-message">
+                {hit ? renderHighlighted(hit.message_snippet) : commit.message}
+              </div>
             
-            <div className="commit-stats">
-              <span className="stat-changes">
-                <span className="text-green">+{commit.additions}</span>
-                {' '}
-                <span className="text-red">-{commit.deletions}</span>
-              </span>
-              <span className="stat-files">
-                {commit.files_changed} 个文件修改
-              </span>
+              {hit && hit.matched_file_count > 0 && (
+                <div className="commit-matched-files">
+                  {hit.matched_files.map(file => (
+                    <div key={file} className="matched-file">{renderHighlighted(file)}</div>
+                  ))}
+                  {hit.matched_file_count > hit.matched_files.length && (
+                    <div className="matched-file-more">
+                      还有 {hit.matched_file_count - hit.matched_files.length} 个匹配的文件
+                    </div>
+                  )}
+                </div>
+              )}
+            
+              <div className="commit-stats">
+                <span className="stat-changes">
+                  <span className="text-green">+{commit.additions}</span>
+                  {' '}
+                  <span className="text-red">-{commit.deletions}</span>
+                </span>
+                <span className="stat-files">
+                  {commit.files_changed} 个文件修改
+                </span>
+              </div>
             </div>
+          );
+        })}
+        {hasMore && (
+          <div className="timeline-load-more">
+            <button onClick={onLoadMore} disabled={loadingMore}>
+              {loadingMore ? '加载中...' : `加载更多 (已加载 ${commits.length} / ${totalCount})`}
+            </button>
           </div>
-        ))}
+        )}
       </div>
     </div>
   );
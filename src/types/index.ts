@@ -1,8 +1,25 @@
 export interface Repository {
   id: number;
   path: string;
-  name: string;
+  name: string; // folder name
   last_scanned?: string;
+  display_name?: string; // shown instead of the folder name when set
+  color?: string; // #rrggbb
+  description?: string;
+  archived: boolean; // left out when refreshing all repositories
+}
+
+// fields of update_repository; an empty string clears a field
+export type RepositoryChanges = Partial<Pick<Repository, 'display_name' | 'color' | 'description' | 'archived'>>;
+
+// the name shown for a repository
+export const repositoryLabel = (repository: Repository) => repository.display_name || repository.name;
+
+// a repository can be in any number of groups
+export interface RepositoryGroup {
+  id: number;
+  name: string;
+  repository_ids: number[];
 }
 
 export interface CommitData {
@@ -18,6 +35,74 @@ export interface CommitData {
   files_changed: number;
   branch?: string;
   remote_url?: string;
+  patch_id?: string;
+  orphaned_at?: string;
+  is_merge?: boolean;
+}
+
+export interface CommitPage {
+  commits: CommitData[];
+  next_cursor: string | null; // null on the last page
+  total_count: number;
+  page_size: number;
+}
+
+// 搜索结果的片段中，匹配部分用 \u0002 和 \u0003 包围
+export interface SearchHit {
+  commit: CommitData;
+  score: number; // 相关度，越大越匹配；只有短词时为 0
+  message_snippet: string;
+  author_snippet: string;
+  matched_files: string[];
+  matched_file_count: number;
+}
+
+export interface SearchPage {
+  hits: SearchHit[];
+  next_cursor: string | null; // null on the last page
+  total_count: number;
+  page_size: number;
+}
+
+// 差异搜索：occurrences 对应 git log -S，lines 对应 git log -G
+export type PickaxeMode = 'occurrences' | 'lines';
+
+export interface DiffSearchQuery {
+  pattern: string;
+  regex?: boolean;
+  ignore_case?: boolean;
+  mode?: PickaxeMode;
+}
+
+export interface DiffSearchHunk {
+  file_path: string;
+  header: string;
+  patch: string;
+  matched_lines: number;
+}
+
+export interface DiffSearchMatch {
+  search_id: string;
+  commit: CommitData;
+  hunks: DiffSearchHunk[];
+}
+
+export interface DiffSearchProgress {
+  search_id: string;
+  checked: number;
+  total: number;
+  matches: number;
+}
+
+export interface DiffSearchFinished {
+  search_id: string;
+  checked: number;
+  total: number;
+  matches: number;
+  cancelled: boolean;
+  cached: boolean;
+  error: string | null;
+  elapsed_ms: number;
 }
 
 export interface FileChange {
@@ -30,6 +115,113 @@ export interface FileChange {
 export interface CommitDetail extends CommitData {
   remote_url?: string;
   file_changes: FileChange[];
+  duplicates: CommitData[];
+  reverts: string[];
+  reverted_by: string[];
+}
+
+export interface RevertPair {
+  repository_id: number;
+  repository_name: string;
+  revert_commit_id: string;
+  reverted_commit_id: string;
+  detection: 'message' | 'patch';
+  revert_author: string;
+  reverted_author: string;
+  timestamp: string;
+}
+
+export interface RevertRate {
+  key: string;
+  repository_id?: number;
+  commits: number;
+  reverts: number;
+  reverted: number;
+  revert_rate: number;
+}
+
+export interface RevertReport {
+  total_commits: number;
+  total_reverts: number;
+  total_reverted: number;
+  authors: RevertRate[];
+  repositories: RevertRate[];
+  pairs: RevertPair[];
+}
+
+export interface RevisionComparison {
+  repository_id: number;
+  repository_name: string;
+  from_revision: string;
+  to_revision: string;
+  from_commit: string;
+  to_commit: string;
+  merge_base?: string;
+  additions: number;
+  deletions: number;
+  files_changed: number;
+  remote_url?: string;
+  file_changes: FileChange[];
+  commits: CommitData[];
+}
+
+export interface FileHistoryEntry {
+  commit_id: string;
+  author: string;
+  email: string;
+  message: string;
+  timestamp: string;
+  path: string;
+  old_path?: string;
+  additions: number;
+  deletions: number;
+}
+
+export interface FileAuthorContribution {
+  author: string;
+  commits: number;
+  additions: number;
+  deletions: number;
+}
+
+export interface FileChangeFrequency {
+  period: string; // Format: "YYYY-MM"
+  commits: number;
+  additions: number;
+  deletions: number;
+}
+
+export interface FileHistory {
+  repository_id: number;
+  path: string;
+  source: 'database' | 'git';
+  paths: string[];
+  commits: FileHistoryEntry[];
+  authors: FileAuthorContribution[];
+  change_frequency: FileChangeFrequency[];
+}
+
+export interface LineHistoryEntry {
+  commit_id: string;
+  author: string;
+  email: string;
+  message: string;
+  timestamp: string;
+  path: string;
+  start_line: number;
+  end_line: number;
+  additions: number;
+  deletions: number;
+  diff: string;
+}
+
+export interface LineHistory {
+  repository_id: number;
+  path: string;
+  revision: string;
+  start_line: number;
+  end_line: number;
+  commits: LineHistoryEntry[];
 }
 
 export interface HourlyStats {
@@ -99,6 +291,41 @@ export interface CommitMessageWord {
   weight: number;
 }
 
+export interface StatisticsTotals {
+  total_commits: number;
+  total_additions: number;
+  total_deletions: number;
+}
+
+export interface StatisticsCacheDiagnostics {
+  persistent: boolean;
+  memory_entries: number;
+  disk_entries: number;
+  hits: number;
+  disk_hits: number;
+  misses: number;
+  invalidations: number;
+}
+
+export interface GroupStats {
+  group_id: number;
+  name: string;
+  additions: number;
+  deletions: number;
+  commits: number;
+  repositories: number; // repositories of the group with commits in the filter
+  authors: number;
+}
+
+export interface RepositoryStats {
+  name: string; // display name, or the folder name
+  path: string; // tells apart repositories shown with the same name
+  color?: string;
+  additions: number;
+  deletions: number;
+  commits: number;
+}
+
 export interface Statistics {
   hourly: HourlyStats[];
   daily: DailyStats[];
@@ -107,7 +334,8 @@ export interface Statistics {
   total_additions: number;
   total_deletions: number;
   authors: { [key: string]: { additions: number; deletions: number; commits: number } };
-  repositories: { [key: string]: { additions: number; deletions: number; commits: number } };
+  repositories: { [id: string]: RepositoryStats }; // by repository ID
+  groups: GroupStats[];
   // New fields for additional charts
   hourly_commit_distribution: HourlyCommitDistribution[];
   author_activity_trends: AuthorActivityTrend[];
@@ -123,7 +351,106 @@ export interface TimeFilter {
   start_date?: string;
   end_date?: string;
   author?: string;
+  authors?: string[]; // any of these, in addition to author
   exclude_authors?: string[];
   repository_id?: number;
+  repository_ids?: number[]; // any of these, in addition to repository_id
+  group_ids?: number[]; // repositories in any of these groups, within the ones above
+  branches?: string[];
+  paths?: string[]; // globs; line counts only cover matching files
+  min_size?: number; // bounds on additions + deletions
+  max_size?: number;
+  message_pattern?: string; // regex the message must match
+  exclude_message_pattern?: string; // regex the message must not match
+  include_merges?: boolean;
+  deduplicate_patches?: boolean;
+  exclude_reverts?: boolean;
+  include_orphaned?: boolean;
   searchTerm?: string;
-}
\ No newline at end of file
+}
+
+export interface FilterQueryError {
+  message: string;
+  start: number; // UTF-16 offsets, as string indices
+  end: number;
+}
+
+export interface ParsedFilterQuery {
+  filter: TimeFilter | null; // null when there are errors
+  errors: FilterQueryError[];
+}
+
+export interface FilterQuerySuggestion {
+  text: string;
+  kind: string; // 'key', 'author', 'repo', 'branch' or a switch key such as 'merges'
+  description: string | null;
+  start: number; // range of the query the text replaces
+  end: number;
+}
+
+export interface VerificationIssue {
+  commit_id: string;
+  kind: 'missing_commit' | 'commit_stats' | 'missing_file' | 'extra_file' | 'file_stats' | 'duplicate_file_rows';
+  file_path?: string;
+  stored?: string;
+  actual?: string;
+  repaired: boolean;
+  repair_error?: string;
+}
+
+export interface VerificationReport {
+  repository_id: number;
+  total_commits: number;
+  checked_commits: number;
+  sampled: boolean;
+  issues: VerificationIssue[];
+  repaired_commits: number;
+}
+
+export interface ReconcileResult {
+  repository_id: number;
+  checked: number;
+  orphaned: number;
+  restored: number;
+  total_orphaned: number;
+}
+export interface IngestReport {
+  commits: number;
+  file_changes: number;
+  batches: number;
+  batch_size: number;
+  deferred_indexes: boolean;
+  elapsed_ms: number;
+  commits_per_second: number;
+  file_changes_per_second: number;
+}
+
+export type GitBackendKind = 'git2' | 'gix';
+
+export interface BackendMismatch {
+  commit_id?: string;
+  field: string;
+  git2: string;
+  gix: string;
+}
+
+export interface BackendComparison {
+  repository_id: number;
+  commits_checked: number;
+  git2_ms: number;
+  gix_ms: number;
+  mismatches: BackendMismatch[];
+}
+
+export interface FilterPreset {
+  id: number;
+  name: string;
+  filter: TimeFilter; // backend form, dates as ISO timestamps
+  created_at: string;
+  updated_at: string;
+}
+
+export interface SettingChanged {
+  key: string;
+  value: unknown | null; // null when the setting was reset
+}